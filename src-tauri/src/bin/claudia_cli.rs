@@ -0,0 +1,6 @@
+//! Headless `claudia-cli` binary. Shares the whole backend with the desktop
+//! app through `claudia_lib`; see `claudia_lib::cli` for the commands.
+
+fn main() {
+    std::process::exit(claudia_lib::cli::run());
+}
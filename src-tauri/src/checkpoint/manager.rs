@@ -7,6 +7,8 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use crate::stream_json::StreamMessage;
+
 use super::{
     storage::{self, CheckpointStorage},
     Checkpoint, CheckpointMetadata, CheckpointPaths, CheckpointResult, CheckpointStrategy,
@@ -66,19 +68,10 @@ impl CheckpointManager {
         messages.push(jsonl_message.clone());
 
         // Parse message to check for tool usage
-        if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&jsonl_message) {
-            if let Some(content) = msg.get("message").and_then(|m| m.get("content")) {
-                if let Some(content_array) = content.as_array() {
-                    for item in content_array {
-                        if item.get("type").and_then(|t| t.as_str()) == Some("tool_use") {
-                            if let Some(tool_name) = item.get("name").and_then(|n| n.as_str()) {
-                                if let Some(input) = item.get("input") {
-                                    self.track_tool_operation(tool_name, input).await?;
-                                }
-                            }
-                        }
-                    }
-                }
+        if let Some(msg) = StreamMessage::parse(&jsonl_message) {
+            for tool_use in msg.tool_uses() {
+                self.track_tool_operation(&tool_use.name, &tool_use.input)
+                    .await?;
             }
         }
 
@@ -312,86 +305,22 @@ impl CheckpointManager {
         let mut total_tokens = 0u64;
 
         // Iterate through messages in reverse to find the last user prompt
-        for msg_str in messages.iter().rev() {
-            if let Ok(msg) = serde_json::from_str::<serde_json::Value>(msg_str) {
-                // Check for user message
-                if msg.get("type").and_then(|t| t.as_str()) == Some("user") {
-                    if let Some(content) = msg
-                        .get("message")
-                        .and_then(|m| m.get("content"))
-                        .and_then(|c| c.as_array())
-                    {
-                        for item in content {
-                            if item.get("type").and_then(|t| t.as_str()) == Some("text") {
-                                if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
-                                    user_prompt = text.to_string();
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-
-                // Extract model info
-                if let Some(model) = msg.get("model").and_then(|m| m.as_str()) {
-                    model_used = model.to_string();
-                }
-
-                // Also check for model in message.model (assistant messages)
-                if let Some(message) = msg.get("message") {
-                    if let Some(model) = message.get("model").and_then(|m| m.as_str()) {
-                        model_used = model.to_string();
-                    }
+        for msg in messages.iter().rev().filter_map(|m| StreamMessage::parse(m)) {
+            // Tool results are sent as user messages too but carry no text
+            if user_prompt.is_empty() && matches!(msg, StreamMessage::User(_)) {
+                if let Some(text) = msg.text() {
+                    user_prompt = text;
                 }
+            }
 
-                // Count tokens - check both top-level and nested usage
-                // First check for usage in message.usage (assistant messages)
-                if let Some(message) = msg.get("message") {
-                    if let Some(usage) = message.get("usage") {
-                        if let Some(input) = usage.get("input_tokens").and_then(|t| t.as_u64()) {
-                            total_tokens += input;
-                        }
-                        if let Some(output) = usage.get("output_tokens").and_then(|t| t.as_u64()) {
-                            total_tokens += output;
-                        }
-                        // Also count cache tokens
-                        if let Some(cache_creation) = usage
-                            .get("cache_creation_input_tokens")
-                            .and_then(|t| t.as_u64())
-                        {
-                            total_tokens += cache_creation;
-                        }
-                        if let Some(cache_read) = usage
-                            .get("cache_read_input_tokens")
-                            .and_then(|t| t.as_u64())
-                        {
-                            total_tokens += cache_read;
-                        }
-                    }
-                }
+            // Model from the init entry or an assistant message
+            if let Some(model) = msg.model() {
+                model_used = model.to_string();
+            }
 
-                // Then check for top-level usage (result messages)
-                if let Some(usage) = msg.get("usage") {
-                    if let Some(input) = usage.get("input_tokens").and_then(|t| t.as_u64()) {
-                        total_tokens += input;
-                    }
-                    if let Some(output) = usage.get("output_tokens").and_then(|t| t.as_u64()) {
-                        total_tokens += output;
-                    }
-                    // Also count cache tokens
-                    if let Some(cache_creation) = usage
-                        .get("cache_creation_input_tokens")
-                        .and_then(|t| t.as_u64())
-                    {
-                        total_tokens += cache_creation;
-                    }
-                    if let Some(cache_read) = usage
-                        .get("cache_read_input_tokens")
-                        .and_then(|t| t.as_u64())
-                    {
-                        total_tokens += cache_read;
-                    }
-                }
+            // Count tokens, including cache tokens, from assistant and result messages
+            if let Some(usage) = msg.usage() {
+                total_tokens += usage.total_tokens();
             }
         }
 
@@ -692,56 +621,22 @@ impl CheckpointManager {
             CheckpointStrategy::Manual => false,
             CheckpointStrategy::PerPrompt => {
                 // Check if message is a user prompt
-                if let Ok(msg) = serde_json::from_str::<serde_json::Value>(message) {
-                    msg.get("type").and_then(|t| t.as_str()) == Some("user")
-                } else {
-                    false
-                }
+                matches!(StreamMessage::parse(message), Some(StreamMessage::User(_)))
             }
             CheckpointStrategy::PerToolUse => {
                 // Check if message contains tool use
-                if let Ok(msg) = serde_json::from_str::<serde_json::Value>(message) {
-                    if let Some(content) = msg
-                        .get("message")
-                        .and_then(|m| m.get("content"))
-                        .and_then(|c| c.as_array())
-                    {
-                        content.iter().any(|item| {
-                            item.get("type").and_then(|t| t.as_str()) == Some("tool_use")
-                        })
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
+                StreamMessage::parse(message).is_some_and(|msg| msg.tool_uses().next().is_some())
             }
             CheckpointStrategy::Smart => {
                 // Smart strategy: checkpoint after destructive operations
-                if let Ok(msg) = serde_json::from_str::<serde_json::Value>(message) {
-                    if let Some(content) = msg
-                        .get("message")
-                        .and_then(|m| m.get("content"))
-                        .and_then(|c| c.as_array())
-                    {
-                        content.iter().any(|item| {
-                            if item.get("type").and_then(|t| t.as_str()) == Some("tool_use") {
-                                let tool_name =
-                                    item.get("name").and_then(|n| n.as_str()).unwrap_or("");
-                                matches!(
-                                    tool_name.to_lowercase().as_str(),
-                                    "write" | "edit" | "multiedit" | "bash" | "rm" | "delete"
-                                )
-                            } else {
-                                false
-                            }
-                        })
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
+                StreamMessage::parse(message).is_some_and(|msg| {
+                    msg.tool_uses().any(|tool_use| {
+                        matches!(
+                            tool_use.name.to_lowercase().as_str(),
+                            "write" | "edit" | "multiedit" | "bash" | "rm" | "delete"
+                        )
+                    })
+                })
             }
         }
     }
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 /// Shared module for detecting Claude Code binary installations
 /// Supports NVM installations, aliased paths, and version-based selection
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use tauri::Manager;
 
@@ -35,10 +35,17 @@ pub struct ClaudeInstallation {
 /// Main function to find the Claude binary
 /// Checks database first for stored path and preference, then prioritizes accordingly
 pub fn find_claude_binary(app_handle: &tauri::AppHandle) -> Result<String, String> {
+    let app_data_dir = app_handle.path().app_data_dir().ok();
+    find_claude_binary_in(app_data_dir.as_deref())
+}
+
+/// Same as `find_claude_binary`, but reads the stored path and preference from
+/// the given app data directory. Used where no `AppHandle` exists (the CLI).
+pub fn find_claude_binary_in(app_data_dir: Option<&Path>) -> Result<String, String> {
     info!("Searching for claude binary...");
 
     // First check if we have a stored path and preference in the database
-    if let Ok(app_data_dir) = app_handle.path().app_data_dir() {
+    if let Some(app_data_dir) = app_data_dir {
         let db_path = app_data_dir.join("agents.db");
         if db_path.exists() {
             if let Ok(conn) = rusqlite::Connection::open(&db_path) {
@@ -0,0 +1,919 @@
+//! Headless command-line interface for Claudia.
+//!
+//! The `claudia-cli` binary is a thin wrapper around `run()`. It shares the
+//! database, Claude binary discovery and `~/.claude` files with the desktop
+//! app, but never creates a window, so it can be used from a terminal, over
+//! SSH or in CI. Every command accepts `--json` for machine-readable output.
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::checkpoint::state::CheckpointState;
+use crate::claude_binary::find_claude_binary_in;
+use crate::commands::agents::{
+    build_agent_args, create_agent_system_command, get_enabled_environment_variables_internal,
+    get_environment_variable_groups_internal, list_agents_internal, open_database,
+    run_agent_process, write_agent_hooks_settings, Agent, AgentDb, AgentRunEvents, AgentRunInfo,
+    AgentRunOutcome, EnvironmentVariableGroup,
+};
+use crate::commands::budget::BudgetLimits;
+use crate::commands::claude::{
+    create_checkpoint_internal, list_checkpoints_internal, load_sessions_page,
+    restore_checkpoint_internal,
+};
+use crate::commands::mcp::{execute_claude_mcp_system, mcp_read_project_config, parse_mcp_list_output};
+use crate::commands::project_index::{find_session_file, refresh_project_index, resolve_project_path};
+use crate::commands::session_metadata::SessionFilter;
+use crate::commands::session_search::{refresh_session_index, search_sessions_internal};
+use crate::commands::usage::{get_usage_by_date_range, get_usage_stats};
+use crate::commands::worktrees::{create_worktree, discard_unused_worktree, set_worktree_agent_run};
+use crate::process::ProcessRegistry;
+use crate::stream_json::{ContentBlock, StreamMessage};
+
+/// Tauri bundle identifier, which names the app data directory
+const APP_IDENTIFIER: &str = "claudia.asterisk.so";
+
+/// Overrides the app data directory (mainly for CI and tests)
+const DATA_DIR_ENV: &str = "CLAUDIA_DATA_DIR";
+
+/// Options that never take a value
+const BOOL_FLAGS: &[&str] = &["json", "help", "include-archived", "isolate"];
+
+/// Options that take a value, as `--key value` or `--key=value`
+const VALUE_OPTIONS: &[&str] = &[
+    "days",
+    "description",
+    "from",
+    "limit",
+    "max-cost",
+    "max-duration",
+    "max-turns",
+    "message-index",
+    "model",
+    "offset",
+    "project",
+    "query",
+    "scope",
+    "task",
+    "to",
+];
+
+const USAGE: &str = "\
+Usage: claudia-cli <command> [options] [--json]
+
+Agents:
+  agents list
+  agents run <name> --project <path> [--task <text>] [--model <model>] [--isolate]
+             [--max-cost <usd>] [--max-turns <n>] [--max-duration <secs>]
+
+Projects and sessions:
+  projects list
+  sessions list <project_id> [--limit <n>] [--offset <n>] [--query <text>] [--include-archived]
+  sessions search <query> [--project <project_id>] [--from <date>] [--to <date>] [--limit <n>]
+
+Checkpoints:
+  checkpoints list <session_id>
+  checkpoints create <session_id> [--description <text>] [--message-index <n>]
+  checkpoints restore <session_id> <checkpoint_id>
+
+Environment variables:
+  env list
+  env groups list
+  env groups enable <id|name>
+  env groups disable <id|name>
+
+MCP servers:
+  mcp list
+  mcp get <name>
+  mcp add-json <name> <json> [--scope local|user|project]
+  mcp remove <name>
+  mcp project-config <project_path>
+
+Usage reports:
+  usage [--days <n>]
+  usage --from <YYYY-MM-DD> --to <YYYY-MM-DD>
+
+Arguments after -- are taken literally, even if they start with --.
+Set CLAUDIA_DATA_DIR to use a different app data directory.";
+
+/// Parsed command line: positional words, `--key value` options and bare flags
+#[derive(Debug, Default)]
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    flags: HashSet<String>,
+}
+
+impl Args {
+    fn parse(raw: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = Args::default();
+        let mut raw = raw.into_iter();
+        while let Some(arg) = raw.next() {
+            // Everything after `--` is positional, even if it looks like an option
+            if arg == "--" {
+                args.positional.extend(raw.by_ref());
+                break;
+            }
+            let Some(name) = arg.strip_prefix("--") else {
+                args.positional.push(arg);
+                continue;
+            };
+            if let Some((key, value)) = name.split_once('=') {
+                if !VALUE_OPTIONS.contains(&key) {
+                    return Err(Self::unexpected(key));
+                }
+                args.options.insert(key.to_string(), value.to_string());
+            } else if BOOL_FLAGS.contains(&name) {
+                args.flags.insert(name.to_string());
+            } else if VALUE_OPTIONS.contains(&name) {
+                let value = raw
+                    .next()
+                    .ok_or_else(|| format!("Missing value for --{}", name))?;
+                args.options.insert(name.to_string(), value);
+            } else {
+                return Err(Self::unexpected(name));
+            }
+        }
+        Ok(args)
+    }
+
+    fn unexpected(name: &str) -> String {
+        if BOOL_FLAGS.contains(&name) {
+            format!("--{} does not take a value", name)
+        } else {
+            format!("Unknown option --{}", name)
+        }
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+
+    fn option(&self, name: &str) -> Option<String> {
+        self.options.get(name).cloned()
+    }
+
+    fn number(&self, name: &str) -> Result<Option<usize>, String> {
+        self.options
+            .get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("--{} expects a number, got '{}'", name, value))
+            })
+            .transpose()
+    }
+
+    /// Positional argument at `index`, named `what` in the error message
+    fn positional(&self, index: usize, what: &str) -> Result<String, String> {
+        self.positional
+            .get(index)
+            .cloned()
+            .ok_or_else(|| format!("Missing {}\n\n{}", what, USAGE))
+    }
+}
+
+struct Cli {
+    args: Args,
+    json: bool,
+    app_dir: PathBuf,
+}
+
+impl Cli {
+    fn open_conn(&self) -> Result<Connection, String> {
+        open_database(&self.app_dir).map_err(|e| format!("Failed to open database: {}", e))
+    }
+
+    /// Database wrapped the way the app manages it, for helpers that take `&AgentDb`
+    fn open_db(&self) -> Result<AgentDb, String> {
+        Ok(AgentDb(Mutex::new(self.open_conn()?)))
+    }
+
+    /// Prints `value` as JSON with `--json`, otherwise through `human`
+    fn emit<T: Serialize>(&self, value: &T, human: impl FnOnce(&T)) -> Result<(), String> {
+        if self.json {
+            let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+            println!("{}", json);
+        } else {
+            human(value);
+        }
+        Ok(())
+    }
+
+    fn claude_binary(&self) -> Result<String, String> {
+        let path = find_claude_binary_in(Some(&self.app_dir))?;
+        if path == "claude-code" {
+            return Err(
+                "The bundled Claude Code sidecar is only available inside the app; select a system installation in Settings or install `claude` on PATH"
+                    .to_string(),
+            );
+        }
+        Ok(path)
+    }
+}
+
+fn app_data_dir() -> Result<PathBuf, String> {
+    if let Ok(dir) = std::env::var(DATA_DIR_ENV) {
+        return Ok(PathBuf::from(dir));
+    }
+    dirs::data_dir()
+        .map(|dir| dir.join(APP_IDENTIFIER))
+        .ok_or_else(|| "Could not determine the app data directory".to_string())
+}
+
+/// Entry point of the `claudia-cli` binary; returns the process exit code
+pub fn run() -> i32 {
+    // Logs go to stderr so stdout stays machine-readable
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if args.flag("help") || args.positional.is_empty() {
+        println!("{}", USAGE);
+        return if args.flag("help") { 0 } else { 2 };
+    }
+
+    let json = args.flag("json");
+    let cli = match app_data_dir() {
+        Ok(app_dir) => Cli { args, json, app_dir },
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 1;
+        }
+    };
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Error: Failed to start async runtime: {}", e);
+            return 1;
+        }
+    };
+
+    match runtime.block_on(dispatch(&cli)) {
+        Ok(code) => code,
+        Err(e) => {
+            if json {
+                println!("{}", serde_json::json!({ "error": e }));
+            } else {
+                eprintln!("Error: {}", e);
+            }
+            1
+        }
+    }
+}
+
+async fn dispatch(cli: &Cli) -> Result<i32, String> {
+    let words: Vec<&str> = cli.args.positional.iter().map(String::as_str).collect();
+    match words.as_slice() {
+        ["agents", "list"] => agents_list(cli)?,
+        ["agents", "run", ..] => return agents_run(cli).await,
+        ["projects", "list"] => projects_list(cli)?,
+        ["sessions", "list", ..] => sessions_list(cli)?,
+        ["sessions", "search", ..] => sessions_search(cli)?,
+        ["checkpoints", "list", ..] => checkpoints_list(cli).await?,
+        ["checkpoints", "create", ..] => checkpoints_create(cli).await?,
+        ["checkpoints", "restore", ..] => checkpoints_restore(cli).await?,
+        ["env", "list"] => env_list(cli)?,
+        ["env", "groups", "list"] => env_groups_list(cli)?,
+        ["env", "groups", "enable", ..] => env_groups_set_enabled(cli, true)?,
+        ["env", "groups", "disable", ..] => env_groups_set_enabled(cli, false)?,
+        ["mcp", "list"] => mcp_list(cli).await?,
+        ["mcp", "get" | "add-json" | "remove", ..] => mcp_passthrough(cli)?,
+        ["mcp", "project-config", ..] => mcp_project_config(cli).await?,
+        ["usage"] => usage_report(cli)?,
+        _ => return Err(format!("Unknown command: {}\n\n{}", words.join(" "), USAGE)),
+    }
+    Ok(0)
+}
+
+fn agents_list(cli: &Cli) -> Result<(), String> {
+    let agents = list_agents_internal(&cli.open_conn()?)?;
+    cli.emit(&agents, |agents| {
+        for agent in agents {
+            println!(
+                "{:>4}  {:<30} {}",
+                agent.id.unwrap_or_default(),
+                agent.name,
+                agent.model
+            );
+        }
+    })
+}
+
+/// Prints the readable parts of one stream-json message
+fn print_stream_message(message: &StreamMessage) {
+    match message {
+        StreamMessage::Assistant(_) => {
+            for block in message.blocks() {
+                match block {
+                    ContentBlock::Text(text) => println!("{}", text.text),
+                    ContentBlock::ToolUse(tool_use) => eprintln!("→ {}", tool_use.name),
+                    _ => {}
+                }
+            }
+        }
+        StreamMessage::Result(result) => {
+            if let (Some(cost), Some(turns)) = (result.total_cost_usd, result.num_turns) {
+                eprintln!("Finished in {} turns, ${:.4}", turns, cost);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Prints what an agent run produces: raw stream-json lines with `--json`,
+/// readable text otherwise. Claude's stderr is passed through.
+struct CliAgentEvents {
+    json: bool,
+}
+
+impl AgentRunEvents for CliAgentEvents {
+    fn output(&self, _run_id: i64, line: &str) {
+        if self.json {
+            println!("{}", line);
+        } else if let Some(message) = StreamMessage::parse(line) {
+            print_stream_message(&message);
+        }
+    }
+
+    fn error(&self, _run_id: i64, line: &str) {
+        eprintln!("{}", line);
+    }
+
+    fn permission_request(&self, _run_id: i64, tool: &str) {
+        eprintln!("Waiting for permission to use {}", tool);
+    }
+
+    fn complete(&self, _run_id: i64, outcome: &AgentRunOutcome) {
+        if let Some(violation) = &outcome.budget_exceeded {
+            eprintln!("{}", violation.message());
+        }
+    }
+}
+
+/// Budget caps from `--max-cost`, `--max-turns` and `--max-duration`; unset
+/// caps are unlimited
+fn budget_limits(cli: &Cli) -> Result<BudgetLimits, String> {
+    let max_cost_usd = cli
+        .args
+        .option("max-cost")
+        .map(|value| {
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("--max-cost expects a number, got '{}'", value))
+        })
+        .transpose()?;
+    Ok(BudgetLimits {
+        max_cost_usd,
+        max_tokens: None,
+        max_duration_secs: cli.args.number("max-duration")?.map(|n| n as u64),
+        max_turns: cli.args.number("max-turns")?.map(|n| n as u32),
+    })
+}
+
+/// Runs an agent to completion, streaming its output. With `--json` the raw
+/// stream-json messages are printed one per line. The run goes through the
+/// same spawn and monitor path as in the app; Ctrl-C cancels it.
+async fn agents_run(cli: &Cli) -> Result<i32, String> {
+    let name = cli.args.positional(2, "agent name")?;
+    let project_path = cli
+        .args
+        .option("project")
+        .ok_or_else(|| "Missing --project <path>".to_string())?;
+    if !Path::new(&project_path).is_dir() {
+        return Err(format!("Project directory not found: {}", project_path));
+    }
+    let budget = budget_limits(cli)?;
+
+    let db = cli.open_db()?;
+    let agents = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        list_agents_internal(&conn)?
+    };
+    let agent: Agent = agents
+        .into_iter()
+        .find(|a| a.name.eq_ignore_ascii_case(&name) || a.id.map(|id| id.to_string()) == Some(name.clone()))
+        .ok_or_else(|| format!("Agent not found: {}", name))?;
+    let agent_id = agent.id.unwrap_or_default();
+    let task = cli
+        .args
+        .option("task")
+        .or_else(|| agent.default_task.clone())
+        .ok_or_else(|| format!("Agent '{}' has no default task; pass --task", agent.name))?;
+    let model = cli.args.option("model").unwrap_or_else(|| agent.model.clone());
+    let claude_path = cli.claude_binary()?;
+
+    // Optionally run in a dedicated git worktree instead of the project itself
+    let worktree = if cli.args.flag("isolate") {
+        Some(create_worktree(&db, &cli.app_dir.join("worktrees"), &project_path, &agent.name)?)
+    } else {
+        None
+    };
+    let project_path = worktree
+        .as_ref()
+        .map(|w| w.worktree_path.clone())
+        .unwrap_or(project_path);
+    let generated_settings = write_agent_hooks_settings(&agent, &project_path).inspect_err(|_| {
+        if let Some(worktree) = &worktree {
+            discard_unused_worktree(&db, worktree);
+        }
+    })?;
+
+    let (run_id, env_vars) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![agent_id, agent.name, agent.icon, task, model, project_path, ""],
+        )
+        .map_err(|e| e.to_string())?;
+        let run_id = conn.last_insert_rowid();
+        if let Some(worktree) = &worktree {
+            set_worktree_agent_run(&conn, worktree.id, run_id, generated_settings)?;
+        }
+        (run_id, get_enabled_environment_variables_internal(&conn)?)
+    };
+
+    let mut cmd = create_agent_system_command(
+        &claude_path,
+        build_agent_args(&agent, &task, &model),
+        &project_path,
+    );
+    cmd.envs(env_vars);
+    let registry = Arc::new(ProcessRegistry::new());
+    let run = AgentRunInfo {
+        run_id,
+        agent_id,
+        agent_name: agent.name.clone(),
+        project_path: project_path.clone(),
+        task,
+        model,
+    };
+    let events = Arc::new(CliAgentEvents { json: cli.json });
+    let mut monitor = match run_agent_process(cmd, run, Some(budget), cli.app_dir.join("agents.db"), registry.clone(), events) {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            {
+                let conn = db.0.lock().map_err(|e| e.to_string())?;
+                let _ = conn.execute(
+                    "UPDATE agent_runs SET status = 'failed', completed_at = CURRENT_TIMESTAMP WHERE id = ?1",
+                    params![run_id],
+                );
+            }
+            if let Some(worktree) = &worktree {
+                discard_unused_worktree(&db, worktree);
+            }
+            return Err(e);
+        }
+    };
+    if !cli.json {
+        match &worktree {
+            Some(worktree) => eprintln!(
+                "Running agent '{}' (run {}) in worktree {}",
+                agent.name, run_id, worktree.worktree_path
+            ),
+            None => eprintln!("Running agent '{}' (run {})", agent.name, run_id),
+        }
+    }
+
+    let outcome = tokio::select! {
+        outcome = &mut monitor => outcome.map_err(|e| format!("Agent monitor failed: {}", e))?,
+        _ = tokio::signal::ctrl_c() => {
+            // Mark the run cancelled first so the monitor keeps that status
+            // when Claude exits
+            {
+                let conn = db.0.lock().map_err(|e| e.to_string())?;
+                conn.execute(
+                    "UPDATE agent_runs SET status = 'cancelled', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'running'",
+                    params![run_id],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            // Claude runs in its own process group, so Ctrl-C did not reach it
+            if let Err(e) = registry.kill_process(run_id).await {
+                log::warn!("Failed to stop agent run {}: {}", run_id, e);
+            }
+            let _ = monitor.await;
+            if !cli.json {
+                eprintln!("Run {} cancelled", run_id);
+            }
+            return Ok(130);
+        }
+    };
+
+    if !cli.json {
+        eprintln!("Run {} {} (session {})", run_id, outcome.status, outcome.session_id);
+    }
+    Ok(if outcome.exited_ok && outcome.budget_exceeded.is_none() { 0 } else { 1 })
+}
+
+fn projects_list(cli: &Cli) -> Result<(), String> {
+    let projects = refresh_project_index(&cli.open_db()?)?;
+    cli.emit(&projects, |projects| {
+        for project in projects {
+            println!("{}  {}", project.project_id, project.project_path);
+        }
+    })
+}
+
+fn sessions_list(cli: &Cli) -> Result<(), String> {
+    let project_id = cli.args.positional(2, "project id")?;
+    let filter = SessionFilter {
+        include_archived: cli.args.flag("include-archived"),
+        query: cli.args.option("query"),
+        ..Default::default()
+    };
+    let offset = cli.args.number("offset")?.unwrap_or(0);
+    let limit = cli.args.number("limit")?;
+
+    let db = cli.open_db()?;
+    let page = load_sessions_page(&db, &project_id, &filter, offset, limit)?;
+    cli.emit(&page, |page| {
+        for session in &page.sessions {
+            let label = session
+                .title
+                .as_deref()
+                .or(session.first_message.as_deref())
+                .unwrap_or("")
+                .lines()
+                .next()
+                .unwrap_or("");
+            let pin = if session.pinned { "*" } else { " " };
+            println!("{}{}  {}", pin, session.id, label);
+        }
+        eprintln!(
+            "{} of {} sessions",
+            page.sessions.len(),
+            page.total
+        );
+    })
+}
+
+fn sessions_search(cli: &Cli) -> Result<(), String> {
+    let query = cli.args.positional[2..].join(" ");
+    if query.trim().is_empty() {
+        return Err(format!("Missing search query\n\n{}", USAGE));
+    }
+
+    // No background indexer runs for the CLI, so index new lines first
+    let db = cli.open_db()?;
+    refresh_session_index(&db)?;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let results = search_sessions_internal(
+        &conn,
+        &query,
+        cli.args.option("project"),
+        cli.args.option("from"),
+        cli.args.option("to"),
+        cli.args.number("limit")?,
+    )?;
+    cli.emit(&results, |results| {
+        for result in results {
+            let snippet = result.snippet.replace("<mark>", "").replace("</mark>", "");
+            println!(
+                "{}  {}  {}  {}",
+                result.timestamp.as_deref().unwrap_or("-"),
+                result.session_id,
+                result.kind,
+                snippet.replace('\n', " ")
+            );
+        }
+    })
+}
+
+/// Resolves `(project_id, project_path)` for a session from its transcript location
+fn locate_session(db: &AgentDb, session_id: &str) -> Result<(String, String), String> {
+    let session_file = find_session_file(db, session_id, None)?
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    let project_id = session_file
+        .parent()
+        .and_then(|dir| dir.file_name())
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| "Invalid session path".to_string())?
+        .to_string();
+    let project_path = resolve_project_path(db, &project_id)?;
+    Ok((project_id, project_path))
+}
+
+async fn checkpoint_state() -> Result<CheckpointState, String> {
+    let claude_dir = dirs::home_dir()
+        .ok_or_else(|| "Could not find home directory".to_string())?
+        .join(".claude");
+    let state = CheckpointState::new();
+    state.set_claude_dir(claude_dir).await;
+    Ok(state)
+}
+
+async fn checkpoints_list(cli: &Cli) -> Result<(), String> {
+    let session_id = cli.args.positional(2, "session id")?;
+    let (project_id, project_path) = locate_session(&cli.open_db()?, &session_id)?;
+    let state = checkpoint_state().await?;
+    let checkpoints =
+        list_checkpoints_internal(&state, session_id, project_id, project_path).await?;
+    cli.emit(&checkpoints, |checkpoints| {
+        for checkpoint in checkpoints {
+            println!(
+                "{}  {}  #{}  {}",
+                checkpoint.id,
+                checkpoint.timestamp.to_rfc3339(),
+                checkpoint.message_index,
+                checkpoint.description.as_deref().unwrap_or("")
+            );
+        }
+    })
+}
+
+async fn checkpoints_create(cli: &Cli) -> Result<(), String> {
+    let session_id = cli.args.positional(2, "session id")?;
+    let (project_id, project_path) = locate_session(&cli.open_db()?, &session_id)?;
+    let state = checkpoint_state().await?;
+    let result = create_checkpoint_internal(
+        &state,
+        session_id,
+        project_id,
+        project_path,
+        cli.args.number("message-index")?,
+        cli.args.option("description"),
+    )
+    .await?;
+    cli.emit(&result, |result| {
+        println!(
+            "Created checkpoint {} ({} files)",
+            result.checkpoint.id, result.files_processed
+        );
+        for warning in &result.warnings {
+            eprintln!("warning: {}", warning);
+        }
+    })
+}
+
+async fn checkpoints_restore(cli: &Cli) -> Result<(), String> {
+    let session_id = cli.args.positional(2, "session id")?;
+    let checkpoint_id = cli.args.positional(3, "checkpoint id")?;
+    let (project_id, project_path) = locate_session(&cli.open_db()?, &session_id)?;
+    let state = checkpoint_state().await?;
+    let result =
+        restore_checkpoint_internal(&state, checkpoint_id, session_id, project_id, project_path)
+            .await?;
+    cli.emit(&result, |result| {
+        println!(
+            "Restored checkpoint {} ({} files)",
+            result.checkpoint.id, result.files_processed
+        );
+        for warning in &result.warnings {
+            eprintln!("warning: {}", warning);
+        }
+    })
+}
+
+/// Prints the variables Claude would be started with
+fn env_list(cli: &Cli) -> Result<(), String> {
+    let vars = get_enabled_environment_variables_internal(&cli.open_conn()?)?;
+    let vars: std::collections::BTreeMap<_, _> = vars.into_iter().collect();
+    cli.emit(&vars, |vars| {
+        for (key, value) in vars {
+            println!("{}={}", key, value);
+        }
+    })
+}
+
+fn env_groups_list(cli: &Cli) -> Result<(), String> {
+    let groups = get_environment_variable_groups_internal(&cli.open_conn()?)?;
+    cli.emit(&groups, |groups| {
+        for group in groups {
+            println!(
+                "{:>4}  [{}] {}",
+                group.id.unwrap_or_default(),
+                if group.enabled { "x" } else { " " },
+                group.name
+            );
+        }
+    })
+}
+
+fn env_groups_set_enabled(cli: &Cli, enabled: bool) -> Result<(), String> {
+    let target = cli.args.positional(3, "group id or name")?;
+    let conn = cli.open_conn()?;
+    let group: EnvironmentVariableGroup = get_environment_variable_groups_internal(&conn)?
+        .into_iter()
+        .find(|g| g.name == target || g.id.map(|id| id.to_string()) == Some(target.clone()))
+        .ok_or_else(|| format!("Environment variable group not found: {}", target))?;
+
+    conn.execute(
+        "UPDATE environment_variable_groups SET enabled = ?1 WHERE id = ?2",
+        params![enabled, group.id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let updated = EnvironmentVariableGroup { enabled, ..group };
+    cli.emit(&updated, |group| {
+        println!(
+            "{} group '{}'",
+            if group.enabled { "Enabled" } else { "Disabled" },
+            group.name
+        );
+    })
+}
+
+async fn mcp_list(cli: &Cli) -> Result<(), String> {
+    let claude_path = cli.claude_binary()?;
+    let output = execute_claude_mcp_system(&claude_path, &["list"]).map_err(|e| e.to_string())?;
+    if !cli.json {
+        print!("{}", output);
+        return Ok(());
+    }
+
+    let current_dir = std::env::current_dir()
+        .unwrap_or_else(|_| PathBuf::from("."))
+        .to_string_lossy()
+        .to_string();
+    let project_config = mcp_read_project_config(current_dir).await?;
+    let trimmed = output.trim();
+    let servers = if trimmed.contains("No MCP servers configured") {
+        Vec::new()
+    } else {
+        parse_mcp_list_output(trimmed, &project_config)
+    };
+    cli.emit(&servers, |_| {})
+}
+
+/// `get`, `add-json` and `remove` are forwarded to `claude mcp` as-is
+fn mcp_passthrough(cli: &Cli) -> Result<(), String> {
+    let subcommand = cli.args.positional[1].as_str();
+    let name = cli.args.positional(2, "server name")?;
+    let mut args = vec![subcommand, name.as_str()];
+    let json_config;
+    let scope;
+    if subcommand == "add-json" {
+        json_config = cli.args.positional(3, "server JSON configuration")?;
+        scope = cli.args.option("scope").unwrap_or_else(|| "local".to_string());
+        args.extend([json_config.as_str(), "-s", scope.as_str()]);
+    }
+
+    let claude_path = cli.claude_binary()?;
+    let output = execute_claude_mcp_system(&claude_path, &args).map_err(|e| e.to_string())?;
+    let result = serde_json::json!({ "name": name, "output": output.trim() });
+    cli.emit(&result, |_| print!("{}", output))
+}
+
+async fn mcp_project_config(cli: &Cli) -> Result<(), String> {
+    let project_path = cli.args.positional(2, "project path")?;
+    let config = mcp_read_project_config(project_path).await?;
+    // The config is JSON either way
+    let json = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+    println!("{}", json);
+    Ok(())
+}
+
+fn usage_report(cli: &Cli) -> Result<(), String> {
+    let stats = match (cli.args.option("from"), cli.args.option("to")) {
+        (Some(from), Some(to)) => get_usage_by_date_range(from, to)?,
+        (None, None) => {
+            let days = cli.args.number("days")?.map(|d| d as u32);
+            get_usage_stats(days)?
+        }
+        _ => return Err("--from and --to must be given together".to_string()),
+    };
+    let stats = serde_json::to_value(&stats).map_err(|e| e.to_string())?;
+
+    cli.emit(&stats, |stats| {
+        let number = |key: &str| stats.get(key).and_then(|v| v.as_f64()).unwrap_or(0.0);
+        println!("Total cost:     ${:.4}", number("total_cost"));
+        println!("Total tokens:   {}", number("total_tokens") as u64);
+        println!("  input:        {}", number("total_input_tokens") as u64);
+        println!("  output:       {}", number("total_output_tokens") as u64);
+        println!("  cache write:  {}", number("total_cache_creation_tokens") as u64);
+        println!("  cache read:   {}", number("total_cache_read_tokens") as u64);
+        println!("Sessions:       {}", number("total_sessions") as u64);
+
+        let models = stats.get("by_model").and_then(|m| m.as_array()).cloned().unwrap_or_default();
+        if !models.is_empty() {
+            println!("\nBy model:");
+            for model in models {
+                println!(
+                    "  {:<40} ${:>10.4}  {:>12} tokens",
+                    model.get("model").and_then(|m| m.as_str()).unwrap_or("?"),
+                    model.get("total_cost").and_then(|c| c.as_f64()).unwrap_or(0.0),
+                    model.get("total_tokens").and_then(|t| t.as_u64()).unwrap_or(0)
+                );
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &[&str]) -> Result<Args, String> {
+        Args::parse(raw.iter().map(|arg| arg.to_string()))
+    }
+
+    fn cli(raw: &[&str]) -> Cli {
+        Cli {
+            args: parse(raw).unwrap(),
+            json: false,
+            app_dir: PathBuf::from("/nonexistent"),
+        }
+    }
+
+    #[test]
+    fn test_parse_options_and_flags() {
+        let args = parse(&[
+            "agents", "run", "reviewer", "--project", "/tmp/p", "--json", "--task=fix it", "--isolate",
+        ])
+        .unwrap();
+        assert_eq!(args.positional, ["agents", "run", "reviewer"]);
+        assert_eq!(args.option("project").as_deref(), Some("/tmp/p"));
+        assert_eq!(args.option("task").as_deref(), Some("fix it"));
+        assert!(args.flag("json"));
+        assert!(args.flag("isolate"));
+        assert!(!args.flag("help"));
+        assert_eq!(args.option("model"), None);
+
+        // Option values may themselves look like options
+        let args = parse(&["agents", "run", "x", "--task", "--help me"]).unwrap();
+        assert_eq!(args.option("task").as_deref(), Some("--help me"));
+        assert!(!args.flag("help"));
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_options() {
+        let cases = [
+            (&["sessions", "list", "--verbose"][..], "Unknown option --verbose"),
+            (&["sessions", "list", "--verbose=1"][..], "Unknown option --verbose"),
+            (&["sessions", "list", "--json=true"][..], "--json does not take a value"),
+            (&["sessions", "list", "--limit"][..], "Missing value for --limit"),
+            (&["agents", "run", "x", "--project"][..], "Missing value for --project"),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(parse(raw).unwrap_err(), expected, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn test_parse_double_dash_ends_options() {
+        let args = parse(&["mcp", "add-json", "--scope", "user", "--", "srv", "--json", "--"]).unwrap();
+        assert_eq!(args.positional, ["mcp", "add-json", "srv", "--json", "--"]);
+        assert_eq!(args.option("scope").as_deref(), Some("user"));
+        assert!(!args.flag("json"));
+
+        let args = parse(&["--"]).unwrap();
+        assert!(args.positional.is_empty());
+    }
+
+    #[test]
+    fn test_numbers() {
+        let args = parse(&["sessions", "list", "p", "--limit", "20", "--offset", "-1"]).unwrap();
+        assert_eq!(args.number("limit"), Ok(Some(20)));
+        assert_eq!(args.number("days"), Ok(None));
+        assert_eq!(
+            args.number("offset"),
+            Err("--offset expects a number, got '-1'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_budget_limits() {
+        let limits = budget_limits(&cli(&["--max-cost", "1.5", "--max-turns", "3", "--max-duration=60"])).unwrap();
+        assert_eq!(limits.max_cost_usd, Some(1.5));
+        assert_eq!(limits.max_turns, Some(3));
+        assert_eq!(limits.max_duration_secs, Some(60));
+        assert_eq!(limits.max_tokens, None);
+
+        let limits = budget_limits(&cli(&[])).unwrap();
+        assert_eq!(
+            (limits.max_cost_usd, limits.max_turns, limits.max_duration_secs),
+            (None, None, None)
+        );
+
+        let cases = [
+            (&["--max-cost", "cheap"][..], "--max-cost expects a number, got 'cheap'"),
+            (&["--max-turns", "2.5"][..], "--max-turns expects a number, got '2.5'"),
+            (&["--max-duration", "-5"][..], "--max-duration expects a number, got '-5'"),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(budget_limits(&cli(raw)).unwrap_err(), expected, "{:?}", raw);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown_command() {
+        for raw in [&["frobnicate"][..], &["agents", "delete"][..], &["usage", "extra"][..]] {
+            let err = dispatch(&cli(raw)).await.unwrap_err();
+            assert!(
+                err.starts_with(&format!("Unknown command: {}\n", raw.join(" "))),
+                "{}",
+                err
+            );
+        }
+    }
+}
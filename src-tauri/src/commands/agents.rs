@@ -3,10 +3,10 @@ use chrono;
 use dirs;
 use log::{debug, error, info, warn};
 use reqwest;
-use rusqlite::{params, Connection, Result as SqliteResult};
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
 use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::Mutex;
 use tauri::{AppHandle, Emitter, Manager, State};
@@ -14,6 +14,19 @@ use tauri_plugin_shell::ShellExt;
 use tauri_plugin_shell::process::CommandEvent;
 use tokio::io::{AsyncBufReadExt, BufReader as TokioBufReader};
 use tokio::process::Command;
+use crate::stream_json::StreamMessage;
+use super::project_index::{
+    find_indexed_session_file, find_session_file, project_dir_for_path, refresh_project_index,
+};
+use super::budget::{BudgetExceeded, BudgetGuard, BudgetLimits};
+use super::worktrees::{
+    create_worktree, discard_unused_worktree, set_worktree_agent_run, worktrees_dir, Worktree,
+};
+use crate::process::{spawn_in_own_group, terminate_process_tree};
+use super::notifications::{
+    agent_subject, notify_budget_exceeded, notify_finished, notify_permission_request,
+    permission_request_tool,
+};
 
 /// Finds the full path to the claude binary
 /// This is necessary because macOS apps have a limited PATH environment
@@ -50,7 +63,7 @@ pub struct AgentRun {
     pub model: String,
     pub project_path: String,
     pub session_id: String, // UUID session ID from Claude Code
-    pub status: String,     // 'pending', 'running', 'completed', 'failed', 'cancelled'
+    pub status: String,     // 'pending', 'running', 'completed', 'failed', 'cancelled', 'budget_exceeded', 'interrupted'
     pub pid: Option<u32>,
     pub process_started_at: Option<String>,
     pub created_at: String,
@@ -134,41 +147,33 @@ impl AgentRunMetrics {
         let mut end_time: Option<chrono::DateTime<chrono::Utc>> = None;
 
         for line in jsonl_content.lines() {
-            if let Ok(json) = serde_json::from_str::<JsonValue>(line) {
-                message_count += 1;
-
-                // Track timestamps
-                if let Some(timestamp_str) = json.get("timestamp").and_then(|t| t.as_str()) {
-                    if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(timestamp_str) {
-                        let utc_time = timestamp.with_timezone(&chrono::Utc);
-                        if start_time.map_or(true, |st| utc_time < st) {
-                            start_time = Some(utc_time);
-                        }
-                        if end_time.map_or(true, |et| utc_time > et) {
-                            end_time = Some(utc_time);
-                        }
-                    }
-                }
-
-                // Extract token usage - check both top-level and nested message.usage
-                let usage = json
-                    .get("usage")
-                    .or_else(|| json.get("message").and_then(|m| m.get("usage")));
-
-                if let Some(usage) = usage {
-                    if let Some(input_tokens) = usage.get("input_tokens").and_then(|t| t.as_i64()) {
-                        total_tokens += input_tokens;
+            let Some(msg) = StreamMessage::parse(line) else {
+                continue;
+            };
+            message_count += 1;
+
+            // Track timestamps
+            if let Some(timestamp_str) = msg.timestamp() {
+                if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(timestamp_str) {
+                    let utc_time = timestamp.with_timezone(&chrono::Utc);
+                    if start_time.map_or(true, |st| utc_time < st) {
+                        start_time = Some(utc_time);
                     }
-                    if let Some(output_tokens) = usage.get("output_tokens").and_then(|t| t.as_i64())
-                    {
-                        total_tokens += output_tokens;
+                    if end_time.map_or(true, |et| utc_time > et) {
+                        end_time = Some(utc_time);
                     }
                 }
+            }
 
-                // Extract cost information
-                if let Some(cost) = json.get("cost").and_then(|c| c.as_f64()) {
-                    cost_usd += cost;
-                }
+            // Token usage of an assistant message, or the totals of the result entry
+            if let Some(usage) = msg.usage() {
+                total_tokens += (usage.input_tokens.unwrap_or(0)
+                    + usage.output_tokens.unwrap_or(0)) as i64;
+            }
+
+            // Cost reported by Claude (costUSD or the result's total_cost_usd)
+            if let Some(cost) = msg.reported_cost_usd() {
+                cost_usd += cost;
             }
         }
 
@@ -195,23 +200,26 @@ impl AgentRunMetrics {
 }
 
 /// Read JSONL content from a session file
-pub async fn read_session_jsonl(session_id: &str, project_path: &str) -> Result<String, String> {
-    let claude_dir = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude")
-        .join("projects");
-
-    // Encode project path to match Claude Code's directory naming
-    let encoded_project = project_path.replace('/', "-");
-    let project_dir = claude_dir.join(&encoded_project);
-    let session_file = project_dir.join(format!("{}.jsonl", session_id));
-
-    if !session_file.exists() {
+pub async fn read_session_jsonl(
+    db: &AgentDb,
+    session_id: &str,
+    project_path: &str,
+) -> Result<String, String> {
+    let session_file = find_session_file(db, session_id, Some(project_path))?;
+    read_session_file(session_file, session_id, project_path).await
+}
+
+async fn read_session_file(
+    session_file: Option<PathBuf>,
+    session_id: &str,
+    project_path: &str,
+) -> Result<String, String> {
+    let Some(session_file) = session_file else {
         return Err(format!(
-            "Session file not found: {}",
-            session_file.display()
+            "Session file not found: {} (project {})",
+            session_id, project_path
         ));
-    }
+    };
 
     match tokio::fs::read_to_string(&session_file).await {
         Ok(content) => Ok(content),
@@ -219,9 +227,8 @@ pub async fn read_session_jsonl(session_id: &str, project_path: &str) -> Result<
     }
 }
 
-/// Get agent run with real-time metrics
-pub async fn get_agent_run_with_metrics(run: AgentRun) -> AgentRunWithMetrics {
-    match read_session_jsonl(&run.session_id, &run.project_path).await {
+fn with_session_jsonl(run: AgentRun, jsonl: Result<String, String>) -> AgentRunWithMetrics {
+    match jsonl {
         Ok(jsonl_content) => {
             let metrics = AgentRunMetrics::from_jsonl(&jsonl_content);
             AgentRunWithMetrics {
@@ -241,6 +248,20 @@ pub async fn get_agent_run_with_metrics(run: AgentRun) -> AgentRunWithMetrics {
     }
 }
 
+/// Get agent run with real-time metrics
+pub async fn get_agent_run_with_metrics(db: &AgentDb, run: AgentRun) -> AgentRunWithMetrics {
+    // Pending and failed runs may never have reported a session
+    if run.session_id.is_empty() {
+        return AgentRunWithMetrics {
+            run,
+            metrics: None,
+            output: None,
+        };
+    }
+    let jsonl = read_session_jsonl(db, &run.session_id, &run.project_path).await;
+    with_session_jsonl(run, jsonl)
+}
+
 /// Initialize the agents database
 pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
     let app_dir = app
@@ -250,14 +271,25 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
             rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN),
             Some(format!("Failed to get app data dir: {}", e))
         ))?;
-    std::fs::create_dir_all(&app_dir).map_err(|e| rusqlite::Error::SqliteFailure(
+    open_database(&app_dir)
+}
+
+/// Opens (and migrates) `agents.db` inside `app_dir` without needing a running
+/// app, so the headless CLI shares the same database as the GUI
+pub fn open_database(app_dir: &std::path::Path) -> SqliteResult<Connection> {
+    std::fs::create_dir_all(app_dir).map_err(|e| rusqlite::Error::SqliteFailure(
         rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN),
         Some(format!("Failed to create app data dir: {}", e))
     ))?;
 
     let db_path = app_dir.join("agents.db");
     let conn = Connection::open(db_path)?;
+    create_schema(&conn)?;
+    Ok(conn)
+}
 
+/// Creates all tables, indexes and triggers, migrating older layouts in place
+pub(crate) fn create_schema(conn: &Connection) -> SqliteResult<()> {
     // Create agents table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS agents (
@@ -616,6 +648,198 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
     }
 
 
+    // Create prompt queue tables (one queue per project path)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS prompt_queue_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            project_path TEXT NOT NULL,
+            prompt TEXT NOT NULL,
+            model TEXT NOT NULL,
+            position INTEGER NOT NULL DEFAULT 0,
+            status TEXT NOT NULL DEFAULT 'pending',
+            session_id TEXT,
+            error TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            started_at TEXT,
+            completed_at TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_prompt_queue_items_project
+         ON prompt_queue_items(project_path, position)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS prompt_queues (
+            project_path TEXT PRIMARY KEY,
+            paused BOOLEAN NOT NULL DEFAULT 0,
+            session_id TEXT,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    // Create project index mapping ~/.claude/projects directories to real paths
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS project_index (
+            project_id TEXT PRIMARY KEY,
+            project_path TEXT NOT NULL,
+            source TEXT NOT NULL DEFAULT 'cwd',
+            dir_modified INTEGER NOT NULL DEFAULT 0,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_project_index_path ON project_index(project_path)",
+        [],
+    )?;
+
+    // Create session metadata cache, keyed by file and invalidated by size/mtime
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS session_cache (
+            project_id TEXT NOT NULL,
+            session_id TEXT NOT NULL,
+            size INTEGER NOT NULL DEFAULT 0,
+            modified INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL DEFAULT 0,
+            first_message TEXT,
+            message_timestamp TEXT,
+            PRIMARY KEY (project_id, session_id)
+        )",
+        [],
+    )?;
+
+    // Create session metadata table for user-assigned titles, tags, pins and notes
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS session_metadata (
+            session_id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL,
+            title TEXT,
+            tags TEXT NOT NULL DEFAULT '[]',
+            pinned BOOLEAN NOT NULL DEFAULT 0,
+            archived BOOLEAN NOT NULL DEFAULT 0,
+            note TEXT,
+            forked_from_session_id TEXT,
+            forked_from_message_uuid TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    let _ = conn.execute("ALTER TABLE session_metadata ADD COLUMN forked_from_session_id TEXT", []);
+    let _ = conn.execute("ALTER TABLE session_metadata ADD COLUMN forked_from_message_uuid TEXT", []);
+
+    // Create full-text index over session transcripts. session_search_files
+    // remembers how far into each JSONL we have indexed so growing files are
+    // picked up incrementally.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS session_search_files (
+            file_path TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL,
+            session_id TEXT NOT NULL,
+            project_path TEXT,
+            size INTEGER NOT NULL DEFAULT 0,
+            modified INTEGER NOT NULL DEFAULT 0,
+            indexed_offset INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS session_search_fts USING fts5(
+            content,
+            kind UNINDEXED,
+            session_id UNINDEXED,
+            project_id UNINDEXED,
+            project_path UNINDEXED,
+            message_uuid UNINDEXED,
+            timestamp UNINDEXED,
+            tokenize = 'unicode61'
+        )",
+        [],
+    )?;
+
+    // Create worktrees table tracking git worktrees used to isolate runs
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS worktrees (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            repo_path TEXT NOT NULL,
+            worktree_path TEXT NOT NULL UNIQUE,
+            branch TEXT NOT NULL,
+            base_branch TEXT NOT NULL,
+            base_commit TEXT NOT NULL,
+            session_id TEXT,
+            agent_run_id INTEGER,
+            status TEXT NOT NULL DEFAULT 'active',
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            generated_settings BOOLEAN NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+    let _ = conn.execute(
+        "ALTER TABLE worktrees ADD COLUMN generated_settings BOOLEAN NOT NULL DEFAULT 0",
+        [],
+    );
+
+    // Create config_versions table recording every config file write
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS config_versions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            file_path TEXT NOT NULL,
+            content TEXT,
+            source TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_config_versions_path ON config_versions(file_path, id)",
+        [],
+    )?;
+
+    // Create scheduler tables for recurring agent runs and session prompts
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schedules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            cron_expression TEXT NOT NULL,
+            target_type TEXT NOT NULL,
+            agent_id INTEGER,
+            project_path TEXT NOT NULL,
+            prompt TEXT NOT NULL,
+            model TEXT,
+            catch_up_policy TEXT NOT NULL DEFAULT 'run_once',
+            enabled BOOLEAN NOT NULL DEFAULT 1,
+            last_run_at TEXT,
+            next_run_at TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schedule_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            schedule_id INTEGER NOT NULL,
+            scheduled_for TEXT NOT NULL,
+            trigger TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'running',
+            agent_run_id INTEGER,
+            session_id TEXT,
+            error TEXT,
+            started_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            completed_at TEXT,
+            FOREIGN KEY (schedule_id) REFERENCES schedules(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_schedule_runs_schedule ON schedule_runs(schedule_id, id)",
+        [],
+    )?;
+
     // Create trigger to update the updated_at timestamp
     conn.execute(
         "CREATE TRIGGER IF NOT EXISTS update_app_settings_timestamp
@@ -648,14 +872,18 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         [],
     )?;
 
-    Ok(conn)
+    Ok(())
 }
 
 /// List all agents
 #[tauri::command]
 pub async fn list_agents(db: State<'_, AgentDb>) -> Result<Vec<Agent>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
+    list_agents_internal(&conn)
+}
 
+/// Internal function to list agents, shared with the CLI
+pub(crate) fn list_agents_internal(conn: &Connection) -> Result<Vec<Agent>, String> {
     let mut stmt = conn
         .prepare("SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, source, created_at, updated_at FROM agents ORDER BY created_at DESC")
         .map_err(|e| e.to_string())?;
@@ -967,8 +1195,9 @@ pub async fn get_agent_run_with_real_time_metrics(
     db: State<'_, AgentDb>,
     id: i64,
 ) -> Result<AgentRunWithMetrics, String> {
+    let db_ref = db.inner();
     let run = get_agent_run(db, id).await?;
-    Ok(get_agent_run_with_metrics(run).await)
+    Ok(get_agent_run_with_metrics(db_ref, run).await)
 }
 
 /// List agent runs with real-time metrics from JSONL
@@ -977,12 +1206,34 @@ pub async fn list_agent_runs_with_metrics(
     db: State<'_, AgentDb>,
     agent_id: Option<i64>,
 ) -> Result<Vec<AgentRunWithMetrics>, String> {
+    let db_ref = db.inner();
     let runs = list_agent_runs(db, agent_id).await?;
     let mut runs_with_metrics = Vec::new();
 
+    // Transcripts not where the index points trigger one rescan for the
+    // whole listing, not one per run
+    let mut rescanned = false;
     for run in runs {
-        let run_with_metrics = get_agent_run_with_metrics(run).await;
-        runs_with_metrics.push(run_with_metrics);
+        if run.session_id.is_empty() {
+            runs_with_metrics.push(AgentRunWithMetrics {
+                run,
+                metrics: None,
+                output: None,
+            });
+            continue;
+        }
+        let mut session_file =
+            find_indexed_session_file(db_ref, &run.session_id, Some(&run.project_path))?;
+        if session_file.is_none() && !rescanned {
+            rescanned = true;
+            if let Err(e) = refresh_project_index(db_ref) {
+                log::warn!("Failed to refresh project index: {}", e);
+            }
+            session_file =
+                find_indexed_session_file(db_ref, &run.session_id, Some(&run.project_path))?;
+        }
+        let jsonl = read_session_file(session_file, &run.session_id, &run.project_path).await;
+        runs_with_metrics.push(with_session_jsonl(run, jsonl));
     }
 
     Ok(runs_with_metrics)
@@ -996,6 +1247,8 @@ pub async fn execute_agent(
     project_path: String,
     task: String,
     model: Option<String>,
+    isolate: Option<bool>,
+    budget: Option<BudgetLimits>,
     db: State<'_, AgentDb>,
     registry: State<'_, crate::process::ProcessRegistryState>,
 ) -> Result<i64, String> {
@@ -1005,7 +1258,94 @@ pub async fn execute_agent(
     let agent = get_agent(db.clone(), agent_id).await?;
     let execution_model = model.unwrap_or(agent.model.clone());
 
-    // Create .claude/settings.json with agent hooks if it doesn't exist
+    // Optionally run in a dedicated git worktree instead of the project itself
+    let worktree = if isolate.unwrap_or(false) {
+        Some(create_worktree(&db, &worktrees_dir(&app)?, &project_path, &agent.name)?)
+    } else {
+        None
+    };
+    let project_path = worktree
+        .as_ref()
+        .map(|w| w.worktree_path.clone())
+        .unwrap_or(project_path);
+
+    let generated_settings = write_agent_hooks_settings(&agent, &project_path)
+        .map_err(|e| abandon_agent_run(&db, None, worktree.as_ref(), e))?;
+
+    // Create a new run record
+    let inserted = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![agent_id, agent.name, agent.icon, task, execution_model, project_path, ""],
+        )
+        .map_err(|e| e.to_string())
+        .and_then(|_| {
+            let run_id = conn.last_insert_rowid();
+            if let Some(worktree) = &worktree {
+                set_worktree_agent_run(&conn, worktree.id, run_id, generated_settings)?;
+            }
+            Ok(run_id)
+        })
+    };
+    let run_id = inserted.map_err(|e| abandon_agent_run(&db, None, worktree.as_ref(), e))?;
+
+    // Find Claude binary
+    info!("Running agent '{}'", agent.name);
+    let claude_path = match find_claude_binary(&app) {
+        Ok(path) => path,
+        Err(e) => {
+            error!("Failed to find claude binary: {}", e);
+            return Err(abandon_agent_run(&db, Some(run_id), worktree.as_ref(), e));
+        }
+    };
+
+    // Build arguments
+    let args = build_agent_args(&agent, &task, &execution_model);
+
+    // Execute based on whether we should use sidecar or system binary
+    let spawned = if should_use_sidecar(&claude_path) {
+        spawn_agent_sidecar(app, run_id, agent_id, agent.name.clone(), args, project_path, task, execution_model, budget, db.clone(), registry).await
+    } else {
+        let run = AgentRunInfo {
+            run_id,
+            agent_id,
+            agent_name: agent.name.clone(),
+            project_path,
+            task,
+            model: execution_model,
+        };
+        spawn_agent_system(app, run, claude_path, args, budget, registry).await
+    };
+    spawned.map_err(|e| abandon_agent_run(&db, Some(run_id), worktree.as_ref(), e))
+}
+
+/// Cleans up after an agent run that failed to start: marks its run record
+/// failed and removes the worktree created for it. Returns `error`.
+fn abandon_agent_run(
+    db: &AgentDb,
+    run_id: Option<i64>,
+    worktree: Option<&Worktree>,
+    error: String,
+) -> String {
+    if let Some(run_id) = run_id {
+        if let Ok(conn) = db.0.lock() {
+            let _ = conn.execute(
+                "UPDATE agent_runs SET status = 'failed', completed_at = CURRENT_TIMESTAMP
+                 WHERE id = ?1 AND status IN ('pending', 'running')",
+                params![run_id],
+            );
+        }
+    }
+    if let Some(worktree) = worktree {
+        discard_unused_worktree(db, worktree);
+    }
+    error
+}
+
+/// Creates .claude/settings.json with the agent's hooks if it doesn't exist.
+/// Returns whether the file was written.
+pub(crate) fn write_agent_hooks_settings(agent: &Agent, project_path: &str) -> Result<bool, String> {
     if let Some(hooks_json) = &agent.hooks {
         let claude_dir = std::path::Path::new(&project_path).join(".claude");
         let settings_path = claude_dir.join("settings.json");
@@ -1036,52 +1376,34 @@ pub async fn execute_agent(
                 .map_err(|e| format!("Failed to write settings.json: {}", e))?;
 
             info!("Created settings.json with agent hooks at: {:?}", settings_path);
-        } else {
-            info!("settings.json already exists at: {:?}", settings_path);
+            return Ok(true);
         }
+        info!("settings.json already exists at: {:?}", settings_path);
     }
+    Ok(false)
+}
 
-    // Create a new run record
-    let run_id = {
-        let conn = db.0.lock().map_err(|e| e.to_string())?;
-        conn.execute(
-            "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![agent_id, agent.name, agent.icon, task, execution_model, project_path, ""],
-        )
-        .map_err(|e| e.to_string())?;
-        conn.last_insert_rowid()
-    };
-
-    // Find Claude binary
-    info!("Running agent '{}'", agent.name);
-    let claude_path = match find_claude_binary(&app) {
-        Ok(path) => path,
-        Err(e) => {
-            error!("Failed to find claude binary: {}", e);
-            return Err(e);
-        }
-    };
-
-    // Build arguments
-    let args = vec![
+/// Builds the Claude Code arguments for running an agent on a task
+pub(crate) fn build_agent_args(agent: &Agent, task: &str, model: &str) -> Vec<String> {
+    vec![
         "-p".to_string(),
-        task.clone(),
+        task.to_string(),
         "--system-prompt".to_string(),
         agent.system_prompt.clone(),
         "--model".to_string(),
-        execution_model.clone(),
+        model.to_string(),
         "--output-format".to_string(),
         "stream-json".to_string(),
         "--verbose".to_string(),
         "--dangerously-skip-permissions".to_string(),
-    ];
+    ]
+}
 
-    // Execute based on whether we should use sidecar or system binary
-    if should_use_sidecar(&claude_path) {
-        spawn_agent_sidecar(app, run_id, agent_id, agent.name.clone(), args, project_path, task, execution_model, db, registry).await
-    } else {
-        spawn_agent_system(app, run_id, agent_id, agent.name.clone(), claude_path, args, project_path, task, execution_model, db, registry).await
-    }
+/// Tells the frontend an agent run was stopped because it hit a budget cap
+fn emit_agent_budget_exceeded(app: &AppHandle, run_id: i64, violation: &BudgetExceeded) {
+    let _ = app.emit(&format!("agent-budget-exceeded:{}", run_id), violation);
+    let _ = app.emit("agent-budget-exceeded", violation);
+    notify_budget_exceeded(app, &agent_subject(app, run_id), violation);
 }
 
 /// Determines whether to use sidecar or system binary execution for agents
@@ -1121,12 +1443,14 @@ fn create_agent_sidecar_command(
 }
 
 /// Creates a system binary command for agent execution
-fn create_agent_system_command(
+pub(crate) fn create_agent_system_command(
     claude_path: &str,
     args: Vec<String>,
     project_path: &str,
 ) -> Command {
     let mut cmd = create_command_with_env(claude_path);
+    // Own process group, so cancelling reaches everything the agent starts
+    spawn_in_own_group(&mut cmd);
 
     // Add all arguments
     for arg in args {
@@ -1141,6 +1465,52 @@ fn create_agent_system_command(
     cmd
 }
 
+/// Final status of an agent run whose process has exited
+fn run_status(budget_exceeded: bool, exited_ok: bool) -> &'static str {
+    if budget_exceeded {
+        "budget_exceeded"
+    } else if exited_ok {
+        "completed"
+    } else {
+        "failed"
+    }
+}
+
+/// Records how an agent run ended, unless it is no longer running (e.g. it
+/// was cancelled while Claude was shutting down). Returns whether the run
+/// was still running, i.e. whether this outcome should be reported.
+fn finish_agent_run(db_path: &std::path::Path, run_id: i64, session_id: &str, status: &str) -> bool {
+    let conn = match Connection::open(db_path) {
+        Ok(conn) => conn,
+        Err(e) => {
+            error!("❌ Failed to open database to finish agent run {}: {}", run_id, e);
+            return true;
+        }
+    };
+    if !session_id.is_empty() {
+        if let Err(e) = conn.execute(
+            "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
+            params![session_id, run_id],
+        ) {
+            error!("❌ Failed to update agent run {} with session ID: {}", run_id, e);
+        }
+    }
+    match conn.execute(
+        "UPDATE agent_runs SET status = ?1, completed_at = CURRENT_TIMESTAMP WHERE id = ?2 AND status = 'running'",
+        params![status, run_id],
+    ) {
+        Ok(0) => {
+            info!("Agent run {} is no longer running, keeping its status", run_id);
+            false
+        }
+        Ok(_) => true,
+        Err(e) => {
+            error!("❌ Failed to update status of agent run {}: {}", run_id, e);
+            true
+        }
+    }
+}
+
 /// Spawn agent using sidecar command
 async fn spawn_agent_sidecar(
     app: AppHandle,
@@ -1151,6 +1521,7 @@ async fn spawn_agent_sidecar(
     project_path: String,
     task: String,
     execution_model: String,
+    budget: Option<BudgetLimits>,
     db: State<'_, AgentDb>,
     registry: State<'_, crate::process::ProcessRegistryState>,
 ) -> Result<i64, String> {
@@ -1168,6 +1539,7 @@ async fn spawn_agent_sidecar(
     let pid = child.pid();
     let now = chrono::Utc::now().to_rfc3339();
     info!("✅ Claude sidecar process spawned successfully with PID: {}", pid);
+    let budget_guard = BudgetGuard::new(budget, &execution_model, pid);
 
     // Update the database with PID and status
     {
@@ -1186,9 +1558,8 @@ async fn spawn_agent_sidecar(
         .map_err(|e| format!("Failed to get app data dir: {}", e))?;
     let db_path = app_dir.join("agents.db");
 
-    // Shared state for collecting session ID and live output
+    // Shared state for collecting the session ID; live output goes to the registry
     let session_id = std::sync::Arc::new(Mutex::new(String::new()));
-    let live_output = std::sync::Arc::new(Mutex::new(String::new()));
     let _start_time = std::time::Instant::now();
 
     // Register the process in the registry
@@ -1206,10 +1577,9 @@ async fn spawn_agent_sidecar(
         .map_err(|e| format!("Failed to register sidecar process: {}", e))?;
     info!("📋 Registered sidecar process in registry");
 
-    // Handle sidecar events
-    let app_handle = app.clone();
+    // Handle sidecar events, reporting them like the system binary's
+    let events = AppAgentEvents(app.clone());
     let session_id_clone = session_id.clone();
-    let live_output_clone = live_output.clone();
     let registry_clone = registry.0.clone();
     let first_output = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
     let first_output_clone = first_output.clone();
@@ -1218,6 +1588,7 @@ async fn spawn_agent_sidecar(
     tokio::spawn(async move {
         info!("📖 Starting to read Claude sidecar events...");
         let mut line_count = 0;
+        let mut permission_notified = false;
 
         while let Some(event) = receiver.recv().await {
             match event {
@@ -1240,41 +1611,31 @@ async fn spawn_agent_sidecar(
                         debug!("sidecar stdout[{}]: {}", line_count, line);
                     }
 
-                    // Store live output
-                    if let Ok(mut output) = live_output_clone.lock() {
-                        output.push_str(&line);
-                        output.push('\n');
-                    }
-
-                    // Also store in process registry
+                    // Store live output in the process registry
                     let _ = registry_clone.append_live_output(run_id, &line);
 
                     // Extract session ID from JSONL output
-                    if let Ok(json) = serde_json::from_str::<JsonValue>(&line) {
-                        // Claude Code uses "session_id" (underscore), not "sessionId"
-                        if json.get("type").and_then(|t| t.as_str()) == Some("system") &&
-                           json.get("subtype").and_then(|s| s.as_str()) == Some("init") {
-                            if let Some(sid) = json.get("session_id").and_then(|s| s.as_str()) {
-                                if let Ok(mut current_session_id) = session_id_clone.lock() {
-                                    if current_session_id.is_empty() {
-                                        *current_session_id = sid.to_string();
-                                        info!("🔑 Extracted session ID: {}", sid);
-
-                                        // Update database immediately with session ID
-                                        if let Ok(conn) = Connection::open(&db_path_for_sidecar) {
-                                            match conn.execute(
-                                                "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
-                                                params![sid, run_id],
-                                            ) {
-                                                Ok(rows) => {
-                                                    if rows > 0 {
-                                                        info!("✅ Updated agent run {} with session ID immediately", run_id);
-                                                    }
-                                                }
-                                                Err(e) => {
-                                                    error!("❌ Failed to update session ID immediately: {}", e);
+                    if let Some(msg) = StreamMessage::parse(&line) {
+                        if let Some(sid) = msg.init_session_id() {
+                            if let Ok(mut current_session_id) = session_id_clone.lock() {
+                                if current_session_id.is_empty() {
+                                    *current_session_id = sid.to_string();
+                                    info!("🔑 Extracted session ID: {}", sid);
+
+                                    // Update database immediately with session ID
+                                    if let Ok(conn) = Connection::open(&db_path_for_sidecar) {
+                                        match conn.execute(
+                                            "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
+                                            params![sid, run_id],
+                                        ) {
+                                            Ok(rows) => {
+                                                if rows > 0 {
+                                                    info!("✅ Updated agent run {} with session ID immediately", run_id);
                                                 }
                                             }
+                                            Err(e) => {
+                                                error!("❌ Failed to update session ID immediately: {}", e);
+                                            }
                                         }
                                     }
                                 }
@@ -1283,14 +1644,25 @@ async fn spawn_agent_sidecar(
                     }
 
                     // Emit the line to the frontend
-                    let _ = app_handle.emit(&format!("agent-output:{}", run_id), &line);
-                    let _ = app_handle.emit("agent-output", &line);
+                    events.output(run_id, &line);
+
+                    // Stop the agent once it runs over budget
+                    if let Some(guard) = &budget_guard {
+                        guard.observe(&line);
+                    }
+
+                    // Let the user know once if the agent is blocked on a permission
+                    if !permission_notified {
+                        if let Some(tool) = permission_request_tool(&line) {
+                            permission_notified = true;
+                            events.permission_request(run_id, &tool);
+                        }
+                    }
                 }
                 CommandEvent::Stderr(line_bytes) => {
                     let line = String::from_utf8_lossy(&line_bytes);
                     error!("sidecar stderr: {}", line);
-                    let _ = app_handle.emit(&format!("agent-error:{}", run_id), &line);
-                    let _ = app_handle.emit("agent-error", &line);
+                    events.error(run_id, &line);
                 }
                 CommandEvent::Terminated(payload) => {
                     info!("Claude sidecar process terminated with code: {:?}", payload.code);
@@ -1302,17 +1674,27 @@ async fn spawn_agent_sidecar(
                         String::new()
                     };
 
-                    // Update database with completion
-                    if let Ok(conn) = Connection::open(&db_path) {
-                        let _ = conn.execute(
-                            "UPDATE agent_runs SET session_id = ?1, status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = ?2",
-                            params![extracted_session_id, run_id],
-                        );
-                    }
-
-                    let success = payload.code.unwrap_or(1) == 0;
-                    let _ = app.emit("agent-complete", success);
-                    let _ = app.emit(&format!("agent-complete:{}", run_id), success);
+                    let budget_exceeded = budget_guard.as_ref().and_then(|guard| {
+                        guard.finish();
+                        guard.exceeded()
+                    });
+                    // A missing code means the process was killed by a signal
+                    let exited_ok = payload.code == Some(0);
+                    let status = run_status(budget_exceeded.is_some(), exited_ok);
+
+                    // Update database with completion; a cancelled run keeps its status
+                    let finished = finish_agent_run(&db_path, run_id, &extracted_session_id, status);
+
+                    events.complete(
+                        run_id,
+                        &AgentRunOutcome {
+                            session_id: extracted_session_id,
+                            status,
+                            exited_ok,
+                            finished,
+                            budget_exceeded,
+                        },
+                    );
                     break;
                 }
                 _ => {}
@@ -1325,22 +1707,121 @@ async fn spawn_agent_sidecar(
     Ok(run_id)
 }
 
+/// Identifies the agent run a spawned process belongs to
+pub(crate) struct AgentRunInfo {
+    pub run_id: i64,
+    pub agent_id: i64,
+    pub agent_name: String,
+    pub project_path: String,
+    pub task: String,
+    pub model: String,
+}
+
+/// How a monitored agent run ended
+#[derive(Debug, Clone)]
+pub(crate) struct AgentRunOutcome {
+    pub session_id: String,
+    /// 'completed', 'failed' or 'budget_exceeded'
+    pub status: &'static str,
+    /// Whether the process exited successfully
+    pub exited_ok: bool,
+    /// False when the run was no longer running, e.g. because it was
+    /// cancelled, in which case its status was left alone
+    pub finished: bool,
+    pub budget_exceeded: Option<BudgetExceeded>,
+}
+
+/// Receives what a monitored agent run produces. The app forwards it to the
+/// frontend and desktop notifications; the CLI prints it.
+pub(crate) trait AgentRunEvents: Send + Sync + 'static {
+    fn output(&self, run_id: i64, line: &str);
+    fn error(&self, run_id: i64, line: &str);
+    /// Claude is blocked on a permission for `tool`; reported once per run
+    fn permission_request(&self, run_id: i64, tool: &str);
+    fn complete(&self, run_id: i64, outcome: &AgentRunOutcome);
+}
+
+/// Forwards agent run events to the frontend and desktop notifications
+struct AppAgentEvents(AppHandle);
+
+impl AgentRunEvents for AppAgentEvents {
+    fn output(&self, run_id: i64, line: &str) {
+        // Emit the line to the frontend with run_id for isolation
+        let _ = self.0.emit(&format!("agent-output:{}", run_id), line);
+        // Also emit to the generic event for backward compatibility
+        let _ = self.0.emit("agent-output", line);
+    }
+
+    fn error(&self, run_id: i64, line: &str) {
+        let _ = self.0.emit(&format!("agent-error:{}", run_id), line);
+        let _ = self.0.emit("agent-error", line);
+    }
+
+    fn permission_request(&self, run_id: i64, tool: &str) {
+        notify_permission_request(&self.0, &agent_subject(&self.0, run_id), tool);
+    }
+
+    fn complete(&self, run_id: i64, outcome: &AgentRunOutcome) {
+        if outcome.finished {
+            info!(
+                "Agent run {} {} (session {})",
+                run_id, outcome.status, outcome.session_id
+            );
+        }
+        if let Some(violation) = &outcome.budget_exceeded {
+            emit_agent_budget_exceeded(&self.0, run_id, violation);
+        }
+        let success = outcome.finished && outcome.exited_ok && outcome.budget_exceeded.is_none();
+        let _ = self.0.emit("agent-complete", success);
+        let _ = self.0.emit(&format!("agent-complete:{}", run_id), success);
+        if outcome.finished && outcome.budget_exceeded.is_none() {
+            notify_finished(&self.0, &agent_subject(&self.0, run_id), outcome.exited_ok);
+        }
+    }
+}
+
 /// Spawn agent using system binary command
 async fn spawn_agent_system(
     app: AppHandle,
-    run_id: i64,
-    agent_id: i64,
-    agent_name: String,
+    run: AgentRunInfo,
     claude_path: String,
     args: Vec<String>,
-    project_path: String,
-    task: String,
-    execution_model: String,
-    db: State<'_, AgentDb>,
+    budget: Option<BudgetLimits>,
     registry: State<'_, crate::process::ProcessRegistryState>,
 ) -> Result<i64, String> {
-    // Build the command
-    let mut cmd = create_agent_system_command(&claude_path, args, &project_path);
+    let cmd = create_agent_system_command(&claude_path, args, &run.project_path);
+    let db_path = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))?
+        .join("agents.db");
+    let run_id = run.run_id;
+    // The monitor reports the outcome through the events; nothing waits on it here
+    let _monitor = run_agent_process(
+        cmd,
+        run,
+        budget,
+        db_path,
+        registry.0.clone(),
+        std::sync::Arc::new(AppAgentEvents(app)),
+    )?;
+    Ok(run_id)
+}
+
+/// Spawns `cmd` for an agent run that already has a row in `agent_runs` and
+/// marks the run as running. A background task then streams its output to
+/// `events` and the process registry, enforces the budget and records the
+/// final status; the returned handle resolves with the outcome. Needs no
+/// `AppHandle`, so the CLI runs agents through it as well.
+pub(crate) fn run_agent_process(
+    mut cmd: Command,
+    run: AgentRunInfo,
+    budget: Option<BudgetLimits>,
+    db_path: std::path::PathBuf,
+    registry: std::sync::Arc<crate::process::ProcessRegistry>,
+    events: std::sync::Arc<dyn AgentRunEvents>,
+) -> Result<tokio::task::JoinHandle<AgentRunOutcome>, String> {
+    let run_id = run.run_id;
 
     // Spawn the process
     info!("🚀 Spawning Claude system process...");
@@ -1355,16 +1836,18 @@ async fn spawn_agent_system(
     let pid = child.id().unwrap_or(0);
     let now = chrono::Utc::now().to_rfc3339();
     info!("✅ Claude process spawned successfully with PID: {}", pid);
+    let budget_guard = BudgetGuard::new(budget, &run.model, pid);
 
     // Update the database with PID and status
-    {
-        let conn = db.0.lock().map_err(|e| e.to_string())?;
-        conn.execute(
-            "UPDATE agent_runs SET status = 'running', pid = ?1, process_started_at = ?2 WHERE id = ?3",
-            params![pid as i64, now, run_id],
-        ).map_err(|e| e.to_string())?;
-        info!("📝 Updated database with running status and PID");
-    }
+    Connection::open(&db_path)
+        .and_then(|conn| {
+            conn.execute(
+                "UPDATE agent_runs SET status = 'running', pid = ?1, process_started_at = ?2 WHERE id = ?3",
+                params![pid as i64, now, run_id],
+            )
+        })
+        .map_err(|e| e.to_string())?;
+    info!("📝 Updated database with running status and PID");
 
     // Get stdout and stderr
     let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
@@ -1375,31 +1858,24 @@ async fn spawn_agent_system(
     let stdout_reader = TokioBufReader::new(stdout);
     let stderr_reader = TokioBufReader::new(stderr);
 
-    // Create variables we need for the spawned tasks
-    let app_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
-    let db_path = app_dir.join("agents.db");
-
-    // Shared state for collecting session ID and live output
+    // Shared state for collecting the session ID; live output goes to the registry
     let session_id = std::sync::Arc::new(Mutex::new(String::new()));
-    let live_output = std::sync::Arc::new(Mutex::new(String::new()));
     let start_time = std::time::Instant::now();
 
     // Spawn tasks to read stdout and stderr
-    let app_handle = app.clone();
+    let events_stdout = events.clone();
     let session_id_clone = session_id.clone();
-    let live_output_clone = live_output.clone();
-    let registry_clone = registry.0.clone();
+    let registry_clone = registry.clone();
     let first_output = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
     let first_output_clone = first_output.clone();
     let db_path_for_stdout = db_path.clone(); // Clone the db_path for the stdout task
+    let budget_guard_clone = budget_guard.clone();
 
     let stdout_task = tokio::spawn(async move {
         info!("📖 Starting to read Claude stdout...");
         let mut lines = stdout_reader.lines();
         let mut line_count = 0;
+        let mut permission_notified = false;
 
         while let Ok(Some(line)) = lines.next_line().await {
             line_count += 1;
@@ -1419,41 +1895,31 @@ async fn spawn_agent_system(
                 debug!("stdout[{}]: {}", line_count, line);
             }
 
-            // Store live output in both local buffer and registry
-            if let Ok(mut output) = live_output_clone.lock() {
-                output.push_str(&line);
-                output.push('\n');
-            }
-
-            // Also store in process registry for cross-session access
+            // Store live output in the process registry for cross-session access
             let _ = registry_clone.append_live_output(run_id, &line);
 
             // Extract session ID from JSONL output
-            if let Ok(json) = serde_json::from_str::<JsonValue>(&line) {
-                // Claude Code uses "session_id" (underscore), not "sessionId"
-                if json.get("type").and_then(|t| t.as_str()) == Some("system") &&
-                   json.get("subtype").and_then(|s| s.as_str()) == Some("init") {
-                    if let Some(sid) = json.get("session_id").and_then(|s| s.as_str()) {
-                        if let Ok(mut current_session_id) = session_id_clone.lock() {
-                            if current_session_id.is_empty() {
-                                *current_session_id = sid.to_string();
-                                info!("🔑 Extracted session ID: {}", sid);
-
-                                // Update database immediately with session ID
-                                if let Ok(conn) = Connection::open(&db_path_for_stdout) {
-                                    match conn.execute(
-                                        "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
-                                        params![sid, run_id],
-                                    ) {
-                                        Ok(rows) => {
-                                            if rows > 0 {
-                                                info!("✅ Updated agent run {} with session ID immediately", run_id);
-                                            }
-                                        }
-                                        Err(e) => {
-                                            error!("❌ Failed to update session ID immediately: {}", e);
+            if let Some(msg) = StreamMessage::parse(&line) {
+                if let Some(sid) = msg.init_session_id() {
+                    if let Ok(mut current_session_id) = session_id_clone.lock() {
+                        if current_session_id.is_empty() {
+                            *current_session_id = sid.to_string();
+                            info!("🔑 Extracted session ID: {}", sid);
+
+                            // Update database immediately with session ID
+                            if let Ok(conn) = Connection::open(&db_path_for_stdout) {
+                                match conn.execute(
+                                    "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
+                                    params![sid, run_id],
+                                ) {
+                                    Ok(rows) => {
+                                        if rows > 0 {
+                                            info!("✅ Updated agent run {} with session ID immediately", run_id);
                                         }
                                     }
+                                    Err(e) => {
+                                        error!("❌ Failed to update session ID immediately: {}", e);
+                                    }
                                 }
                             }
                         }
@@ -1461,10 +1927,20 @@ async fn spawn_agent_system(
                 }
             }
 
-            // Emit the line to the frontend with run_id for isolation
-            let _ = app_handle.emit(&format!("agent-output:{}", run_id), &line);
-            // Also emit to the generic event for backward compatibility
-            let _ = app_handle.emit("agent-output", &line);
+            events_stdout.output(run_id, &line);
+
+            // Stop the agent once it runs over budget
+            if let Some(guard) = &budget_guard_clone {
+                guard.observe(&line);
+            }
+
+            // Let the user know once if the agent is blocked on a permission
+            if !permission_notified {
+                if let Some(tool) = permission_request_tool(&line) {
+                    permission_notified = true;
+                    events_stdout.permission_request(run_id, &tool);
+                }
+            }
         }
 
         info!(
@@ -1473,7 +1949,7 @@ async fn spawn_agent_system(
         );
     });
 
-    let app_handle_stderr = app.clone();
+    let events_stderr = events.clone();
     let first_error = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
     let first_error_clone = first_error.clone();
 
@@ -1492,10 +1968,7 @@ async fn spawn_agent_system(
             }
 
             error!("stderr[{}]: {}", error_count, line);
-            // Emit error lines to the frontend with run_id for isolation
-            let _ = app_handle_stderr.emit(&format!("agent-error:{}", run_id), &line);
-            // Also emit to the generic event for backward compatibility
-            let _ = app_handle_stderr.emit("agent-error", &line);
+            events_stderr.error(run_id, &line);
         }
 
         if error_count > 0 {
@@ -1510,24 +1983,21 @@ async fn spawn_agent_system(
 
     // Register the process in the registry for live output tracking (after stdout/stderr setup)
     registry
-        .0
         .register_process(
             run_id,
-            agent_id,
-            agent_name,
+            run.agent_id,
+            run.agent_name,
             pid,
-            project_path.clone(),
-            task.clone(),
-            execution_model.clone(),
+            run.project_path,
+            run.task,
+            run.model,
             child,
         )
         .map_err(|e| format!("Failed to register process: {}", e))?;
     info!("📋 Registered process in registry");
 
-    let db_path_for_monitor = db_path.clone(); // Clone for the monitor task
-
     // Monitor process status and wait for completion
-    tokio::spawn(async move {
+    Ok(tokio::spawn(async move {
         info!("🕐 Starting process monitoring...");
 
         // Wait for first output with timeout
@@ -1554,38 +2024,24 @@ async fn spawn_agent_system(
                     "🔍 Process likely stuck waiting for input, attempting to kill PID: {}",
                     pid
                 );
-                let mut cmd = std::process::Command::new("kill");
-                cmd.arg("-TERM").arg(pid.to_string());
-                
-                // On Unix systems, this doesn't need CREATE_NO_WINDOW
-                let kill_result = cmd.output();
-
-                match kill_result {
-                    Ok(output) if output.status.success() => {
-                        warn!("🔍 Successfully sent TERM signal to process");
-                    }
-                    Ok(_) => {
-                        warn!("🔍 Failed to kill process with TERM, trying KILL");
-                        let mut cmd = std::process::Command::new("kill");
-                        cmd.arg("-KILL").arg(pid.to_string());
-                        let _ = cmd.output();
-                    }
-                    Err(e) => {
-                        warn!("🔍 Error killing process: {}", e);
-                    }
-                }
+                let report = terminate_process_tree(pid).await;
+                warn!("🔍 Stuck process stopped at stage {:?}", report.stage);
 
                 // Update database
-                if let Ok(conn) = Connection::open(&db_path_for_monitor) {
-                    let _ = conn.execute(
-                        "UPDATE agent_runs SET status = 'failed', completed_at = CURRENT_TIMESTAMP WHERE id = ?1",
-                        params![run_id],
-                    );
-                }
+                let finished = finish_agent_run(&db_path, run_id, "", "failed");
 
-                let _ = app.emit("agent-complete", false);
-                let _ = app.emit(&format!("agent-complete:{}", run_id), false);
-                return;
+                if let Some(guard) = &budget_guard {
+                    guard.finish();
+                }
+                let outcome = AgentRunOutcome {
+                    session_id: String::new(),
+                    status: "failed",
+                    exited_ok: false,
+                    finished,
+                    budget_exceeded: None,
+                };
+                events.complete(run_id, &outcome);
+                return outcome;
             }
 
             tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
@@ -1596,6 +2052,12 @@ async fn spawn_agent_system(
         let _ = stdout_task.await;
         let _ = stderr_task.await;
 
+        // The pipes are closed, so the process is exiting; an unknown status
+        // (the child was taken by a cancellation) counts as a failure
+        let exit_status = registry.wait_for_exit(run_id).await;
+        info!("Claude process exited with status: {:?}", exit_status);
+        let exited_ok = exit_status.is_some_and(|status| status.success());
+
         let duration_ms = start_time.elapsed().as_millis() as i64;
         info!("⏱️ Process execution took {} ms", duration_ms);
 
@@ -1608,36 +2070,28 @@ async fn spawn_agent_system(
 
         // Wait for process completion and update status
         info!("✅ Claude process execution monitoring complete");
+        let budget_exceeded = budget_guard.as_ref().and_then(|guard| {
+            guard.finish();
+            guard.exceeded()
+        });
+        let status = run_status(budget_exceeded.is_some(), exited_ok);
 
-        // Update the run record with session ID and mark as completed - open a new connection
-        if let Ok(conn) = Connection::open(&db_path_for_monitor) {
-            info!("🔄 Updating database with extracted session ID: {}", extracted_session_id);
-            match conn.execute(
-                "UPDATE agent_runs SET session_id = ?1, status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = ?2",
-                params![extracted_session_id, run_id],
-            ) {
-                Ok(rows_affected) => {
-                    if rows_affected > 0 {
-                        info!("✅ Successfully updated agent run {} with session ID: {}", run_id, extracted_session_id);
-                    } else {
-                        warn!("⚠️ No rows affected when updating agent run {} with session ID", run_id);
-                    }
-                }
-                Err(e) => {
-                    error!("❌ Failed to update agent run {} with session ID: {}", run_id, e);
-                }
-            }
-        } else {
-            error!("❌ Failed to open database to update session ID for run {}", run_id);
-        }
+        // Update the run record with session ID and final status; a run
+        // cancelled while Claude was shutting down keeps its status
+        let finished = finish_agent_run(&db_path, run_id, &extracted_session_id, status);
 
         // Cleanup will be handled by the cleanup_finished_processes function
 
-        let _ = app.emit("agent-complete", true);
-        let _ = app.emit(&format!("agent-complete:{}", run_id), true);
-    });
-
-    Ok(run_id)
+        let outcome = AgentRunOutcome {
+            session_id: extracted_session_id,
+            status,
+            exited_ok,
+            finished,
+            budget_exceeded,
+        };
+        events.complete(run_id, &outcome);
+        outcome
+    }))
 }
 
 /// List all currently running agent sessions
@@ -1716,52 +2170,58 @@ pub async fn kill_agent_session(
 ) -> Result<bool, String> {
     info!("Attempting to kill agent session {}", run_id);
 
-    // First try to kill using the process registry
-    let killed_via_registry = match registry.0.kill_process(run_id).await {
-        Ok(success) => {
-            if success {
-                info!("Successfully killed process {} via registry", run_id);
-                true
-            } else {
-                warn!("Process {} not found in registry", run_id);
-                false
-            }
-        }
-        Err(e) => {
-            warn!("Failed to kill process {} via registry: {}", run_id, e);
-            false
-        }
-    };
-
-    // If registry kill didn't work, try fallback with PID from database
-    if !killed_via_registry {
-        let pid_result = {
-            let conn = db.0.lock().map_err(|e| e.to_string())?;
-            conn.query_row(
+    // Mark the run cancelled before stopping it. Claude may exit cleanly on
+    // SIGINT, and the monitor must not then record the run as completed.
+    let (db_pid, marked) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let pid = conn
+            .query_row(
                 "SELECT pid FROM agent_runs WHERE id = ?1 AND status = 'running'",
                 params![run_id],
                 |row| row.get::<_, Option<i64>>(0),
             )
+            .optional()
             .map_err(|e| e.to_string())?
-        };
+            .flatten();
+        let updated = conn.execute(
+            "UPDATE agent_runs SET status = 'cancelled', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'running'",
+            params![run_id],
+        ).map_err(|e| e.to_string())?;
+        (pid, updated > 0)
+    };
 
-        if let Some(pid) = pid_result {
+    // First try to stop the process tree through the process registry
+    let report = match registry.0.kill_process(run_id).await {
+        Ok(Some(report)) => Some(report),
+        Ok(None) => {
+            warn!("Process {} not found in registry", run_id);
+            None
+        }
+        Err(e) => {
+            warn!("Failed to kill process {} via registry: {}", run_id, e);
+            None
+        }
+    };
+
+    // If the registry doesn't know the run, fall back to the PID from the database
+    let report = match (report, db_pid) {
+        (Some(report), _) => Some(report),
+        (None, Some(pid)) => {
             info!("Attempting fallback kill for PID {} from database", pid);
-            let _ = registry.0.kill_process_by_pid(run_id, pid as u32)?;
+            Some(registry.0.kill_process_by_pid(run_id, pid as u32).await)
         }
+        (None, None) => None,
+    };
+    let killed_via_registry = report.as_ref().is_some_and(|r| r.survivors.is_empty());
+    if let Some(report) = &report {
+        info!("Agent run {} cancellation stopped at stage {:?}", run_id, report.stage);
+        let _ = app.emit(&format!("agent-terminated:{}", run_id), report);
     }
 
-    // Update the database to mark as cancelled
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
-    let updated = conn.execute(
-        "UPDATE agent_runs SET status = 'cancelled', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'running'",
-        params![run_id],
-    ).map_err(|e| e.to_string())?;
-
     // Emit cancellation event with run_id for proper isolation
     let _ = app.emit(&format!("agent-cancelled:{}", run_id), true);
 
-    Ok(updated > 0 || killed_via_registry)
+    Ok(marked || killed_via_registry)
 }
 
 /// Get the status of a specific agent session
@@ -1864,7 +2324,19 @@ pub async fn get_live_session_output(
     registry: State<'_, crate::process::ProcessRegistryState>,
     run_id: i64,
 ) -> Result<String, String> {
-    registry.0.get_live_output(run_id)
+    registry.0.get_live_output_text(run_id)
+}
+
+/// Get a page of live output from a running process. Clients poll with the
+/// previous page's `next_offset` to receive only new lines.
+#[tauri::command]
+pub async fn get_live_output(
+    registry: State<'_, crate::process::ProcessRegistryState>,
+    run_id: i64,
+    from_offset: Option<u64>,
+    limit: Option<usize>,
+) -> Result<crate::process::LiveOutputPage, String> {
+    registry.0.get_live_output(run_id, from_offset.unwrap_or(0), limit)
 }
 
 /// Get real-time output for a running session by reading its JSONL file with live output fallback
@@ -1875,55 +2347,20 @@ pub async fn get_session_output(
     run_id: i64,
 ) -> Result<String, String> {
     // Get the session information
+    let db_ref = db.inner();
     let run = get_agent_run(db, run_id).await?;
 
     // If no session ID yet, try to get live output from registry
     if run.session_id.is_empty() {
-        let live_output = registry.0.get_live_output(run_id)?;
+        let live_output = registry.0.get_live_output_text(run_id)?;
         if !live_output.is_empty() {
             return Ok(live_output);
         }
         return Ok(String::new());
     }
 
-    // Get the Claude directory
-    let claude_dir = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude");
-
-    // Find the correct project directory by searching for the session file
-    let projects_dir = claude_dir.join("projects");
-
-    // Check if projects directory exists
-    if !projects_dir.exists() {
-        log::error!("Projects directory not found at: {:?}", projects_dir);
-        return Err("Projects directory not found".to_string());
-    }
-
-    // Search for the session file in all project directories
-    let mut session_file_path = None;
-    log::info!("Searching for session file {} in all project directories", run.session_id);
-
-    if let Ok(entries) = std::fs::read_dir(&projects_dir) {
-        for entry in entries.filter_map(Result::ok) {
-            let path = entry.path();
-            if path.is_dir() {
-                let dir_name = path.file_name().unwrap_or_default().to_string_lossy();
-                log::debug!("Checking project directory: {}", dir_name);
-
-                let potential_session_file = path.join(format!("{}.jsonl", run.session_id));
-                if potential_session_file.exists() {
-                    log::info!("Found session file at: {:?}", potential_session_file);
-                    session_file_path = Some(potential_session_file);
-                    break;
-                } else {
-                    log::debug!("Session file not found in: {}", dir_name);
-                }
-            }
-        }
-    } else {
-        log::error!("Failed to read projects directory");
-    }
+    // Find the session file through the project index, falling back to every project directory
+    let session_file_path = find_session_file(db_ref, &run.session_id, Some(&run.project_path))?;
 
     // If we found the session file, read it
     if let Some(session_path) = session_file_path {
@@ -1932,21 +2369,15 @@ pub async fn get_session_output(
             Err(e) => {
                 log::error!("Failed to read session file {}: {}", session_path.display(), e);
                 // Fallback to live output if file read fails
-                let live_output = registry.0.get_live_output(run_id)?;
+                let live_output = registry.0.get_live_output_text(run_id)?;
                 Ok(live_output)
             }
         }
     } else {
-        // If session file not found, try the old method as fallback
-        log::warn!("Session file not found for {}, trying legacy method", run.session_id);
-        match read_session_jsonl(&run.session_id, &run.project_path).await {
-            Ok(content) => Ok(content),
-            Err(_) => {
-                // Final fallback to live output
-                let live_output = registry.0.get_live_output(run_id)?;
-                Ok(live_output)
-            }
-        }
+        // Fall back to live output if the session file doesn't exist yet
+        log::warn!("Session file not found for {}, using live output", run.session_id);
+        let live_output = registry.0.get_live_output_text(run_id)?;
+        Ok(live_output)
     }
 }
 
@@ -1958,6 +2389,7 @@ pub async fn stream_session_output(
     run_id: i64,
 ) -> Result<(), String> {
     // Get the session information
+    let db_ref = db.inner();
     let run = get_agent_run(db, run_id).await?;
 
     // If no session ID yet, can't stream
@@ -1965,20 +2397,15 @@ pub async fn stream_session_output(
         return Err("Session not started yet".to_string());
     }
 
-    let session_id = run.session_id.clone();
-    let project_path = run.project_path.clone();
+    // Resolve the session file up front; it may not have been written yet
+    let session_file = match find_session_file(db_ref, &run.session_id, Some(&run.project_path))? {
+        Some(path) => path,
+        None => project_dir_for_path(db_ref, &run.project_path)?
+            .join(format!("{}.jsonl", run.session_id)),
+    };
 
     // Spawn a task to monitor the file
     tokio::spawn(async move {
-        let claude_dir = match dirs::home_dir() {
-            Some(home) => home.join(".claude").join("projects"),
-            None => return,
-        };
-
-        let encoded_project = project_path.replace('/', "-");
-        let project_dir = claude_dir.join(&encoded_project);
-        let session_file = project_dir.join(format!("{}.jsonl", session_id));
-
         let mut last_size = 0u64;
 
         // Monitor file changes continuously while session is running
@@ -2629,45 +3056,12 @@ pub async fn import_agent_from_github(
 /// Similar to Claude Code's load_session_history, but searches across all project directories
 #[tauri::command]
 pub async fn load_agent_session_history(
+    db: State<'_, AgentDb>,
     session_id: String,
 ) -> Result<Vec<serde_json::Value>, String> {
     log::info!("Loading agent session history for session: {}", session_id);
 
-    let claude_dir = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude");
-
-    let projects_dir = claude_dir.join("projects");
-
-    if !projects_dir.exists() {
-        log::error!("Projects directory not found at: {:?}", projects_dir);
-        return Err("Projects directory not found".to_string());
-    }
-
-    // Search for the session file in all project directories
-    let mut session_file_path = None;
-    log::info!("Searching for session file {} in all project directories", session_id);
-
-    if let Ok(entries) = std::fs::read_dir(&projects_dir) {
-        for entry in entries.filter_map(Result::ok) {
-            let path = entry.path();
-            if path.is_dir() {
-                let dir_name = path.file_name().unwrap_or_default().to_string_lossy();
-                log::debug!("Checking project directory: {}", dir_name);
-
-                let potential_session_file = path.join(format!("{}.jsonl", session_id));
-                if potential_session_file.exists() {
-                    log::info!("Found session file at: {:?}", potential_session_file);
-                    session_file_path = Some(potential_session_file);
-                    break;
-                } else {
-                    log::debug!("Session file not found in: {}", dir_name);
-                }
-            }
-        }
-    } else {
-        log::error!("Failed to read projects directory");
-    }
+    let session_file_path = find_session_file(&db, &session_id, None)?;
 
     if let Some(session_path) = session_file_path {
         let file = std::fs::File::open(&session_path)
@@ -3247,7 +3641,13 @@ pub async fn get_enabled_environment_variables(db: State<'_, AgentDb>) -> Result
 #[tauri::command]
 pub async fn get_environment_variable_groups(db: State<'_, AgentDb>) -> Result<Vec<EnvironmentVariableGroup>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
-    
+    get_environment_variable_groups_internal(&conn)
+}
+
+/// Internal function to list environment variable groups
+pub(crate) fn get_environment_variable_groups_internal(
+    conn: &rusqlite::Connection,
+) -> Result<Vec<EnvironmentVariableGroup>, String> {
     let mut stmt = conn
         .prepare("SELECT id, name, description, enabled, sort_order, is_system, created_at, updated_at FROM environment_variable_groups ORDER BY sort_order, name")
         .map_err(|e| e.to_string())?;
@@ -3462,7 +3862,7 @@ pub async fn get_available_models(db: State<'_, AgentDb>) -> Result<Vec<ModelInf
 
 /// Internal helper function to get enabled environment variables
 /// This is similar to get_enabled_environment_variables but returns Result for internal use
-fn get_enabled_environment_variables_internal(conn: &rusqlite::Connection) -> Result<std::collections::HashMap<String, String>, String> {
+pub(crate) fn get_enabled_environment_variables_internal(conn: &rusqlite::Connection) -> Result<std::collections::HashMap<String, String>, String> {
     // Query enabled variables from enabled groups with conflict resolution
     let mut stmt = conn
         .prepare("
@@ -0,0 +1,795 @@
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, Request, State as AxumState};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Listener, Manager, State};
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use super::agents::{
+    execute_agent, get_agent_run_with_real_time_metrics, kill_agent_session,
+    list_agent_runs_with_metrics, AgentDb,
+};
+use super::budget::BudgetLimits;
+use super::claude::{
+    cancel_claude_execution, resume_claude_code, start_claude_session, start_new_session,
+    SessionChannels, SessionMode,
+};
+use super::quick_prompt::remember_last_project;
+use super::usage::{get_usage_by_date_range, get_usage_stats};
+use super::worktrees::Worktree;
+use crate::process::ProcessRegistryState;
+
+const DEFAULT_PORT: u16 = 8765;
+
+/// How long a start/continue request waits for Claude to report its session ID
+const SESSION_START_TIMEOUT: Duration = Duration::from_secs(30);
+
+const DEFAULT_MODEL: &str = "sonnet";
+
+/// Persisted configuration of the automation API
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AutomationApiSettings {
+    pub enabled: bool,
+    pub port: u16,
+    /// Bearer token clients must present
+    pub token: String,
+}
+
+/// Settings plus whether the server is currently listening
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AutomationApiStatus {
+    #[serde(flatten)]
+    pub settings: AutomationApiSettings,
+    pub running: bool,
+    /// Base URL while running
+    pub url: Option<String>,
+}
+
+struct RunningServer {
+    port: u16,
+    shutdown: oneshot::Sender<()>,
+}
+
+/// Handle to the running automation server, if any
+#[derive(Default)]
+pub struct AutomationApiState(Mutex<Option<RunningServer>>);
+
+fn generate_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+fn load_settings(db: &AgentDb) -> Result<AutomationApiSettings, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let read = |key: &str| {
+        conn.query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![key],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+    };
+
+    let enabled = read("automation_api_enabled").as_deref() == Some("true");
+    let port = read("automation_api_port")
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+    let token = match read("automation_api_token").filter(|t| !t.is_empty()) {
+        Some(token) => token,
+        None => {
+            // Generated once so the token survives restarts
+            let token = generate_token();
+            conn.execute(
+                "INSERT OR REPLACE INTO app_settings (key, value) VALUES ('automation_api_token', ?1)",
+                params![token],
+            )
+            .map_err(|e| format!("Failed to save automation API token: {}", e))?;
+            token
+        }
+    };
+
+    Ok(AutomationApiSettings {
+        enabled,
+        port,
+        token,
+    })
+}
+
+fn store_settings(db: &AgentDb, settings: &AutomationApiSettings) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let values = [
+        ("automation_api_enabled", settings.enabled.to_string()),
+        ("automation_api_port", settings.port.to_string()),
+        ("automation_api_token", settings.token.clone()),
+    ];
+    for (key, value) in values {
+        conn.execute(
+            "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )
+        .map_err(|e| format!("Failed to save {}: {}", key, e))?;
+    }
+    Ok(())
+}
+
+async fn status(state: &AutomationApiState, settings: AutomationApiSettings) -> AutomationApiStatus {
+    let running = state.0.lock().await.as_ref().map(|server| server.port);
+    AutomationApiStatus {
+        settings,
+        running: running.is_some(),
+        url: running.map(|port| format!("http://127.0.0.1:{}", port)),
+    }
+}
+
+/// Stops the server if running, then starts it again when enabled
+async fn apply_settings(
+    app: &AppHandle,
+    state: &AutomationApiState,
+    settings: &AutomationApiSettings,
+) -> Result<(), String> {
+    let mut running = state.0.lock().await;
+    if let Some(server) = running.take() {
+        let _ = server.shutdown.send(());
+        log::info!("Stopped automation API on port {}", server.port);
+    }
+    if !settings.enabled {
+        return Ok(());
+    }
+
+    // Never listen on anything but loopback
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", settings.port))
+        .await
+        .map_err(|e| format!("Failed to bind 127.0.0.1:{}: {}", settings.port, e))?;
+    let (shutdown, shutdown_rx) = oneshot::channel();
+    let router = build_router(ServerState {
+        app: app.clone(),
+        auth: ApiAuth {
+            token: settings.token.clone(),
+            port: settings.port,
+        },
+    });
+
+    tauri::async_runtime::spawn(async move {
+        let server = axum::serve(listener, router).with_graceful_shutdown(async {
+            let _ = shutdown_rx.await;
+        });
+        if let Err(e) = server.await {
+            log::error!("Automation API server failed: {}", e);
+        }
+    });
+
+    log::info!("Automation API listening on 127.0.0.1:{}", settings.port);
+    *running = Some(RunningServer {
+        port: settings.port,
+        shutdown,
+    });
+    Ok(())
+}
+
+/// Starts the automation API at launch if the user enabled it
+pub async fn start_automation_api_if_enabled(app: AppHandle) {
+    let settings = match load_settings(&app.state::<AgentDb>()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            log::warn!("Failed to load automation API settings: {}", e);
+            return;
+        }
+    };
+    if settings.enabled {
+        if let Err(e) = apply_settings(&app, &app.state::<AutomationApiState>(), &settings).await {
+            log::error!("Failed to start automation API: {}", e);
+        }
+    }
+}
+
+/// Gets the automation API settings and whether it is running
+#[tauri::command]
+pub async fn get_automation_api_status(
+    db: State<'_, AgentDb>,
+    state: State<'_, AutomationApiState>,
+) -> Result<AutomationApiStatus, String> {
+    let settings = load_settings(&db)?;
+    Ok(status(&state, settings).await)
+}
+
+/// Enables or disables the automation API, restarting it on the new port
+#[tauri::command]
+pub async fn save_automation_api_settings(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    state: State<'_, AutomationApiState>,
+    enabled: bool,
+    port: Option<u16>,
+) -> Result<AutomationApiStatus, String> {
+    let mut settings = load_settings(&db)?;
+    settings.enabled = enabled;
+    if let Some(port) = port {
+        if port == 0 {
+            return Err("Port must be between 1 and 65535".to_string());
+        }
+        settings.port = port;
+    }
+
+    apply_settings(&app, &state, &settings).await?;
+    store_settings(&db, &settings)?;
+    Ok(status(&state, settings).await)
+}
+
+/// Replaces the token, invalidating every existing client
+#[tauri::command]
+pub async fn regenerate_automation_api_token(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    state: State<'_, AutomationApiState>,
+) -> Result<AutomationApiStatus, String> {
+    let mut settings = load_settings(&db)?;
+    settings.token = generate_token();
+    store_settings(&db, &settings)?;
+    apply_settings(&app, &state, &settings).await?;
+    Ok(status(&state, settings).await)
+}
+
+// HTTP server
+
+#[derive(Clone)]
+struct ServerState {
+    app: AppHandle,
+    auth: ApiAuth,
+}
+
+/// What `authorize` checks requests against
+#[derive(Clone)]
+struct ApiAuth {
+    token: String,
+    port: u16,
+}
+
+/// Command errors are reported as `{"error": "..."}`
+struct ApiError(StatusCode, String);
+
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        ApiError(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+fn build_router(state: ServerState) -> Router {
+    let routes = Router::new()
+        .route("/api/health", get(health))
+        .route("/api/sessions", post(start_session))
+        .route("/api/sessions/continue", post(continue_session))
+        .route("/api/sessions/:session_id/resume", post(resume_session))
+        .route("/api/sessions/:session_id/cancel", post(cancel_session))
+        .route("/api/sessions/:session_id/stream", get(session_stream))
+        .route("/api/agents/:agent_id/execute", post(run_agent))
+        .route("/api/runs", get(list_runs))
+        .route("/api/runs/:run_id", get(get_run))
+        .route("/api/runs/:run_id/cancel", post(cancel_run))
+        .route("/api/runs/:run_id/stream", get(run_stream))
+        .route("/api/usage", get(usage))
+        .route("/api/events", get(event_stream));
+    with_auth(routes, state.auth.clone()).with_state(state)
+}
+
+/// Guards every route of `router` with `authorize`
+fn with_auth<S: Clone + Send + Sync + 'static>(router: Router<S>, auth: ApiAuth) -> Router<S> {
+    router.layer(middleware::from_fn_with_state(auth, authorize))
+}
+
+/// Compares tokens without short-circuiting on the first differing byte
+fn tokens_match(provided: &str, expected: &str) -> bool {
+    provided.len() == expected.len()
+        && provided
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Requires the token as `Authorization: Bearer <token>`, or as `?token=` for
+/// WebSocket clients that cannot set headers. Requests whose Host is not
+/// loopback are refused to defeat DNS rebinding.
+async fn authorize(
+    AxumState(state): AxumState<ApiAuth>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("");
+    let allowed_hosts = [
+        format!("127.0.0.1:{}", state.port),
+        format!("localhost:{}", state.port),
+    ];
+    if !allowed_hosts.iter().any(|allowed| allowed == host) {
+        return ApiError(StatusCode::FORBIDDEN, "Invalid Host header".to_string()).into_response();
+    }
+
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let query_token = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+            .map(str::to_string)
+    });
+
+    match bearer.or(query_token) {
+        Some(token) if tokens_match(&token, &state.token) => next.run(request).await,
+        _ => ApiError(StatusCode::UNAUTHORIZED, "Missing or invalid token".to_string())
+            .into_response(),
+    }
+}
+
+async fn health() -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "ok": true,
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
+#[derive(Deserialize)]
+struct SessionRequest {
+    project_path: String,
+    prompt: String,
+    model: Option<String>,
+    /// Run a new session in its own git worktree
+    isolate: Option<bool>,
+    budget: Option<BudgetLimits>,
+}
+
+#[derive(Serialize)]
+struct SessionStarted {
+    /// Claude's session ID, or null if it was not reported in time
+    session_id: Option<String>,
+    /// Worktree the session runs in, when it was started isolated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    worktree: Option<Worktree>,
+}
+
+/// Waits for the session started with `started` to report its ID, which
+/// happens once Claude prints its init message
+async fn wait_for_session_id(started: oneshot::Receiver<String>) -> Option<String> {
+    tokio::time::timeout(SESSION_START_TIMEOUT, started)
+        .await
+        .ok()
+        .and_then(Result::ok)
+}
+
+async fn start_session(
+    AxumState(state): AxumState<ServerState>,
+    Json(body): Json<SessionRequest>,
+) -> ApiResult<SessionStarted> {
+    let (tx, rx) = oneshot::channel();
+    let worktree = start_new_session(
+        state.app.clone(),
+        body.project_path,
+        body.prompt,
+        body.model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+        body.isolate,
+        body.budget,
+        Some(tx),
+    )
+    .await?;
+    let session_id = wait_for_session_id(rx).await;
+    Ok(Json(SessionStarted { session_id, worktree }))
+}
+
+async fn continue_session(
+    AxumState(state): AxumState<ServerState>,
+    Json(body): Json<SessionRequest>,
+) -> ApiResult<SessionStarted> {
+    let (tx, rx) = oneshot::channel();
+    remember_last_project(&state.app, &body.project_path);
+    start_claude_session(
+        state.app.clone(),
+        body.project_path,
+        body.prompt,
+        body.model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+        SessionMode::Continue,
+        body.budget,
+        SessionChannels {
+            started: Some(tx),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let session_id = wait_for_session_id(rx).await;
+    Ok(Json(SessionStarted {
+        session_id,
+        worktree: None,
+    }))
+}
+
+async fn resume_session(
+    AxumState(state): AxumState<ServerState>,
+    Path(session_id): Path<String>,
+    Json(body): Json<SessionRequest>,
+) -> ApiResult<SessionStarted> {
+    resume_claude_code(
+        state.app.clone(),
+        body.project_path,
+        session_id.clone(),
+        body.prompt,
+        body.model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+        body.budget,
+    )
+    .await?;
+    Ok(Json(SessionStarted {
+        session_id: Some(session_id),
+        worktree: None,
+    }))
+}
+
+async fn cancel_session(
+    AxumState(state): AxumState<ServerState>,
+    Path(session_id): Path<String>,
+) -> ApiResult<serde_json::Value> {
+    let termination = cancel_claude_execution(state.app.clone(), Some(session_id)).await?;
+    Ok(Json(serde_json::json!({ "cancelled": true, "termination": termination })))
+}
+
+#[derive(Deserialize)]
+struct AgentRequest {
+    project_path: String,
+    task: String,
+    model: Option<String>,
+    /// Run the agent in its own git worktree
+    isolate: Option<bool>,
+    budget: Option<BudgetLimits>,
+}
+
+async fn run_agent(
+    AxumState(state): AxumState<ServerState>,
+    Path(agent_id): Path<i64>,
+    Json(body): Json<AgentRequest>,
+) -> ApiResult<serde_json::Value> {
+    let app = state.app.clone();
+    let run_id = execute_agent(
+        app.clone(),
+        agent_id,
+        body.project_path,
+        body.task,
+        body.model,
+        body.isolate,
+        body.budget,
+        app.state::<AgentDb>(),
+        app.state::<ProcessRegistryState>(),
+    )
+    .await?;
+    Ok(Json(serde_json::json!({ "run_id": run_id })))
+}
+
+#[derive(Deserialize)]
+struct RunsQuery {
+    agent_id: Option<i64>,
+}
+
+async fn list_runs(
+    AxumState(state): AxumState<ServerState>,
+    Query(query): Query<RunsQuery>,
+) -> ApiResult<serde_json::Value> {
+    let runs = list_agent_runs_with_metrics(state.app.state::<AgentDb>(), query.agent_id).await?;
+    Ok(Json(serde_json::to_value(runs).map_err(|e| e.to_string())?))
+}
+
+async fn get_run(
+    AxumState(state): AxumState<ServerState>,
+    Path(run_id): Path<i64>,
+) -> ApiResult<serde_json::Value> {
+    let run = get_agent_run_with_real_time_metrics(state.app.state::<AgentDb>(), run_id)
+        .await
+        .map_err(|e| ApiError(StatusCode::NOT_FOUND, e))?;
+    Ok(Json(serde_json::to_value(run).map_err(|e| e.to_string())?))
+}
+
+async fn cancel_run(
+    AxumState(state): AxumState<ServerState>,
+    Path(run_id): Path<i64>,
+) -> ApiResult<serde_json::Value> {
+    let app = state.app.clone();
+    let killed = kill_agent_session(
+        app.clone(),
+        app.state::<AgentDb>(),
+        app.state::<ProcessRegistryState>(),
+        run_id,
+    )
+    .await?;
+    Ok(Json(serde_json::json!({ "cancelled": killed })))
+}
+
+#[derive(Deserialize)]
+struct UsageQuery {
+    days: Option<u32>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+}
+
+async fn usage(Query(query): Query<UsageQuery>) -> ApiResult<serde_json::Value> {
+    let stats = match (query.start_date, query.end_date) {
+        (Some(start), Some(end)) => get_usage_by_date_range(start, end)?,
+        (None, None) => get_usage_stats(query.days)?,
+        _ => {
+            return Err(ApiError(
+                StatusCode::BAD_REQUEST,
+                "start_date and end_date must be given together".to_string(),
+            ))
+        }
+    };
+    Ok(Json(serde_json::to_value(stats).map_err(|e| e.to_string())?))
+}
+
+// WebSocket streaming
+
+/// Forwards the named Tauri events to the socket as
+/// `{"event": "<name>", "payload": <payload>}` frames, exactly as the
+/// frontend would receive them. `replay` lines are sent first, and the socket
+/// is closed after an event listed in `closing` (a completion event).
+async fn forward_events(
+    mut socket: WebSocket,
+    app: AppHandle,
+    events: Vec<String>,
+    closing: Vec<String>,
+    replay: Option<(String, String)>,
+) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<(String, serde_json::Value)>();
+    let listeners: Vec<_> = events
+        .into_iter()
+        .map(|name| {
+            let tx = tx.clone();
+            let event_name = name.clone();
+            app.listen_any(name, move |event| {
+                let payload = serde_json::from_str(event.payload())
+                    .unwrap_or_else(|_| serde_json::Value::String(event.payload().to_string()));
+                let _ = tx.send((event_name.clone(), payload));
+            })
+        })
+        .collect();
+    drop(tx);
+
+    // Output produced before the client connected
+    if let Some((event_name, output)) = replay {
+        for line in output.lines().filter(|l| !l.is_empty()) {
+            let frame = serde_json::json!({ "event": event_name, "payload": line, "replay": true });
+            if socket.send(Message::Text(frame.to_string())).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    loop {
+        tokio::select! {
+            event = rx.recv() => {
+                let Some((name, payload)) = event else { break };
+                let frame = serde_json::json!({ "event": name, "payload": payload });
+                if socket.send(Message::Text(frame.to_string())).await.is_err() {
+                    break;
+                }
+                if closing.contains(&name) {
+                    let _ = socket.send(Message::Close(None)).await;
+                    break;
+                }
+            }
+            incoming = socket.recv() => {
+                match incoming {
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    for id in listeners {
+        app.unlisten(id);
+    }
+}
+
+fn scoped_events(prefix: &str, id: &str, kinds: &[&str]) -> Vec<String> {
+    kinds
+        .iter()
+        .map(|kind| format!("{}-{}:{}", prefix, kind, id))
+        .collect()
+}
+
+/// Mirrors `claude-output:<session>`, `claude-error:<session>`,
+/// `claude-cancelled:<session>` and `claude-complete:<session>`
+async fn session_stream(
+    ws: WebSocketUpgrade,
+    AxumState(state): AxumState<ServerState>,
+    Path(session_id): Path<String>,
+) -> Response {
+    let registry = state.app.state::<ProcessRegistryState>();
+    let replay = registry
+        .0
+        .get_claude_session_by_id(&session_id)
+        .ok()
+        .flatten()
+        .and_then(|info| registry.0.get_live_output_text(info.run_id).ok())
+        .map(|output| (format!("claude-output:{}", session_id), output));
+
+    let events = scoped_events("claude", &session_id, &["output", "error", "cancelled", "complete"]);
+    let closing = vec![format!("claude-complete:{}", session_id)];
+    let app = state.app.clone();
+    ws.on_upgrade(move |socket| forward_events(socket, app, events, closing, replay))
+}
+
+/// Mirrors `agent-output:<run>`, `agent-error:<run>` and `agent-complete:<run>`
+async fn run_stream(
+    ws: WebSocketUpgrade,
+    AxumState(state): AxumState<ServerState>,
+    Path(run_id): Path<i64>,
+) -> Response {
+    let replay = state
+        .app
+        .state::<ProcessRegistryState>()
+        .0
+        .get_live_output_text(run_id)
+        .ok()
+        .map(|output| (format!("agent-output:{}", run_id), output));
+
+    let run_id = run_id.to_string();
+    let events = scoped_events("agent", &run_id, &["output", "error", "complete"]);
+    let closing = vec![format!("agent-complete:{}", run_id)];
+    let app = state.app.clone();
+    ws.on_upgrade(move |socket| forward_events(socket, app, events, closing, replay))
+}
+
+/// Mirrors the unscoped session and agent events for every run
+async fn event_stream(ws: WebSocketUpgrade, AxumState(state): AxumState<ServerState>) -> Response {
+    let events = [
+        "claude-output",
+        "claude-error",
+        "claude-cancelled",
+        "claude-complete",
+        "agent-output",
+        "agent-error",
+        "agent-complete",
+    ]
+    .iter()
+    .map(|name| name.to_string())
+    .collect();
+    let app = state.app.clone();
+    ws.on_upgrade(move |socket| forward_events(socket, app, events, Vec::new(), None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request as HttpRequest;
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "0123456789abcdef";
+    const PORT: u16 = 8765;
+
+    /// The auth layer around stand-ins for a plain and a WebSocket route
+    fn router() -> Router {
+        let routes = Router::new().route("/api/health", get(health)).route(
+            "/api/sessions/:session_id/stream",
+            get(|ws: WebSocketUpgrade| async move { ws.on_upgrade(|_socket| async {}) }),
+        );
+        with_auth(
+            routes,
+            ApiAuth {
+                token: TOKEN.to_string(),
+                port: PORT,
+            },
+        )
+    }
+
+    fn request(uri: &str, host: &str, bearer: Option<&str>) -> HttpRequest<Body> {
+        let mut builder = HttpRequest::get(uri).header(header::HOST, host);
+        if let Some(token) = bearer {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    async fn status_of(request: HttpRequest<Body>) -> StatusCode {
+        router().oneshot(request).await.unwrap().status()
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match(TOKEN, TOKEN));
+        assert!(!tokens_match("0123456789abcdeF", TOKEN));
+        assert!(!tokens_match("0123456789abcde", TOKEN));
+        assert!(!tokens_match("", TOKEN));
+    }
+
+    #[tokio::test]
+    async fn test_token_is_required() {
+        let host = "127.0.0.1:8765";
+        let cases = [
+            (None, StatusCode::UNAUTHORIZED),
+            (Some("wrong"), StatusCode::UNAUTHORIZED),
+            (Some("0123456789abcdeX"), StatusCode::UNAUTHORIZED),
+            (Some(TOKEN), StatusCode::OK),
+        ];
+        for (bearer, expected) in cases {
+            assert_eq!(status_of(request("/api/health", host, bearer)).await, expected, "{:?}", bearer);
+        }
+        assert_eq!(
+            status_of(request("/api/health", "localhost:8765", Some(TOKEN))).await,
+            StatusCode::OK
+        );
+
+        let basic = HttpRequest::get("/api/health")
+            .header(header::HOST, host)
+            .header(header::AUTHORIZATION, format!("Basic {}", TOKEN))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status_of(basic).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_query_token_on_websocket_route() {
+        let host = "127.0.0.1:8765";
+        let upgrade = |uri: &str| {
+            HttpRequest::get(uri)
+                .header(header::HOST, host)
+                .header(header::CONNECTION, "upgrade")
+                .header(header::UPGRADE, "websocket")
+                .header(header::SEC_WEBSOCKET_VERSION, "13")
+                .header(header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // Past the auth layer the handshake fails only because a oneshot
+        // request carries no connection to upgrade
+        let authorized = format!("/api/sessions/s1/stream?token={}", TOKEN);
+        assert_eq!(status_of(upgrade(&authorized)).await, StatusCode::UPGRADE_REQUIRED);
+        let with_other_params = format!("/api/sessions/s1/stream?since=0&token={}", TOKEN);
+        assert_eq!(status_of(upgrade(&with_other_params)).await, StatusCode::UPGRADE_REQUIRED);
+
+        assert_eq!(
+            status_of(upgrade("/api/sessions/s1/stream?token=wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status_of(upgrade("/api/sessions/s1/stream")).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_non_loopback_host_is_forbidden() {
+        for host in ["evil.example.com:8765", "127.0.0.1:9999", "127.0.0.1", "localhost", ""] {
+            assert_eq!(
+                status_of(request("/api/health", host, Some(TOKEN))).await,
+                StatusCode::FORBIDDEN,
+                "{}",
+                host
+            );
+        }
+        // The Host check comes first, so a rebinding page learns nothing about the token
+        assert_eq!(
+            status_of(request("/api/health", "evil.example.com:8765", None)).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+}
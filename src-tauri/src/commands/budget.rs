@@ -0,0 +1,333 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::usage::calculate_cost;
+use crate::process::terminate_process_tree;
+use crate::stream_json::StreamMessage;
+
+/// Caps on what a single session or agent run may consume. Unset fields are
+/// unlimited.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct BudgetLimits {
+    pub max_cost_usd: Option<f64>,
+    /// Input, output and cache tokens combined
+    pub max_tokens: Option<u64>,
+    pub max_duration_secs: Option<u64>,
+    /// Number of assistant turns allowed to finish. The run is stopped when
+    /// Claude starts the turn after the last one.
+    pub max_turns: Option<u32>,
+}
+
+impl BudgetLimits {
+    fn is_empty(&self) -> bool {
+        self.max_cost_usd.is_none()
+            && self.max_tokens.is_none()
+            && self.max_duration_secs.is_none()
+            && self.max_turns.is_none()
+    }
+}
+
+/// Which cap was hit and the usage at that point
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetExceeded {
+    /// 'cost_usd', 'tokens', 'duration_secs' or 'turns'
+    pub limit: String,
+    pub max: f64,
+    pub actual: f64,
+    pub cost_usd: f64,
+    pub tokens: u64,
+    pub turns: u32,
+    pub duration_secs: u64,
+}
+
+impl BudgetExceeded {
+    pub fn message(&self) -> String {
+        format!(
+            "Budget exceeded: {} reached {} (limit {})",
+            self.limit, self.actual, self.max
+        )
+    }
+}
+
+#[derive(Default)]
+struct Usage {
+    /// Usage per assistant message ID. Claude repeats the usage of a message
+    /// on every content block line, so the latest one wins.
+    messages: HashMap<String, (f64, u64)>,
+    /// Usage from lines without a message ID, summed since there is no way
+    /// to tell repeats apart
+    anonymous: (f64, u64),
+}
+
+impl Usage {
+    fn totals(&self) -> (f64, u64) {
+        self.messages
+            .values()
+            .fold(self.anonymous, |(cost, tokens), (c, t)| (cost + c, tokens + t))
+    }
+
+    /// Assistant turns so far. Only messages with an ID count: a message
+    /// without one may be a repeated content block of the previous turn, and
+    /// over-counting would stop a run early. Claude sets the ID on every
+    /// assistant message it streams.
+    fn turns(&self) -> u32 {
+        self.messages.len() as u32
+    }
+}
+
+/// Tracks the running usage of one Claude process from its stream-json
+/// output and terminates the process once a cap is hit
+pub(crate) struct BudgetGuard {
+    limits: BudgetLimits,
+    model: String,
+    pid: u32,
+    started: Instant,
+    usage: Mutex<Usage>,
+    exceeded: Mutex<Option<BudgetExceeded>>,
+    finished: AtomicBool,
+}
+
+impl BudgetGuard {
+    /// Returns `None` when no limits are set, so callers can skip tracking
+    pub(crate) fn new(limits: Option<BudgetLimits>, model: &str, pid: u32) -> Option<Arc<Self>> {
+        let limits = limits.filter(|l| !l.is_empty())?;
+        log::info!("Enforcing budget {:?} for PID {}", limits, pid);
+        let guard = Arc::new(Self {
+            limits,
+            model: model.to_string(),
+            pid,
+            started: Instant::now(),
+            usage: Mutex::new(Usage::default()),
+            exceeded: Mutex::new(None),
+            finished: AtomicBool::new(false),
+        });
+
+        if let Some(secs) = guard.limits.max_duration_secs {
+            let watchdog = guard.clone();
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(secs)).await;
+                if !watchdog.finished.load(Ordering::SeqCst) {
+                    watchdog.exceed("duration_secs", secs as f64, watchdog.elapsed_secs() as f64);
+                }
+            });
+        }
+        Some(guard)
+    }
+
+    fn elapsed_secs(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    /// Feeds one stdout line. Returns the violation the first time a cap is
+    /// hit, after the process has been asked to stop.
+    pub(crate) fn observe(&self, line: &str) -> Option<BudgetExceeded> {
+        let msg = StreamMessage::parse(line)?;
+        if !matches!(msg, StreamMessage::Assistant(_)) {
+            return None;
+        }
+        let message = msg.message()?;
+        let usage = message.usage.as_ref()?;
+        let model = message.model.as_deref().unwrap_or(&self.model);
+        let cost = calculate_cost(model, usage);
+        let tokens = usage.total_tokens();
+
+        let (total_cost, total_tokens, turns) = {
+            let mut tracked = self.usage.lock().ok()?;
+            match message.id.as_deref() {
+                Some(id) => {
+                    tracked.messages.insert(id.to_string(), (cost, tokens));
+                }
+                None => {
+                    tracked.anonymous.0 += cost;
+                    tracked.anonymous.1 += tokens;
+                }
+            }
+            let (total_cost, total_tokens) = tracked.totals();
+            (total_cost, total_tokens, tracked.turns())
+        };
+
+        if let Some(max) = self.limits.max_cost_usd.filter(|max| total_cost >= *max) {
+            return self.exceed("cost_usd", max, total_cost);
+        }
+        if let Some(max) = self.limits.max_tokens.filter(|max| total_tokens >= *max) {
+            return self.exceed("tokens", max as f64, total_tokens as f64);
+        }
+        if let Some(max) = self.limits.max_turns.filter(|max| turns > *max) {
+            return self.exceed("turns", max as f64, turns as f64);
+        }
+        None
+    }
+
+    /// Records the violation and stops the process; only the first call wins
+    fn exceed(&self, limit: &str, max: f64, actual: f64) -> Option<BudgetExceeded> {
+        let mut exceeded = self.exceeded.lock().ok()?;
+        if exceeded.is_some() {
+            return None;
+        }
+        let (cost_usd, tokens, turns) = self
+            .usage
+            .lock()
+            .map(|u| {
+                let (cost, tokens) = u.totals();
+                (cost, tokens, u.turns())
+            })
+            .unwrap_or_default();
+        let violation = BudgetExceeded {
+            limit: limit.to_string(),
+            max,
+            actual,
+            cost_usd,
+            tokens,
+            turns,
+            duration_secs: self.elapsed_secs(),
+        };
+        log::warn!("PID {}: {}", self.pid, violation.message());
+        *exceeded = Some(violation.clone());
+        terminate_gracefully(self.pid);
+        Some(violation)
+    }
+
+    /// The violation that stopped the process, if any
+    pub(crate) fn exceeded(&self) -> Option<BudgetExceeded> {
+        self.exceeded.lock().ok().and_then(|e| e.clone())
+    }
+
+    /// Marks the process as exited so the duration watchdog leaves it alone
+    pub(crate) fn finish(&self) {
+        self.finished.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Stops the process and its descendants, interrupting first so Claude can
+/// flush its transcript before it is killed
+fn terminate_gracefully(pid: u32) {
+    if pid == 0 {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        let report = terminate_process_tree(pid).await;
+        log::info!("Over-budget PID {} stopped at stage {:?}", pid, report.stage);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream_json::Usage as TokenUsage;
+
+    const MODEL: &str = "sonnet-3-5";
+
+    fn guard(limits: BudgetLimits) -> Arc<BudgetGuard> {
+        // PID 0 is never signalled
+        BudgetGuard::new(Some(limits), MODEL, 0).unwrap()
+    }
+
+    fn assistant_line(id: Option<&str>, input_tokens: u64, output_tokens: u64) -> String {
+        let mut message = serde_json::json!({
+            "role": "assistant",
+            "model": MODEL,
+            "content": [{ "type": "text", "text": "hi" }],
+            "usage": { "input_tokens": input_tokens, "output_tokens": output_tokens },
+        });
+        if let Some(id) = id {
+            message["id"] = id.into();
+        }
+        serde_json::json!({ "type": "assistant", "message": message }).to_string()
+    }
+
+    fn cost_of(input_tokens: u64, output_tokens: u64) -> f64 {
+        let usage = TokenUsage {
+            input_tokens: Some(input_tokens),
+            output_tokens: Some(output_tokens),
+            ..Default::default()
+        };
+        calculate_cost(MODEL, &usage)
+    }
+
+    #[test]
+    fn test_no_limits_means_no_guard() {
+        assert!(BudgetGuard::new(None, MODEL, 0).is_none());
+        assert!(BudgetGuard::new(Some(BudgetLimits::default()), MODEL, 0).is_none());
+    }
+
+    #[test]
+    fn test_cost_cap() {
+        let max = cost_of(1000, 1000) * 2.5;
+        let guard = guard(BudgetLimits {
+            max_cost_usd: Some(max),
+            ..Default::default()
+        });
+        assert!(guard.observe(&assistant_line(Some("msg_1"), 1000, 1000)).is_none());
+        assert!(guard.observe(&assistant_line(Some("msg_2"), 1000, 1000)).is_none());
+        let violation = guard.observe(&assistant_line(Some("msg_3"), 1000, 1000)).unwrap();
+        assert_eq!(violation.limit, "cost_usd");
+        assert_eq!(violation.max, max);
+        assert!((violation.actual - cost_of(1000, 1000) * 3.0).abs() < 1e-9);
+        assert_eq!(violation.turns, 3);
+        assert_eq!(violation.tokens, 6000);
+    }
+
+    #[test]
+    fn test_token_cap_counts_repeated_usage_once() {
+        let guard = guard(BudgetLimits {
+            max_tokens: Some(300),
+            ..Default::default()
+        });
+        // Every content block line of a message repeats its usage
+        for _ in 0..5 {
+            assert!(guard.observe(&assistant_line(Some("msg_1"), 100, 50)).is_none());
+        }
+        // A later line for the same message carries the final usage
+        assert!(guard.observe(&assistant_line(Some("msg_1"), 100, 120)).is_none());
+        assert_eq!(guard.usage.lock().unwrap().totals().1, 220);
+
+        let violation = guard.observe(&assistant_line(Some("msg_2"), 50, 30)).unwrap();
+        assert_eq!(violation.limit, "tokens");
+        assert_eq!((violation.max, violation.actual), (300.0, 300.0));
+        assert_eq!(violation.turns, 2);
+    }
+
+    #[test]
+    fn test_anonymous_usage_is_summed_but_not_a_turn() {
+        let guard = guard(BudgetLimits {
+            max_tokens: Some(1000),
+            max_turns: Some(2),
+            ..Default::default()
+        });
+        assert!(guard.observe(&assistant_line(None, 100, 100)).is_none());
+        assert!(guard.observe(&assistant_line(None, 100, 100)).is_none());
+        assert!(guard.observe(&assistant_line(Some("msg_1"), 100, 100)).is_none());
+        let usage = guard.usage.lock().unwrap();
+        assert_eq!((usage.totals().1, usage.turns()), (600, 1));
+    }
+
+    #[test]
+    fn test_turn_cap_returns_first_violation_only() {
+        let guard = guard(BudgetLimits {
+            max_turns: Some(2),
+            ..Default::default()
+        });
+        // Every line of the last allowed turn goes through
+        assert!(guard.observe(&assistant_line(Some("msg_1"), 10, 10)).is_none());
+        assert!(guard.observe(&assistant_line(Some("msg_2"), 10, 10)).is_none());
+        assert!(guard.observe(&assistant_line(Some("msg_2"), 10, 20)).is_none());
+        // Lines that are not assistant messages are ignored
+        assert!(guard
+            .observe(r#"{"type":"user","message":{"role":"user","content":"go on"}}"#)
+            .is_none());
+        assert!(guard.observe("not json").is_none());
+
+        // Starting a third turn stops the run
+        let violation = guard.observe(&assistant_line(Some("msg_3"), 10, 10)).unwrap();
+        assert_eq!(violation.limit, "turns");
+        assert_eq!((violation.max, violation.actual), (2.0, 3.0));
+
+        // Output still arriving while the process stops reports nothing new
+        assert!(guard.observe(&assistant_line(Some("msg_4"), 10, 10)).is_none());
+        let recorded = guard.exceeded().unwrap();
+        assert_eq!((recorded.limit.as_str(), recorded.turns), ("turns", 3));
+    }
+}
@@ -13,6 +13,23 @@ use tauri_plugin_shell::ShellExt;
 use tauri_plugin_shell::process::CommandEvent;
 use regex;
 use crate::{debug_log, info_log, error_log};
+use crate::stream_json::StreamMessage;
+
+use super::agents::AgentDb;
+use super::config_history::write_config_file;
+use super::project_index::{refresh_project_index, resolve_project_path};
+use super::session_cache::{list_cached_sessions, refresh_session_cache};
+use super::session_metadata::SessionFilter;
+use super::budget::{BudgetExceeded, BudgetGuard, BudgetLimits};
+use super::worktrees::{
+    create_worktree, discard_unused_worktree, set_worktree_session, worktrees_dir, Worktree,
+};
+use super::notifications::{
+    notify_budget_exceeded, notify_finished, notify_permission_request, permission_request_tool,
+    session_subject,
+};
+use super::quick_prompt::remember_last_project;
+use crate::process::{spawn_in_own_group, terminate_process_tree, TerminationReport};
 
 /// Global state to track current Claude process
 pub struct ClaudeProcessState {
@@ -57,23 +74,28 @@ pub struct Session {
     pub first_message: Option<String>,
     /// Timestamp of the first user message (if available)
     pub message_timestamp: Option<String>,
-}
-
-/// Represents a message entry in the JSONL file
-#[derive(Debug, Deserialize)]
-struct JsonlEntry {
-    #[serde(rename = "type")]
-    #[allow(dead_code)]
-    entry_type: Option<String>,
-    message: Option<MessageContent>,
-    timestamp: Option<String>,
-}
-
-/// Represents the message content
-#[derive(Debug, Deserialize)]
-struct MessageContent {
-    role: Option<String>,
-    content: Option<String>,
+    /// Custom title set by the user
+    pub title: Option<String>,
+    /// User-assigned tags
+    pub tags: Vec<String>,
+    /// Whether the session is pinned to the top of the list
+    pub pinned: bool,
+    /// Whether the session is archived (hidden unless requested)
+    pub archived: bool,
+    /// Free-form note
+    pub note: Option<String>,
+    /// Session this one was forked from, if any
+    pub forked_from_session_id: Option<String>,
+}
+
+/// A page of sessions along with the total number available
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionPage {
+    pub sessions: Vec<Session>,
+    /// Total number of sessions in the project
+    pub total: usize,
+    /// Offset of the first session in this page
+    pub offset: usize,
 }
 
 /// Represents the settings from ~/.claude/settings.json
@@ -137,7 +159,7 @@ fn find_claude_binary(app_handle: &AppHandle) -> Result<String, String> {
 }
 
 /// Gets the path to the ~/.claude directory
-fn get_claude_dir() -> Result<PathBuf> {
+pub(crate) fn get_claude_dir() -> Result<PathBuf> {
     dirs::home_dir()
         .context("Could not find home directory")?
         .join(".claude")
@@ -145,47 +167,8 @@ fn get_claude_dir() -> Result<PathBuf> {
         .context("Could not find ~/.claude directory")
 }
 
-/// Gets the actual project path by reading the cwd from the first JSONL entry
-fn get_project_path_from_sessions(project_dir: &PathBuf) -> Result<String, String> {
-    // Try to read any JSONL file in the directory
-    let entries = fs::read_dir(project_dir)
-        .map_err(|e| format!("Failed to read project directory: {}", e))?;
-
-    for entry in entries {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
-                // Read the first line of the JSONL file
-                if let Ok(file) = fs::File::open(&path) {
-                    let reader = BufReader::new(file);
-                    if let Some(Ok(first_line)) = reader.lines().next() {
-                        // Parse the JSON and extract cwd
-                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&first_line) {
-                            if let Some(cwd) = json.get("cwd").and_then(|v| v.as_str()) {
-                                return Ok(cwd.to_string());
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    Err("Could not determine project path from session files".to_string())
-}
-
-/// Decodes a project directory name back to its original path
-/// The directory names in ~/.claude/projects are encoded paths
-/// DEPRECATED: Use get_project_path_from_sessions instead when possible
-fn decode_project_path(encoded: &str) -> String {
-    // This is a fallback - the encoding isn't reversible when paths contain hyphens
-    // For example: -Users-mufeedvh-dev-jsonl-viewer could be /Users/mufeedvh/dev/jsonl-viewer
-    // or /Users/mufeedvh/dev/jsonl/viewer
-    encoded.replace('-', "/")
-}
-
 /// Extracts the first valid user message from a JSONL file
-fn extract_first_user_message(jsonl_path: &PathBuf) -> (Option<String>, Option<String>) {
+pub(crate) fn extract_first_user_message(jsonl_path: &PathBuf) -> (Option<String>, Option<String>) {
     let file = match fs::File::open(jsonl_path) {
         Ok(file) => file,
         Err(_) => return (None, None),
@@ -195,27 +178,28 @@ fn extract_first_user_message(jsonl_path: &PathBuf) -> (Option<String>, Option<S
 
     for line in reader.lines() {
         if let Ok(line) = line {
-            if let Ok(entry) = serde_json::from_str::<JsonlEntry>(&line) {
-                if let Some(message) = entry.message {
-                    if message.role.as_deref() == Some("user") {
-                        if let Some(content) = message.content {
-                            // Skip if it contains the caveat message
-                            if content.contains("Caveat: The messages below were generated by the user while running local commands") {
-                                continue;
-                            }
-
-                            // Skip if it starts with command tags
-                            if content.starts_with("<command-name>")
-                                || content.starts_with("<local-command-stdout>")
-                            {
-                                continue;
-                            }
+            let Some(msg) = StreamMessage::parse(&line) else {
+                continue;
+            };
+            if msg.message().and_then(|m| m.role.as_deref()) != Some("user") {
+                continue;
+            }
+            // Content may be a plain string or a list of blocks; tool results have no text
+            if let Some(content) = msg.text() {
+                // Skip if it contains the caveat message
+                if content.contains("Caveat: The messages below were generated by the user while running local commands") {
+                    continue;
+                }
 
-                            // Found a valid user message
-                            return (Some(content), entry.timestamp);
-                        }
-                    }
+                // Skip if it starts with command tags
+                if content.starts_with("<command-name>")
+                    || content.starts_with("<local-command-stdout>")
+                {
+                    continue;
                 }
+
+                // Found a valid user message
+                return (Some(content), msg.timestamp().map(str::to_string));
             }
         }
     }
@@ -486,6 +470,8 @@ fn create_system_command_with_env(
     env_vars: &std::collections::HashMap<String, String>,
 ) -> Command {
     let mut cmd = create_command_with_custom_env(claude_path, env_vars);
+    // Own process group, so cancelling reaches everything Claude starts
+    spawn_in_own_group(&mut cmd);
 
     // Add all arguments
     for arg in args {
@@ -501,7 +487,7 @@ fn create_system_command_with_env(
 
 /// Lists all projects in the ~/.claude/projects directory
 #[tauri::command]
-pub async fn list_projects() -> Result<Vec<Project>, String> {
+pub async fn list_projects(db: tauri::State<'_, AgentDb>) -> Result<Vec<Project>, String> {
     info_log!("Listing projects from ~/.claude/projects");
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
@@ -512,6 +498,12 @@ pub async fn list_projects() -> Result<Vec<Project>, String> {
         return Ok(Vec::new());
     }
 
+    // Resolve real project paths through the project index
+    let project_paths: std::collections::HashMap<String, String> = refresh_project_index(&db)?
+        .into_iter()
+        .map(|entry| (entry.project_id, entry.project_path))
+        .collect();
+
     let mut projects = Vec::new();
 
     // Read all directories in the projects folder
@@ -540,13 +532,9 @@ pub async fn list_projects() -> Result<Vec<Project>, String> {
                 .unwrap_or_default()
                 .as_secs();
 
-            // Get the actual project path from JSONL files
-            let project_path = match get_project_path_from_sessions(&path) {
-                Ok(path) => path,
-                Err(e) => {
-                    log::warn!("Failed to get project path from sessions for {}: {}, falling back to decode", dir_name, e);
-                    decode_project_path(dir_name)
-                }
+            let Some(project_path) = project_paths.get(dir_name).cloned() else {
+                log::warn!("Project directory {} is missing from the project index", dir_name);
+                continue;
             };
 
             // List all JSONL files (sessions) in this project directory
@@ -584,91 +572,94 @@ pub async fn list_projects() -> Result<Vec<Project>, String> {
     Ok(projects)
 }
 
-/// Gets sessions for a specific project
-#[tauri::command]
-pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, String> {
-    log::info!("Getting sessions for project: {}", project_id);
+/// Loads the todo list Claude Code keeps for a session, if any
+fn load_session_todos(todos_dir: &PathBuf, session_id: &str) -> Option<serde_json::Value> {
+    let todo_path = todos_dir.join(format!("{}.json", session_id));
+    if !todo_path.exists() {
+        return None;
+    }
+    fs::read_to_string(&todo_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+}
 
+/// Reads one page of a project's sessions from the session cache,
+/// rescanning only the session files that changed since the last call
+pub(crate) fn load_sessions_page(
+    db: &AgentDb,
+    project_id: &str,
+    filter: &SessionFilter,
+    offset: usize,
+    limit: Option<usize>,
+) -> Result<SessionPage, String> {
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
-    let project_dir = claude_dir.join("projects").join(&project_id);
     let todos_dir = claude_dir.join("todos");
 
-    if !project_dir.exists() {
-        return Err(format!("Project directory not found: {}", project_id));
-    }
-
-    // Get the actual project path from JSONL files
-    let project_path = match get_project_path_from_sessions(&project_dir) {
-        Ok(path) => path,
-        Err(e) => {
-            log::warn!(
-                "Failed to get project path from sessions for {}: {}, falling back to decode",
-                project_id,
-                e
-            );
-            decode_project_path(&project_id)
-        }
+    let project_path = resolve_project_path(db, project_id)?;
+    refresh_session_cache(db, project_id)?;
+    let (cached, total) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        list_cached_sessions(&conn, project_id, filter, offset, limit)?
     };
 
-    let mut sessions = Vec::new();
-
-    // Read all JSONL files in the project directory
-    let entries = fs::read_dir(&project_dir)
-        .map_err(|e| format!("Failed to read project directory: {}", e))?;
-
-    for entry in entries {
-        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
-        let path = entry.path();
+    let sessions = cached
+        .into_iter()
+        .map(|session| Session {
+            todo_data: load_session_todos(&todos_dir, &session.session_id),
+            id: session.session_id,
+            project_id: session.project_id,
+            project_path: project_path.clone(),
+            created_at: session.created_at,
+            first_message: session.first_message,
+            message_timestamp: session.message_timestamp,
+            title: session.title,
+            tags: session.tags,
+            pinned: session.pinned,
+            archived: session.archived,
+            note: session.note,
+            forked_from_session_id: session.forked_from_session_id,
+        })
+        .collect();
 
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
-            if let Some(session_id) = path.file_stem().and_then(|s| s.to_str()) {
-                // Get file creation time
-                let metadata = fs::metadata(&path)
-                    .map_err(|e| format!("Failed to read file metadata: {}", e))?;
-
-                let created_at = metadata
-                    .created()
-                    .or_else(|_| metadata.modified())
-                    .unwrap_or(SystemTime::UNIX_EPOCH)
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs();
-
-                // Extract first user message and timestamp
-                let (first_message, message_timestamp) = extract_first_user_message(&path);
-
-                // Try to load associated todo data
-                let todo_path = todos_dir.join(format!("{}.json", session_id));
-                let todo_data = if todo_path.exists() {
-                    fs::read_to_string(&todo_path)
-                        .ok()
-                        .and_then(|content| serde_json::from_str(&content).ok())
-                } else {
-                    None
-                };
+    Ok(SessionPage {
+        sessions,
+        total,
+        offset,
+    })
+}
 
-                sessions.push(Session {
-                    id: session_id.to_string(),
-                    project_id: project_id.clone(),
-                    project_path: project_path.clone(),
-                    todo_data,
-                    created_at,
-                    first_message,
-                    message_timestamp,
-                });
-            }
-        }
-    }
+/// Gets sessions for a specific project.
+/// Without a filter, archived sessions are hidden and pinned ones come first, newest first.
+#[tauri::command]
+pub async fn get_project_sessions(
+    db: tauri::State<'_, AgentDb>,
+    project_id: String,
+    filter: Option<SessionFilter>,
+) -> Result<Vec<Session>, String> {
+    log::info!("Getting sessions for project: {}", project_id);
 
-    // Sort sessions by creation time (newest first)
-    sessions.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    let filter = filter.unwrap_or_default();
+    let page = load_sessions_page(&db, &project_id, &filter, 0, None)?;
 
     log::info!(
         "Found {} sessions for project {}",
-        sessions.len(),
+        page.total,
         project_id
     );
-    Ok(sessions)
+    Ok(page.sessions)
+}
+
+/// Gets one page of a project's sessions, filtered and sorted like `get_project_sessions`
+#[tauri::command]
+pub async fn get_project_sessions_page(
+    db: tauri::State<'_, AgentDb>,
+    project_id: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    filter: Option<SessionFilter>,
+) -> Result<SessionPage, String> {
+    let filter = filter.unwrap_or_default();
+    load_sessions_page(&db, &project_id, &filter, offset.unwrap_or(0), limit)
 }
 
 /// Reads the Claude settings file
@@ -942,31 +933,38 @@ pub async fn check_claude_version(app: AppHandle) -> Result<ClaudeVersionStatus,
 
 /// Saves the CLAUDE.md system prompt file
 #[tauri::command]
-pub async fn save_system_prompt(content: String) -> Result<String, String> {
+pub async fn save_system_prompt(
+    db: tauri::State<'_, AgentDb>,
+    content: String,
+) -> Result<String, String> {
     log::info!("Saving CLAUDE.md system prompt");
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
     let claude_md_path = claude_dir.join("CLAUDE.md");
 
-    fs::write(&claude_md_path, content).map_err(|e| format!("Failed to write CLAUDE.md: {}", e))?;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    write_config_file(&conn, &claude_md_path, &content, "save_system_prompt")?;
 
     Ok("System prompt saved successfully".to_string())
 }
 
 /// Saves the Claude settings file
 #[tauri::command]
-pub async fn save_claude_settings(settings: serde_json::Value) -> Result<String, String> {
+pub async fn save_claude_settings(
+    db: tauri::State<'_, AgentDb>,
+    settings: serde_json::Value,
+) -> Result<String, String> {
     log::info!("Saving Claude settings");
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
     let settings_path = claude_dir.join("settings.json");
 
-    // Pretty print the JSON with 2-space indentation
-    let json_string = serde_json::to_string_pretty(&settings)
-        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    // Problems already in the file must not block saving unrelated keys
+    let previous = super::settings::read_settings_file(&settings_path).ok().flatten();
+    super::settings::ensure_valid_settings(&settings, previous.as_ref())?;
 
-    fs::write(&settings_path, json_string)
-        .map_err(|e| format!("Failed to write settings file: {}", e))?;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    super::settings::write_settings_file(&conn, &settings_path, &settings, "save_claude_settings")?;
 
     Ok("Settings saved successfully".to_string())
 }
@@ -1072,18 +1070,18 @@ pub async fn read_claude_md_file(file_path: String) -> Result<String, String> {
 
 /// Saves a specific CLAUDE.md file by its absolute path
 #[tauri::command]
-pub async fn save_claude_md_file(file_path: String, content: String) -> Result<String, String> {
+pub async fn save_claude_md_file(
+    db: tauri::State<'_, AgentDb>,
+    file_path: String,
+    content: String,
+) -> Result<String, String> {
     log::info!("Saving CLAUDE.md file: {}", file_path);
 
     let path = PathBuf::from(&file_path);
 
-    // Ensure the parent directory exists
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create parent directory: {}", e))?;
-    }
-
-    fs::write(&path, content).map_err(|e| format!("Failed to write file: {}", e))?;
+    // Creates the parent directory if needed
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    write_config_file(&conn, &path, &content, "save_claude_md_file")?;
 
     Ok("File saved successfully".to_string())
 }
@@ -1129,21 +1127,63 @@ pub async fn load_session_history(
 
 
 
-/// Execute a new interactive Claude Code session with streaming output
-#[tauri::command]
-pub async fn execute_claude_code(
-    app: AppHandle,
-    project_path: String,
-    prompt: String,
-    model: String,
-) -> Result<(), String> {
-    use crate::commands::agents::{AgentDb, get_enabled_environment_variables};
+/// How a spawned Claude Code process relates to earlier conversations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "session_id", rename_all = "snake_case")]
+pub enum SessionMode {
+    /// Start a brand new conversation
+    New,
+    /// Continue the most recent conversation in the project (`-c`)
+    Continue,
+    /// Resume a specific conversation by session ID (`--resume`)
+    Resume(String),
+}
+
+/// Outcome reported once a spawned Claude Code process has exited
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionOutcome {
+    /// Claude's session ID, if the init message was received
+    pub session_id: Option<String>,
+    /// Whether the process exited successfully
+    pub success: bool,
+    /// Set when the process was stopped for exceeding its budget
+    pub budget_exceeded: Option<BudgetExceeded>,
+}
+
+/// Sender used by background callers to wait for a session to finish
+pub type SessionCompletion = tokio::sync::oneshot::Sender<SessionOutcome>;
+
+/// Channels through which a background caller follows the session it spawned
+#[derive(Default)]
+pub struct SessionChannels {
+    /// Receives Claude's session ID as soon as the init message arrives
+    pub started: Option<tokio::sync::oneshot::Sender<String>>,
+    /// Receives the [`SessionOutcome`] after the process exits
+    pub completion: Option<SessionCompletion>,
+}
+
+/// Tells the frontend a session was stopped because it hit a budget cap
+fn emit_budget_exceeded(
+    app: &AppHandle,
+    session_id: Option<&str>,
+    project_path: &str,
+    violation: &BudgetExceeded,
+) {
+    if let Some(session_id) = session_id {
+        let _ = app.emit(&format!("claude-budget-exceeded:{}", session_id), violation);
+    }
+    let _ = app.emit("claude-budget-exceeded", violation);
+    notify_budget_exceeded(app, &session_subject(project_path), violation);
+}
+
+/// Collects the environment for a Claude Code session: enabled variables from the
+/// database plus the list of MCP servers disabled for the project
+async fn build_session_env(
+    app: &AppHandle,
+    project_path: &str,
+) -> std::collections::HashMap<String, String> {
+    use crate::commands::agents::get_enabled_environment_variables;
     use crate::commands::mcp::{mcp_read_project_config, MCPProjectConfig};
-    log::info!(
-        "Starting new Claude Code session in: {} with model: {}",
-        project_path,
-        model
-    );
 
     // Get enabled environment variables from database
     let mut env_vars = match get_enabled_environment_variables(app.state::<AgentDb>()).await {
@@ -1161,7 +1201,7 @@ pub async fn execute_claude_code(
     };
 
     // Read project MCP configuration to get disabled servers
-    let project_config = mcp_read_project_config(project_path.clone()).await.unwrap_or_else(|_| MCPProjectConfig {
+    let project_config = mcp_read_project_config(project_path.to_string()).await.unwrap_or_else(|_| MCPProjectConfig {
         mcp_servers: std::collections::HashMap::new(),
     });
 
@@ -1184,9 +1224,33 @@ pub async fn execute_claude_code(
         env_vars.insert("CLAUDIA_DISABLED_MCP_SERVERS".to_string(), disabled_list);
     }
 
+    env_vars
+}
+
+/// Spawns Claude Code for the given mode and streams its output to the frontend.
+///
+/// `channels` report this particular process's session ID once Claude prints
+/// its init message and its [`SessionOutcome`] after it exits, which lets
+/// background callers (such as the prompt queue) chain work on top of a session.
+/// With a `budget` the process is stopped once it exceeds any of the limits.
+pub(crate) async fn start_claude_session(
+    app: AppHandle,
+    project_path: String,
+    prompt: String,
+    model: String,
+    mode: SessionMode,
+    budget: Option<BudgetLimits>,
+    channels: SessionChannels,
+) -> Result<(), String> {
+    let env_vars = build_session_env(&app, &project_path).await;
     let claude_path = find_claude_binary(&app)?;
 
-    let args = vec![
+    let mut args = match &mode {
+        SessionMode::New => Vec::new(),
+        SessionMode::Continue => vec!["-c".to_string()],
+        SessionMode::Resume(session_id) => vec!["--resume".to_string(), session_id.clone()],
+    };
+    args.extend([
         "-p".to_string(),
         prompt.clone(),
         "--model".to_string(),
@@ -1195,90 +1259,136 @@ pub async fn execute_claude_code(
         "stream-json".to_string(),
         "--verbose".to_string(),
         "--dangerously-skip-permissions".to_string(),
-    ];
+    ]);
 
     // On macOS, when the stored path is the special sidecar identifier, use sidecar to spawn
     #[cfg(target_os = "macos")]
     if claude_path == "claude-code" {
         // TODO: Update sidecar to also use environment variables
-        return spawn_claude_sidecar(app, args, prompt, model, project_path).await;
+        return spawn_claude_sidecar(app, args, prompt, model, project_path, budget, channels).await;
     }
 
     let cmd = create_system_command_with_env(&claude_path, args, &project_path, &env_vars);
-    spawn_claude_process(app, cmd, prompt, model, project_path).await
+    spawn_claude_process(app, cmd, prompt, model, project_path, budget, channels).await
 }
 
-/// Continue an existing Claude Code conversation with streaming output
+/// Execute a new interactive Claude Code session with streaming output.
+/// With `isolate` the session runs in a new git worktree, which is returned.
 #[tauri::command]
-pub async fn continue_claude_code(
+pub async fn execute_claude_code(
     app: AppHandle,
     project_path: String,
     prompt: String,
     model: String,
-) -> Result<(), String> {
-    use crate::commands::agents::{AgentDb, get_enabled_environment_variables};
-    use crate::commands::mcp::{mcp_read_project_config, MCPProjectConfig};
+    isolate: Option<bool>,
+    budget: Option<BudgetLimits>,
+) -> Result<Option<Worktree>, String> {
+    start_new_session(app, project_path, prompt, model, isolate, budget, None).await
+}
+
+/// Starts a new session like [`execute_claude_code`]; `started` receives the
+/// session ID from this process's init message.
+pub(crate) async fn start_new_session(
+    app: AppHandle,
+    project_path: String,
+    prompt: String,
+    model: String,
+    isolate: Option<bool>,
+    budget: Option<BudgetLimits>,
+    started: Option<tokio::sync::oneshot::Sender<String>>,
+) -> Result<Option<Worktree>, String> {
     log::info!(
-        "Continuing Claude Code conversation in: {} with model: {}",
+        "Starting new Claude Code session in: {} with model: {}",
         project_path,
         model
     );
+    remember_last_project(&app, &project_path);
+
+    if !isolate.unwrap_or(false) {
+        start_claude_session(
+            app,
+            project_path,
+            prompt,
+            model,
+            SessionMode::New,
+            budget,
+            SessionChannels {
+                started,
+                ..Default::default()
+            },
+        )
+        .await?;
+        return Ok(None);
+    }
+
+    // Run in a dedicated worktree and remember which session used it
+    let worktree = create_worktree(
+        &app.state::<AgentDb>(),
+        &worktrees_dir(&app)?,
+        &project_path,
+        "session",
+    )?;
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let spawned = start_claude_session(
+        app.clone(),
+        worktree.worktree_path.clone(),
+        prompt,
+        model,
+        SessionMode::New,
+        budget,
+        SessionChannels {
+            started,
+            completion: Some(tx),
+        },
+    )
+    .await;
+    if let Err(e) = spawned {
+        discard_unused_worktree(&app.state::<AgentDb>(), &worktree);
+        return Err(e);
+    }
 
-    // Get enabled environment variables from database
-    let mut env_vars = match get_enabled_environment_variables(app.state::<AgentDb>()).await {
-        Ok(vars) => vars,
-        Err(e) => {
-            log::warn!("Failed to get environment variables: {}", e);
-            std::collections::HashMap::new()
+    let worktree_id = worktree.id;
+    tauri::async_runtime::spawn(async move {
+        if let Ok(SessionOutcome { session_id: Some(session_id), .. }) = rx.await {
+            let db = app.state::<AgentDb>();
+            let result = match db.0.lock() {
+                Ok(conn) => set_worktree_session(&conn, worktree_id, &session_id),
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = result {
+                log::warn!("Failed to link session {} to worktree: {}", session_id, e);
+            }
         }
-    };
-
-    // Read project MCP configuration to get disabled servers
-    let project_config = mcp_read_project_config(project_path.clone()).await.unwrap_or_else(|_| MCPProjectConfig {
-        mcp_servers: std::collections::HashMap::new(),
     });
+    Ok(Some(worktree))
+}
 
-    // Collect disabled server names
-    let disabled_servers: Vec<String> = project_config.mcp_servers
-        .iter()
-        .filter_map(|(name, config)| {
-            if config.disabled {
-                Some(name.clone())
-            } else {
-                None
-            }
-        })
-        .collect();
-
-    if !disabled_servers.is_empty() {
-        let disabled_list = disabled_servers.join(",");
-        log::info!("Disabling MCP servers for continue session: {}", disabled_list);
-        // Set environment variable to pass disabled servers to Claude Code
-        env_vars.insert("CLAUDIA_DISABLED_MCP_SERVERS".to_string(), disabled_list);
-    }
-
-    let claude_path = find_claude_binary(&app)?;
-
-    let args = vec![
-        "-c".to_string(), // Continue flag
-        "-p".to_string(),
-        prompt.clone(),
-        "--model".to_string(),
-        model.clone(),
-        "--output-format".to_string(),
-        "stream-json".to_string(),
-        "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
-    ];
-
-    // On macOS, when the stored path is the special sidecar identifier, use sidecar to spawn
-    #[cfg(target_os = "macos")]
-    if claude_path == "claude-code" {
-        return spawn_claude_sidecar(app, args, prompt, model, project_path).await;
-    }
+/// Continue an existing Claude Code conversation with streaming output
+#[tauri::command]
+pub async fn continue_claude_code(
+    app: AppHandle,
+    project_path: String,
+    prompt: String,
+    model: String,
+    budget: Option<BudgetLimits>,
+) -> Result<(), String> {
+    log::info!(
+        "Continuing Claude Code conversation in: {} with model: {}",
+        project_path,
+        model
+    );
+    remember_last_project(&app, &project_path);
 
-    let cmd = create_system_command_with_env(&claude_path, args, &project_path, &env_vars);
-    spawn_claude_process(app, cmd, prompt, model, project_path).await
+    start_claude_session(
+        app,
+        project_path,
+        prompt,
+        model,
+        SessionMode::Continue,
+        budget,
+        SessionChannels::default(),
+    )
+    .await
 }
 
 /// Resume an existing Claude Code session by ID with streaming output
@@ -1289,89 +1399,46 @@ pub async fn resume_claude_code(
     session_id: String,
     prompt: String,
     model: String,
+    budget: Option<BudgetLimits>,
 ) -> Result<(), String> {
-    use crate::commands::agents::{AgentDb, get_enabled_environment_variables};
-    use crate::commands::mcp::{mcp_read_project_config, MCPProjectConfig};
     log::info!(
         "Resuming Claude Code session: {} in: {} with model: {}",
         session_id,
         project_path,
         model
     );
+    remember_last_project(&app, &project_path);
 
-    // Get enabled environment variables from database
-    let mut env_vars = match get_enabled_environment_variables(app.state::<AgentDb>()).await {
-        Ok(vars) => vars,
-        Err(e) => {
-            log::warn!("Failed to get environment variables: {}", e);
-            std::collections::HashMap::new()
-        }
-    };
-
-    // Read project MCP configuration to get disabled servers
-    let project_config = mcp_read_project_config(project_path.clone()).await.unwrap_or_else(|_| MCPProjectConfig {
-        mcp_servers: std::collections::HashMap::new(),
-    });
-
-    // Collect disabled server names
-    let disabled_servers: Vec<String> = project_config.mcp_servers
-        .iter()
-        .filter_map(|(name, config)| {
-            if config.disabled {
-                Some(name.clone())
-            } else {
-                None
-            }
-        })
-        .collect();
-
-    if !disabled_servers.is_empty() {
-        let disabled_list = disabled_servers.join(",");
-        log::info!("Disabling MCP servers for resume session: {}", disabled_list);
-        // Set environment variable to pass disabled servers to Claude Code
-        env_vars.insert("CLAUDIA_DISABLED_MCP_SERVERS".to_string(), disabled_list);
-    }
-
-    let claude_path = find_claude_binary(&app)?;
-
-    let args = vec![
-        "--resume".to_string(),
-        session_id.clone(),
-        "-p".to_string(),
-        prompt.clone(),
-        "--model".to_string(),
-        model.clone(),
-        "--output-format".to_string(),
-        "stream-json".to_string(),
-        "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
-    ];
-
-    // On macOS, when the stored path is the special sidecar identifier, use sidecar to spawn
-    #[cfg(target_os = "macos")]
-    if claude_path == "claude-code" {
-        return spawn_claude_sidecar(app, args, prompt, model, project_path).await;
-    }
-
-    let cmd = create_system_command_with_env(&claude_path, args, &project_path, &env_vars);
-    spawn_claude_process(app, cmd, prompt, model, project_path).await
-}
-
-/// Cancel the currently running Claude Code execution
+    start_claude_session(
+        app,
+        project_path,
+        prompt,
+        model,
+        SessionMode::Resume(session_id),
+        budget,
+        SessionChannels::default(),
+    )
+    .await
+}
+
+/// Cancel the currently running Claude Code execution. The process and its
+/// descendants get SIGINT first so Claude can flush the session transcript,
+/// then SIGTERM and SIGKILL; the report says which step stopped them.
 #[tauri::command]
 pub async fn cancel_claude_execution(
     app: AppHandle,
     session_id: Option<String>,
-) -> Result<(), String> {
+) -> Result<Option<TerminationReport>, String> {
     log::info!(
         "Cancelling Claude Code execution for session: {:?}",
         session_id
     );
 
     let mut killed = false;
+    let mut report = None;
     let mut attempted_methods = Vec::new();
 
-    // Method 1: Try to find and kill via ProcessRegistry using session ID
+    // Method 1: Try to find and stop via ProcessRegistry using session ID
     if let Some(sid) = &session_id {
         let registry = app.state::<crate::process::ProcessRegistryState>();
         match registry.0.get_claude_session_by_id(sid) {
@@ -1379,13 +1446,12 @@ pub async fn cancel_claude_execution(
                 log::info!("Found process in registry for session {}: run_id={}, PID={}",
                     sid, process_info.run_id, process_info.pid);
                 match registry.0.kill_process(process_info.run_id).await {
-                    Ok(success) => {
-                        if success {
-                            log::info!("Successfully killed process via registry");
-                            killed = true;
-                        } else {
-                            log::warn!("Registry kill returned false");
-                        }
+                    Ok(Some(termination)) => {
+                        killed = termination.survivors.is_empty();
+                        report = Some(termination);
+                    }
+                    Ok(None) => {
+                        log::warn!("Process for session {} was no longer registered", sid);
                     }
                     Err(e) => {
                         log::warn!("Failed to kill via registry: {}", e);
@@ -1408,69 +1474,35 @@ pub async fn cancel_claude_execution(
         let mut current_process = claude_state.current_process.lock().await;
 
         if let Some(mut child) = current_process.take() {
-            // Try to get the PID before killing
-            let pid = child.id();
-            log::info!("Attempting to kill Claude process via ClaudeProcessState with PID: {:?}", pid);
-
-            // Kill the process
-            match child.kill().await {
-                Ok(_) => {
-                    log::info!("Successfully killed Claude process via ClaudeProcessState");
-                    killed = true;
-                }
-                Err(e) => {
-                    log::error!("Failed to kill Claude process via ClaudeProcessState: {}", e);
-
-                    // Method 3: If we have a PID, try system kill as last resort
-                    if let Some(pid) = pid {
-                        log::info!("Attempting system kill as last resort for PID: {}", pid);
-                        let kill_result = if cfg!(target_os = "windows") {
-                            {
-                                let mut cmd = std::process::Command::new("taskkill");
-                                cmd.args(["/F", "/PID", &pid.to_string()]);
-                                
-                                // On Windows, hide the console window to prevent CMD popup
-                                #[cfg(target_os = "windows")]
-                                {
-                                    use std::os::windows::process::CommandExt;
-                                    const CREATE_NO_WINDOW: u32 = 0x08000000;
-                                    cmd.creation_flags(CREATE_NO_WINDOW);
-                                }
-                                
-                                cmd.output()
-                            }
-                        } else {
-                            {
-                                let mut cmd = std::process::Command::new("kill");
-                                cmd.args(["-KILL", &pid.to_string()]);
-                                
-                                // On Unix systems, this doesn't need CREATE_NO_WINDOW
-                                cmd.output()
-                            }
-                        };
-
-                        match kill_result {
-                            Ok(output) if output.status.success() => {
-                                log::info!("Successfully killed process via system command");
-                                killed = true;
-                            }
-                            Ok(output) => {
-                                let stderr = String::from_utf8_lossy(&output.stderr);
-                                log::error!("System kill failed: {}", stderr);
-                            }
-                            Err(e) => {
-                                log::error!("Failed to execute system kill command: {}", e);
-                            }
-                        }
-                    }
+            match child.id() {
+                Some(pid) => {
+                    log::info!("Stopping Claude process via ClaudeProcessState with PID: {}", pid);
+                    let termination = terminate_process_tree(pid).await;
+                    killed = termination.survivors.is_empty();
+                    report = Some(termination);
                 }
+                None => log::info!("Claude process in ClaudeProcessState had already exited"),
             }
+            // Reap the child so it does not linger as a zombie
+            let _ = child.try_wait();
             attempted_methods.push("claude_state");
         } else {
             log::warn!("No active Claude process in ClaudeProcessState");
         }
     }
 
+    if let Some(report) = &report {
+        log::info!(
+            "Claude process {} stopped at stage {:?}, survivors: {:?}",
+            report.pid,
+            report.stage,
+            report.survivors
+        );
+        if let Some(sid) = &session_id {
+            let _ = app.emit(&format!("claude-terminated:{}", sid), report);
+        }
+    }
+
     if !killed && attempted_methods.is_empty() {
         log::warn!("No active Claude process found to cancel");
     }
@@ -1493,7 +1525,7 @@ pub async fn cancel_claude_execution(
         log::warn!("Claude process cancellation attempted but process may have already exited. Attempted methods: {:?}", attempted_methods);
     }
 
-    Ok(())
+    Ok(report)
 }
 
 /// Get all running Claude sessions
@@ -1512,14 +1544,22 @@ pub async fn get_claude_session_output(
 ) -> Result<String, String> {
     // Find the process by session ID
     if let Some(process_info) = registry.0.get_claude_session_by_id(&session_id)? {
-        registry.0.get_live_output(process_info.run_id)
+        registry.0.get_live_output_text(process_info.run_id)
     } else {
         Ok(String::new())
     }
 }
 
 /// Helper function to spawn Claude process and handle streaming
-async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String, model: String, project_path: String) -> Result<(), String> {
+async fn spawn_claude_process(
+    app: AppHandle,
+    mut cmd: Command,
+    prompt: String,
+    model: String,
+    project_path: String,
+    budget: Option<BudgetLimits>,
+    channels: SessionChannels,
+) -> Result<(), String> {
     use tokio::io::{AsyncBufReadExt, BufReader};
     use std::sync::Mutex;
 
@@ -1538,6 +1578,7 @@ async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String,
         "Spawned Claude process with PID: {:?}",
         pid
     );
+    let budget_guard = BudgetGuard::new(budget, &model, pid);
 
     // Create readers first (before moving child)
     let stdout_reader = BufReader::new(stdout);
@@ -1568,48 +1609,52 @@ async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String,
     let project_path_clone = project_path.clone();
     let prompt_clone = prompt.clone();
     let model_clone = model.clone();
+    let budget_guard_clone = budget_guard.clone();
+    let SessionChannels { mut started, completion } = channels;
     let stdout_task = tokio::spawn(async move {
         let mut lines = stdout_reader.lines();
+        let mut permission_notified = false;
         while let Ok(Some(line)) = lines.next_line().await {
             log::debug!("Claude stdout: {}", line);
 
             // Parse the line to check for init message with session ID
-            if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&line) {
-                if msg["type"] == "system" && msg["subtype"] == "init" {
-                    if let Some(claude_session_id) = msg["session_id"].as_str() {
-                        let mut session_id_guard = match session_id_holder_clone.lock() {
-                            Ok(guard) => guard,
-                            Err(e) => {
-                                error_log!("Failed to lock session_id_holder: {}", e);
-                                return;
+            if let Some(msg) = StreamMessage::parse(&line) {
+                if let Some(claude_session_id) = msg.init_session_id() {
+                    let mut session_id_guard = match session_id_holder_clone.lock() {
+                        Ok(guard) => guard,
+                        Err(e) => {
+                            error_log!("Failed to lock session_id_holder: {}", e);
+                            return;
+                        }
+                    };
+                    if session_id_guard.is_none() {
+                        *session_id_guard = Some(claude_session_id.to_string());
+                        log::info!("Extracted Claude session ID: {}", claude_session_id);
+                        if let Some(started) = started.take() {
+                            let _ = started.send(claude_session_id.to_string());
+                        }
+
+                        // Now register with ProcessRegistry using Claude's session ID
+                        match registry_clone.register_claude_session(
+                            claude_session_id.to_string(),
+                            pid,
+                            project_path_clone.clone(),
+                            prompt_clone.clone(),
+                            model_clone.clone(),
+                        ) {
+                            Ok(run_id) => {
+                                log::info!("Registered Claude session with run_id: {}", run_id);
+                                let mut run_id_guard = match run_id_holder_clone.lock() {
+                                    Ok(guard) => guard,
+                                    Err(e) => {
+                                        error_log!("Failed to lock run_id_holder: {}", e);
+                                        return;
+                                    }
+                                };
+                                *run_id_guard = Some(run_id);
                             }
-                        };
-                        if session_id_guard.is_none() {
-                            *session_id_guard = Some(claude_session_id.to_string());
-                            log::info!("Extracted Claude session ID: {}", claude_session_id);
-
-                            // Now register with ProcessRegistry using Claude's session ID
-                            match registry_clone.register_claude_session(
-                                claude_session_id.to_string(),
-                                pid,
-                                project_path_clone.clone(),
-                                prompt_clone.clone(),
-                                model_clone.clone(),
-                            ) {
-                                Ok(run_id) => {
-                                    log::info!("Registered Claude session with run_id: {}", run_id);
-                                    let mut run_id_guard = match run_id_holder_clone.lock() {
-                                        Ok(guard) => guard,
-                                        Err(e) => {
-                                            error_log!("Failed to lock run_id_holder: {}", e);
-                                            return;
-                                        }
-                                    };
-                                    *run_id_guard = Some(run_id);
-                                }
-                                Err(e) => {
-                                    log::error!("Failed to register Claude session: {}", e);
-                                }
+                            Err(e) => {
+                                log::error!("Failed to register Claude session: {}", e);
                             }
                         }
                     }
@@ -1633,6 +1678,19 @@ async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String,
             }
             // Also emit to the generic event for backward compatibility
             let _ = app_handle.emit("claude-output", &line);
+
+            // Stop the process once it runs over budget
+            if let Some(guard) = &budget_guard_clone {
+                guard.observe(&line);
+            }
+
+            // Let the user know once if Claude is blocked on a permission
+            if !permission_notified {
+                if let Some(tool) = permission_request_tool(&line) {
+                    permission_notified = true;
+                    notify_permission_request(&app_handle, &session_subject(&project_path_clone), &tool);
+                }
+            }
         }
     });
 
@@ -1663,26 +1721,46 @@ async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String,
     let session_id_holder_clone3 = session_id_holder.clone();
     let run_id_holder_clone2 = run_id_holder.clone();
     let registry_clone2 = registry.0.clone();
+    let project_path_wait = project_path.clone();
     tokio::spawn(async move {
         let _ = stdout_task.await;
         let _ = stderr_task.await;
 
         // Get the child from the state to wait on it
         let mut current_process = claude_state_wait.lock().await;
+        let mut success = false;
+        let mut budget_exceeded = None;
         if let Some(mut child) = current_process.take() {
             match child.wait().await {
                 Ok(status) => {
                     log::info!("Claude process exited with status: {}", status);
+                    budget_exceeded = budget_guard.as_ref().and_then(|guard| {
+                        guard.finish();
+                        guard.exceeded()
+                    });
+                    success = status.success() && budget_exceeded.is_none();
+                    if let Some(violation) = &budget_exceeded {
+                        let session_id = session_id_holder_clone3.lock().ok().and_then(|guard| guard.clone());
+                        emit_budget_exceeded(
+                            &app_handle_wait,
+                            session_id.as_deref(),
+                            &project_path_wait,
+                            violation,
+                        );
+                    }
                     // Add a small delay to ensure all messages are processed
                     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                     if let Some(ref session_id) = *session_id_holder_clone3.lock().unwrap() {
                         let _ = app_handle_wait.emit(
                             &format!("claude-complete:{}", session_id),
-                            status.success(),
+                            success,
                         );
                     }
                     // Also emit to the generic event for backward compatibility
-                    let _ = app_handle_wait.emit("claude-complete", status.success());
+                    let _ = app_handle_wait.emit("claude-complete", success);
+                    if budget_exceeded.is_none() {
+                        notify_finished(&app_handle_wait, &session_subject(&project_path_wait), success);
+                    }
                 }
                 Err(e) => {
                     log::error!("Failed to wait for Claude process: {}", e);
@@ -1694,9 +1772,13 @@ async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String,
                     }
                     // Also emit to the generic event for backward compatibility
                     let _ = app_handle_wait.emit("claude-complete", false);
+                    notify_finished(&app_handle_wait, &session_subject(&project_path_wait), false);
                 }
             }
         }
+        if let Some(guard) = &budget_guard {
+            guard.finish();
+        }
 
         // Unregister from ProcessRegistry if we have a run_id
         if let Some(run_id) = match run_id_holder_clone2.lock() {
@@ -1711,6 +1793,16 @@ async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String,
 
         // Clear the process from state
         *current_process = None;
+
+        // Notify any background caller waiting on this session
+        if let Some(completion) = completion {
+            let session_id = session_id_holder_clone3.lock().ok().and_then(|guard| guard.clone());
+            let _ = completion.send(SessionOutcome {
+                session_id,
+                success,
+                budget_exceeded,
+            });
+        }
     });
 
     Ok(())
@@ -1724,6 +1816,8 @@ async fn spawn_claude_sidecar(
     prompt: String,
     model: String,
     project_path: String,
+    budget: Option<BudgetLimits>,
+    channels: SessionChannels,
 ) -> Result<(), String> {
     use std::sync::Mutex;
 
@@ -1738,6 +1832,7 @@ async fn spawn_claude_sidecar(
     // Get the child PID for logging
     let pid = child.pid();
     log::info!("Spawned Claude sidecar process with PID: {:?}", pid);
+    let budget_guard = BudgetGuard::new(budget, &model, pid);
 
     // We'll extract the session ID from Claude's init message
     let session_id_holder: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
@@ -1754,8 +1849,13 @@ async fn spawn_claude_sidecar(
     let app_handle = app.clone();
     let session_id_holder_clone = session_id_holder.clone();
     let run_id_holder_clone = run_id_holder.clone();
+    let SessionChannels {
+        mut started,
+        mut completion,
+    } = channels;
 
     tauri::async_runtime::spawn(async move {
+        let mut permission_notified = false;
         while let Some(event) = rx.recv().await {
             match event {
                 CommandEvent::Stdout(line_bytes) => {
@@ -1766,32 +1866,33 @@ async fn spawn_claude_sidecar(
                         log::debug!("Claude sidecar stdout: {}", line_str);
 
                         // Parse the line to check for init message with session ID
-                        if let Ok(msg) = serde_json::from_str::<serde_json::Value>(line_str) {
-                            if msg["type"] == "system" && msg["subtype"] == "init" {
-                                if let Some(claude_session_id) = msg["session_id"].as_str() {
-                                    if let Ok(mut session_id_guard) = session_id_holder_clone.lock() {
-                                        if session_id_guard.is_none() {
-                                            *session_id_guard = Some(claude_session_id.to_string());
-                                            log::info!("Extracted Claude session ID: {}", claude_session_id);
-
-                                            // Register with ProcessRegistry using Claude's session ID
-                                            match registry_clone.register_claude_session(
-                                                claude_session_id.to_string(),
-                                                pid,
-                                                project_path_clone.clone(),
-                                                prompt_clone.clone(),
-                                                model_clone.clone(),
-                                            ) {
-                                                Ok(run_id) => {
-                                                    log::info!("Registered Claude sidecar session with run_id: {}", run_id);
-                                                    if let Ok(mut run_id_guard) = run_id_holder_clone.lock() {
-                                                        *run_id_guard = Some(run_id);
-                                                    }
-                                                }
-                                                Err(e) => {
-                                                    log::error!("Failed to register Claude sidecar session: {}", e);
+                        if let Some(msg) = StreamMessage::parse(line_str) {
+                            if let Some(claude_session_id) = msg.init_session_id() {
+                                if let Ok(mut session_id_guard) = session_id_holder_clone.lock() {
+                                    if session_id_guard.is_none() {
+                                        *session_id_guard = Some(claude_session_id.to_string());
+                                        log::info!("Extracted Claude session ID: {}", claude_session_id);
+                                        if let Some(started) = started.take() {
+                                            let _ = started.send(claude_session_id.to_string());
+                                        }
+
+                                        // Register with ProcessRegistry using Claude's session ID
+                                        match registry_clone.register_claude_session(
+                                            claude_session_id.to_string(),
+                                            pid,
+                                            project_path_clone.clone(),
+                                            prompt_clone.clone(),
+                                            model_clone.clone(),
+                                        ) {
+                                            Ok(run_id) => {
+                                                log::info!("Registered Claude sidecar session with run_id: {}", run_id);
+                                                if let Ok(mut run_id_guard) = run_id_holder_clone.lock() {
+                                                    *run_id_guard = Some(run_id);
                                                 }
                                             }
+                                            Err(e) => {
+                                                log::error!("Failed to register Claude sidecar session: {}", e);
+                                            }
                                         }
                                     }
                                 }
@@ -1811,6 +1912,23 @@ async fn spawn_claude_sidecar(
                         }
                         // Also emit to the generic event for backward compatibility
                         let _ = app_handle.emit("claude-output", line_str);
+
+                        // Stop the process once it runs over budget
+                        if let Some(guard) = &budget_guard {
+                            guard.observe(line_str);
+                        }
+
+                        // Let the user know once if Claude is blocked on a permission
+                        if !permission_notified {
+                            if let Some(tool) = permission_request_tool(line_str) {
+                                permission_notified = true;
+                                notify_permission_request(
+                                    &app_handle,
+                                    &session_subject(&project_path_clone),
+                                    &tool,
+                                );
+                            }
+                        }
                     }
                 }
                 CommandEvent::Stderr(line_bytes) => {
@@ -1834,13 +1952,29 @@ async fn spawn_claude_sidecar(
                     // Add a small delay to ensure all messages are processed
                     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
-                    let success = payload.code.unwrap_or(-1) == 0;
+                    let budget_exceeded = budget_guard.as_ref().and_then(|guard| {
+                        guard.finish();
+                        guard.exceeded()
+                    });
+                    let success = payload.code.unwrap_or(-1) == 0 && budget_exceeded.is_none();
+                    if let Some(violation) = &budget_exceeded {
+                        let session_id = session_id_holder_clone.lock().ok().and_then(|guard| guard.clone());
+                        emit_budget_exceeded(
+                            &app_handle,
+                            session_id.as_deref(),
+                            &project_path_clone,
+                            violation,
+                        );
+                    }
 
                     if let Some(ref session_id) = *session_id_holder_clone.lock().unwrap() {
                         let _ = app_handle.emit(&format!("claude-complete:{}", session_id), success);
                     }
                     // Also emit to the generic event for backward compatibility
                     let _ = app_handle.emit("claude-complete", success);
+                    if budget_exceeded.is_none() {
+                        notify_finished(&app_handle, &session_subject(&project_path_clone), success);
+                    }
 
                     // Unregister from ProcessRegistry if we have a run_id
                     if let Ok(guard) = run_id_holder_clone.lock() {
@@ -1849,6 +1983,16 @@ async fn spawn_claude_sidecar(
                         }
                     }
 
+                    // Notify any background caller waiting on this session
+                    if let Some(completion) = completion.take() {
+                        let session_id = session_id_holder_clone.lock().ok().and_then(|guard| guard.clone());
+                        let _ = completion.send(SessionOutcome {
+                            session_id,
+                            success,
+                            budget_exceeded,
+                        });
+                    }
+
                     break;
                 }
                 _ => {
@@ -1939,123 +2083,6 @@ pub async fn list_directory_contents(directory_path: String) -> Result<Vec<FileE
     Ok(entries)
 }
 
-/// Search for files and directories matching a pattern
-#[tauri::command]
-pub async fn search_files(base_path: String, query: String) -> Result<Vec<FileEntry>, String> {
-    log::info!("Searching files in '{}' for: '{}'", base_path, query);
-
-    // Check if path is empty
-    if base_path.trim().is_empty() {
-        log::error!("Base path is empty or whitespace");
-        return Err("Base path cannot be empty".to_string());
-    }
-
-    // Check if query is empty
-    if query.trim().is_empty() {
-        log::warn!("Search query is empty, returning empty results");
-        return Ok(Vec::new());
-    }
-
-    let path = PathBuf::from(&base_path);
-    log::debug!("Resolved search base path: {:?}", path);
-
-    if !path.exists() {
-        log::error!("Base path does not exist: {:?}", path);
-        return Err(format!("Path does not exist: {}", base_path));
-    }
-
-    let query_lower = query.to_lowercase();
-    let mut results = Vec::new();
-
-    search_files_recursive(&path, &path, &query_lower, &mut results, 0)?;
-
-    // Sort by relevance: exact matches first, then by name
-    results.sort_by(|a, b| {
-        let a_exact = a.name.to_lowercase() == query_lower;
-        let b_exact = b.name.to_lowercase() == query_lower;
-
-        match (a_exact, b_exact) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-        }
-    });
-
-    // Limit results to prevent overwhelming the UI
-    results.truncate(50);
-
-    Ok(results)
-}
-
-fn search_files_recursive(
-    current_path: &PathBuf,
-    base_path: &PathBuf,
-    query: &str,
-    results: &mut Vec<FileEntry>,
-    depth: usize,
-) -> Result<(), String> {
-    // Limit recursion depth to prevent excessive searching
-    if depth > 5 || results.len() >= 50 {
-        return Ok(());
-    }
-
-    let entries = fs::read_dir(current_path)
-        .map_err(|e| format!("Failed to read directory {:?}: {}", current_path, e))?;
-
-    for entry in entries {
-        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
-        let entry_path = entry.path();
-
-        // Skip hidden files/directories
-        if let Some(name) = entry_path.file_name().and_then(|n| n.to_str()) {
-            if name.starts_with('.') {
-                continue;
-            }
-
-            // Check if name matches query
-            if name.to_lowercase().contains(query) {
-                let metadata = entry
-                    .metadata()
-                    .map_err(|e| format!("Failed to read metadata: {}", e))?;
-
-                let extension = if metadata.is_file() {
-                    entry_path
-                        .extension()
-                        .and_then(|e| e.to_str())
-                        .map(|e| e.to_string())
-                } else {
-                    None
-                };
-
-                results.push(FileEntry {
-                    name: name.to_string(),
-                    path: entry_path.to_string_lossy().to_string(),
-                    is_directory: metadata.is_dir(),
-                    size: metadata.len(),
-                    extension,
-                });
-            }
-        }
-
-        // Recurse into directories
-        if entry_path.is_dir() {
-            // Skip common directories that shouldn't be searched
-            if let Some(dir_name) = entry_path.file_name().and_then(|n| n.to_str()) {
-                if matches!(
-                    dir_name,
-                    "node_modules" | "target" | ".git" | "dist" | "build" | ".next" | "__pycache__"
-                ) {
-                    continue;
-                }
-            }
-
-            search_files_recursive(&entry_path, base_path, query, results, depth + 1)?;
-        }
-    }
-
-    Ok(())
-}
-
 /// Creates a checkpoint for the current session state
 #[tauri::command]
 pub async fn create_checkpoint(
@@ -2065,6 +2092,18 @@ pub async fn create_checkpoint(
     project_path: String,
     message_index: Option<usize>,
     description: Option<String>,
+) -> Result<crate::checkpoint::CheckpointResult, String> {
+    create_checkpoint_internal(&app, session_id, project_id, project_path, message_index, description).await
+}
+
+/// Internal function to create a checkpoint, shared with the CLI
+pub(crate) async fn create_checkpoint_internal(
+    app: &crate::checkpoint::state::CheckpointState,
+    session_id: String,
+    project_id: String,
+    project_path: String,
+    message_index: Option<usize>,
+    description: Option<String>,
 ) -> Result<crate::checkpoint::CheckpointResult, String> {
     log::info!(
         "Creating checkpoint for session: {} in project: {}",
@@ -2124,6 +2163,17 @@ pub async fn restore_checkpoint(
     session_id: String,
     project_id: String,
     project_path: String,
+) -> Result<crate::checkpoint::CheckpointResult, String> {
+    restore_checkpoint_internal(&app, checkpoint_id, session_id, project_id, project_path).await
+}
+
+/// Internal function to restore a checkpoint
+pub(crate) async fn restore_checkpoint_internal(
+    app: &crate::checkpoint::state::CheckpointState,
+    checkpoint_id: String,
+    session_id: String,
+    project_id: String,
+    project_path: String,
 ) -> Result<crate::checkpoint::CheckpointResult, String> {
     log::info!(
         "Restoring checkpoint: {} for session: {}",
@@ -2172,6 +2222,16 @@ pub async fn list_checkpoints(
     session_id: String,
     project_id: String,
     project_path: String,
+) -> Result<Vec<crate::checkpoint::Checkpoint>, String> {
+    list_checkpoints_internal(&app, session_id, project_id, project_path).await
+}
+
+/// Internal function to list checkpoints
+pub(crate) async fn list_checkpoints_internal(
+    app: &crate::checkpoint::state::CheckpointState,
+    session_id: String,
+    project_id: String,
+    project_path: String,
 ) -> Result<Vec<crate::checkpoint::Checkpoint>, String> {
     log::info!(
         "Listing checkpoints for session: {} in project: {}",
@@ -2581,33 +2641,12 @@ pub async fn track_session_messages(
 pub async fn get_hooks_config(scope: String, project_path: Option<String>) -> Result<serde_json::Value, String> {
     log::info!("Getting hooks config for scope: {}, project: {:?}", scope, project_path);
 
-    let settings_path = match scope.as_str() {
-        "user" => {
-            get_claude_dir()
-                .map_err(|e| e.to_string())?
-                .join("settings.json")
-        },
-        "project" => {
-            let path = project_path.ok_or("Project path required for project scope")?;
-            PathBuf::from(path).join(".claude").join("settings.json")
-        },
-        "local" => {
-            let path = project_path.ok_or("Project path required for local scope")?;
-            PathBuf::from(path).join(".claude").join("settings.local.json")
-        },
-        _ => return Err("Invalid scope".to_string())
-    };
+    let settings_path = super::settings::settings_path(&scope, project_path.as_deref())?;
 
-    if !settings_path.exists() {
+    let Some(settings) = super::settings::read_settings_file(&settings_path)? else {
         log::info!("Settings file does not exist at {:?}, returning empty hooks", settings_path);
         return Ok(serde_json::json!({}));
-    }
-
-    let content = fs::read_to_string(&settings_path)
-        .map_err(|e| format!("Failed to read settings: {}", e))?;
-
-    let settings: serde_json::Value = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse settings: {}", e))?;
+    };
 
     Ok(settings.get("hooks").cloned().unwrap_or(serde_json::json!({})))
 }
@@ -2615,54 +2654,32 @@ pub async fn get_hooks_config(scope: String, project_path: Option<String>) -> Re
 /// Updates hooks configuration in settings at specified scope
 #[tauri::command]
 pub async fn update_hooks_config(
+    db: tauri::State<'_, AgentDb>,
     scope: String,
     hooks: serde_json::Value,
     project_path: Option<String>
 ) -> Result<String, String> {
     log::info!("Updating hooks config for scope: {}, project: {:?}", scope, project_path);
 
-    let settings_path = match scope.as_str() {
-        "user" => {
-            get_claude_dir()
-                .map_err(|e| e.to_string())?
-                .join("settings.json")
-        },
-        "project" => {
-            let path = project_path.ok_or("Project path required for project scope")?;
-            let claude_dir = PathBuf::from(path).join(".claude");
-            fs::create_dir_all(&claude_dir)
-                .map_err(|e| format!("Failed to create .claude directory: {}", e))?;
-            claude_dir.join("settings.json")
-        },
-        "local" => {
-            let path = project_path.ok_or("Project path required for local scope")?;
-            let claude_dir = PathBuf::from(path).join(".claude");
-            fs::create_dir_all(&claude_dir)
-                .map_err(|e| format!("Failed to create .claude directory: {}", e))?;
-            claude_dir.join("settings.local.json")
-        },
-        _ => return Err("Invalid scope".to_string())
-    };
+    if scope == "enterprise" {
+        return Err("Enterprise settings are managed by the organization".to_string());
+    }
+    let settings_path = super::settings::settings_path(&scope, project_path.as_deref())?;
+
+    // Reject malformed hooks before touching the file
+    let update = serde_json::json!({ "hooks": hooks });
+    super::settings::ensure_valid_settings(&update, None)?;
 
     // Read existing settings or create new
-    let mut settings = if settings_path.exists() {
-        let content = fs::read_to_string(&settings_path)
-            .map_err(|e| format!("Failed to read settings: {}", e))?;
-        serde_json::from_str(&content)
-            .map_err(|e| format!("Failed to parse settings: {}", e))?
-    } else {
-        serde_json::json!({})
-    };
+    let mut settings = super::settings::read_settings_file(&settings_path)?
+        .unwrap_or_else(|| serde_json::json!({}));
 
     // Update hooks section
-    settings["hooks"] = hooks;
+    settings["hooks"] = update["hooks"].clone();
 
     // Write back with pretty formatting
-    let json_string = serde_json::to_string_pretty(&settings)
-        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
-
-    fs::write(&settings_path, json_string)
-        .map_err(|e| format!("Failed to write settings: {}", e))?;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    super::settings::write_settings_file(&conn, &settings_path, &settings, "update_hooks_config")?;
 
     Ok("Hooks configuration updated successfully".to_string())
 }
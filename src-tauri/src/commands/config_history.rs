@@ -0,0 +1,485 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use std::fs;
+use std::path::Path;
+use tauri::State;
+
+use super::agents::AgentDb;
+
+/// Older versions beyond this many per file are pruned
+const MAX_VERSIONS_PER_FILE: i64 = 100;
+
+/// Source recorded for content found on disk that Claudia did not write
+const EXTERNAL_SOURCE: &str = "external";
+
+/// One recorded state of a config file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConfigVersion {
+    pub id: i64,
+    pub file_path: String,
+    /// The command that wrote this version, 'external' for edits made
+    /// outside Claudia, or 'restore_config_version'
+    pub source: String,
+    pub created_at: String,
+    /// False if the file did not exist at this version
+    pub exists: bool,
+    pub size: i64,
+    /// Whether the file on disk currently matches this version
+    pub is_current: bool,
+}
+
+/// A config file with recorded history
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConfigFileHistory {
+    pub file_path: String,
+    pub versions: i64,
+    pub last_changed_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConfigVersionContent {
+    pub version: ConfigVersion,
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConfigDiff {
+    pub file_path: String,
+    pub from_version_id: i64,
+    /// None when comparing against the file on disk
+    pub to_version_id: Option<i64>,
+    /// Unified diff with three lines of context
+    pub diff: String,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+fn read_current(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok()
+}
+
+/// Content of the newest version, or None if the file has no history
+fn latest_content(conn: &Connection, file_path: &str) -> rusqlite::Result<Option<Option<String>>> {
+    conn.query_row(
+        "SELECT content FROM config_versions WHERE file_path = ?1 ORDER BY id DESC LIMIT 1",
+        params![file_path],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn record_version(
+    conn: &Connection,
+    file_path: &str,
+    content: Option<&str>,
+    source: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO config_versions (file_path, content, source) VALUES (?1, ?2, ?3)",
+        params![file_path, content, source],
+    )?;
+    conn.execute(
+        "DELETE FROM config_versions WHERE file_path = ?1 AND id NOT IN (
+            SELECT id FROM config_versions WHERE file_path = ?1 ORDER BY id DESC LIMIT ?2
+        )",
+        params![file_path, MAX_VERSIONS_PER_FILE],
+    )?;
+    Ok(())
+}
+
+/// Records what is on disk if it differs from the newest version, so edits
+/// made outside Claudia (or before history existed) can be restored too. A
+/// missing file is recorded as a version without content.
+fn record_external_changes(conn: &Connection, path: &Path, file_path: &str) -> rusqlite::Result<()> {
+    let current = read_current(path);
+    if latest_content(conn, file_path)? != Some(current.clone()) {
+        record_version(conn, file_path, current.as_deref(), EXTERNAL_SOURCE)?;
+    }
+    Ok(())
+}
+
+/// Writes a config file and records the write in its version history.
+/// History failures are logged but never fail the write itself.
+pub(crate) fn write_config_file(
+    conn: &Connection,
+    path: &Path,
+    content: &str,
+    source: &str,
+) -> Result<(), String> {
+    let file_path = path.to_string_lossy().to_string();
+    if let Err(e) = record_external_changes(conn, path, &file_path) {
+        log::warn!("Failed to record previous version of {}: {}", file_path, e);
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create parent directory: {}", e))?;
+    }
+    fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", file_path, e))?;
+
+    let unchanged = matches!(latest_content(conn, &file_path), Ok(Some(Some(ref c))) if c == content);
+    if !unchanged {
+        if let Err(e) = record_version(conn, &file_path, Some(content), source) {
+            log::warn!("Failed to record version of {}: {}", file_path, e);
+        }
+    }
+    Ok(())
+}
+
+fn load_version(conn: &Connection, id: i64) -> Result<(ConfigVersion, Option<String>), String> {
+    let (mut version, content) = conn
+        .query_row(
+            "SELECT id, file_path, source, created_at, content FROM config_versions WHERE id = ?1",
+            params![id],
+            |row| {
+                let content: Option<String> = row.get(4)?;
+                Ok((
+                    ConfigVersion {
+                        id: row.get(0)?,
+                        file_path: row.get(1)?,
+                        source: row.get(2)?,
+                        created_at: row.get(3)?,
+                        exists: content.is_some(),
+                        size: content.as_ref().map_or(0, |c| c.len() as i64),
+                        is_current: false,
+                    },
+                    content,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Config version not found: {}", id))?;
+    version.is_current = read_current(Path::new(&version.file_path)) == content;
+    Ok((version, content))
+}
+
+/// Lists every config file Claudia has written, most recently changed first
+#[tauri::command]
+pub async fn list_config_history(db: State<'_, AgentDb>) -> Result<Vec<ConfigFileHistory>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT file_path, COUNT(*), MAX(created_at) FROM config_versions
+             GROUP BY file_path ORDER BY MAX(id) DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let files = stmt
+        .query_map([], |row| {
+            Ok(ConfigFileHistory {
+                file_path: row.get(0)?,
+                versions: row.get(1)?,
+                last_changed_at: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(files)
+}
+
+/// Lists the recorded versions of a config file, newest first
+#[tauri::command]
+pub async fn list_config_versions(
+    db: State<'_, AgentDb>,
+    file_path: String,
+) -> Result<Vec<ConfigVersion>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let current = read_current(Path::new(&file_path));
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, file_path, source, created_at, content FROM config_versions
+             WHERE file_path = ?1 ORDER BY id DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let versions = stmt
+        .query_map(params![file_path], |row| {
+            let content: Option<String> = row.get(4)?;
+            Ok(ConfigVersion {
+                id: row.get(0)?,
+                file_path: row.get(1)?,
+                source: row.get(2)?,
+                created_at: row.get(3)?,
+                exists: content.is_some(),
+                size: content.as_ref().map_or(0, |c| c.len() as i64),
+                is_current: content == current,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(versions)
+}
+
+/// Gets the content of a recorded version
+#[tauri::command]
+pub async fn get_config_version(
+    db: State<'_, AgentDb>,
+    version_id: i64,
+) -> Result<ConfigVersionContent, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let (version, content) = load_version(&conn, version_id)?;
+    Ok(ConfigVersionContent { version, content })
+}
+
+fn diff_text(old: &str, new: &str, old_label: &str, new_label: &str) -> (String, usize, usize) {
+    let diff = TextDiff::from_lines(old, new);
+    let (mut additions, mut deletions) = (0, 0);
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => additions += 1,
+            ChangeTag::Delete => deletions += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    let unified = diff
+        .unified_diff()
+        .context_radius(3)
+        .header(old_label, new_label)
+        .to_string();
+    (unified, additions, deletions)
+}
+
+fn diff_versions(
+    conn: &Connection,
+    from_version_id: i64,
+    to_version_id: Option<i64>,
+) -> Result<ConfigDiff, String> {
+    let (from, from_content) = load_version(conn, from_version_id)?;
+    let (to_label, to_content) = match to_version_id {
+        Some(id) => {
+            let (to, content) = load_version(conn, id)?;
+            if to.file_path != from.file_path {
+                return Err("Versions belong to different files".to_string());
+            }
+            (format!("version {}", id), content)
+        }
+        None => ("current".to_string(), read_current(Path::new(&from.file_path))),
+    };
+
+    let (diff, additions, deletions) = diff_text(
+        from_content.as_deref().unwrap_or(""),
+        to_content.as_deref().unwrap_or(""),
+        &format!("version {}", from_version_id),
+        &to_label,
+    );
+    Ok(ConfigDiff {
+        file_path: from.file_path,
+        from_version_id,
+        to_version_id,
+        diff,
+        additions,
+        deletions,
+    })
+}
+
+/// Diffs a version against a later version of the same file, or against the
+/// file on disk when `to_version_id` is omitted
+#[tauri::command]
+pub async fn diff_config_versions(
+    db: State<'_, AgentDb>,
+    from_version_id: i64,
+    to_version_id: Option<i64>,
+) -> Result<ConfigDiff, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    diff_versions(&conn, from_version_id, to_version_id)
+}
+
+/// Puts a config file back to a recorded version. The restore is itself
+/// recorded, so it can be undone the same way.
+#[tauri::command]
+pub async fn restore_config_version(
+    db: State<'_, AgentDb>,
+    version_id: i64,
+) -> Result<ConfigVersion, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let (version, content) = load_version(&conn, version_id)?;
+    let path = Path::new(&version.file_path);
+    log::info!("Restoring {} to version {}", version.file_path, version_id);
+
+    match content {
+        Some(content) => write_config_file(&conn, path, &content, "restore_config_version")?,
+        // The file did not exist at this version
+        None => {
+            if let Err(e) = record_external_changes(&conn, path, &version.file_path) {
+                log::warn!("Failed to record previous version of {}: {}", version.file_path, e);
+            }
+            if path.exists() {
+                fs::remove_file(path)
+                    .map_err(|e| format!("Failed to remove {}: {}", version.file_path, e))?;
+            }
+            record_version(&conn, &version.file_path, None, "restore_config_version")
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let id: i64 = conn
+        .query_row(
+            "SELECT MAX(id) FROM config_versions WHERE file_path = ?1",
+            params![version.file_path],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(load_version(&conn, id)?.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::create_schema;
+    use tempfile::TempDir;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        conn
+    }
+
+    /// (source, content) of every version of a file, oldest first
+    fn versions(conn: &Connection, path: &Path) -> Vec<(String, Option<String>)> {
+        let mut stmt = conn
+            .prepare("SELECT source, content FROM config_versions WHERE file_path = ?1 ORDER BY id")
+            .unwrap();
+        stmt.query_map(params![path.to_string_lossy()], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+    }
+
+    fn version_ids(conn: &Connection, path: &Path) -> Vec<i64> {
+        let mut stmt = conn
+            .prepare("SELECT id FROM config_versions WHERE file_path = ?1 ORDER BY id")
+            .unwrap();
+        stmt.query_map(params![path.to_string_lossy()], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_external_content_is_recorded_before_the_write() {
+        let dir = TempDir::new().unwrap();
+        let conn = test_db();
+        let path = dir.path().join("settings.json");
+        fs::write(&path, "{\"hand\":\"edited\"}").unwrap();
+
+        write_config_file(&conn, &path, "{}", "save_claude_settings").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+        assert_eq!(
+            versions(&conn, &path),
+            vec![
+                (
+                    EXTERNAL_SOURCE.to_string(),
+                    Some("{\"hand\":\"edited\"}".to_string())
+                ),
+                ("save_claude_settings".to_string(), Some("{}".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_file_is_recorded_without_content() {
+        let dir = TempDir::new().unwrap();
+        let conn = test_db();
+        let path = dir.path().join("nested").join("CLAUDE.md");
+
+        write_config_file(&conn, &path, "# Notes", "save_claude_md_file").unwrap();
+        assert_eq!(
+            versions(&conn, &path),
+            vec![
+                (EXTERNAL_SOURCE.to_string(), None),
+                (
+                    "save_claude_md_file".to_string(),
+                    Some("# Notes".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unchanged_content_is_not_recorded_again() {
+        let dir = TempDir::new().unwrap();
+        let conn = test_db();
+        let path = dir.path().join("settings.json");
+
+        write_config_file(&conn, &path, "{}", "save_claude_settings").unwrap();
+        write_config_file(&conn, &path, "{}", "save_claude_settings").unwrap();
+        assert_eq!(versions(&conn, &path).len(), 2);
+
+        // An outside edit is picked up on the next write
+        fs::write(&path, "{\"a\":1}").unwrap();
+        write_config_file(&conn, &path, "{}", "save_claude_settings").unwrap();
+        let sources: Vec<String> = versions(&conn, &path).into_iter().map(|(s, _)| s).collect();
+        assert_eq!(
+            sources,
+            vec![
+                EXTERNAL_SOURCE,
+                "save_claude_settings",
+                EXTERNAL_SOURCE,
+                "save_claude_settings"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_history_is_pruned_per_file() {
+        let dir = TempDir::new().unwrap();
+        let conn = test_db();
+        let path = dir.path().join("settings.json");
+        let other = dir.path().join("other.json");
+        write_config_file(&conn, &other, "{}", "save_claude_settings").unwrap();
+
+        for i in 0..MAX_VERSIONS_PER_FILE + 20 {
+            write_config_file(
+                &conn,
+                &path,
+                &format!("{{\"v\":{}}}", i),
+                "save_claude_settings",
+            )
+            .unwrap();
+        }
+        let kept = versions(&conn, &path);
+        assert_eq!(kept.len() as i64, MAX_VERSIONS_PER_FILE);
+        assert_eq!(
+            kept.last().unwrap().1.as_deref(),
+            Some(format!("{{\"v\":{}}}", MAX_VERSIONS_PER_FILE + 19).as_str())
+        );
+        // Other files keep their history
+        assert_eq!(versions(&conn, &other).len(), 2);
+    }
+
+    #[test]
+    fn test_diff_between_versions_and_disk() {
+        let dir = TempDir::new().unwrap();
+        let conn = test_db();
+        let path = dir.path().join("CLAUDE.md");
+        write_config_file(&conn, &path, "one\ntwo\n", "save_claude_md_file").unwrap();
+        write_config_file(&conn, &path, "one\nthree\n", "save_claude_md_file").unwrap();
+        let ids = version_ids(&conn, &path);
+
+        let diff = diff_versions(&conn, ids[1], Some(ids[2])).unwrap();
+        assert_eq!((diff.additions, diff.deletions), (1, 1));
+        assert!(diff.diff.contains("-two") && diff.diff.contains("+three"));
+
+        let diff = diff_versions(&conn, ids[2], None).unwrap();
+        assert_eq!((diff.additions, diff.deletions), (0, 0));
+    }
+
+    #[test]
+    fn test_diff_refuses_versions_of_different_files() {
+        let dir = TempDir::new().unwrap();
+        let conn = test_db();
+        let first = dir.path().join("a.json");
+        let second = dir.path().join("b.json");
+        write_config_file(&conn, &first, "{}", "save_claude_settings").unwrap();
+        write_config_file(&conn, &second, "{}", "save_claude_settings").unwrap();
+
+        let from = *version_ids(&conn, &first).last().unwrap();
+        let to = *version_ids(&conn, &second).last().unwrap();
+        let err = diff_versions(&conn, from, Some(to)).unwrap_err();
+        assert_eq!(err, "Versions belong to different files");
+    }
+}
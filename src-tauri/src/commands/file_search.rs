@@ -0,0 +1,725 @@
+use ignore::{WalkBuilder, WalkState};
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::State;
+
+use super::claude::FileEntry;
+
+/// After this long an index is rebuilt in the background on the next query
+const INDEX_TTL: Duration = Duration::from_secs(30);
+/// Paths beyond this are left out of the index
+const MAX_INDEXED_PATHS: usize = 500_000;
+const DEFAULT_RESULT_LIMIT: usize = 50;
+/// Content search skips files larger than this
+const MAX_GREP_FILE_BYTES: u64 = 2 * 1024 * 1024;
+/// Matched lines longer than this are cut off in results
+const MAX_SNIPPET_CHARS: usize = 300;
+
+/// One file or directory in a project index
+struct IndexedPath {
+    relative_path: String,
+    /// Lowercased chars of `relative_path`, matched against the query
+    lowered: Vec<char>,
+    /// Whether each char starts a word (after a separator or a camelCase hump)
+    boundaries: Vec<bool>,
+    /// Char index where the file name starts in `lowered`
+    name_start: usize,
+    is_directory: bool,
+    size: u64,
+    /// Seconds since the epoch
+    modified: u64,
+}
+
+/// All non-ignored paths under a root, built once and reused across queries
+pub struct FileIndex {
+    root: PathBuf,
+    paths: Vec<IndexedPath>,
+    built_at: Instant,
+    /// Set when a file watcher saw paths appear or disappear
+    stale: AtomicBool,
+    refreshing: AtomicBool,
+}
+
+/// Path indexes per project root
+#[derive(Default)]
+pub struct FileSearchState(pub Arc<Mutex<HashMap<PathBuf, Arc<FileIndex>>>>);
+
+/// A fuzzy path match, best first
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileMatch {
+    #[serde(flatten)]
+    pub entry: FileEntry,
+    pub relative_path: String,
+    pub score: i64,
+    /// Char indices in `relative_path` that matched the query, for highlighting
+    pub positions: Vec<usize>,
+    pub modified: u64,
+}
+
+/// A line matching a content search
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContentMatch {
+    pub path: String,
+    pub relative_path: String,
+    /// 1-based
+    pub line_number: usize,
+    pub line: String,
+    /// Byte ranges of the matches within `line`
+    pub ranges: Vec<(usize, usize)>,
+}
+
+fn modified_secs(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl IndexedPath {
+    fn new(relative_path: String, is_directory: bool, size: u64, modified: u64) -> Self {
+        let chars: Vec<char> = relative_path.chars().collect();
+        let lowered: Vec<char> = chars.iter().map(|c| lower(*c)).collect();
+        let boundaries = (0..chars.len())
+            .map(|i| is_boundary(i.checked_sub(1).map(|p| chars[p]), chars[i]))
+            .collect();
+        let name_start = lowered
+            .iter()
+            .rposition(|c| *c == '/')
+            .map_or(0, |i| i + 1);
+        Self {
+            relative_path,
+            lowered,
+            boundaries,
+            name_start,
+            is_directory,
+            size,
+            modified,
+        }
+    }
+}
+
+impl FileIndex {
+    /// Walks `root` honoring .gitignore, .ignore and git excludes. Hidden
+    /// files are skipped as before.
+    fn build(root: &Path) -> Self {
+        let started = Instant::now();
+        let paths = Mutex::new(Vec::new());
+        let count = AtomicUsize::new(0);
+
+        WalkBuilder::new(root)
+            .hidden(true)
+            .git_ignore(true)
+            .git_exclude(true)
+            .require_git(false)
+            .build_parallel()
+            .run(|| {
+                Box::new(|entry| {
+                    let Ok(entry) = entry else {
+                        return WalkState::Continue;
+                    };
+                    if entry.depth() == 0 {
+                        return WalkState::Continue;
+                    }
+                    if count.fetch_add(1, Ordering::Relaxed) >= MAX_INDEXED_PATHS {
+                        return WalkState::Quit;
+                    }
+                    let Ok(relative) = entry.path().strip_prefix(root) else {
+                        return WalkState::Continue;
+                    };
+                    let metadata = entry.metadata().ok();
+                    let indexed = IndexedPath::new(
+                        relative.to_string_lossy().replace('\\', "/"),
+                        entry.file_type().is_some_and(|t| t.is_dir()),
+                        metadata.as_ref().map_or(0, |m| m.len()),
+                        metadata.as_ref().map_or(0, modified_secs),
+                    );
+                    if let Ok(mut paths) = paths.lock() {
+                        paths.push(indexed);
+                    }
+                    WalkState::Continue
+                })
+            });
+
+        let mut paths = paths.into_inner().unwrap_or_default();
+        paths.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        log::info!(
+            "Indexed {} paths under {:?} in {:?}",
+            paths.len(),
+            root,
+            started.elapsed()
+        );
+        Self {
+            root: root.to_path_buf(),
+            paths,
+            built_at: Instant::now(),
+            stale: AtomicBool::new(false),
+            refreshing: AtomicBool::new(false),
+        }
+    }
+
+    fn entry(&self, path: &IndexedPath) -> FileEntry {
+        let name = path
+            .relative_path
+            .rsplit('/')
+            .next()
+            .unwrap_or(&path.relative_path)
+            .to_string();
+        let extension = if path.is_directory {
+            None
+        } else {
+            Path::new(&name)
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e.to_string())
+        };
+        FileEntry {
+            path: self.root.join(&path.relative_path).to_string_lossy().to_string(),
+            name,
+            is_directory: path.is_directory,
+            size: if path.is_directory { 0 } else { path.size },
+            extension,
+        }
+    }
+}
+
+/// Lowercases one char without changing the char count, so match positions
+/// line up with the original path
+fn lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn is_boundary(previous: Option<char>, current: char) -> bool {
+    match previous {
+        None => true,
+        Some(p) => matches!(p, '/' | '_' | '-' | '.' | ' ') || (p.is_lowercase() && current.is_uppercase()),
+    }
+}
+
+/// Scores `query` as a subsequence of the path, or `None` if it does not match.
+/// Matches in the file name, at word boundaries and in runs score higher;
+/// gaps and long paths cost a little.
+fn score_path(path: &IndexedPath, query: &[char]) -> Option<(i64, Vec<usize>)> {
+    let text = &path.lowered;
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    // Forward pass finds the earliest end of a match, the backward pass then
+    // pulls the start as far right as possible for a tighter match
+    let mut qi = 0;
+    let mut end = None;
+    for (i, c) in text.iter().enumerate() {
+        if *c == query[qi] {
+            qi += 1;
+            if qi == query.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+    // Prefer a match inside the file name when the query fits there
+    let end = if end < path.name_start {
+        let mut qi = 0;
+        let mut name_end = None;
+        for (i, c) in text.iter().enumerate().skip(path.name_start) {
+            if *c == query[qi] {
+                qi += 1;
+                if qi == query.len() {
+                    name_end = Some(i);
+                    break;
+                }
+            }
+        }
+        name_end.unwrap_or(end)
+    } else {
+        end
+    };
+
+    let mut positions = vec![0; query.len()];
+    let mut qi = query.len();
+    for i in (0..=end).rev() {
+        if text[i] == query[qi - 1] {
+            qi -= 1;
+            positions[qi] = i;
+            if qi == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut score = 0i64;
+    for (n, &pos) in positions.iter().enumerate() {
+        score += 10;
+        if pos >= path.name_start {
+            score += 8;
+        }
+        if path.boundaries[pos] {
+            score += 12;
+        }
+        if n > 0 {
+            let gap = pos - positions[n - 1] - 1;
+            if gap == 0 {
+                score += 15;
+            } else {
+                score -= (gap as i64).min(20);
+            }
+        }
+    }
+
+    let name = &text[path.name_start..];
+    if name == query {
+        score += 100;
+    } else if name.starts_with(query) {
+        score += 40;
+    }
+    score -= (text.len() as i64) / 10;
+    Some((score, positions))
+}
+
+/// Extra score for recently modified files
+fn recency_boost(modified: u64, now: u64) -> i64 {
+    match now.saturating_sub(modified) {
+        age if age < 60 * 60 => 30,
+        age if age < 24 * 60 * 60 => 20,
+        age if age < 7 * 24 * 60 * 60 => 10,
+        age if age < 30 * 24 * 60 * 60 => 5,
+        _ => 0,
+    }
+}
+
+/// Ranks indexed paths against a whitespace-separated query; every term must match
+fn fuzzy_match(index: &FileIndex, query: &str, limit: usize) -> Vec<FileMatch> {
+    let terms: Vec<Vec<char>> = query
+        .split_whitespace()
+        .map(|t| t.chars().map(lower).collect())
+        .collect();
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut scored: Vec<(i64, usize, Vec<usize>)> = Vec::new();
+    for (i, path) in index.paths.iter().enumerate() {
+        let mut total = 0;
+        let mut positions = Vec::new();
+        let mut matched = true;
+        for term in &terms {
+            match score_path(path, term) {
+                Some((score, term_positions)) => {
+                    total += score;
+                    positions.extend(term_positions);
+                }
+                None => {
+                    matched = false;
+                    break;
+                }
+            }
+        }
+        if matched {
+            positions.sort_unstable();
+            positions.dedup();
+            scored.push((total + recency_boost(path.modified, now), i, positions));
+        }
+    }
+
+    let by_rank = |a: &(i64, usize, Vec<usize>), b: &(i64, usize, Vec<usize>)| {
+        b.0.cmp(&a.0).then_with(|| {
+            index.paths[a.1]
+                .relative_path
+                .len()
+                .cmp(&index.paths[b.1].relative_path.len())
+        })
+    };
+    if scored.len() > limit {
+        scored.select_nth_unstable_by(limit, by_rank);
+        scored.truncate(limit);
+    }
+    scored.sort_by(by_rank);
+
+    scored
+        .into_iter()
+        .map(|(score, i, positions)| {
+            let path = &index.paths[i];
+            FileMatch {
+                entry: index.entry(path),
+                relative_path: path.relative_path.clone(),
+                score,
+                positions,
+                modified: path.modified,
+            }
+        })
+        .collect()
+}
+
+/// Returns the index for `root`, building it on first use. A stale index is
+/// still served while a fresh one is built in the background, so typing in
+/// an autocomplete never waits on a rebuild.
+pub(crate) async fn get_index(
+    state: &FileSearchState,
+    root: &Path,
+) -> Result<Arc<FileIndex>, String> {
+    let existing = state
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .get(root)
+        .cloned();
+
+    if let Some(index) = existing {
+        let expired =
+            index.built_at.elapsed() > INDEX_TTL || index.stale.load(Ordering::SeqCst);
+        if expired && !index.refreshing.swap(true, Ordering::SeqCst) {
+            let indexes = state.0.clone();
+            let root = root.to_path_buf();
+            let stale = index.clone();
+            tauri::async_runtime::spawn(async move {
+                let build_root = root.clone();
+                match tokio::task::spawn_blocking(move || FileIndex::build(&build_root)).await {
+                    Ok(fresh) => {
+                        if let Ok(mut indexes) = indexes.lock() {
+                            indexes.insert(root, Arc::new(fresh));
+                        }
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to refresh file index for {:?}: {}", root, e);
+                        stale.refreshing.store(false, Ordering::SeqCst);
+                    }
+                }
+            });
+        }
+        return Ok(index);
+    }
+
+    let build_root = root.to_path_buf();
+    let index = Arc::new(
+        tokio::task::spawn_blocking(move || FileIndex::build(&build_root))
+            .await
+            .map_err(|e| format!("Failed to index files: {}", e))?,
+    );
+    state
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(root.to_path_buf(), index.clone());
+    Ok(index)
+}
+
+/// Drops the cached index for `root` so the next query rebuilds it
+pub(crate) fn invalidate_index(state: &FileSearchState, root: &Path) {
+    if let Ok(mut indexes) = state.0.lock() {
+        indexes.remove(root);
+    }
+}
+
+/// Flags every index overlapping `path` for a background rebuild on its next query
+pub(crate) fn mark_indexes_stale(state: &FileSearchState, path: &Path) {
+    if let Ok(indexes) = state.0.lock() {
+        for (root, index) in indexes.iter() {
+            if path.starts_with(root) || root.starts_with(path) {
+                index.stale.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+fn resolve_root(base_path: &str) -> Result<PathBuf, String> {
+    if base_path.trim().is_empty() {
+        return Err("Base path cannot be empty".to_string());
+    }
+    let path = PathBuf::from(base_path);
+    if !path.is_dir() {
+        return Err(format!("Path does not exist: {}", base_path));
+    }
+    Ok(fs::canonicalize(&path).unwrap_or(path))
+}
+
+/// Runs `regex` over the indexed files, splitting the work across threads
+fn grep_index(index: &FileIndex, regex: &regex::Regex, limit: usize) -> Vec<ContentMatch> {
+    let files: Vec<&IndexedPath> = index
+        .paths
+        .iter()
+        .filter(|p| !p.is_directory && p.size <= MAX_GREP_FILE_BYTES)
+        .collect();
+    let found = AtomicUsize::new(0);
+    let threads = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let chunk_size = files.len().div_ceil(threads).max(1);
+
+    let mut matches: Vec<ContentMatch> = std::thread::scope(|scope| {
+        let workers: Vec<_> = files
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let found = &found;
+                scope.spawn(move || {
+                    let mut matches = Vec::new();
+                    for path in chunk {
+                        if found.load(Ordering::Relaxed) >= limit {
+                            break;
+                        }
+                        let full_path = index.root.join(&path.relative_path);
+                        let Ok(bytes) = fs::read(&full_path) else {
+                            continue;
+                        };
+                        // Skip binary files
+                        if bytes.iter().take(8192).any(|b| *b == 0) {
+                            continue;
+                        }
+                        let content = String::from_utf8_lossy(&bytes);
+                        for (n, line) in content.lines().enumerate() {
+                            let ranges: Vec<(usize, usize)> = regex
+                                .find_iter(line)
+                                .filter(|m| m.start() < MAX_SNIPPET_CHARS * 4)
+                                .map(|m| (m.start(), m.end()))
+                                .collect();
+                            if ranges.is_empty() {
+                                continue;
+                            }
+                            if found.fetch_add(1, Ordering::Relaxed) >= limit {
+                                break;
+                            }
+                            let snippet: String = line.chars().take(MAX_SNIPPET_CHARS).collect();
+                            let ranges = ranges
+                                .into_iter()
+                                .filter(|(start, _)| *start < snippet.len())
+                                .map(|(start, end)| (start, end.min(snippet.len())))
+                                .collect();
+                            matches.push(ContentMatch {
+                                path: full_path.to_string_lossy().to_string(),
+                                relative_path: path.relative_path.clone(),
+                                line_number: n + 1,
+                                line: snippet,
+                                ranges,
+                            });
+                        }
+                    }
+                    matches
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap_or_default())
+            .collect()
+    });
+
+    matches.sort_by(|a, b| {
+        a.relative_path
+            .cmp(&b.relative_path)
+            .then(a.line_number.cmp(&b.line_number))
+    });
+    matches.truncate(limit);
+    matches
+}
+
+/// Search for files and directories matching a pattern
+#[tauri::command]
+pub async fn search_files(
+    state: State<'_, FileSearchState>,
+    base_path: String,
+    query: String,
+) -> Result<Vec<FileEntry>, String> {
+    Ok(fuzzy_search_files(state, base_path, query, None)
+        .await?
+        .into_iter()
+        .map(|m| m.entry)
+        .collect())
+}
+
+/// Fuzzy-matches paths under `base_path`, skipping anything git ignores.
+/// Results are ranked by match quality with a boost for recently modified files.
+#[tauri::command]
+pub async fn fuzzy_search_files(
+    state: State<'_, FileSearchState>,
+    base_path: String,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<FileMatch>, String> {
+    log::info!("Searching files in '{}' for: '{}'", base_path, query);
+
+    let root = resolve_root(&base_path)?;
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let index = get_index(&state, &root).await?;
+    let limit = limit.unwrap_or(DEFAULT_RESULT_LIMIT);
+    tokio::task::spawn_blocking(move || fuzzy_match(&index, &query, limit))
+        .await
+        .map_err(|e| format!("File search failed: {}", e))
+}
+
+/// Searches file contents under `base_path` with a regular expression and
+/// returns the matching lines
+#[tauri::command]
+pub async fn grep_files(
+    state: State<'_, FileSearchState>,
+    base_path: String,
+    pattern: String,
+    case_sensitive: Option<bool>,
+    limit: Option<usize>,
+) -> Result<Vec<ContentMatch>, String> {
+    log::info!("Searching file contents in '{}' for: '{}'", base_path, pattern);
+
+    let root = resolve_root(&base_path)?;
+    if pattern.is_empty() {
+        return Ok(Vec::new());
+    }
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive.unwrap_or(false))
+        .build()
+        .map_err(|e| format!("Invalid pattern: {}", e))?;
+    let index = get_index(&state, &root).await?;
+    let limit = limit.unwrap_or(DEFAULT_RESULT_LIMIT * 4);
+    tokio::task::spawn_blocking(move || grep_index(&index, &regex, limit))
+        .await
+        .map_err(|e| format!("Content search failed: {}", e))
+}
+
+/// Rebuilds the file index for `base_path` now, e.g. after a branch switch
+#[tauri::command]
+pub async fn refresh_file_index(
+    state: State<'_, FileSearchState>,
+    base_path: String,
+) -> Result<usize, String> {
+    let root = resolve_root(&base_path)?;
+    invalidate_index(&state, &root);
+    Ok(get_index(&state, &root).await?.paths.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index_of(root: &Path, paths: &[&str]) -> FileIndex {
+        let mut paths: Vec<IndexedPath> = paths
+            .iter()
+            .map(|p| IndexedPath::new(p.to_string(), false, 0, 0))
+            .collect();
+        paths.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        FileIndex {
+            root: root.to_path_buf(),
+            paths,
+            built_at: Instant::now(),
+            stale: AtomicBool::new(false),
+            refreshing: AtomicBool::new(false),
+        }
+    }
+
+    fn score(path: &str, query: &str) -> i64 {
+        let query: Vec<char> = query.chars().collect();
+        score_path(&IndexedPath::new(path.to_string(), false, 0, 0), &query)
+            .expect("query matches")
+            .0
+    }
+
+    fn ranked(index: &FileIndex, query: &str) -> Vec<String> {
+        fuzzy_match(index, query, 10)
+            .into_iter()
+            .map(|m| m.relative_path)
+            .collect()
+    }
+
+    #[test]
+    fn test_file_name_match_beats_directory_match() {
+        assert!(score("src/main.rs", "main") > score("main/src.rs", "main"));
+        let index = index_of(Path::new("/project"), &["main/lib.rs", "src/main.rs", "docs/readme.md"]);
+        assert_eq!(ranked(&index, "main"), ["src/main.rs", "main/lib.rs"]);
+
+        // Positions point into the file name even though the directory also matches
+        let matches = fuzzy_match(&index_of(Path::new("/project"), &["main/main.rs"]), "main", 1);
+        assert_eq!(matches[0].positions, [5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_word_boundaries_score_higher() {
+        assert!(score("file_search.rs", "fs") > score("filexsearch.rs", "fs"));
+        assert!(score("FileSearch.tsx", "fs") > score("Filesearch.tsx", "fs"));
+        assert!(score("src/query.rs", "q") > score("src/aquery.rs", "q"));
+    }
+
+    #[test]
+    fn test_every_term_must_match() {
+        let index = index_of(
+            Path::new("/project"),
+            &["src/components/Button.tsx", "src/components/Input.tsx", "src/button.css"],
+        );
+        assert_eq!(ranked(&index, "comp button"), ["src/components/Button.tsx"]);
+        assert!(ranked(&index, "button missing").is_empty());
+        assert!(ranked(&index, "   ").is_empty());
+
+        let matches = fuzzy_match(&index, "tsx butt", 10);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].positions, [15, 16, 17, 18, 22, 23, 24]);
+    }
+
+    #[test]
+    fn test_recency_boost() {
+        let now = 100 * 24 * 60 * 60;
+        assert_eq!(recency_boost(now - 10, now), 30);
+        assert_eq!(recency_boost(now - 2 * 24 * 60 * 60, now), 10);
+        assert_eq!(recency_boost(0, now), 0);
+        // Clock skew never yields a negative age
+        assert_eq!(recency_boost(now + 60, now), 30);
+    }
+
+    #[test]
+    fn test_build_skips_ignored_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::create_dir_all(root.join("src/generated")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::write(root.join("src/.gitignore"), "generated/\n").unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("src/generated/out.rs"), "").unwrap();
+        fs::write(root.join("target/debug/app"), "").unwrap();
+        fs::write(root.join("build.log"), "").unwrap();
+        fs::write(root.join(".env"), "").unwrap();
+
+        let index = FileIndex::build(root);
+        let paths: Vec<&str> = index.paths.iter().map(|p| p.relative_path.as_str()).collect();
+        assert_eq!(paths, ["src", "src/main.rs"]);
+        assert!(index.paths[0].is_directory);
+        assert_eq!(index.paths[1].size, 12);
+    }
+
+    #[test]
+    fn test_grep_clamps_snippets_and_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let long_line = format!("{}needle{}needle", "x".repeat(MAX_SNIPPET_CHARS - 3), "y".repeat(500));
+        let wide_line = format!("{}needle", "é".repeat(10));
+        fs::write(root.join("a.txt"), format!("short needle\n{}\n{}\n", long_line, wide_line)).unwrap();
+        fs::write(root.join("b.bin"), b"needle\0").unwrap();
+
+        let index = index_of(root, &["a.txt", "b.bin"]);
+        let regex = regex::Regex::new("needle").unwrap();
+        let matches = grep_index(&index, &regex, 10);
+        assert_eq!(matches.len(), 3);
+
+        assert_eq!(matches[0].line_number, 1);
+        assert_eq!(matches[0].ranges, [(6, 12)]);
+
+        // The first match is cut at the snippet end, the second is past it
+        let long = &matches[1];
+        assert_eq!(long.line.chars().count(), MAX_SNIPPET_CHARS);
+        assert_eq!(long.ranges, [(MAX_SNIPPET_CHARS - 3, MAX_SNIPPET_CHARS)]);
+
+        // Ranges are byte offsets into the line
+        let wide = &matches[2];
+        assert_eq!(wide.ranges, [(20, 26)]);
+        assert_eq!(&wide.line[20..26], "needle");
+
+        assert_eq!(grep_index(&index, &regex, 2).len(), 2);
+    }
+}
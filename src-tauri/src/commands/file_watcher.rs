@@ -0,0 +1,545 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::WalkBuilder;
+use notify_debouncer_full::notify::event::{ModifyKind, RenameMode};
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, Debouncer, RecommendedCache};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use super::file_search::{mark_indexes_stale, FileSearchState};
+
+const DEFAULT_DEBOUNCE_MS: u64 = 300;
+const MIN_DEBOUNCE_MS: u64 = 50;
+const MAX_DEBOUNCE_MS: u64 = 5_000;
+
+/// An active directory watch
+struct Watch {
+    info: DirectoryWatch,
+    /// Dropping the debouncer stops the watch
+    _debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+}
+
+/// Directory watches by watch ID
+#[derive(Default)]
+pub struct FileWatcherState(Mutex<HashMap<String, Watch>>);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DirectoryWatch {
+    pub watch_id: String,
+    pub path: String,
+    pub recursive: bool,
+    pub debounce_ms: u64,
+}
+
+/// One change under a watched directory
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct FileChangeEvent {
+    /// 'create', 'delete', 'rename' or 'modify'
+    pub kind: String,
+    pub path: String,
+    /// The old path of a rename
+    pub from_path: Option<String>,
+    pub is_directory: bool,
+}
+
+/// The changes collected during one debounce window
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DirectoryChanges {
+    pub watch_id: String,
+    pub path: String,
+    pub events: Vec<FileChangeEvent>,
+}
+
+/// Ignore files read in every directory, lowest precedence first
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// Decides which paths under a watched root are reported
+struct IgnoreRules {
+    root: PathBuf,
+    recursive: bool,
+    extra_patterns: Vec<String>,
+    /// Rules from each directory's ignore files, deepest directory first.
+    /// The root's also include git excludes.
+    matchers: Vec<(PathBuf, Gitignore)>,
+    /// Caller patterns, matched against paths relative to the root
+    extra: Gitignore,
+    /// Directories under the root, so a deleted one keeps its type
+    directories: HashSet<PathBuf>,
+}
+
+/// Builds a matcher from the ignore files that exist in `dir`
+fn dir_matcher(dir: &Path, files: &[&str]) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(dir);
+    let mut found = false;
+    for file in files {
+        let path = dir.join(file);
+        if path.is_file() {
+            found = true;
+            if let Some(e) = builder.add(&path) {
+                log::warn!("Ignoring invalid rules in {:?}: {}", path, e);
+            }
+        }
+    }
+    if !found {
+        return None;
+    }
+    builder
+        .build()
+        .map_err(|e| log::warn!("Failed to build ignore rules for {:?}: {}", dir, e))
+        .ok()
+}
+
+impl IgnoreRules {
+    /// The .gitignore and .ignore files of the root and every directory below
+    /// it, the root's git excludes and caller patterns in gitignore syntax
+    fn new(root: &Path, extra_patterns: &[String], recursive: bool) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in extra_patterns {
+            if let Err(e) = builder.add_line(None, pattern) {
+                log::warn!("Ignoring invalid watch pattern '{}': {}", pattern, e);
+            }
+        }
+        let extra = builder.build().unwrap_or_else(|e| {
+            log::warn!("Failed to build watch patterns for {:?}: {}", root, e);
+            Gitignore::empty()
+        });
+
+        let mut rules = Self {
+            root: root.to_path_buf(),
+            recursive,
+            extra_patterns: extra_patterns.to_vec(),
+            matchers: Vec::new(),
+            extra,
+            directories: HashSet::new(),
+        };
+        let mut root_files = vec![".git/info/exclude"];
+        root_files.extend(IGNORE_FILES);
+        if let Some(matcher) = dir_matcher(root, &root_files) {
+            rules.matchers.push((root.to_path_buf(), matcher));
+        }
+        if !recursive {
+            return rules;
+        }
+
+        // Walk the directories the watch reports on to find nested ignore
+        // files, skipping the ones that are ignored themselves
+        let extra = rules.extra.clone();
+        let walker = WalkBuilder::new(root)
+            .hidden(true)
+            .git_ignore(true)
+            .git_exclude(true)
+            .require_git(false)
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                !extra.matched(entry.path(), is_dir).is_ignore()
+            })
+            .build();
+        for entry in walker.flatten() {
+            if entry.depth() == 0 || !entry.file_type().is_some_and(|t| t.is_dir()) {
+                continue;
+            }
+            let dir = entry.into_path();
+            if let Some(matcher) = dir_matcher(&dir, &IGNORE_FILES) {
+                rules.matchers.push((dir.clone(), matcher));
+            }
+            rules.directories.insert(dir);
+        }
+        rules
+            .matchers
+            .sort_by_key(|(dir, _)| std::cmp::Reverse(dir.components().count()));
+        rules
+    }
+
+    /// Re-reads the ignore files after one of them changed
+    fn reload(&mut self) {
+        *self = Self::new(&self.root, &self.extra_patterns, self.recursive);
+    }
+
+    fn is_ignored(&self, path: &Path, is_directory: bool) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return true;
+        };
+        // Hidden entries are not listed by the file browser, except .claude
+        let hidden = relative.components().any(|c| {
+            let name = c.as_os_str().to_string_lossy();
+            name.starts_with('.') && name != ".claude"
+        });
+        if hidden
+            || self
+                .extra
+                .matched_path_or_any_parents(relative, is_directory)
+                .is_ignore()
+        {
+            return true;
+        }
+        // As in git, the deepest ignore file with a matching rule decides
+        self.matchers
+            .iter()
+            .filter(|(dir, _)| path.starts_with(dir) && path != dir)
+            .map(|(_, matcher)| matcher.matched_path_or_any_parents(path, is_directory))
+            .find(|m| !m.is_none())
+            .is_some_and(|m| m.is_ignore())
+    }
+
+    /// Forgets `path` and any directories below it, returning whether it was a
+    /// known directory
+    fn forget(&mut self, path: &Path) -> bool {
+        let was_directory = self.directories.contains(path);
+        if was_directory {
+            self.directories.retain(|d| !d.starts_with(path));
+        }
+        was_directory
+    }
+
+    /// Tracks directories as they come and go and decides whether a change is
+    /// reported. A deleted path takes the type it was last seen with; one that
+    /// was never seen, such as a path inside an ignored directory, is matched
+    /// both as a file and as a directory.
+    fn observe(&mut self, change: &mut FileChangeEvent) -> bool {
+        let path = PathBuf::from(&change.path);
+        let mut unknown_type = false;
+        match change.kind.as_str() {
+            "delete" => {
+                change.is_directory = self.forget(&path);
+                unknown_type = !change.is_directory;
+            }
+            "rename" => {
+                if let Some(from) = &change.from_path {
+                    self.forget(Path::new(from));
+                }
+            }
+            _ => {}
+        }
+        if change.is_directory && change.kind != "delete" {
+            self.directories.insert(path.clone());
+        }
+
+        let ignored = |path: &Path| {
+            self.is_ignored(path, change.is_directory)
+                || (unknown_type && self.is_ignored(path, true))
+        };
+        !(ignored(&path) && change.from_path.as_ref().is_none_or(|from| ignored(Path::new(from))))
+    }
+}
+
+fn change(kind: &str, path: &Path, from_path: Option<&Path>) -> FileChangeEvent {
+    FileChangeEvent {
+        kind: kind.to_string(),
+        path: path.to_string_lossy().to_string(),
+        from_path: from_path.map(|p| p.to_string_lossy().to_string()),
+        is_directory: path.is_dir(),
+    }
+}
+
+/// Maps raw notify events to browser-level changes. Access and metadata-only
+/// events are dropped; renames whose other side is outside the watch become
+/// a create or delete.
+fn to_changes(kind: &EventKind, paths: &[PathBuf]) -> Vec<FileChangeEvent> {
+    match kind {
+        EventKind::Create(_) => paths.iter().map(|p| change("create", p, None)).collect(),
+        EventKind::Remove(_) => paths.iter().map(|p| change("delete", p, None)).collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if paths.len() == 2 => {
+            vec![change("rename", &paths[1], Some(&paths[0]))]
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            paths.iter().map(|p| change("delete", p, None)).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            paths.iter().map(|p| change("create", p, None)).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(_)) => paths
+            .iter()
+            .map(|p| change(if p.exists() { "create" } else { "delete" }, p, None))
+            .collect(),
+        EventKind::Modify(ModifyKind::Metadata(_)) => Vec::new(),
+        EventKind::Modify(_) => paths
+            .iter()
+            .filter(|p| p.is_file())
+            .map(|p| change("modify", p, None))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn handle_events(app: &AppHandle, info: &DirectoryWatch, rules: &Mutex<IgnoreRules>, result: DebounceEventResult) {
+    let events = match result {
+        Ok(events) => events,
+        Err(errors) => {
+            for e in errors {
+                log::warn!("Watch {} on {}: {}", info.watch_id, info.path, e);
+            }
+            return;
+        }
+    };
+    let Ok(mut rules) = rules.lock() else {
+        return;
+    };
+
+    let ignore_files_changed = events.iter().flat_map(|e| &e.paths).any(|p| {
+        p.file_name()
+            .is_some_and(|name| IGNORE_FILES.iter().any(|f| name == *f))
+    });
+    if ignore_files_changed {
+        rules.reload();
+    }
+
+    let mut seen = HashSet::new();
+    let changes: Vec<FileChangeEvent> = events
+        .iter()
+        .flat_map(|event| to_changes(&event.kind, &event.paths))
+        .filter_map(|mut c| rules.observe(&mut c).then_some(c))
+        .filter(|c| seen.insert(c.clone()))
+        .collect();
+    drop(rules);
+    if changes.is_empty() {
+        return;
+    }
+
+    // Keep fuzzy file search in step with files appearing and disappearing
+    if changes.iter().any(|c| c.kind != "modify") {
+        if let Some(search) = app.try_state::<FileSearchState>() {
+            mark_indexes_stale(&search, Path::new(&info.path));
+        }
+    }
+
+    let batch = DirectoryChanges {
+        watch_id: info.watch_id.clone(),
+        path: info.path.clone(),
+        events: changes,
+    };
+    let _ = app.emit(&format!("directory-changed:{}", info.watch_id), &batch);
+    let _ = app.emit("directory-changed", &batch);
+}
+
+/// Starts watching a directory and emits `directory-changed` events with the
+/// debounced creates, deletes, renames and modifications under it. Paths
+/// matched by the directory's ignore files or `ignore_patterns` are skipped.
+#[tauri::command]
+pub async fn watch_directory(
+    app: AppHandle,
+    state: State<'_, FileWatcherState>,
+    directory_path: String,
+    recursive: Option<bool>,
+    debounce_ms: Option<u64>,
+    ignore_patterns: Option<Vec<String>>,
+) -> Result<DirectoryWatch, String> {
+    let path = PathBuf::from(&directory_path);
+    if !path.is_dir() {
+        return Err(format!("Directory does not exist: {}", directory_path));
+    }
+    let path = std::fs::canonicalize(&path).unwrap_or(path);
+
+    let info = DirectoryWatch {
+        watch_id: uuid::Uuid::new_v4().to_string(),
+        path: path.to_string_lossy().to_string(),
+        recursive: recursive.unwrap_or(true),
+        debounce_ms: debounce_ms
+            .unwrap_or(DEFAULT_DEBOUNCE_MS)
+            .clamp(MIN_DEBOUNCE_MS, MAX_DEBOUNCE_MS),
+    };
+    log::info!("Watching {} ({})", info.path, info.watch_id);
+
+    let rules = Mutex::new(IgnoreRules::new(
+        &path,
+        &ignore_patterns.unwrap_or_default(),
+        info.recursive,
+    ));
+    let handler_info = info.clone();
+    let mut debouncer = new_debouncer(
+        Duration::from_millis(info.debounce_ms),
+        None,
+        move |result: DebounceEventResult| handle_events(&app, &handler_info, &rules, result),
+    )
+    .map_err(|e| format!("Failed to create watcher: {}", e))?;
+
+    let mode = if info.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    debouncer
+        .watch(&path, mode)
+        .map_err(|e| format!("Failed to watch {}: {}", info.path, e))?;
+
+    state.0.lock().map_err(|e| e.to_string())?.insert(
+        info.watch_id.clone(),
+        Watch {
+            info: info.clone(),
+            _debouncer: debouncer,
+        },
+    );
+    Ok(info)
+}
+
+/// Stops a watch started with `watch_directory`
+#[tauri::command]
+pub async fn unwatch_directory(
+    state: State<'_, FileWatcherState>,
+    watch_id: String,
+) -> Result<(), String> {
+    let removed = state.0.lock().map_err(|e| e.to_string())?.remove(&watch_id);
+    match removed {
+        Some(watch) => {
+            log::info!("Stopped watching {} ({})", watch.info.path, watch_id);
+            Ok(())
+        }
+        None => Err(format!("Watch not found: {}", watch_id)),
+    }
+}
+
+/// Lists the active directory watches
+#[tauri::command]
+pub async fn list_directory_watches(
+    state: State<'_, FileWatcherState>,
+) -> Result<Vec<DirectoryWatch>, String> {
+    let watches = state.0.lock().map_err(|e| e.to_string())?;
+    Ok(watches.values().map(|w| w.info.clone()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify_debouncer_full::notify::event::{AccessKind, CreateKind, DataChange, MetadataKind, RemoveKind};
+    use std::fs;
+
+    fn summary(changes: &[FileChangeEvent], root: &Path) -> Vec<(String, String, bool)> {
+        changes
+            .iter()
+            .map(|c| {
+                let relative = Path::new(&c.path).strip_prefix(root).unwrap();
+                (c.kind.clone(), relative.to_string_lossy().to_string(), c.is_directory)
+            })
+            .collect()
+    }
+
+    /// Kind, path relative to the root and whether it is a directory
+    type Expected = Vec<(&'static str, &'static str, bool)>;
+
+    fn change_for(kind: &str, path: &Path) -> FileChangeEvent {
+        change(kind, path, None)
+    }
+
+    #[test]
+    fn test_to_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        let file = root.join("src/main.rs");
+        let folder = root.join("src");
+        let gone = root.join("gone.rs");
+
+        let cases: Vec<(EventKind, Vec<PathBuf>, Expected)> = vec![
+            (EventKind::Create(CreateKind::File), vec![file.clone()], vec![("create", "src/main.rs", false)]),
+            (EventKind::Create(CreateKind::Folder), vec![folder.clone()], vec![("create", "src", true)]),
+            (EventKind::Remove(RemoveKind::Any), vec![gone.clone()], vec![("delete", "gone.rs", false)]),
+            (
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)),
+                vec![gone.clone()],
+                vec![("delete", "gone.rs", false)],
+            ),
+            (
+                EventKind::Modify(ModifyKind::Name(RenameMode::To)),
+                vec![file.clone()],
+                vec![("create", "src/main.rs", false)],
+            ),
+            (
+                EventKind::Modify(ModifyKind::Name(RenameMode::Any)),
+                vec![file.clone(), gone.clone()],
+                vec![("create", "src/main.rs", false), ("delete", "gone.rs", false)],
+            ),
+            (
+                EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+                vec![file.clone(), folder.clone(), gone.clone()],
+                vec![("modify", "src/main.rs", false)],
+            ),
+            (EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any)), vec![file.clone()], vec![]),
+            (EventKind::Access(AccessKind::Any), vec![file.clone()], vec![]),
+        ];
+        for (kind, paths, expected) in cases {
+            let expected: Vec<(String, String, bool)> = expected
+                .into_iter()
+                .map(|(k, p, d)| (k.to_string(), p.to_string(), d))
+                .collect();
+            assert_eq!(summary(&to_changes(&kind, &paths), root), expected, "{:?}", kind);
+        }
+
+        let renamed = to_changes(
+            &EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+            &[gone.clone(), file.clone()],
+        );
+        assert_eq!(renamed.len(), 1);
+        assert_eq!(renamed[0].kind, "rename");
+        assert_eq!(renamed[0].path, file.to_string_lossy());
+        assert_eq!(renamed[0].from_path.as_deref(), Some(&*gone.to_string_lossy()));
+    }
+
+    #[test]
+    fn test_is_ignored_uses_nested_ignore_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join(".gitignore"), "node_modules/\n*.log\n").unwrap();
+        fs::create_dir_all(root.join("src/generated")).unwrap();
+        fs::write(root.join("src/.gitignore"), "generated/\n!keep.log\n").unwrap();
+        fs::create_dir_all(root.join("node_modules/pkg")).unwrap();
+
+        let rules = IgnoreRules::new(&root, &["*.tmp".to_string()], true);
+        assert!(rules.is_ignored(&root.join("node_modules"), true));
+        assert!(!rules.is_ignored(&root.join("node_modules"), false));
+        assert!(rules.is_ignored(&root.join("node_modules/pkg/index.js"), false));
+        assert!(rules.is_ignored(&root.join("debug.log"), false));
+        assert!(rules.is_ignored(&root.join("scratch.tmp"), false));
+        assert!(rules.is_ignored(&root.join(".env"), false));
+        assert!(!rules.is_ignored(&root.join(".claude/settings.json"), false));
+        assert!(!rules.is_ignored(&root.join("src/main.rs"), false));
+
+        // Rules in src/.gitignore apply below src only, and override the root's
+        assert!(rules.is_ignored(&root.join("src/generated/out.rs"), false));
+        assert!(!rules.is_ignored(&root.join("generated/out.rs"), false));
+        assert!(!rules.is_ignored(&root.join("src/keep.log"), false));
+        assert!(rules.is_ignored(&root.join("src/other.log"), false));
+
+        // A non-recursive watch only reads the root's ignore files
+        let shallow = IgnoreRules::new(&root, &[], false);
+        assert!(!shallow.is_ignored(&root.join("src/generated"), true));
+        assert!(shallow.is_ignored(&root.join("node_modules"), true));
+        assert!(rules.is_ignored(Path::new("/elsewhere/file.rs"), false));
+    }
+
+    #[test]
+    fn test_deleted_directories_keep_their_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join(".gitignore"), "build/\n").unwrap();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("build/out")).unwrap();
+        let mut rules = IgnoreRules::new(&root, &[], true);
+
+        fs::remove_dir_all(root.join("src")).unwrap();
+        fs::remove_dir_all(root.join("build")).unwrap();
+
+        let mut deleted = change_for("delete", &root.join("src"));
+        assert!(rules.observe(&mut deleted));
+        assert!(deleted.is_directory);
+        // Directories below a deleted one are forgotten with it
+        let mut nested = change_for("delete", &root.join("src/nested"));
+        assert!(rules.observe(&mut nested));
+        assert!(!nested.is_directory);
+
+        // Never seen because it was ignored; matched as a directory too
+        let mut ignored = change_for("delete", &root.join("build"));
+        assert!(!rules.observe(&mut ignored));
+
+        fs::create_dir(root.join("lib")).unwrap();
+        let mut created = change_for("create", &root.join("lib"));
+        assert!(rules.observe(&mut created));
+        fs::remove_dir(root.join("lib")).unwrap();
+        let mut removed = change_for("delete", &root.join("lib"));
+        assert!(rules.observe(&mut removed));
+        assert!(removed.is_directory);
+    }
+}
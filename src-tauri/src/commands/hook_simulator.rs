@@ -0,0 +1,572 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Claude Code's default hook timeout
+const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 60;
+/// Exit code with which a hook blocks the action
+const BLOCKING_EXIT_CODE: i32 = 2;
+
+/// A synthetic hook event. Only the fields relevant to `hook_event_name` are
+/// used; the rest of the payload is filled in like Claude Code would.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SimulatedHookEvent {
+    /// 'PreToolUse', 'PostToolUse', 'UserPromptSubmit', 'Notification',
+    /// 'Stop', 'SubagentStop', 'PreCompact', 'SessionStart' or 'SessionEnd'
+    pub hook_event_name: String,
+    pub tool_name: Option<String>,
+    pub tool_input: Option<Value>,
+    pub tool_response: Option<Value>,
+    pub prompt: Option<String>,
+    pub message: Option<String>,
+    /// PreCompact: 'manual' or 'auto'
+    pub trigger: Option<String>,
+    /// SessionStart: 'startup', 'resume' or 'clear'
+    pub source: Option<String>,
+    /// SessionEnd: why the session ended
+    pub reason: Option<String>,
+    pub session_id: Option<String>,
+}
+
+/// How one matcher group of the configuration relates to the event
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MatcherResult {
+    pub matcher: String,
+    pub matched: bool,
+    pub commands: Vec<String>,
+}
+
+/// The outcome of running one hook command
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HookRunResult {
+    pub matcher: String,
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub timed_out: bool,
+    pub duration_ms: u64,
+    /// JSON the hook printed on stdout, if any
+    pub output_json: Option<Value>,
+    /// 'allow', 'block', 'ask' or 'feedback'
+    pub decision: String,
+    /// Text Claude or the user would see with the decision
+    pub reason: Option<String>,
+    pub error: Option<String>,
+}
+
+/// What Claude Code would do with an event given a hooks configuration
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HookSimulation {
+    pub hook_event_name: String,
+    /// The JSON sent to each hook on stdin
+    pub payload: Value,
+    pub matchers: Vec<MatcherResult>,
+    pub results: Vec<HookRunResult>,
+    /// The combined decision: any block wins, then ask, then feedback
+    pub decision: String,
+    pub reason: Option<String>,
+}
+
+fn is_tool_event(event: &str) -> bool {
+    matches!(event, "PreToolUse" | "PostToolUse")
+}
+
+/// Tool events match on the tool name, PreCompact on the trigger and
+/// SessionStart on the source. Other events ignore the matcher.
+fn matches(event: &SimulatedHookEvent, matcher: &str) -> bool {
+    let subject = match event.hook_event_name.as_str() {
+        e if is_tool_event(e) => event.tool_name.as_deref(),
+        "PreCompact" => event.trigger.as_deref(),
+        "SessionStart" => event.source.as_deref(),
+        _ => return true,
+    };
+    if matcher.is_empty() || matcher == "*" {
+        return true;
+    }
+    let Some(subject) = subject else {
+        return false;
+    };
+    if matcher == subject {
+        return true;
+    }
+    Regex::new(&format!("^(?:{})$", matcher))
+        .map(|re| re.is_match(subject))
+        .unwrap_or(false)
+}
+
+/// Builds the stdin payload Claude Code sends for the event
+fn build_payload(event: &SimulatedHookEvent, cwd: &str) -> Value {
+    let session_id = event
+        .session_id
+        .clone()
+        .unwrap_or_else(|| "simulated-session".to_string());
+    let mut payload = json!({
+        "session_id": session_id,
+        "transcript_path": std::env::temp_dir()
+            .join(format!("{}.jsonl", session_id))
+            .to_string_lossy(),
+        "cwd": cwd,
+        "hook_event_name": event.hook_event_name,
+    });
+    let fields: Vec<(&str, Value)> = match event.hook_event_name.as_str() {
+        "PreToolUse" => vec![
+            ("tool_name", json!(event.tool_name)),
+            ("tool_input", event.tool_input.clone().unwrap_or(json!({}))),
+        ],
+        "PostToolUse" => vec![
+            ("tool_name", json!(event.tool_name)),
+            ("tool_input", event.tool_input.clone().unwrap_or(json!({}))),
+            ("tool_response", event.tool_response.clone().unwrap_or(json!({}))),
+        ],
+        "UserPromptSubmit" => vec![("prompt", json!(event.prompt.clone().unwrap_or_default()))],
+        "Notification" => vec![("message", json!(event.message.clone().unwrap_or_default()))],
+        "Stop" | "SubagentStop" => vec![("stop_hook_active", json!(false))],
+        "PreCompact" => vec![
+            ("trigger", json!(event.trigger.clone().unwrap_or_else(|| "manual".to_string()))),
+            ("custom_instructions", json!("")),
+        ],
+        "SessionStart" => vec![(
+            "source",
+            json!(event.source.clone().unwrap_or_else(|| "startup".to_string())),
+        )],
+        "SessionEnd" => vec![(
+            "reason",
+            json!(event.reason.clone().unwrap_or_else(|| "other".to_string())),
+        )],
+        _ => Vec::new(),
+    };
+    for (key, value) in fields {
+        payload[key] = value;
+    }
+    payload
+}
+
+/// Applies Claude Code's rules for exit codes and JSON output to one hook run
+fn decide(event: &str, result: &mut HookRunResult) {
+    let stderr = Some(result.stderr.trim().to_string()).filter(|s| !s.is_empty());
+
+    if result.timed_out || result.error.is_some() {
+        // Failures that are not exit code 2 are shown to the user and do not block
+        result.decision = "allow".to_string();
+        result.reason = result.error.clone().or_else(|| Some("Hook timed out".to_string()));
+        return;
+    }
+
+    // JSON output is only honored when the hook exits successfully
+    if let Some(output) = result.output_json.as_ref().filter(|_| result.exit_code == Some(0)) {
+        if output.get("continue").and_then(Value::as_bool) == Some(false) {
+            result.decision = "block".to_string();
+            result.reason = output
+                .get("stopReason")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            return;
+        }
+        let specific = output.get("hookSpecificOutput");
+        let permission = specific
+            .and_then(|s| s.get("permissionDecision"))
+            .and_then(Value::as_str);
+        let legacy = output.get("decision").and_then(Value::as_str);
+        let reason = specific
+            .and_then(|s| s.get("permissionDecisionReason"))
+            .or_else(|| output.get("reason"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let context = specific
+            .and_then(|s| s.get("additionalContext"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        let decision = match (permission, legacy) {
+            // A PostToolUse "block" cannot undo the tool call, it only prompts Claude
+            (None, Some("block")) if event == "PostToolUse" => Some("feedback"),
+            (Some("deny"), _) | (None, Some("block")) => Some("block"),
+            (Some("ask"), _) if event == "PreToolUse" => Some("ask"),
+            (Some("allow"), _) | (None, Some("approve")) => Some("allow"),
+            _ => None,
+        };
+        if let Some(decision) = decision {
+            result.decision = decision.to_string();
+            result.reason = reason.or(context);
+            return;
+        }
+        if context.is_some() {
+            result.decision = "feedback".to_string();
+            result.reason = context;
+            return;
+        }
+    }
+
+    match result.exit_code {
+        Some(0) => {
+            // Plain stdout becomes context for the prompt and session start
+            let stdout = result.stdout.trim();
+            if matches!(event, "UserPromptSubmit" | "SessionStart") && !stdout.is_empty() {
+                result.decision = "feedback".to_string();
+                result.reason = Some(stdout.to_string());
+            } else {
+                result.decision = "allow".to_string();
+            }
+        }
+        Some(BLOCKING_EXIT_CODE) => {
+            result.decision = match event {
+                "PreToolUse" | "UserPromptSubmit" | "Stop" | "SubagentStop" => "block",
+                // The tool already ran; stderr is only fed back to Claude
+                "PostToolUse" => "feedback",
+                // Nothing to block; stderr is shown to the user
+                _ => "allow",
+            }
+            .to_string();
+            result.reason = stderr;
+        }
+        _ => {
+            result.decision = "allow".to_string();
+            result.reason = stderr;
+        }
+    }
+}
+
+async fn run_hook(
+    matcher: &str,
+    command: &str,
+    timeout: Duration,
+    payload: &Value,
+    cwd: &str,
+    event: &str,
+) -> HookRunResult {
+    let mut result = HookRunResult {
+        matcher: matcher.to_string(),
+        command: command.to_string(),
+        exit_code: None,
+        stdout: String::new(),
+        stderr: String::new(),
+        timed_out: false,
+        duration_ms: 0,
+        output_json: None,
+        decision: "allow".to_string(),
+        reason: None,
+        error: None,
+    };
+
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    cmd.current_dir(cwd)
+        .env("CLAUDE_PROJECT_DIR", cwd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    // On Windows, hide the console window to prevent CMD popup
+    #[cfg(target_os = "windows")]
+    {
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let started = Instant::now();
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            result.error = Some(format!("Failed to run hook: {}", e));
+            decide(event, &mut result);
+            return result;
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that never reads stdin closes the pipe early; that is fine
+        let _ = stdin.write_all(payload.to_string().as_bytes()).await;
+    }
+
+    match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(Ok(output)) => {
+            result.exit_code = output.status.code();
+            result.stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            result.stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            result.output_json = serde_json::from_str::<Value>(result.stdout.trim())
+                .ok()
+                .filter(Value::is_object);
+        }
+        Ok(Err(e)) => result.error = Some(format!("Failed to wait for hook: {}", e)),
+        // Dropping the future kills the child
+        Err(_) => result.timed_out = true,
+    }
+    result.duration_ms = started.elapsed().as_millis() as u64;
+    decide(event, &mut result);
+    result
+}
+
+/// Runs the hooks in `hooks` (as returned by `get_hooks_config`) that would
+/// fire for `event`, feeding each the JSON payload Claude Code would send.
+/// With `dry_run` only the matching is reported and nothing is executed.
+#[tauri::command]
+pub async fn simulate_hooks(
+    hooks: Value,
+    event: SimulatedHookEvent,
+    project_path: Option<String>,
+    dry_run: Option<bool>,
+) -> Result<HookSimulation, String> {
+    log::info!("Simulating {} hooks", event.hook_event_name);
+
+    let cwd = match project_path {
+        Some(path) if PathBuf::from(&path).is_dir() => path,
+        Some(path) => return Err(format!("Project path does not exist: {}", path)),
+        None => std::env::current_dir()
+            .map_err(|e| e.to_string())?
+            .to_string_lossy()
+            .to_string(),
+    };
+    if is_tool_event(&event.hook_event_name) && event.tool_name.is_none() {
+        return Err(format!("{} requires a tool name", event.hook_event_name));
+    }
+
+    let payload = build_payload(&event, &cwd);
+    let groups = hooks
+        .get(&event.hook_event_name)
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    let mut matchers = Vec::new();
+    let mut to_run: Vec<(String, String, Duration)> = Vec::new();
+    for group in &groups {
+        let matcher = group
+            .get("matcher")
+            .and_then(Value::as_str)
+            .unwrap_or("")
+            .to_string();
+        let entries = group
+            .get("hooks")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        let commands: Vec<(String, Duration)> = entries
+            .iter()
+            .filter(|h| h.get("type").and_then(Value::as_str).unwrap_or("command") == "command")
+            .filter_map(|h| {
+                let command = h.get("command").and_then(Value::as_str)?.to_string();
+                let timeout = h
+                    .get("timeout")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(DEFAULT_HOOK_TIMEOUT_SECS);
+                Some((command, Duration::from_secs(timeout)))
+            })
+            .collect();
+
+        let matched = matches(&event, &matcher);
+        if matched {
+            for (command, timeout) in &commands {
+                // Identical commands run once, as in Claude Code
+                if !to_run.iter().any(|(_, c, _)| c == command) {
+                    to_run.push((matcher.clone(), command.clone(), *timeout));
+                }
+            }
+        }
+        matchers.push(MatcherResult {
+            matcher,
+            matched,
+            commands: commands.into_iter().map(|(c, _)| c).collect(),
+        });
+    }
+
+    // Matching hooks run in parallel
+    let results = if dry_run.unwrap_or(false) {
+        Vec::new()
+    } else {
+        futures::future::join_all(to_run.iter().map(|(matcher, command, timeout)| {
+            run_hook(matcher, command, *timeout, &payload, &cwd, &event.hook_event_name)
+        }))
+        .await
+    };
+
+    let combined = ["block", "ask", "feedback"]
+        .iter()
+        .find_map(|d| results.iter().find(|r| r.decision == *d))
+        .map(|r| (r.decision.clone(), r.reason.clone()))
+        .unwrap_or_else(|| ("allow".to_string(), None));
+
+    Ok(HookSimulation {
+        hook_event_name: event.hook_event_name,
+        payload,
+        matchers,
+        results,
+        decision: combined.0,
+        reason: combined.1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_result(exit_code: Option<i32>, stdout: &str, stderr: &str) -> HookRunResult {
+        HookRunResult {
+            matcher: String::new(),
+            command: String::new(),
+            exit_code,
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+            timed_out: false,
+            duration_ms: 0,
+            output_json: serde_json::from_str::<Value>(stdout).ok().filter(Value::is_object),
+            decision: "allow".to_string(),
+            reason: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_decide() {
+        // (event, exit code, stdout, stderr, decision, reason)
+        let cases = [
+            // Exit code 2 depends on the event
+            ("PreToolUse", Some(2), "", "no rm\n", "block", Some("no rm")),
+            ("UserPromptSubmit", Some(2), "", "secret", "block", Some("secret")),
+            ("Stop", Some(2), "", "keep going", "block", Some("keep going")),
+            ("PostToolUse", Some(2), "", "lint failed", "feedback", Some("lint failed")),
+            ("Notification", Some(2), "", "oops", "allow", Some("oops")),
+            // Other failures never block
+            ("PreToolUse", Some(1), "", "crashed", "allow", Some("crashed")),
+            ("PreToolUse", Some(0), "", "", "allow", None),
+            // Plain stdout is context only for some events
+            ("UserPromptSubmit", Some(0), "today is friday", "", "feedback", Some("today is friday")),
+            ("PreToolUse", Some(0), "hello", "", "allow", None),
+            // JSON output
+            ("PreToolUse", Some(0), r#"{"continue": false, "stopReason": "halt"}"#, "", "block", Some("halt")),
+            ("Stop", Some(0), r#"{"continue": false}"#, "", "block", None),
+            (
+                "PreToolUse",
+                Some(0),
+                r#"{"hookSpecificOutput": {"permissionDecision": "deny", "permissionDecisionReason": "prod"}}"#,
+                "",
+                "block",
+                Some("prod"),
+            ),
+            (
+                "PreToolUse",
+                Some(0),
+                r#"{"hookSpecificOutput": {"permissionDecision": "ask"}}"#,
+                "",
+                "ask",
+                None,
+            ),
+            (
+                "PreToolUse",
+                Some(0),
+                r#"{"hookSpecificOutput": {"permissionDecision": "allow"}, "decision": "block"}"#,
+                "",
+                "allow",
+                None,
+            ),
+            ("PreToolUse", Some(0), r#"{"decision": "approve", "reason": "ok"}"#, "", "allow", Some("ok")),
+            ("PreToolUse", Some(0), r#"{"decision": "block", "reason": "no"}"#, "", "block", Some("no")),
+            // A PostToolUse block only prompts Claude
+            ("PostToolUse", Some(0), r#"{"decision": "block", "reason": "fix it"}"#, "", "feedback", Some("fix it")),
+            (
+                "SessionStart",
+                Some(0),
+                r#"{"hookSpecificOutput": {"additionalContext": "branch main"}}"#,
+                "",
+                "feedback",
+                Some("branch main"),
+            ),
+            // JSON is ignored unless the hook succeeds
+            ("PreToolUse", Some(1), r#"{"continue": false}"#, "", "allow", None),
+        ];
+        for (event, exit_code, stdout, stderr, decision, reason) in cases {
+            let mut result = run_result(exit_code, stdout, stderr);
+            decide(event, &mut result);
+            assert_eq!(
+                (result.decision.as_str(), result.reason.as_deref()),
+                (decision, reason),
+                "{} exit {:?} stdout {}",
+                event,
+                exit_code,
+                stdout
+            );
+        }
+
+        let mut timed_out = run_result(None, "", "");
+        timed_out.timed_out = true;
+        decide("PreToolUse", &mut timed_out);
+        assert_eq!(timed_out.decision, "allow");
+        assert_eq!(timed_out.reason.as_deref(), Some("Hook timed out"));
+    }
+
+    #[test]
+    fn test_matches() {
+        let tool = |name: &str| SimulatedHookEvent {
+            hook_event_name: "PreToolUse".to_string(),
+            tool_name: Some(name.to_string()),
+            ..Default::default()
+        };
+        assert!(matches(&tool("Bash"), ""));
+        assert!(matches(&tool("Bash"), "*"));
+        assert!(matches(&tool("Bash"), "Bash"));
+        assert!(matches(&tool("Edit"), "Edit|Write"));
+        assert!(!matches(&tool("MultiEdit"), "Edit"));
+        assert!(matches(&tool("mcp__github__create_issue"), "mcp__github__.*"));
+        assert!(!matches(&tool("Bash"), "Bash("));
+
+        let compact = SimulatedHookEvent {
+            hook_event_name: "PreCompact".to_string(),
+            trigger: Some("auto".to_string()),
+            ..Default::default()
+        };
+        assert!(matches(&compact, "auto"));
+        assert!(!matches(&compact, "manual"));
+
+        // Events without a subject ignore the matcher
+        let stop = SimulatedHookEvent {
+            hook_event_name: "Stop".to_string(),
+            ..Default::default()
+        };
+        assert!(matches(&stop, "anything"));
+        let no_source = SimulatedHookEvent {
+            hook_event_name: "SessionStart".to_string(),
+            ..Default::default()
+        };
+        assert!(!matches(&no_source, "startup"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_hook_reads_stdin_and_times_out() {
+        let payload = json!({"hook_event_name": "PreToolUse", "tool_name": "Bash"});
+        let cwd = std::env::temp_dir().to_string_lossy().to_string();
+
+        // The hook only exits once stdin is closed after the payload
+        let blocked = run_hook(
+            "Bash",
+            "cat >/dev/null; echo denied >&2; exit 2",
+            Duration::from_secs(10),
+            &payload,
+            &cwd,
+            "PreToolUse",
+        )
+        .await;
+        assert!(!blocked.timed_out);
+        assert_eq!(blocked.exit_code, Some(2));
+        assert_eq!(blocked.decision, "block");
+        assert_eq!(blocked.reason.as_deref(), Some("denied"));
+
+        let slow = run_hook("Bash", "sleep 5", Duration::from_millis(100), &payload, &cwd, "PreToolUse").await;
+        assert!(slow.timed_out);
+        assert!(slow.duration_ms < 5_000);
+        assert_eq!(slow.decision, "allow");
+        assert_eq!(slow.reason.as_deref(), Some("Hook timed out"));
+    }
+}
@@ -238,7 +238,13 @@ async fn execute_claude_mcp_command(app_handle: &AppHandle, args: Vec<&str>) ->
     }
 
     // Otherwise, use system command execution as before
-    let mut cmd = create_command_with_env(&claude_path);
+    execute_claude_mcp_system(&claude_path, &args)
+}
+
+/// Runs `claude mcp <args>` with a system binary. Used directly by the CLI,
+/// which has no sidecar to fall back on.
+pub(crate) fn execute_claude_mcp_system(claude_path: &str, args: &[&str]) -> Result<String> {
+    let mut cmd = create_command_with_env(claude_path);
     cmd.arg("mcp");
     for arg in args {
         cmd.arg(arg);
@@ -354,6 +360,119 @@ pub async fn mcp_add(
     }
 }
 
+/// Parses the text output of `claude mcp list`, handling multi-line commands.
+/// Disabled status comes from the project's `.mcp.json`.
+pub(crate) fn parse_mcp_list_output(
+    trimmed: &str,
+    project_config: &MCPProjectConfig,
+) -> Vec<MCPServer> {
+    let mut servers = Vec::new();
+    let lines: Vec<&str> = trimmed.lines().collect();
+    info!("Total lines in output: {}", lines.len());
+    for (idx, line) in lines.iter().enumerate() {
+        info!("Line {}: {:?}", idx, line);
+    }
+
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        info!("Processing line {}: {:?}", i, line);
+
+        // Check if this line starts a new server entry
+        if let Some(colon_pos) = line.find(':') {
+            info!("Found colon at position {} in line: {:?}", colon_pos, line);
+            // Make sure this is a server name line (not part of a path)
+            // Server names typically don't contain '/' or '\'
+            let potential_name = line[..colon_pos].trim();
+            info!("Potential server name: {:?}", potential_name);
+
+            if !potential_name.contains('/') && !potential_name.contains('\\') {
+                info!("Valid server name detected: {:?}", potential_name);
+                let name = potential_name.to_string();
+                let mut command_parts = vec![line[colon_pos + 1..].trim().to_string()];
+                info!("Initial command part: {:?}", command_parts[0]);
+
+                // Check if command continues on next lines
+                i += 1;
+                while i < lines.len() {
+                    let next_line = lines[i];
+                    info!("Checking next line {} for continuation: {:?}", i, next_line);
+
+                    // If the next line starts with a server name pattern, break
+                    if next_line.contains(':') {
+                        let potential_next_name =
+                            next_line.split(':').next().unwrap_or("").trim();
+                        info!(
+                            "Found colon in next line, potential name: {:?}",
+                            potential_next_name
+                        );
+                        if !potential_next_name.is_empty()
+                            && !potential_next_name.contains('/')
+                            && !potential_next_name.contains('\\')
+                        {
+                            info!("Next line is a new server, breaking");
+                            break;
+                        }
+                    }
+                    // Otherwise, this line is a continuation of the command
+                    info!("Line {} is a continuation", i);
+                    command_parts.push(next_line.trim().to_string());
+                    i += 1;
+                }
+
+                // Join all command parts
+                let full_command = command_parts.join(" ");
+                info!("Full command for server '{}': {:?}", name, full_command);
+
+                // Check if server is disabled in project config
+                let disabled = project_config.mcp_servers
+                    .get(&name)
+                    .map(|config| config.disabled)
+                    .unwrap_or(false);
+                
+                info!("Server '{}' disabled status from config: {}", name, disabled);
+
+                // For now, we'll create a basic server entry
+                servers.push(MCPServer {
+                    name: name.clone(),
+                    transport: "stdio".to_string(), // Default assumption
+                    command: Some(full_command),
+                    args: vec![],
+                    env: HashMap::new(),
+                    url: None,
+                    scope: "local".to_string(), // Default assumption
+                    is_active: false,
+                    disabled, // Read from project config
+                    status: ServerStatus {
+                        running: false,
+                        error: None,
+                        last_checked: None,
+                    },
+                });
+                info!("Added server: {:?}", name);
+
+                continue;
+            } else {
+                info!("Skipping line - name contains path separators");
+            }
+        } else {
+            info!("No colon found in line {}", i);
+        }
+
+        i += 1;
+    }
+
+    info!("Found {} MCP servers total", servers.len());
+    for (idx, server) in servers.iter().enumerate() {
+        info!(
+            "Server {}: name='{}', command={:?}, disabled={}",
+            idx, server.name, server.command, server.disabled
+        );
+    }
+    servers
+}
+
 /// Lists all configured MCP servers
 #[tauri::command]
 pub async fn mcp_list(app: AppHandle) -> Result<Vec<MCPServer>, String> {
@@ -381,112 +500,7 @@ pub async fn mcp_list(app: AppHandle) -> Result<Vec<MCPServer>, String> {
                 mcp_servers: HashMap::new(),
             });
 
-            // Parse the text output, handling multi-line commands
-            let mut servers = Vec::new();
-            let lines: Vec<&str> = trimmed.lines().collect();
-            info!("Total lines in output: {}", lines.len());
-            for (idx, line) in lines.iter().enumerate() {
-                info!("Line {}: {:?}", idx, line);
-            }
-
-            let mut i = 0;
-
-            while i < lines.len() {
-                let line = lines[i];
-                info!("Processing line {}: {:?}", i, line);
-
-                // Check if this line starts a new server entry
-                if let Some(colon_pos) = line.find(':') {
-                    info!("Found colon at position {} in line: {:?}", colon_pos, line);
-                    // Make sure this is a server name line (not part of a path)
-                    // Server names typically don't contain '/' or '\'
-                    let potential_name = line[..colon_pos].trim();
-                    info!("Potential server name: {:?}", potential_name);
-
-                    if !potential_name.contains('/') && !potential_name.contains('\\') {
-                        info!("Valid server name detected: {:?}", potential_name);
-                        let name = potential_name.to_string();
-                        let mut command_parts = vec![line[colon_pos + 1..].trim().to_string()];
-                        info!("Initial command part: {:?}", command_parts[0]);
-
-                        // Check if command continues on next lines
-                        i += 1;
-                        while i < lines.len() {
-                            let next_line = lines[i];
-                            info!("Checking next line {} for continuation: {:?}", i, next_line);
-
-                            // If the next line starts with a server name pattern, break
-                            if next_line.contains(':') {
-                                let potential_next_name =
-                                    next_line.split(':').next().unwrap_or("").trim();
-                                info!(
-                                    "Found colon in next line, potential name: {:?}",
-                                    potential_next_name
-                                );
-                                if !potential_next_name.is_empty()
-                                    && !potential_next_name.contains('/')
-                                    && !potential_next_name.contains('\\')
-                                {
-                                    info!("Next line is a new server, breaking");
-                                    break;
-                                }
-                            }
-                            // Otherwise, this line is a continuation of the command
-                            info!("Line {} is a continuation", i);
-                            command_parts.push(next_line.trim().to_string());
-                            i += 1;
-                        }
-
-                        // Join all command parts
-                        let full_command = command_parts.join(" ");
-                        info!("Full command for server '{}': {:?}", name, full_command);
-
-                        // Check if server is disabled in project config
-                        let disabled = project_config.mcp_servers
-                            .get(&name)
-                            .map(|config| config.disabled)
-                            .unwrap_or(false);
-                        
-                        info!("Server '{}' disabled status from config: {}", name, disabled);
-
-                        // For now, we'll create a basic server entry
-                        servers.push(MCPServer {
-                            name: name.clone(),
-                            transport: "stdio".to_string(), // Default assumption
-                            command: Some(full_command),
-                            args: vec![],
-                            env: HashMap::new(),
-                            url: None,
-                            scope: "local".to_string(), // Default assumption
-                            is_active: false,
-                            disabled, // Read from project config
-                            status: ServerStatus {
-                                running: false,
-                                error: None,
-                                last_checked: None,
-                            },
-                        });
-                        info!("Added server: {:?}", name);
-
-                        continue;
-                    } else {
-                        info!("Skipping line - name contains path separators");
-                    }
-                } else {
-                    info!("No colon found in line {}", i);
-                }
-
-                i += 1;
-            }
-
-            info!("Found {} MCP servers total", servers.len());
-            for (idx, server) in servers.iter().enumerate() {
-                info!(
-                    "Server {}: name='{}', command={:?}, disabled={}",
-                    idx, server.name, server.command, server.disabled
-                );
-            }
-            Ok(servers)
+            Ok(parse_mcp_list_output(trimmed, &project_config))
         }
         Err(e) => {
             error!("Failed to list MCP servers: {}", e);
@@ -0,0 +1,297 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// How many levels of `@path` imports Claude Code follows
+const MAX_IMPORT_DEPTH: u32 = 5;
+
+/// One file that contributes to the memory Claude loads
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MemorySource {
+    /// 'enterprise', 'user', 'ancestor', 'project', 'local' or 'import'
+    pub kind: String,
+    pub path: String,
+    /// The file whose `@path` line pulled this one in
+    pub imported_by: Option<String>,
+    /// 0 for files Claude loads directly, 1+ for imports
+    pub depth: u32,
+    pub content: String,
+    pub tokens: u64,
+    /// Why an import could not be loaded; such sources have no content
+    pub error: Option<String>,
+}
+
+/// The memory Claude Code loads for a working directory, in load order
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EffectiveMemory {
+    pub working_directory: String,
+    pub sources: Vec<MemorySource>,
+    /// All sources joined the way they are presented to the model
+    pub merged_text: String,
+    pub total_tokens: u64,
+    pub max_import_depth: u32,
+}
+
+/// Rough token count, about four characters per token
+fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
+fn describe(kind: &str) -> &'static str {
+    match kind {
+        "enterprise" => "organization-wide instructions",
+        "user" => "user's private global instructions for all projects",
+        "ancestor" | "project" => "project instructions, checked into the codebase",
+        "local" => "user's private project instructions, not checked in",
+        _ => "imported instructions",
+    }
+}
+
+fn enterprise_memory_path() -> PathBuf {
+    if cfg!(target_os = "macos") {
+        PathBuf::from("/Library/Application Support/ClaudeCode/CLAUDE.md")
+    } else if cfg!(windows) {
+        PathBuf::from(r"C:\ProgramData\ClaudeCode\CLAUDE.md")
+    } else {
+        PathBuf::from("/etc/claude-code/CLAUDE.md")
+    }
+}
+
+fn import_regex() -> &'static Regex {
+    static IMPORT: OnceLock<Regex> = OnceLock::new();
+    IMPORT.get_or_init(|| Regex::new(r"(?:^|\s)@((?:[^\s\\]|\\ )+)").expect("valid regex"))
+}
+
+/// Finds `@path` references outside of fenced code blocks and inline code
+fn find_imports(content: &str) -> Vec<String> {
+    let mut imports = Vec::new();
+    let mut in_fence = false;
+    for line in content.lines() {
+        if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        // Drop inline code spans; an unmatched backtick keeps the rest of the line
+        let mut prose = String::new();
+        for (i, part) in line.split('`').enumerate() {
+            if i % 2 == 0 {
+                prose.push_str(part);
+                prose.push(' ');
+            }
+        }
+        for capture in import_regex().captures_iter(&prose) {
+            let path = capture[1].replace("\\ ", " ");
+            // Trailing punctuation belongs to the sentence, not the path
+            let path = path.trim_end_matches(['.', ',', ';', ':', ')', '!', '?']);
+            if !path.is_empty() {
+                imports.push(path.to_string());
+            }
+        }
+    }
+    imports
+}
+
+fn resolve_import(reference: &str, importer: &Path, home: Option<&Path>) -> Option<PathBuf> {
+    if let Some(rest) = reference.strip_prefix("~/") {
+        return home.map(|h| h.join(rest));
+    }
+    let path = PathBuf::from(reference);
+    if path.is_absolute() {
+        Some(path)
+    } else {
+        Some(importer.parent()?.join(path))
+    }
+}
+
+struct Composer<'a> {
+    home: Option<&'a Path>,
+    sources: Vec<MemorySource>,
+    seen: HashSet<PathBuf>,
+}
+
+impl Composer<'_> {
+    /// Adds a file Claude loads directly, if it exists
+    fn add_root(&mut self, kind: &str, path: &Path) {
+        if path.is_file() {
+            self.add(kind, path, None, 0);
+        }
+    }
+
+    fn add(&mut self, kind: &str, path: &Path, imported_by: Option<&Path>, depth: u32) {
+        let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        // A file reached twice (or through a cycle) is only loaded once
+        if !self.seen.insert(key) {
+            return;
+        }
+
+        let mut source = MemorySource {
+            kind: kind.to_string(),
+            path: path.to_string_lossy().to_string(),
+            imported_by: imported_by.map(|p| p.to_string_lossy().to_string()),
+            depth,
+            content: String::new(),
+            tokens: 0,
+            error: None,
+        };
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                source.tokens = estimate_tokens(&content);
+                source.content = content;
+            }
+            Err(e) => {
+                source.error = Some(format!("Failed to read file: {}", e));
+                self.sources.push(source);
+                return;
+            }
+        }
+        let imports = find_imports(&source.content);
+        self.sources.push(source);
+
+        for reference in imports {
+            if depth >= MAX_IMPORT_DEPTH {
+                self.sources.push(MemorySource {
+                    kind: "import".to_string(),
+                    path: reference,
+                    imported_by: Some(path.to_string_lossy().to_string()),
+                    depth: depth + 1,
+                    content: String::new(),
+                    tokens: 0,
+                    error: Some(format!(
+                        "Not loaded: imports are followed at most {} levels deep",
+                        MAX_IMPORT_DEPTH
+                    )),
+                });
+                continue;
+            }
+            match resolve_import(&reference, path, self.home) {
+                Some(target) => self.add("import", &target, Some(path), depth + 1),
+                None => self.sources.push(MemorySource {
+                    kind: "import".to_string(),
+                    path: reference,
+                    imported_by: Some(path.to_string_lossy().to_string()),
+                    depth: depth + 1,
+                    content: String::new(),
+                    tokens: 0,
+                    error: Some("Could not resolve import path".to_string()),
+                }),
+            }
+        }
+    }
+}
+
+/// Collects the memory files Claude Code loads for `working_directory`, in the
+/// order they are loaded: enterprise policy, user memory, then project files
+/// from the outermost ancestor down to the directory itself. Each file's
+/// imports follow right after it.
+pub(crate) fn compose_effective_memory(
+    working_directory: &Path,
+    home: Option<&Path>,
+) -> EffectiveMemory {
+    let mut composer = Composer {
+        home,
+        sources: Vec::new(),
+        seen: HashSet::new(),
+    };
+
+    composer.add_root("enterprise", &enterprise_memory_path());
+    if let Some(home) = home {
+        composer.add_root("user", &home.join(".claude").join("CLAUDE.md"));
+    }
+
+    let mut directories: Vec<&Path> = working_directory.ancestors().collect();
+    directories.reverse();
+    for dir in directories {
+        // Claude Code does not read memory from the filesystem root
+        if dir.parent().is_none() {
+            continue;
+        }
+        let kind = if dir == working_directory { "project" } else { "ancestor" };
+        composer.add_root(kind, &dir.join("CLAUDE.md"));
+        composer.add_root(kind, &dir.join(".claude").join("CLAUDE.md"));
+        composer.add_root("local", &dir.join("CLAUDE.local.md"));
+    }
+
+    let sources = composer.sources;
+    let merged_text = sources
+        .iter()
+        .filter(|s| s.error.is_none())
+        .map(|s| format!("Contents of {} ({}):\n\n{}", s.path, describe(&s.kind), s.content))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    EffectiveMemory {
+        working_directory: working_directory.to_string_lossy().to_string(),
+        total_tokens: estimate_tokens(&merged_text),
+        sources,
+        merged_text,
+        max_import_depth: MAX_IMPORT_DEPTH,
+    }
+}
+
+/// Computes the CLAUDE.md memory Claude Code would load when started in
+/// `working_directory`, including resolved `@path` imports
+#[tauri::command]
+pub async fn get_effective_memory(working_directory: String) -> Result<EffectiveMemory, String> {
+    log::info!("Composing effective memory for: {}", working_directory);
+
+    let path = PathBuf::from(&working_directory);
+    if !path.is_dir() {
+        return Err(format!("Directory does not exist: {}", working_directory));
+    }
+    let path = fs::canonicalize(&path).unwrap_or(path);
+    let home = dirs::home_dir();
+
+    Ok(compose_effective_memory(&path, home.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_imports_outside_code() {
+        let content = "See @docs/a.md and @~/notes.md.\nmail me@example.com\n`@skip.md`\n```\n@fenced.md\n```\n@b\\ c.md";
+        assert_eq!(find_imports(content), ["docs/a.md", "~/notes.md", "b c.md"]);
+    }
+
+    #[test]
+    fn test_composes_in_load_order_and_stops_cycles() {
+        let root = tempfile::tempdir().unwrap();
+        let home = root.path().join("home");
+        let project = root.path().join("repo").join("app");
+        fs::create_dir_all(home.join(".claude")).unwrap();
+        fs::create_dir_all(&project).unwrap();
+        fs::write(home.join(".claude/CLAUDE.md"), "user").unwrap();
+        fs::write(root.path().join("repo/CLAUDE.md"), "repo @shared.md").unwrap();
+        fs::write(root.path().join("repo/shared.md"), "shared @CLAUDE.md @missing.md").unwrap();
+        fs::write(project.join("CLAUDE.md"), "app").unwrap();
+        fs::write(project.join("CLAUDE.local.md"), "mine").unwrap();
+
+        let memory = compose_effective_memory(&project, Some(&home));
+        let kinds: Vec<_> = memory
+            .sources
+            .iter()
+            .filter(|s| s.kind != "enterprise")
+            .map(|s| (s.kind.as_str(), s.content.as_str(), s.error.is_some()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("user", "user", false),
+                ("ancestor", "repo @shared.md", false),
+                ("import", "shared @CLAUDE.md @missing.md", false),
+                ("import", "", true),
+                ("project", "app", false),
+                ("local", "mine", false),
+            ]
+        );
+        assert!(memory.merged_text.ends_with("mine"));
+        assert!(memory.total_tokens > 0);
+    }
+}
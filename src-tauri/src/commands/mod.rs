@@ -7,11 +7,33 @@
 /// ## Module Structure
 /// 
 /// - `agents` - Agent management and execution commands
+/// - `automation_api` - Opt-in localhost HTTP/WebSocket API for scripts and editor plugins
+/// - `budget` - Cost, token, duration and turn caps for sessions and agent runs
 /// - `claude` - Claude Code integration and session management  
+/// - `config_history` - Version history, diffs and restores for config files Claudia writes
+/// - `file_search` - Indexed, gitignore-aware fuzzy path search and content grep
+/// - `file_watcher` - Debounced directory change events for the file browser
+/// - `hook_simulator` - Dry runs of configured hooks against synthetic events
 /// - `mcp` - Model Context Protocol server management
+/// - `memory` - Effective CLAUDE.md memory for a working directory, with imports resolved
+/// - `notifications` - Desktop notifications for finished, failed, over-budget and blocked runs
+/// - `permissions` - Permission rule management and replay of past tool calls against rule sets
+/// - `project_index` - Mapping between project paths and ~/.claude/projects directories
+/// - `prompt_queue` - Per-project queues of prompts run back to back in one session
+/// - `quick_prompt` - Global shortcut and popup window for prompting the last used project
+/// - `run_recovery` - Startup reconciliation of agent runs orphaned by a crash
+/// - `scheduler` - Cron-style schedules that start agent runs or session prompts
+/// - `session_cache` - Cached session metadata for fast, paginated session listing
+/// - `session_export` - Session transcript export as Markdown, HTML or JSON
+/// - `session_fork` - Forking a session at an arbitrary message
+/// - `session_metadata` - User-assigned session titles, tags, pins, archiving and notes
+/// - `session_search` - Full-text search across session transcripts
+/// - `settings` - Effective settings merged across scopes, and settings schema validation
 /// - `slash_commands` - Slash command discovery and management
 /// - `storage` - Database operations and data management
+/// - `tray` - Tray menu of running sessions and agent runs with a count badge
 /// - `usage` - Usage statistics and cost tracking
+/// - `worktrees` - Git worktree isolation for sessions and agent runs
 /// 
 /// ## Security
 /// 
@@ -25,3 +47,25 @@ pub mod usage;
 pub mod storage;
 pub mod slash_commands;
 pub mod proxy;
+pub mod project_index;
+pub mod prompt_queue;
+pub mod session_cache;
+pub mod session_fork;
+pub mod session_metadata;
+pub mod session_search;
+pub mod session_export;
+pub mod automation_api;
+pub mod worktrees;
+pub mod budget;
+pub mod run_recovery;
+pub mod memory;
+pub mod file_search;
+pub mod file_watcher;
+pub mod hook_simulator;
+pub mod settings;
+pub mod permissions;
+pub mod config_history;
+pub mod scheduler;
+pub mod notifications;
+pub mod quick_prompt;
+pub mod tray;
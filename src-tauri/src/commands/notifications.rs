@@ -0,0 +1,257 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_notification::NotificationExt;
+
+use super::agents::AgentDb;
+use super::budget::BudgetExceeded;
+use crate::stream_json::{ContentBlock, StreamMessage};
+
+/// Key of the settings row in `app_settings`
+const SETTINGS_KEY: &str = "notification_settings";
+
+/// Text of the tool result Claude returns when a tool call needs a
+/// permission that has not been granted
+const PERMISSION_REQUEST_PREFIX: &str = "Claude requested permissions to use ";
+
+/// Which events raise a desktop notification
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    /// A session or agent run finished successfully
+    pub on_complete: bool,
+    /// A session or agent run exited with an error
+    pub on_failure: bool,
+    /// A run was stopped for exceeding its budget
+    pub on_budget_exceeded: bool,
+    /// A run is blocked on a permission it has not been granted
+    pub on_permission_request: bool,
+    /// Skip notifications while a Claudia window has focus
+    pub suppress_when_focused: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            on_complete: true,
+            on_failure: true,
+            on_budget_exceeded: true,
+            on_permission_request: true,
+            suppress_when_focused: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NotificationEvent {
+    Complete,
+    Failure,
+    BudgetExceeded,
+    PermissionRequest,
+}
+
+impl NotificationSettings {
+    fn allows(&self, event: NotificationEvent) -> bool {
+        self.enabled
+            && match event {
+                NotificationEvent::Complete => self.on_complete,
+                NotificationEvent::Failure => self.on_failure,
+                NotificationEvent::BudgetExceeded => self.on_budget_exceeded,
+                NotificationEvent::PermissionRequest => self.on_permission_request,
+            }
+    }
+}
+
+fn load_settings(conn: &Connection) -> NotificationSettings {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![SETTINGS_KEY],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .ok()
+    .flatten()
+    .and_then(|value| serde_json::from_str(&value).ok())
+    .unwrap_or_default()
+}
+
+fn any_window_focused(app: &AppHandle) -> bool {
+    app.webview_windows()
+        .values()
+        .any(|window| window.is_focused().unwrap_or(false))
+}
+
+/// Shows a desktop notification if the settings allow it for this event
+fn notify(app: &AppHandle, event: NotificationEvent, title: &str, body: &str) {
+    let settings = match app.try_state::<AgentDb>() {
+        Some(db) => match db.0.lock() {
+            Ok(conn) => load_settings(&conn),
+            Err(_) => NotificationSettings::default(),
+        },
+        None => NotificationSettings::default(),
+    };
+    if !settings.allows(event) {
+        return;
+    }
+    if settings.suppress_when_focused && any_window_focused(app) {
+        log::debug!("Suppressing notification while focused: {}", title);
+        return;
+    }
+
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        log::warn!("Failed to show notification: {}", e);
+    }
+}
+
+/// How a Claude session is named in notifications
+pub(crate) fn session_subject(project_path: &str) -> String {
+    let name = std::path::Path::new(project_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| project_path.to_string());
+    format!("Claude session in {}", name)
+}
+
+/// How an agent run is named in notifications
+pub(crate) fn agent_subject(app: &AppHandle, run_id: i64) -> String {
+    let name = app.try_state::<AgentDb>().and_then(|db| {
+        let conn = db.0.lock().ok()?;
+        conn.query_row(
+            "SELECT agent_name FROM agent_runs WHERE id = ?1",
+            params![run_id],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+    });
+    match name {
+        Some(name) => format!("Agent '{}'", name),
+        None => format!("Agent run {}", run_id),
+    }
+}
+
+/// Notifies that a session or agent run exited. Runs stopped over budget are
+/// reported by [`notify_budget_exceeded`] instead.
+pub(crate) fn notify_finished(app: &AppHandle, subject: &str, success: bool) {
+    if success {
+        notify(
+            app,
+            NotificationEvent::Complete,
+            "Run completed",
+            &format!("{} finished", subject),
+        );
+    } else {
+        notify(
+            app,
+            NotificationEvent::Failure,
+            "Run failed",
+            &format!("{} exited with an error", subject),
+        );
+    }
+}
+
+pub(crate) fn notify_budget_exceeded(app: &AppHandle, subject: &str, violation: &BudgetExceeded) {
+    notify(
+        app,
+        NotificationEvent::BudgetExceeded,
+        "Budget exceeded",
+        &format!("{} was stopped. {}", subject, violation.message()),
+    );
+}
+
+pub(crate) fn notify_permission_request(app: &AppHandle, subject: &str, tool: &str) {
+    notify(
+        app,
+        NotificationEvent::PermissionRequest,
+        "Permission needed",
+        &format!("{} is waiting for permission to use {}", subject, tool),
+    );
+}
+
+/// Returns the tool a stream-json line reports as blocked on a permission
+/// that has not been granted
+pub(crate) fn permission_request_tool(line: &str) -> Option<String> {
+    // Cheap check first, this runs on every line of output
+    if !line.contains(PERMISSION_REQUEST_PREFIX) {
+        return None;
+    }
+    let message = StreamMessage::parse(line)?;
+    message.blocks().iter().find_map(|block| {
+        let ContentBlock::ToolResult(result) = block else {
+            return None;
+        };
+        let text = match result.content.as_ref()? {
+            Value::String(text) => text.clone(),
+            Value::Array(parts) => parts
+                .iter()
+                .filter_map(|part| part.get("text").and_then(Value::as_str))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => return None,
+        };
+        let rest = &text[text.find(PERMISSION_REQUEST_PREFIX)? + PERMISSION_REQUEST_PREFIX.len()..];
+        let tool = rest.split([',', ' ']).next()?.trim();
+        (!tool.is_empty()).then(|| tool.to_string())
+    })
+}
+
+/// Get the notification settings
+#[tauri::command]
+pub async fn get_notification_settings(
+    db: State<'_, AgentDb>,
+) -> Result<NotificationSettings, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(load_settings(&conn))
+}
+
+/// Save the notification settings
+#[tauri::command]
+pub async fn save_notification_settings(
+    db: State<'_, AgentDb>,
+    settings: NotificationSettings,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let value = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+        params![SETTINGS_KEY, value],
+    )
+    .map_err(|e| format!("Failed to save notification settings: {}", e))?;
+    Ok(())
+}
+
+/// Show a notification regardless of settings and focus, to check that the
+/// OS allows Claudia to notify
+#[tauri::command]
+pub async fn send_test_notification(app: AppHandle) -> Result<(), String> {
+    app.notification()
+        .builder()
+        .title("Claudia")
+        .body("Notifications are working")
+        .show()
+        .map_err(|e| format!("Failed to show notification: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_request_tool() {
+        let line = r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","is_error":true,"content":"Claude requested permissions to use Bash, but you haven't granted it yet."}]}}"#;
+        assert_eq!(permission_request_tool(line).as_deref(), Some("Bash"));
+
+        let line = r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"t2","content":[{"type":"text","text":"Claude requested permissions to use mcp__github__create_issue, but you haven't granted it yet."}]}]}}"#;
+        assert_eq!(
+            permission_request_tool(line).as_deref(),
+            Some("mcp__github__create_issue")
+        );
+
+        // Mentions in assistant text are not permission requests
+        let line = r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Claude requested permissions to use Bash"}]}}"#;
+        assert_eq!(permission_request_tool(line), None);
+        assert_eq!(permission_request_tool(r#"{"type":"result"}"#), None);
+    }
+}
@@ -0,0 +1,556 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::MutexGuard;
+use std::time::SystemTime;
+use tauri::State;
+
+use super::agents::AgentDb;
+
+/// How many lines of a session file to look through for a `cwd` field.
+/// The first lines are sometimes summaries that carry no working directory.
+const CWD_SCAN_LINES: usize = 20;
+
+/// Maps a directory under ~/.claude/projects to the real project path
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProjectIndexEntry {
+    /// Encoded directory name under ~/.claude/projects
+    pub project_id: String,
+    pub project_path: String,
+    /// 'cwd' when read from a session file, 'decoded' when guessed from the directory name
+    pub source: String,
+    /// Directory modification time (seconds since epoch) when the entry was resolved
+    pub dir_modified: i64,
+}
+
+pub(crate) fn projects_dir() -> Result<PathBuf, String> {
+    dirs::home_dir()
+        .map(|home| home.join(".claude").join("projects"))
+        .ok_or_else(|| "Could not find home directory".to_string())
+}
+
+/// Rejects IDs that would resolve outside the projects directory once joined
+pub(crate) fn check_id(kind: &str, id: &str) -> Result<(), String> {
+    if id.is_empty() || id.contains('/') || id.contains('\\') || id.contains("..") {
+        return Err(format!("Invalid {}: {}", kind, id));
+    }
+    Ok(())
+}
+
+/// Encodes a project path the way Claude Code names its project directories:
+/// every character other than an ASCII letter or digit becomes '-'
+pub(crate) fn encode_project_path(project_path: &str) -> String {
+    project_path
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// Best-effort reverse of the directory encoding, only used when no session
+/// file records a `cwd`. Lossy for paths containing '-', '.', '_' or spaces.
+fn decode_project_path(encoded: &str) -> String {
+    encoded.replace('-', "/")
+}
+
+fn normalize_path(project_path: &str) -> &str {
+    let trimmed = project_path.trim_end_matches(['/', '\\']);
+    if trimmed.is_empty() {
+        project_path
+    } else {
+        trimmed
+    }
+}
+
+fn modified_secs(path: &Path) -> i64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// Reads the working directory recorded in the project's session files
+fn read_project_cwd(project_dir: &Path) -> Option<String> {
+    let entries = fs::read_dir(project_dir).ok()?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let Ok(file) = fs::File::open(&path) else {
+            continue;
+        };
+        for line in BufReader::new(file)
+            .lines()
+            .take(CWD_SCAN_LINES)
+            .map_while(Result::ok)
+        {
+            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
+                if let Some(cwd) = json.get("cwd").and_then(|v| v.as_str()) {
+                    return Some(cwd.to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+fn map_entry(row: &rusqlite::Row) -> rusqlite::Result<ProjectIndexEntry> {
+    Ok(ProjectIndexEntry {
+        project_id: row.get(0)?,
+        project_path: row.get(1)?,
+        source: row.get(2)?,
+        dir_modified: row.get(3)?,
+    })
+}
+
+fn list_entries(conn: &Connection) -> Result<Vec<ProjectIndexEntry>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT project_id, project_path, source, dir_modified
+             FROM project_index ORDER BY project_path",
+        )
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map([], map_entry)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+/// A project directory read from disk, before it is written to the index
+struct ScannedDir {
+    project_id: String,
+    dir_modified: i64,
+    /// None when the indexed entry is still current
+    resolved: Option<(String, &'static str)>,
+}
+
+fn known_entries(conn: &Connection) -> Result<HashMap<String, ProjectIndexEntry>, String> {
+    Ok(list_entries(conn)?
+        .into_iter()
+        .map(|entry| (entry.project_id.clone(), entry))
+        .collect())
+}
+
+/// Lists the project directories, re-reading only those whose modification
+/// time changed or whose path was a guess. Touches only the filesystem, so it
+/// can run without the database lock.
+fn scan_projects(projects_dir: &Path, known: &HashMap<String, ProjectIndexEntry>) -> Vec<ScannedDir> {
+    let mut scanned = Vec::new();
+    let Ok(entries) = fs::read_dir(projects_dir) else {
+        return scanned;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let Some(project_id) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+
+        let dir_modified = modified_secs(&path);
+        let current = known
+            .get(project_id)
+            .is_some_and(|existing| existing.source == "cwd" && existing.dir_modified == dir_modified);
+        let resolved = if current {
+            None
+        } else {
+            Some(match read_project_cwd(&path) {
+                Some(cwd) => (cwd, "cwd"),
+                None => (decode_project_path(project_id), "decoded"),
+            })
+        };
+        scanned.push(ScannedDir {
+            project_id: project_id.to_string(),
+            dir_modified,
+            resolved,
+        });
+    }
+    scanned
+}
+
+/// Writes a scan to the index and drops directories that no longer exist
+fn apply_scan(
+    conn: &Connection,
+    known: &HashMap<String, ProjectIndexEntry>,
+    scanned: &[ScannedDir],
+) -> Result<Vec<ProjectIndexEntry>, String> {
+    for dir in scanned {
+        let Some((project_path, source)) = &dir.resolved else {
+            continue;
+        };
+        conn.execute(
+            "INSERT INTO project_index (project_id, project_path, source, dir_modified)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(project_id) DO UPDATE SET
+                project_path = excluded.project_path,
+                source = excluded.source,
+                dir_modified = excluded.dir_modified,
+                updated_at = CURRENT_TIMESTAMP",
+            params![dir.project_id, normalize_path(project_path), source, dir.dir_modified],
+        )
+        .map_err(|e| format!("Failed to update project index: {}", e))?;
+    }
+
+    for project_id in known.keys() {
+        if !scanned.iter().any(|dir| &dir.project_id == project_id) {
+            conn.execute(
+                "DELETE FROM project_index WHERE project_id = ?1",
+                params![project_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    list_entries(conn)
+}
+
+fn lock(db: &AgentDb) -> Result<MutexGuard<'_, Connection>, String> {
+    db.0.lock().map_err(|e| e.to_string())
+}
+
+/// Syncs the index with ~/.claude/projects. Directories are only re-read when
+/// their modification time changed or their path was a guess. The database
+/// lock is only held to read and write the index, not during the scan.
+pub(crate) fn refresh_project_index(db: &AgentDb) -> Result<Vec<ProjectIndexEntry>, String> {
+    refresh_project_index_in(db, &projects_dir()?)
+}
+
+pub(crate) fn refresh_project_index_in(
+    db: &AgentDb,
+    projects_dir: &Path,
+) -> Result<Vec<ProjectIndexEntry>, String> {
+    let known = {
+        let conn = lock(db)?;
+        known_entries(&conn)?
+    };
+    let scanned = scan_projects(projects_dir, &known);
+    let conn = lock(db)?;
+    apply_scan(&conn, &known, &scanned)
+}
+
+fn lookup_path(conn: &Connection, project_id: &str) -> Option<String> {
+    conn.query_row(
+        "SELECT project_path FROM project_index WHERE project_id = ?1",
+        params![project_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .ok()
+    .flatten()
+}
+
+fn lookup_dir(conn: &Connection, project_path: &str) -> Option<String> {
+    // Prefer entries resolved from a real cwd over guessed ones
+    conn.query_row(
+        "SELECT project_id FROM project_index WHERE project_path = ?1
+         ORDER BY source = 'cwd' DESC, dir_modified DESC LIMIT 1",
+        params![normalize_path(project_path)],
+        |row| row.get(0),
+    )
+    .optional()
+    .ok()
+    .flatten()
+}
+
+/// Indexed project path for an encoded project directory, if the index has it
+pub(crate) fn indexed_project_path(conn: &Connection, project_id: &str) -> Option<String> {
+    lookup_path(conn, project_id)
+}
+
+/// Real project path for an encoded project directory, as far as the index
+/// knows. Does not rescan: use `resolve_project_path` for directories that
+/// may be newer than the index.
+pub(crate) fn project_path_for_id(conn: &Connection, project_id: &str) -> String {
+    lookup_path(conn, project_id).unwrap_or_else(|| decode_project_path(project_id))
+}
+
+/// Real project path for an encoded project directory, rescanning
+/// ~/.claude/projects once, outside the lock, if the index does not have it
+pub(crate) fn resolve_project_path(db: &AgentDb, project_id: &str) -> Result<String, String> {
+    resolve_project_path_in(db, &projects_dir()?, project_id)
+}
+
+fn resolve_project_path_in(
+    db: &AgentDb,
+    projects_dir: &Path,
+    project_id: &str,
+) -> Result<String, String> {
+    let indexed = {
+        let conn = lock(db)?;
+        lookup_path(&conn, project_id)
+    };
+    if let Some(path) = indexed {
+        return Ok(path);
+    }
+    if let Err(e) = refresh_project_index_in(db, projects_dir) {
+        log::warn!("Failed to refresh project index: {}", e);
+    }
+    let conn = lock(db)?;
+    Ok(project_path_for_id(&conn, project_id))
+}
+
+/// Project directory under ~/.claude/projects for a real project path.
+/// Falls back to where Claude Code would create it for projects with no sessions yet.
+pub(crate) fn project_dir_for_path(db: &AgentDb, project_path: &str) -> Result<PathBuf, String> {
+    project_dir_in(db, &projects_dir()?, project_path)
+}
+
+fn project_dir_in(db: &AgentDb, projects_dir: &Path, project_path: &str) -> Result<PathBuf, String> {
+    let indexed = {
+        let conn = lock(db)?;
+        lookup_dir(&conn, project_path)
+    };
+    if let Some(dir) = indexed.map(|project_id| projects_dir.join(project_id)) {
+        if dir.exists() {
+            return Ok(dir);
+        }
+    }
+    if let Err(e) = refresh_project_index_in(db, projects_dir) {
+        log::warn!("Failed to refresh project index: {}", e);
+    }
+    let conn = lock(db)?;
+    let project_id = lookup_dir(&conn, project_path)
+        .unwrap_or_else(|| encode_project_path(normalize_path(project_path)));
+    Ok(projects_dir.join(project_id))
+}
+
+/// Locates a session JSONL, trying the project's own directory first and then
+/// every known project, since a session may have been started from another
+/// path. The index is rescanned at most once, and never under the lock.
+pub(crate) fn find_session_file(
+    db: &AgentDb,
+    session_id: &str,
+    project_path: Option<&str>,
+) -> Result<Option<PathBuf>, String> {
+    find_session_file_in(db, &projects_dir()?, session_id, project_path)
+}
+
+/// Like `find_session_file`, but only looks where the index already points,
+/// so callers resolving many sessions can rescan once for all of them
+pub(crate) fn find_indexed_session_file(
+    db: &AgentDb,
+    session_id: &str,
+    project_path: Option<&str>,
+) -> Result<Option<PathBuf>, String> {
+    find_indexed_in(db, &projects_dir()?, session_id, project_path)
+}
+
+fn find_session_file_in(
+    db: &AgentDb,
+    projects_dir: &Path,
+    session_id: &str,
+    project_path: Option<&str>,
+) -> Result<Option<PathBuf>, String> {
+    if let Some(found) = find_indexed_in(db, projects_dir, session_id, project_path)? {
+        return Ok(Some(found));
+    }
+    // The session may live in a directory created since the last refresh
+    refresh_project_index_in(db, projects_dir)?;
+    find_indexed_in(db, projects_dir, session_id, project_path)
+}
+
+fn find_indexed_in(
+    db: &AgentDb,
+    projects_dir: &Path,
+    session_id: &str,
+    project_path: Option<&str>,
+) -> Result<Option<PathBuf>, String> {
+    // The project's own directory goes first; the encoded path covers
+    // projects the index has not seen yet
+    let mut project_ids = Vec::new();
+    {
+        let conn = lock(db)?;
+        if let Some(project_path) = project_path {
+            project_ids.push(
+                lookup_dir(&conn, project_path)
+                    .unwrap_or_else(|| encode_project_path(normalize_path(project_path))),
+            );
+        }
+        project_ids.extend(list_entries(&conn)?.into_iter().map(|entry| entry.project_id));
+    }
+    let file_name = format!("{}.jsonl", session_id);
+    Ok(project_ids
+        .iter()
+        .map(|project_id| projects_dir.join(project_id).join(&file_name))
+        .find(|candidate| candidate.exists()))
+}
+
+/// Lists the project index after syncing it with ~/.claude/projects
+#[tauri::command]
+pub async fn get_project_index(db: State<'_, AgentDb>) -> Result<Vec<ProjectIndexEntry>, String> {
+    refresh_project_index(&db)
+}
+
+/// Clears the project index and resolves every project directory again
+#[tauri::command]
+pub async fn rebuild_project_index(db: State<'_, AgentDb>) -> Result<Vec<ProjectIndexEntry>, String> {
+    log::info!("Rebuilding project index");
+
+    lock(&db)?
+        .execute("DELETE FROM project_index", [])
+        .map_err(|e| e.to_string())?;
+    refresh_project_index(&db)
+}
+
+/// Finds the ~/.claude/projects directory used for a project path
+#[tauri::command]
+pub async fn resolve_project_dir(
+    db: State<'_, AgentDb>,
+    project_path: String,
+) -> Result<String, String> {
+    let dir = project_dir_for_path(&db, &project_path)?;
+    Ok(dir.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::create_schema;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    fn index_db() -> AgentDb {
+        let conn = Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        AgentDb(Mutex::new(conn))
+    }
+
+    /// Creates a project directory holding one session that records `cwd`
+    fn add_session(projects_dir: &Path, project_id: &str, session_id: &str, cwd: &str) -> PathBuf {
+        let dir = projects_dir.join(project_id);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(format!("{}.jsonl", session_id));
+        let line = serde_json::json!({ "type": "user", "cwd": cwd });
+        fs::write(&file, format!("{}\n", line)).unwrap();
+        file
+    }
+
+    fn indexed_ids(db: &AgentDb) -> Vec<String> {
+        list_entries(&db.0.lock().unwrap())
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.project_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_encode_project_path() {
+        let cases = [
+            ("/Users/me/project", "-Users-me-project"),
+            ("/Users/me/my.project", "-Users-me-my-project"),
+            ("/Users/me/my_project", "-Users-me-my-project"),
+            ("/Users/me/my project", "-Users-me-my-project"),
+            ("/Users/me/my-project", "-Users-me-my-project"),
+            ("/home/jürgen/café", "-home-j-rgen-caf-"),
+            ("/home/me/项目", "-home-me---"),
+            ("C:\\Users\\me\\project", "C--Users-me-project"),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(encode_project_path(path), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_project_dir_uses_recorded_cwd() {
+        let projects = TempDir::new().unwrap();
+        let db = index_db();
+        // These all encode to the same name, so only the recorded cwd tells
+        // their directories apart
+        let cwds = ["/home/me/my.project", "/home/me/my_project", "/home/me/my project", "/home/me/my-project"];
+        for (i, cwd) in cwds.iter().enumerate() {
+            add_session(projects.path(), &format!("project-{}", i), "s1", cwd);
+        }
+        add_session(projects.path(), "unicode", "s1", "/home/jürgen/café");
+
+        for (i, cwd) in cwds.iter().enumerate() {
+            let dir = project_dir_in(&db, projects.path(), cwd).unwrap();
+            assert_eq!(dir, projects.path().join(format!("project-{}", i)), "{}", cwd);
+        }
+        let dir = project_dir_in(&db, projects.path(), "/home/jürgen/café").unwrap();
+        assert_eq!(dir, projects.path().join("unicode"));
+        // A trailing separator names the same project
+        let dir = project_dir_in(&db, projects.path(), "/home/me/my.project/").unwrap();
+        assert_eq!(dir, projects.path().join("project-0"));
+    }
+
+    #[test]
+    fn test_project_dir_falls_back_to_encoded_path() {
+        let projects = TempDir::new().unwrap();
+        let db = index_db();
+        let dir = project_dir_in(&db, projects.path(), "/home/me/new.project").unwrap();
+        assert_eq!(dir, projects.path().join("-home-me-new-project"));
+        assert!(indexed_ids(&db).is_empty());
+    }
+
+    #[test]
+    fn test_find_session_file_rescans_on_cache_miss() {
+        let projects = TempDir::new().unwrap();
+        let db = index_db();
+        let first = add_session(projects.path(), "-home-me-a", "s1", "/home/me/a");
+        assert_eq!(
+            find_session_file_in(&db, projects.path(), "s1", None).unwrap(),
+            Some(first)
+        );
+        assert_eq!(indexed_ids(&db), ["-home-me-a"]);
+
+        // A project created after the index was filled is found by the rescan
+        let second = add_session(projects.path(), "-home-me-b", "s2", "/home/me/b");
+        assert_eq!(
+            find_session_file_in(&db, projects.path(), "s2", None).unwrap(),
+            Some(second)
+        );
+        let mut ids = indexed_ids(&db);
+        ids.sort();
+        assert_eq!(ids, ["-home-me-a", "-home-me-b"]);
+
+        assert_eq!(find_session_file_in(&db, projects.path(), "missing", None).unwrap(), None);
+
+        // Lookups that must not rescan miss new projects until the next refresh
+        let third = add_session(projects.path(), "-home-me-c", "s3", "/home/me/c");
+        assert_eq!(find_indexed_in(&db, projects.path(), "s3", None).unwrap(), None);
+        assert_eq!(
+            find_indexed_in(&db, projects.path(), "s3", Some("/home/me/c")).unwrap(),
+            Some(third)
+        );
+    }
+
+    #[test]
+    fn test_resolve_project_path_rescans_on_cache_miss() {
+        let projects = TempDir::new().unwrap();
+        let db = index_db();
+        add_session(projects.path(), "-home-me-a", "s1", "/home/me/a.b");
+        assert_eq!(project_path_for_id(&db.0.lock().unwrap(), "-home-me-a"), "/home/me/a");
+
+        assert_eq!(
+            resolve_project_path_in(&db, projects.path(), "-home-me-a").unwrap(),
+            "/home/me/a.b"
+        );
+        assert_eq!(project_path_for_id(&db.0.lock().unwrap(), "-home-me-a"), "/home/me/a.b");
+        // Unknown directories fall back to the decoded name
+        assert_eq!(
+            resolve_project_path_in(&db, projects.path(), "-home-me-c").unwrap(),
+            "/home/me/c"
+        );
+    }
+
+    #[test]
+    fn test_find_session_file_searches_other_projects() {
+        let projects = TempDir::new().unwrap();
+        let db = index_db();
+        add_session(projects.path(), "-home-me-a", "s1", "/home/me/a");
+        // Started from a subdirectory, so it lives under another project
+        let moved = add_session(projects.path(), "-home-me-a-sub", "s2", "/home/me/a/sub");
+        assert_eq!(
+            find_session_file_in(&db, projects.path(), "s2", Some("/home/me/a")).unwrap(),
+            Some(moved)
+        );
+    }
+}
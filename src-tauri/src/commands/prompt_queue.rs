@@ -0,0 +1,784 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use super::agents::AgentDb;
+use super::claude::{start_claude_session, SessionChannels, SessionMode, SessionOutcome};
+
+/// Event emitted whenever a queue or one of its items changes state
+const QUEUE_EVENT: &str = "prompt-queue-progress";
+
+/// A prompt waiting (or done waiting) in a project's queue
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PromptQueueItem {
+    pub id: i64,
+    pub project_path: String,
+    pub prompt: String,
+    pub model: String,
+    pub position: i64,
+    pub status: String, // 'pending', 'running', 'completed', 'failed'
+    pub session_id: Option<String>,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub completed_at: Option<String>,
+}
+
+/// The queue for a single project, including all of its items in order
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PromptQueue {
+    pub project_path: String,
+    pub paused: bool,
+    /// Session the next item will resume (set after the first item completes)
+    pub session_id: Option<String>,
+    /// Whether a runner is currently working through the queue
+    pub running: bool,
+    pub items: Vec<PromptQueueItem>,
+}
+
+/// Payload of the `prompt-queue-progress` event
+#[derive(Debug, Serialize, Clone)]
+pub struct PromptQueueEvent {
+    pub project_path: String,
+    /// 'running', 'paused' or 'idle'
+    pub queue_status: String,
+    /// The item whose status changed, if any
+    pub item: Option<PromptQueueItem>,
+}
+
+/// Tracks which project queues currently have a runner task
+#[derive(Default)]
+pub struct PromptQueueState {
+    active: Mutex<HashSet<String>>,
+}
+
+impl PromptQueueState {
+    fn is_active(&self, project_path: &str) -> bool {
+        self.active
+            .lock()
+            .map(|active| active.contains(project_path))
+            .unwrap_or(false)
+    }
+}
+
+fn map_item(row: &rusqlite::Row) -> rusqlite::Result<PromptQueueItem> {
+    Ok(PromptQueueItem {
+        id: row.get(0)?,
+        project_path: row.get(1)?,
+        prompt: row.get(2)?,
+        model: row.get(3)?,
+        position: row.get(4)?,
+        status: row.get(5)?,
+        session_id: row.get(6)?,
+        error: row.get(7)?,
+        created_at: row.get(8)?,
+        started_at: row.get(9)?,
+        completed_at: row.get(10)?,
+    })
+}
+
+const ITEM_COLUMNS: &str = "id, project_path, prompt, model, position, status, session_id, error, created_at, started_at, completed_at";
+
+fn get_item(conn: &Connection, id: i64) -> Result<PromptQueueItem, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM prompt_queue_items WHERE id = ?1", ITEM_COLUMNS),
+        params![id],
+        map_item,
+    )
+    .map_err(|e| format!("Queue item {} not found: {}", id, e))
+}
+
+fn ensure_queue(conn: &Connection, project_path: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR IGNORE INTO prompt_queues (project_path) VALUES (?1)",
+        params![project_path],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Returns `(paused, session_id)` for a project's queue
+fn queue_settings(conn: &Connection, project_path: &str) -> Result<(bool, Option<String>), String> {
+    Ok(conn
+        .query_row(
+            "SELECT paused, session_id FROM prompt_queues WHERE project_path = ?1",
+            params![project_path],
+            |row| Ok((row.get::<_, bool>(0)?, row.get::<_, Option<String>>(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .unwrap_or((false, None)))
+}
+
+fn set_paused(conn: &Connection, project_path: &str, paused: bool) -> Result<(), String> {
+    ensure_queue(conn, project_path)?;
+    conn.execute(
+        "UPDATE prompt_queues SET paused = ?1, updated_at = CURRENT_TIMESTAMP WHERE project_path = ?2",
+        params![paused, project_path],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Picks the next pending item unless the queue is paused
+fn next_pending_item(
+    conn: &Connection,
+    project_path: &str,
+) -> Result<Option<(PromptQueueItem, Option<String>)>, String> {
+    let (paused, session_id) = queue_settings(conn, project_path)?;
+    if paused {
+        return Ok(None);
+    }
+
+    let item = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM prompt_queue_items WHERE project_path = ?1 AND status = 'pending'
+                 ORDER BY position ASC, id ASC LIMIT 1",
+                ITEM_COLUMNS
+            ),
+            params![project_path],
+            map_item,
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    Ok(item.map(|item| (item, session_id)))
+}
+
+fn emit_queue_event(app: &AppHandle, project_path: &str, queue_status: &str, item: Option<PromptQueueItem>) {
+    let _ = app.emit(
+        QUEUE_EVENT,
+        PromptQueueEvent {
+            project_path: project_path.to_string(),
+            queue_status: queue_status.to_string(),
+            item,
+        },
+    );
+}
+
+/// Marks an item as running and returns the updated row
+fn mark_running(conn: &Connection, id: i64) -> Result<PromptQueueItem, String> {
+    conn.execute(
+        "UPDATE prompt_queue_items SET status = 'running', error = NULL, started_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        params![id],
+    )
+    .map_err(|e| e.to_string())?;
+    get_item(conn, id)
+}
+
+/// Marks an item as finished and returns the updated row. A failure pauses the
+/// queue; a success makes the queue resume the item's session next.
+fn record_outcome(
+    conn: &Connection,
+    item_id: i64,
+    project_path: &str,
+    session_id: Option<&str>,
+    error: Option<&str>,
+) -> Result<PromptQueueItem, String> {
+    let status = if error.is_some() { "failed" } else { "completed" };
+
+    conn.execute(
+        "UPDATE prompt_queue_items SET status = ?1, session_id = COALESCE(?2, session_id), error = ?3,
+         completed_at = CURRENT_TIMESTAMP WHERE id = ?4",
+        params![status, session_id, error, item_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    if error.is_some() {
+        // Stop on the first failure so later prompts don't run against a broken state
+        set_paused(conn, project_path, true)?;
+    } else if let Some(session_id) = session_id {
+        conn.execute(
+            "UPDATE prompt_queues SET session_id = ?1, updated_at = CURRENT_TIMESTAMP WHERE project_path = ?2",
+            params![session_id, project_path],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    get_item(conn, item_id)
+}
+
+/// Marks an item as finished and returns the updated row
+fn finish_item(
+    app: &AppHandle,
+    item_id: i64,
+    project_path: &str,
+    session_id: Option<&str>,
+    error: Option<&str>,
+) -> Result<PromptQueueItem, String> {
+    let db = app.state::<AgentDb>();
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    record_outcome(&conn, item_id, project_path, session_id, error)
+}
+
+/// Appends a prompt to a project's queue. Returns the new item and whether
+/// the queue is paused.
+fn enqueue(conn: &Connection, project_path: &str, prompt: &str, model: &str) -> Result<(PromptQueueItem, bool), String> {
+    ensure_queue(conn, project_path)?;
+
+    let position: i64 = conn
+        .query_row(
+            "SELECT COALESCE(MAX(position), -1) + 1 FROM prompt_queue_items WHERE project_path = ?1",
+            params![project_path],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "INSERT INTO prompt_queue_items (project_path, prompt, model, position) VALUES (?1, ?2, ?3, ?4)",
+        params![project_path, prompt, model, position],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let item = get_item(conn, conn.last_insert_rowid())?;
+    let (paused, _) = queue_settings(conn, project_path)?;
+    Ok((item, paused))
+}
+
+/// Puts a failed item back in line; other items are left alone
+fn retry_item(conn: &Connection, id: i64) -> Result<PromptQueueItem, String> {
+    conn.execute(
+        "UPDATE prompt_queue_items SET status = 'pending', error = NULL, started_at = NULL, completed_at = NULL
+         WHERE id = ?1 AND status = 'failed'",
+        params![id],
+    )
+    .map_err(|e| e.to_string())?;
+    get_item(conn, id)
+}
+
+/// Renumbers a project's items: `item_ids` first, in that order, then the
+/// rest in their current order
+fn reorder_items(conn: &mut Connection, project_path: &str, item_ids: &[i64]) -> Result<(), String> {
+    let current: Vec<i64> = {
+        let mut stmt = conn
+            .prepare("SELECT id FROM prompt_queue_items WHERE project_path = ?1 ORDER BY position ASC, id ASC")
+            .map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map(params![project_path], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        ids
+    };
+
+    if let Some(unknown) = item_ids.iter().find(|id| !current.contains(id)) {
+        return Err(format!("Queue item {} does not belong to this project", unknown));
+    }
+
+    let mut seen = HashSet::new();
+    let mut order: Vec<i64> = item_ids.iter().copied().filter(|id| seen.insert(*id)).collect();
+    order.extend(current.into_iter().filter(|id| !seen.contains(id)));
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for (position, id) in order.iter().enumerate() {
+        tx.execute(
+            "UPDATE prompt_queue_items SET position = ?1 WHERE id = ?2",
+            params![position as i64, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Puts items left running by a previous app instance back in line and
+/// pauses their queues until the user resumes them
+fn requeue_running_items(conn: &Connection) -> Result<usize, String> {
+    conn.execute(
+        "UPDATE prompt_queues SET paused = 1 WHERE project_path IN
+         (SELECT project_path FROM prompt_queue_items WHERE status = 'running')",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE prompt_queue_items SET status = 'pending', started_at = NULL WHERE status = 'running'",
+        [],
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Projects whose queue is not paused and still has pending items
+fn runnable_queues(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT DISTINCT project_path FROM prompt_queue_items WHERE status = 'pending'
+             AND project_path NOT IN (SELECT project_path FROM prompt_queues WHERE paused = 1)
+             ORDER BY project_path",
+        )
+        .map_err(|e| e.to_string())?;
+    let projects = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string());
+    projects
+}
+
+/// Recovers queue items that never reported completion because the app
+/// exited while they ran, then starts a runner for every queue that can
+/// keep going. Queues with an interrupted item stay paused until the user
+/// resumes them. Only the app runs queues, so this must not happen when the
+/// CLI opens the database.
+pub fn interrupt_stale_items(app: &AppHandle) {
+    let result = match app.state::<AgentDb>().0.lock() {
+        Ok(conn) => requeue_running_items(&conn)
+            .and_then(|count| Ok((count, runnable_queues(&conn)?))),
+        Err(e) => Err(e.to_string()),
+    };
+    match result {
+        Ok((count, runnable)) => {
+            if count > 0 {
+                log::info!("Re-queued {} interrupted prompt queue items", count);
+            }
+            for project_path in runnable {
+                spawn_queue_runner(app, project_path);
+            }
+        }
+        Err(e) => log::error!("Failed to reset interrupted prompt queue items: {}", e),
+    }
+}
+
+/// Starts a runner for the project's queue unless one is already active
+fn spawn_queue_runner(app: &AppHandle, project_path: String) {
+    {
+        let state = app.state::<PromptQueueState>();
+        let mut active = match state.active.lock() {
+            Ok(active) => active,
+            Err(e) => {
+                log::error!("Failed to lock prompt queue state: {}", e);
+                return;
+            }
+        };
+        if !active.insert(project_path.clone()) {
+            return;
+        }
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        log::info!("Prompt queue runner started for {}", project_path);
+        run_queue(&app, &project_path).await;
+        log::info!("Prompt queue runner stopped for {}", project_path);
+    });
+}
+
+/// Works through pending items one at a time, resuming the same session each time
+async fn run_queue(app: &AppHandle, project_path: &str) {
+    loop {
+        let next = {
+            let state = app.state::<PromptQueueState>();
+            let db = app.state::<AgentDb>();
+            // Hold the active set while deciding to stop so an item added in the
+            // meantime is either seen here or starts a fresh runner
+            let mut active = match state.active.lock() {
+                Ok(active) => active,
+                Err(e) => {
+                    log::error!("Failed to lock prompt queue state: {}", e);
+                    return;
+                }
+            };
+            let next = match db.0.lock() {
+                Ok(conn) => next_pending_item(&conn, project_path),
+                Err(e) => Err(e.to_string()),
+            };
+            match next {
+                Ok(Some(next)) => next,
+                Ok(None) => {
+                    active.remove(project_path);
+                    let paused = db
+                        .0
+                        .lock()
+                        .ok()
+                        .and_then(|conn| queue_settings(&conn, project_path).ok())
+                        .map(|(paused, _)| paused)
+                        .unwrap_or(false);
+                    drop(active);
+                    emit_queue_event(app, project_path, if paused { "paused" } else { "idle" }, None);
+                    return;
+                }
+                Err(e) => {
+                    log::error!("Failed to read prompt queue for {}: {}", project_path, e);
+                    active.remove(project_path);
+                    return;
+                }
+            }
+        };
+        let (item, session_id) = next;
+
+        let running_item = {
+            let db = app.state::<AgentDb>();
+            let conn = match db.0.lock() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    log::error!("Failed to lock database: {}", e);
+                    if let Ok(mut active) = app.state::<PromptQueueState>().active.lock() {
+                        active.remove(project_path);
+                    }
+                    return;
+                }
+            };
+            mark_running(&conn, item.id).unwrap_or(item.clone())
+        };
+        emit_queue_event(app, project_path, "running", Some(running_item));
+
+        let mode = match session_id {
+            Some(session_id) => SessionMode::Resume(session_id),
+            None => SessionMode::New,
+        };
+        log::info!("Running queued prompt {} for {} ({:?})", item.id, project_path, mode);
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let result = match start_claude_session(
+            app.clone(),
+            project_path.to_string(),
+            item.prompt.clone(),
+            item.model.clone(),
+            mode,
+            None,
+            SessionChannels {
+                completion: Some(tx),
+                ..Default::default()
+            },
+        )
+        .await
+        {
+            Ok(()) => match rx.await {
+                Ok(outcome) if outcome.success => Ok(outcome.session_id),
+                Ok(SessionOutcome { session_id, budget_exceeded: Some(violation), .. }) => {
+                    Err((session_id, violation.message()))
+                }
+                Ok(outcome) => Err((outcome.session_id, "Claude exited with an error".to_string())),
+                Err(_) => Err((None, "Session ended without reporting completion".to_string())),
+            },
+            Err(e) => Err((None, e)),
+        };
+
+        let finished = match &result {
+            Ok(session_id) => finish_item(app, item.id, project_path, session_id.as_deref(), None),
+            Err((session_id, error)) => {
+                log::warn!("Queued prompt {} failed: {}", item.id, error);
+                finish_item(app, item.id, project_path, session_id.as_deref(), Some(error))
+            }
+        };
+        match finished {
+            Ok(finished) => {
+                let queue_status = if result.is_ok() { "running" } else { "paused" };
+                emit_queue_event(app, project_path, queue_status, Some(finished));
+            }
+            Err(e) => log::error!("Failed to record queue item {}: {}", item.id, e),
+        }
+    }
+}
+
+/// Get the queue for a project with all of its items
+#[tauri::command]
+pub async fn prompt_queue_list(
+    db: State<'_, AgentDb>,
+    queue_state: State<'_, PromptQueueState>,
+    project_path: String,
+) -> Result<PromptQueue, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let (paused, session_id) = queue_settings(&conn, &project_path)?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM prompt_queue_items WHERE project_path = ?1 ORDER BY position ASC, id ASC",
+            ITEM_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let items = stmt
+        .query_map(params![project_path], map_item)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(PromptQueue {
+        running: queue_state.is_active(&project_path),
+        project_path,
+        paused,
+        session_id,
+        items,
+    })
+}
+
+/// Add a prompt to the end of a project's queue, starting the queue if it is idle
+#[tauri::command]
+pub async fn prompt_queue_add(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    project_path: String,
+    prompt: String,
+    model: String,
+) -> Result<PromptQueueItem, String> {
+    if prompt.trim().is_empty() {
+        return Err("Prompt cannot be empty".to_string());
+    }
+
+    let (item, paused) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        enqueue(&conn, &project_path, &prompt, &model)?
+    };
+
+    log::info!("Queued prompt {} for {}", item.id, project_path);
+
+    if !paused {
+        spawn_queue_runner(&app, project_path);
+    }
+
+    Ok(item)
+}
+
+/// Remove an item from its queue. Running items cannot be removed.
+#[tauri::command]
+pub async fn prompt_queue_remove(db: State<'_, AgentDb>, id: i64) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let item = get_item(&conn, id)?;
+
+    if item.status == "running" {
+        return Err("Cannot remove a prompt that is currently running".to_string());
+    }
+
+    conn.execute("DELETE FROM prompt_queue_items WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Reorder a project's queue. `item_ids` lists items in their new order; items
+/// not mentioned keep their relative order after the listed ones.
+#[tauri::command]
+pub async fn prompt_queue_reorder(
+    db: State<'_, AgentDb>,
+    project_path: String,
+    item_ids: Vec<i64>,
+) -> Result<(), String> {
+    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+    reorder_items(&mut conn, &project_path, &item_ids)
+}
+
+/// Pause a project's queue. The prompt currently running is allowed to finish.
+#[tauri::command]
+pub async fn prompt_queue_pause(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    project_path: String,
+) -> Result<(), String> {
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        set_paused(&conn, &project_path, true)?;
+    }
+    log::info!("Paused prompt queue for {}", project_path);
+    emit_queue_event(&app, &project_path, "paused", None);
+    Ok(())
+}
+
+/// Resume a paused queue and start working through pending prompts
+#[tauri::command]
+pub async fn prompt_queue_resume(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    project_path: String,
+) -> Result<(), String> {
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        set_paused(&conn, &project_path, false)?;
+    }
+    log::info!("Resumed prompt queue for {}", project_path);
+    spawn_queue_runner(&app, project_path);
+    Ok(())
+}
+
+/// Put a failed item back in line so it runs again when the queue resumes
+#[tauri::command]
+pub async fn prompt_queue_retry(db: State<'_, AgentDb>, id: i64) -> Result<PromptQueueItem, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    retry_item(&conn, id)
+}
+
+/// Attach the queue to an existing session (or detach with `None`) so the next
+/// item resumes it instead of starting a new conversation
+#[tauri::command]
+pub async fn prompt_queue_set_session(
+    db: State<'_, AgentDb>,
+    project_path: String,
+    session_id: Option<String>,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    ensure_queue(&conn, &project_path)?;
+    conn.execute(
+        "UPDATE prompt_queues SET session_id = ?1, updated_at = CURRENT_TIMESTAMP WHERE project_path = ?2",
+        params![session_id, project_path],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Delete completed and failed items from a project's queue
+#[tauri::command]
+pub async fn prompt_queue_clear_finished(
+    db: State<'_, AgentDb>,
+    project_path: String,
+) -> Result<usize, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM prompt_queue_items WHERE project_path = ?1 AND status IN ('completed', 'failed')",
+        params![project_path],
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::create_schema;
+
+    const PROJECT: &str = "/tmp/project";
+
+    fn queue_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        conn
+    }
+
+    fn add(conn: &Connection, project_path: &str, prompt: &str) -> i64 {
+        enqueue(conn, project_path, prompt, "sonnet").unwrap().0.id
+    }
+
+    fn next_id(conn: &Connection) -> Option<i64> {
+        next_pending_item(conn, PROJECT).unwrap().map(|(item, _)| item.id)
+    }
+
+    fn order(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT prompt FROM prompt_queue_items WHERE project_path = ?1 ORDER BY position, id")
+            .unwrap();
+        stmt.query_map(params![PROJECT], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_pause_holds_pending_items() {
+        let conn = queue_db();
+        let first = add(&conn, PROJECT, "first");
+        add(&conn, PROJECT, "second");
+        assert_eq!(next_id(&conn), Some(first));
+
+        set_paused(&conn, PROJECT, true).unwrap();
+        assert_eq!(next_id(&conn), None);
+        // Items added to a paused queue report it, so no runner is started
+        let (_, paused) = enqueue(&conn, PROJECT, "third", "sonnet").unwrap();
+        assert!(paused);
+
+        set_paused(&conn, PROJECT, false).unwrap();
+        assert_eq!(next_id(&conn), Some(first));
+    }
+
+    #[test]
+    fn test_success_resumes_session_and_failure_pauses() {
+        let conn = queue_db();
+        let first = add(&conn, PROJECT, "first");
+        let second = add(&conn, PROJECT, "second");
+
+        assert_eq!(mark_running(&conn, first).unwrap().status, "running");
+        assert_eq!(next_id(&conn), Some(second));
+        let done = record_outcome(&conn, first, PROJECT, Some("session-1"), None).unwrap();
+        assert_eq!(done.status, "completed");
+        assert!(done.completed_at.is_some());
+        // The next item resumes the session the first one started
+        let (item, session_id) = next_pending_item(&conn, PROJECT).unwrap().unwrap();
+        assert_eq!((item.id, session_id.as_deref()), (second, Some("session-1")));
+
+        mark_running(&conn, second).unwrap();
+        let failed = record_outcome(&conn, second, PROJECT, None, Some("Claude exited with an error")).unwrap();
+        assert_eq!(failed.status, "failed");
+        assert_eq!(failed.error.as_deref(), Some("Claude exited with an error"));
+        assert_eq!(queue_settings(&conn, PROJECT).unwrap(), (true, Some("session-1".to_string())));
+    }
+
+    #[test]
+    fn test_retry_requeues_only_failed_items() {
+        let conn = queue_db();
+        let first = add(&conn, PROJECT, "first");
+        let second = add(&conn, PROJECT, "second");
+        mark_running(&conn, first).unwrap();
+        record_outcome(&conn, first, PROJECT, Some("session-1"), None).unwrap();
+        mark_running(&conn, second).unwrap();
+        record_outcome(&conn, second, PROJECT, None, Some("boom")).unwrap();
+
+        let retried = retry_item(&conn, second).unwrap();
+        assert_eq!(retried.status, "pending");
+        assert_eq!(retried.error, None);
+        assert_eq!(retried.started_at, None);
+        assert_eq!(retried.completed_at, None);
+        // The queue stays paused until the user resumes it
+        assert_eq!(next_id(&conn), None);
+        set_paused(&conn, PROJECT, false).unwrap();
+        assert_eq!(next_id(&conn), Some(second));
+
+        assert_eq!(retry_item(&conn, first).unwrap().status, "completed");
+        assert!(retry_item(&conn, 999).is_err());
+    }
+
+    #[test]
+    fn test_reorder() {
+        let mut conn = queue_db();
+        let a = add(&conn, PROJECT, "a");
+        let b = add(&conn, PROJECT, "b");
+        let c = add(&conn, PROJECT, "c");
+        let other = add(&conn, "/tmp/other", "other");
+
+        // Unlisted items keep their relative order after the listed ones
+        reorder_items(&mut conn, PROJECT, &[c, a, c]).unwrap();
+        assert_eq!(order(&conn), ["c", "a", "b"]);
+        assert_eq!(next_id(&conn), Some(c));
+        reorder_items(&mut conn, PROJECT, &[b]).unwrap();
+        assert_eq!(order(&conn), ["b", "c", "a"]);
+
+        assert!(reorder_items(&mut conn, PROJECT, &[a, other]).is_err());
+        assert_eq!(order(&conn), ["b", "c", "a"]);
+        // New items go to the end
+        add(&conn, PROJECT, "d");
+        assert_eq!(order(&conn), ["b", "c", "a", "d"]);
+    }
+
+    #[test]
+    fn test_requeue_running_items_pauses_their_queues() {
+        let conn = queue_db();
+        let first = add(&conn, PROJECT, "first");
+        add(&conn, PROJECT, "second");
+        let other = add(&conn, "/tmp/other", "other");
+        mark_running(&conn, first).unwrap();
+
+        assert_eq!(requeue_running_items(&conn).unwrap(), 1);
+        let item = get_item(&conn, first).unwrap();
+        assert_eq!(item.status, "pending");
+        assert_eq!(item.started_at, None);
+        assert_eq!(next_id(&conn), None);
+        // Queues without an interrupted item keep going
+        assert_eq!(
+            next_pending_item(&conn, "/tmp/other").unwrap().map(|(item, _)| item.id),
+            Some(other)
+        );
+        assert_eq!(requeue_running_items(&conn).unwrap(), 0);
+        // Only the queue that was not interrupted gets a runner at startup
+        assert_eq!(runnable_queues(&conn).unwrap(), ["/tmp/other"]);
+    }
+
+    #[test]
+    fn test_runnable_queues_skip_paused_and_finished() {
+        let conn = queue_db();
+        assert!(runnable_queues(&conn).unwrap().is_empty());
+        let done = add(&conn, "/tmp/done", "done");
+        mark_running(&conn, done).unwrap();
+        record_outcome(&conn, done, "/tmp/done", Some("session-1"), None).unwrap();
+        add(&conn, "/tmp/paused", "held");
+        set_paused(&conn, "/tmp/paused", true).unwrap();
+        add(&conn, PROJECT, "first");
+        add(&conn, PROJECT, "second");
+
+        assert_eq!(runnable_queues(&conn).unwrap(), [PROJECT]);
+    }
+}
@@ -0,0 +1,293 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State, WebviewUrl, WebviewWindowBuilder, WindowEvent};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+
+use super::agents::AgentDb;
+use super::claude::{start_claude_session, SessionChannels, SessionMode};
+use super::tray::refresh_tray;
+
+/// Label of the quick-prompt window. `main.tsx` renders `QuickPrompt` instead
+/// of the app when loaded with `?window=quick-prompt`.
+pub const WINDOW_LABEL: &str = "quick-prompt";
+const WINDOW_URL: &str = "index.html?window=quick-prompt";
+
+const SETTINGS_KEY: &str = "quick_prompt_settings";
+const LAST_PROJECT_KEY: &str = "last_project_path";
+const DEFAULT_SHORTCUT: &str = "CommandOrControl+Shift+Space";
+const DEFAULT_MODEL: &str = "sonnet";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct QuickPromptSettings {
+    /// Off until the user opts in, so no system-wide shortcut is taken
+    /// without asking
+    pub enabled: bool,
+    /// Accelerator such as `CommandOrControl+Shift+Space`
+    pub shortcut: String,
+}
+
+impl Default for QuickPromptSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            shortcut: DEFAULT_SHORTCUT.to_string(),
+        }
+    }
+}
+
+/// Emitted to the main window when a quick prompt starts a session
+#[derive(Debug, Serialize, Clone)]
+pub struct QuickPromptSubmitted {
+    pub project_path: String,
+    pub prompt: String,
+    pub model: String,
+}
+
+fn load_settings(conn: &Connection) -> QuickPromptSettings {
+    read_setting(conn, SETTINGS_KEY)
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default()
+}
+
+fn read_setting(conn: &Connection, key: &str) -> Option<String> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![key],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .ok()
+    .flatten()
+}
+
+fn write_setting(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )
+    .map_err(|e| format!("Failed to save {}: {}", key, e))?;
+    Ok(())
+}
+
+fn parse_shortcut(shortcut: &str) -> Result<Shortcut, String> {
+    shortcut
+        .parse::<Shortcut>()
+        .map_err(|e| format!("Invalid shortcut '{}': {}", shortcut, e))
+}
+
+/// Remembers the project of a session started from the main window, so the
+/// quick prompt can target it
+pub(crate) fn remember_last_project(app: &AppHandle, project_path: &str) {
+    let db = app.state::<AgentDb>();
+    let result = match db.0.lock() {
+        Ok(conn) => write_setting(&conn, LAST_PROJECT_KEY, project_path),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to remember last project: {}", e);
+    }
+}
+
+fn last_project(conn: &Connection) -> Option<String> {
+    read_setting(conn, LAST_PROJECT_KEY).filter(|path| std::path::Path::new(path).is_dir())
+}
+
+/// Whether the user turned the quick prompt on
+pub(crate) fn quick_prompt_enabled(app: &AppHandle) -> bool {
+    let db = app.state::<AgentDb>();
+    db.0.lock()
+        .map(|conn| load_settings(&conn).enabled)
+        .unwrap_or(false)
+}
+
+/// Handler of the global-shortcut plugin. The quick prompt is the only
+/// shortcut registered, so any press opens it.
+pub fn handle_shortcut(app: &AppHandle, _shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state() != ShortcutState::Pressed {
+        return;
+    }
+    if let Err(e) = show_quick_prompt(app) {
+        log::error!("Failed to open quick prompt: {}", e);
+    }
+}
+
+/// Registers the saved shortcut at startup
+pub fn register_saved_shortcut(app: &AppHandle) {
+    let settings = {
+        let db = app.state::<AgentDb>();
+        let conn = match db.0.lock() {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::error!("Failed to load quick prompt settings: {}", e);
+                return;
+            }
+        };
+        load_settings(&conn)
+    };
+    if !settings.enabled {
+        return;
+    }
+    let result = parse_shortcut(&settings.shortcut).and_then(|shortcut| {
+        app.global_shortcut()
+            .register(shortcut)
+            .map_err(|e| e.to_string())
+    });
+    match result {
+        Ok(()) => log::info!("Registered quick prompt shortcut {}", settings.shortcut),
+        Err(e) => log::warn!(
+            "Failed to register quick prompt shortcut {}: {}",
+            settings.shortcut,
+            e
+        ),
+    }
+}
+
+/// Shows the quick-prompt window, creating it on first use
+pub(crate) fn show_quick_prompt(app: &AppHandle) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window(WINDOW_LABEL) {
+        window.center().map_err(|e| e.to_string())?;
+        window.show().map_err(|e| e.to_string())?;
+        window.set_focus().map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+
+    let window = WebviewWindowBuilder::new(app, WINDOW_LABEL, WebviewUrl::App(WINDOW_URL.into()))
+        .title("Quick Prompt")
+        .inner_size(640.0, 180.0)
+        .resizable(false)
+        .decorations(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .center()
+        .focused(true)
+        .build()
+        .map_err(|e| format!("Failed to create quick prompt window: {}", e))?;
+
+    // Behave like a popup: hide instead of lingering behind other windows
+    let popup = window.clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::Focused(false) = event {
+            let _ = popup.hide();
+        }
+    });
+    Ok(())
+}
+
+/// Brings the main window to the front
+pub(crate) fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Get the quick prompt settings
+#[tauri::command]
+pub async fn get_quick_prompt_settings(
+    db: State<'_, AgentDb>,
+) -> Result<QuickPromptSettings, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(load_settings(&conn))
+}
+
+/// Save the quick prompt settings and re-register the global shortcut. If the
+/// new shortcut cannot be registered (e.g. another app owns it) the previous
+/// one stays active and nothing is saved.
+#[tauri::command]
+pub async fn save_quick_prompt_settings(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    settings: QuickPromptSettings,
+) -> Result<QuickPromptSettings, String> {
+    let new_shortcut = parse_shortcut(&settings.shortcut)?;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let previous = load_settings(&conn);
+    let previous_shortcut = parse_shortcut(&previous.shortcut).ok();
+
+    let shortcuts = app.global_shortcut();
+    if let Some(shortcut) = previous_shortcut.filter(|s| shortcuts.is_registered(*s)) {
+        shortcuts.unregister(shortcut).map_err(|e| e.to_string())?;
+    }
+    if settings.enabled {
+        if let Err(e) = shortcuts.register(new_shortcut) {
+            if let Some(shortcut) = previous_shortcut.filter(|_| previous.enabled) {
+                let _ = shortcuts.register(shortcut);
+            }
+            return Err(format!(
+                "Failed to register shortcut '{}': {}",
+                settings.shortcut, e
+            ));
+        }
+    }
+
+    let value = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
+    write_setting(&conn, SETTINGS_KEY, &value)?;
+    drop(conn);
+    // The tray only offers the quick prompt while it is enabled
+    refresh_tray(&app);
+    Ok(settings)
+}
+
+/// Open the quick prompt window
+#[tauri::command]
+pub async fn open_quick_prompt(app: AppHandle) -> Result<(), String> {
+    show_quick_prompt(&app)
+}
+
+/// The project a quick prompt runs in: the last one a session was started in
+#[tauri::command]
+pub async fn get_quick_prompt_target(db: State<'_, AgentDb>) -> Result<Option<String>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(last_project(&conn))
+}
+
+/// Start a new session from the quick prompt window, then hand over to the
+/// main window, which receives `quick-prompt-submitted` and the usual
+/// `claude-output` events
+#[tauri::command]
+pub async fn submit_quick_prompt(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    prompt: String,
+    model: Option<String>,
+    project_path: Option<String>,
+) -> Result<QuickPromptSubmitted, String> {
+    if prompt.trim().is_empty() {
+        return Err("Prompt is empty".to_string());
+    }
+    let project_path = match project_path {
+        Some(path) => path,
+        None => {
+            let conn = db.0.lock().map_err(|e| e.to_string())?;
+            last_project(&conn).ok_or("No recent project to run the prompt in")?
+        }
+    };
+    let model = model.unwrap_or_else(|| DEFAULT_MODEL.to_string());
+    log::info!("Starting quick prompt session in {}", project_path);
+
+    if let Some(window) = app.get_webview_window(WINDOW_LABEL) {
+        let _ = window.hide();
+    }
+    remember_last_project(&app, &project_path);
+    start_claude_session(
+        app.clone(),
+        project_path.clone(),
+        prompt.clone(),
+        model.clone(),
+        SessionMode::New,
+        None,
+        SessionChannels::default(),
+    )
+    .await?;
+
+    let submitted = QuickPromptSubmitted {
+        project_path,
+        prompt,
+        model,
+    };
+    show_main_window(&app);
+    let _ = app.emit("quick-prompt-submitted", &submitted);
+    Ok(submitted)
+}
@@ -0,0 +1,560 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use super::agents::AgentDb;
+use super::project_index::find_session_file;
+use crate::process::{ProcessRegistry, ProcessRegistryState};
+use crate::stream_json::StreamMessage;
+
+/// Event emitted after reconciliation found something worth reporting
+const RECOVERY_EVENT: &str = "runs-reconciled";
+
+/// How far a process's start time may drift from the recorded one
+const START_TIME_TOLERANCE_SECS: i64 = 5;
+
+/// How often a re-attached run's transcript is polled for new lines
+const TAIL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// An agent run that was still marked running when reconciliation ran
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecoveredRun {
+    pub run_id: i64,
+    pub agent_name: String,
+    pub pid: Option<u32>,
+    pub session_id: String,
+    pub project_path: String,
+    /// 'interrupted' when the process is gone, 'alive' when it can be re-attached
+    pub outcome: String,
+    pub reason: String,
+}
+
+/// A Claude process left behind by a previous Claudia instance that no run
+/// accounts for
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnownedProcess {
+    pub pid: u32,
+    pub ppid: u32,
+    pub elapsed_secs: i64,
+    pub command: String,
+}
+
+/// Result of a reconciliation pass
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecoveryReport {
+    pub checked_at: String,
+    pub runs: Vec<RecoveredRun>,
+    pub unowned_processes: Vec<UnownedProcess>,
+}
+
+/// Report from the last reconciliation pass
+#[derive(Default)]
+pub struct RecoveryState(Mutex<Option<RecoveryReport>>);
+
+/// One row of the system process table
+struct ProcessEntry {
+    pid: u32,
+    ppid: u32,
+    elapsed_secs: i64,
+    command: String,
+}
+
+/// Parses `ps` elapsed time, formatted as `[[dd-]hh:]mm:ss`
+fn parse_elapsed(etime: &str) -> Option<i64> {
+    let (days, rest) = match etime.split_once('-') {
+        Some((days, rest)) => (days.parse::<i64>().ok()?, rest),
+        None => (0, etime),
+    };
+    let mut secs = 0;
+    for part in rest.split(':') {
+        secs = secs * 60 + part.parse::<i64>().ok()?;
+    }
+    Some(days * 86_400 + secs)
+}
+
+/// Parses `ps -A -o pid=,ppid=,etime=,command=` output, keyed by PID
+#[cfg(any(unix, test))]
+fn parse_process_table(output: &str) -> HashMap<u32, ProcessEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid = fields.next()?.parse().ok()?;
+            let elapsed_secs = parse_elapsed(fields.next()?)?;
+            let command = fields.collect::<Vec<_>>().join(" ");
+            Some((pid, ProcessEntry { pid, ppid, elapsed_secs, command }))
+        })
+        .collect()
+}
+
+/// Snapshot of all processes, keyed by PID
+#[cfg(unix)]
+fn process_table() -> Result<HashMap<u32, ProcessEntry>, String> {
+    let output = std::process::Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid=,etime=,command="])
+        .output()
+        .map_err(|e| format!("Failed to run ps: {}", e))?;
+    if !output.status.success() {
+        return Err("ps exited with an error".to_string());
+    }
+    Ok(parse_process_table(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[cfg(not(unix))]
+fn process_table() -> Result<HashMap<u32, ProcessEntry>, String> {
+    Err("Process inspection is not supported on this platform".to_string())
+}
+
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    // SAFETY: signal 0 performs no action, it only checks the PID exists
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    false
+}
+
+/// Whether a command line looks like Claude Code started by Claudia, which
+/// always asks for stream-json output
+fn is_claude_command(command: &str) -> bool {
+    command.contains("claude") && command.contains("stream-json")
+}
+
+/// Checks that `pid` is still the Claude process started at `started_at`
+/// rather than an unrelated process that reused the PID
+fn verify_process(
+    table: &HashMap<u32, ProcessEntry>,
+    pid: u32,
+    started_at: Option<&str>,
+) -> Result<(), String> {
+    let entry = table.get(&pid).ok_or("process is no longer running")?;
+    if !entry.command.contains("claude") {
+        return Err(format!("PID {} now belongs to another program", pid));
+    }
+    if let Some(recorded) = started_at.and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok()) {
+        let started = chrono::Utc::now().timestamp() - entry.elapsed_secs;
+        if (started - recorded.timestamp()).abs() > START_TIME_TOLERANCE_SECS {
+            return Err(format!("PID {} was reused by a newer process", pid));
+        }
+    }
+    Ok(())
+}
+
+/// Checks every run still marked running against the process table. Runs
+/// whose process is gone are marked interrupted; live ones are reported so
+/// they can be re-attached. Runs owned by `registry` are left alone.
+pub(crate) fn reconcile(conn: &Connection, registry: &ProcessRegistry) -> Result<RecoveryReport, String> {
+    let owned: HashMap<i64, u32> = registry
+        .get_running_processes()?
+        .into_iter()
+        .map(|info| (info.run_id, info.pid))
+        .collect();
+    let table = process_table();
+    if let Err(e) = &table {
+        log::warn!("Cannot verify running agent processes: {}", e);
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, agent_name, pid, process_started_at, session_id, project_path
+             FROM agent_runs WHERE status = 'running'",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<i64>>(2)?.map(|p| p as u32),
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+
+    let mut runs = Vec::new();
+    for (run_id, agent_name, pid, started_at, session_id, project_path) in rows {
+        if owned.contains_key(&run_id) {
+            continue;
+        }
+        let verified = match (&table, pid) {
+            (Ok(table), Some(pid)) => verify_process(table, pid, started_at.as_deref()),
+            (Err(e), Some(_)) => Err(e.clone()),
+            (_, None) => Err("no PID was recorded".to_string()),
+        };
+        let (outcome, reason) = match verified {
+            Ok(()) => ("alive", "process is still running".to_string()),
+            Err(reason) => {
+                conn.execute(
+                    "UPDATE agent_runs SET status = 'interrupted', completed_at = CURRENT_TIMESTAMP WHERE id = ?1",
+                    params![run_id],
+                )
+                .map_err(|e| e.to_string())?;
+                ("interrupted", reason)
+            }
+        };
+        log::info!("Agent run {} ({}): {} - {}", run_id, agent_name, outcome, reason);
+        runs.push(RecoveredRun {
+            run_id,
+            agent_name,
+            pid,
+            session_id,
+            project_path,
+            outcome: outcome.to_string(),
+            reason,
+        });
+    }
+
+    // Claude processes nobody accounts for: orphans reparented to init, or
+    // our own children that the registry lost track of
+    let unowned_processes = match &table {
+        Ok(table) => {
+            let known: HashSet<u32> = owned
+                .values()
+                .copied()
+                .chain(runs.iter().filter(|r| r.outcome == "alive").filter_map(|r| r.pid))
+                .collect();
+            let own_pid = std::process::id();
+            let mut unowned: Vec<UnownedProcess> = table
+                .values()
+                .filter(|entry| is_claude_command(&entry.command) && !known.contains(&entry.pid))
+                .filter(|entry| entry.ppid == 1 || entry.ppid == own_pid)
+                .map(|entry| UnownedProcess {
+                    pid: entry.pid,
+                    ppid: entry.ppid,
+                    elapsed_secs: entry.elapsed_secs,
+                    command: entry.command.clone(),
+                })
+                .collect();
+            unowned.sort_by_key(|p| p.pid);
+            unowned
+        }
+        Err(_) => Vec::new(),
+    };
+    if !unowned_processes.is_empty() {
+        log::warn!("Found {} unowned Claude processes", unowned_processes.len());
+    }
+
+    Ok(RecoveryReport {
+        checked_at: chrono::Utc::now().to_rfc3339(),
+        runs,
+        unowned_processes,
+    })
+}
+
+/// Runs reconciliation once the app state is set up and tells the frontend
+/// if anything was found
+pub async fn reconcile_on_startup(app: AppHandle) {
+    let report = {
+        let db = app.state::<AgentDb>();
+        let registry = app.state::<ProcessRegistryState>();
+        let conn = match db.0.lock() {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::error!("Failed to lock database for run reconciliation: {}", e);
+                return;
+            }
+        };
+        match reconcile(&conn, &registry.0) {
+            Ok(report) => report,
+            Err(e) => {
+                log::error!("Run reconciliation failed: {}", e);
+                return;
+            }
+        }
+    };
+
+    if !report.runs.is_empty() || !report.unowned_processes.is_empty() {
+        let _ = app.emit(RECOVERY_EVENT, &report);
+    }
+    if let Ok(mut last) = app.state::<RecoveryState>().0.lock() {
+        *last = Some(report);
+    }
+}
+
+/// Gets the report of the last reconciliation pass
+#[tauri::command]
+pub async fn get_recovery_report(
+    state: State<'_, RecoveryState>,
+) -> Result<Option<RecoveryReport>, String> {
+    Ok(state.0.lock().map_err(|e| e.to_string())?.clone())
+}
+
+/// Runs reconciliation again, e.g. after the user killed stray processes
+#[tauri::command]
+pub async fn reconcile_agent_runs(
+    db: State<'_, AgentDb>,
+    registry: State<'_, ProcessRegistryState>,
+    state: State<'_, RecoveryState>,
+) -> Result<RecoveryReport, String> {
+    let report = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        reconcile(&conn, &registry.0)?
+    };
+    *state.0.lock().map_err(|e| e.to_string())? = Some(report.clone());
+    Ok(report)
+}
+
+/// Re-attaches to an agent run whose process outlived a previous Claudia
+/// instance. The run is registered again and its session transcript is tailed
+/// to the usual `agent-output` events until the process exits.
+#[tauri::command]
+pub async fn reattach_agent_run(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    registry: State<'_, ProcessRegistryState>,
+    run_id: i64,
+) -> Result<(), String> {
+    if registry.0.get_process(run_id)?.is_some() {
+        return Err(format!("Agent run {} is already attached", run_id));
+    }
+
+    let (agent_id, agent_name, task, model, project_path, session_id, pid, started_at, status) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT agent_id, agent_name, task, model, project_path, session_id, pid, process_started_at, status
+             FROM agent_runs WHERE id = ?1",
+            params![run_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, Option<i64>>(6)?.map(|p| p as u32),
+                    row.get::<_, Option<String>>(7)?,
+                    row.get::<_, String>(8)?,
+                ))
+            },
+        )
+        .map_err(|e| format!("Agent run {} not found: {}", run_id, e))?
+    };
+    if status != "running" {
+        return Err(format!("Agent run {} is {}", run_id, status));
+    }
+    let pid = pid.ok_or("No PID was recorded for this run")?;
+    verify_process(&process_table()?, pid, started_at.as_deref())?;
+    if session_id.is_empty() {
+        return Err("The run never reported a session ID, so there is no transcript to follow".to_string());
+    }
+    let session_file = find_session_file(&db, &session_id, Some(&project_path))?
+        .ok_or_else(|| format!("Transcript for session {} not found", session_id))?;
+
+    registry.0.register_sidecar_process(
+        run_id,
+        agent_id,
+        agent_name.clone(),
+        pid,
+        project_path,
+        task,
+        model,
+    )?;
+    log::info!("Re-attached to agent run {} ({}) with PID {}", run_id, agent_name, pid);
+
+    let registry = registry.0.clone();
+    tauri::async_runtime::spawn(tail_run(app, registry, run_id, pid, session_file));
+    Ok(())
+}
+
+/// Streams new transcript lines of a re-attached run until its process exits
+async fn tail_run(
+    app: AppHandle,
+    registry: std::sync::Arc<ProcessRegistry>,
+    run_id: i64,
+    pid: u32,
+    session_file: PathBuf,
+) {
+    let mut offset = 0u64;
+    let mut pending: Vec<u8> = Vec::new();
+    // Outcome implied by the latest conversation entry
+    let mut status = "interrupted";
+    loop {
+        // Check liveness before reading so the final lines are not missed
+        let alive = is_alive(pid);
+        let chunk = std::fs::File::open(&session_file).and_then(|mut file| {
+            file.seek(SeekFrom::Start(offset))?;
+            let mut buf = Vec::new();
+            file.read_to_end(&mut buf)?;
+            Ok(buf)
+        });
+        match chunk {
+            Ok(chunk) => {
+                offset += chunk.len() as u64;
+                pending.extend_from_slice(&chunk);
+                // Only complete lines; a partially written one waits for the next poll
+                while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+                    let bytes: Vec<u8> = pending.drain(..=end).collect();
+                    let line = String::from_utf8_lossy(&bytes);
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    if let Some(next) = StreamMessage::parse(line).as_ref().and_then(status_after) {
+                        status = next;
+                    }
+                    let _ = registry.append_live_output(run_id, line);
+                    let _ = app.emit(&format!("agent-output:{}", run_id), line);
+                    let _ = app.emit("agent-output", line);
+                }
+            }
+            Err(e) => log::warn!("Failed to read transcript for run {}: {}", run_id, e),
+        }
+
+        if !alive || registry.get_process(run_id).ok().flatten().is_none() {
+            break;
+        }
+        tokio::time::sleep(TAIL_INTERVAL).await;
+    }
+
+    // The exit status of a process we did not spawn is unknown, so the
+    // outcome comes from the transcript. A run cancelled meanwhile keeps its status.
+    let db = app.state::<AgentDb>();
+    let updated = match db.0.lock() {
+        Ok(conn) => conn
+            .execute(
+                "UPDATE agent_runs SET status = ?1, completed_at = CURRENT_TIMESTAMP
+                 WHERE id = ?2 AND status = 'running'",
+                params![status, run_id],
+            )
+            .unwrap_or(0),
+        Err(_) => 0,
+    };
+    let _ = registry.unregister_process(run_id);
+    log::info!("Re-attached agent run {} finished: {}", run_id, status);
+    let success = updated > 0 && status == "completed";
+    let _ = app.emit(&format!("agent-complete:{}", run_id), success);
+    let _ = app.emit("agent-complete", success);
+}
+
+/// Status a re-attached run finishes with if `entry` is the last one its
+/// transcript gets, or `None` for entries that say nothing about the outcome.
+/// Transcripts have no `result` entry, so a run counts as completed when
+/// Claude's last message ends its turn.
+fn status_after(entry: &StreamMessage) -> Option<&'static str> {
+    let conversation = entry.conversation()?;
+    if conversation.extra.get("isMeta").and_then(|m| m.as_bool()) == Some(true) {
+        return None;
+    }
+    match entry {
+        // A prompt or tool result still waiting for Claude's reply
+        StreamMessage::User(_) => Some("interrupted"),
+        _ if conversation.extra.get("isApiErrorMessage").and_then(|e| e.as_bool()) == Some(true) => {
+            Some("failed")
+        }
+        _ => Some(match entry.stop_reason() {
+            Some("end_turn") | Some("stop_sequence") => "completed",
+            Some("tool_use") => "interrupted",
+            Some(_) => "failed",
+            // Lines written mid-stream have no stop reason yet
+            None if entry.tool_uses().next().is_some() => "interrupted",
+            None => "completed",
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PS_OUTPUT: &str = "    1     0  3-04:05:06 /sbin/init
+  812     1       01:40 node /usr/local/bin/claude -p fix --output-format stream-json
+  990   812       00:05 /bin/bash -c npm test
+garbage line
+";
+
+    #[test]
+    fn test_parse_process_table() {
+        let table = parse_process_table(PS_OUTPUT);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table[&1].elapsed_secs, 3 * 86_400 + 4 * 3_600 + 5 * 60 + 6);
+        let claude = &table[&812];
+        assert_eq!((claude.ppid, claude.elapsed_secs), (1, 100));
+        assert!(is_claude_command(&claude.command));
+        assert!(!is_claude_command(&table[&990].command));
+        assert_eq!(parse_elapsed("1:2:3:4"), Some(((60 + 2) * 60 + 3) * 60 + 4));
+        assert_eq!(parse_elapsed("xx:10"), None);
+    }
+
+    #[test]
+    fn test_verify_process_start_time_tolerance() {
+        let table = parse_process_table(PS_OUTPUT);
+        // ps says PID 812 started 100 seconds ago
+        let started = |secs_ago: i64| {
+            (chrono::Utc::now() - chrono::Duration::seconds(secs_ago)).to_rfc3339()
+        };
+
+        assert!(verify_process(&table, 812, None).is_ok());
+        assert!(verify_process(&table, 812, Some(&started(102))).is_ok());
+        assert!(verify_process(&table, 812, Some(&started(98))).is_ok());
+        assert!(verify_process(&table, 812, Some(&started(160))).is_err());
+        assert!(verify_process(&table, 812, Some(&started(40))).is_err());
+        // Unparseable timestamps skip the start-time check
+        assert!(verify_process(&table, 812, Some("yesterday")).is_ok());
+        assert!(verify_process(&table, 1, None).is_err());
+        assert!(verify_process(&table, 4242, None).is_err());
+    }
+
+    /// Folds transcript lines the way `tail_run` does
+    fn final_status(transcript: &str) -> &'static str {
+        transcript
+            .lines()
+            .filter_map(StreamMessage::parse)
+            .fold("interrupted", |status, entry| status_after(&entry).unwrap_or(status))
+    }
+
+    /// The tail of a transcript: a prompt, a tool call and its result, the
+    /// answer split over two lines, and trailing entries that are not part of
+    /// the conversation
+    const TRANSCRIPT: &str = r#"{"parentUuid":null,"isSidechain":false,"userType":"external","cwd":"/p","sessionId":"s","version":"1.0.51","type":"user","message":{"role":"user","content":"Fix the build"},"uuid":"u1","timestamp":"2025-07-01T10:00:00.000Z"}
+{"parentUuid":"u1","isSidechain":false,"userType":"external","cwd":"/p","sessionId":"s","version":"1.0.51","message":{"id":"msg_1","type":"message","role":"assistant","model":"claude-sonnet-4-20250514","content":[{"type":"tool_use","id":"toolu_1","name":"Bash","input":{"command":"cargo build"}}],"stop_reason":"tool_use","stop_sequence":null,"usage":{"input_tokens":4,"output_tokens":30}},"requestId":"req_1","type":"assistant","uuid":"a1","timestamp":"2025-07-01T10:00:02.000Z"}
+{"parentUuid":"a1","isSidechain":false,"userType":"external","cwd":"/p","sessionId":"s","version":"1.0.51","type":"user","message":{"role":"user","content":[{"tool_use_id":"toolu_1","type":"tool_result","content":"Finished dev profile","is_error":false}]},"uuid":"u2","timestamp":"2025-07-01T10:00:09.000Z","toolUseResult":{"stdout":"Finished dev profile","stderr":"","interrupted":false}}
+{"parentUuid":"u2","isSidechain":false,"userType":"external","cwd":"/p","sessionId":"s","version":"1.0.51","message":{"id":"msg_2","type":"message","role":"assistant","model":"claude-sonnet-4-20250514","content":[{"type":"text","text":"The build passes."}],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":6,"output_tokens":8}},"requestId":"req_2","type":"assistant","uuid":"a2","timestamp":"2025-07-01T10:00:11.000Z"}
+{"parentUuid":"a2","isSidechain":false,"userType":"external","cwd":"/p","sessionId":"s","version":"1.0.51","message":{"id":"msg_2","type":"message","role":"assistant","model":"claude-sonnet-4-20250514","content":[{"type":"text","text":"Nothing else to change."}],"stop_reason":"end_turn","stop_sequence":null,"usage":{"input_tokens":6,"output_tokens":15}},"requestId":"req_2","type":"assistant","uuid":"a3","timestamp":"2025-07-01T10:00:12.000Z"}
+{"parentUuid":"a3","isSidechain":false,"userType":"external","cwd":"/p","sessionId":"s","version":"1.0.51","type":"user","isMeta":true,"message":{"role":"user","content":"Caveat: the messages below were generated by the user"},"uuid":"u3","timestamp":"2025-07-01T10:00:13.000Z"}
+{"type":"summary","summary":"Fix the build","leafUuid":"a3"}
+"#;
+
+    #[test]
+    fn test_status_from_transcript_tail() {
+        assert_eq!(final_status(TRANSCRIPT), "completed");
+        assert_eq!(final_status(""), "interrupted");
+
+        // Stopped while a tool ran, or before answering its result
+        let lines: Vec<&str> = TRANSCRIPT.lines().collect();
+        assert_eq!(final_status(&lines[..2].join("\n")), "interrupted");
+        assert_eq!(final_status(&lines[..3].join("\n")), "interrupted");
+        // Answer text written without a stop reason yet
+        assert_eq!(final_status(&lines[..4].join("\n")), "completed");
+
+        let interrupted = format!(
+            "{}\n{}",
+            TRANSCRIPT,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"[Request interrupted by user]"}]},"uuid":"u4"}"#
+        );
+        assert_eq!(final_status(&interrupted), "interrupted");
+
+        let api_error = format!(
+            "{}\n{}",
+            lines[..3].join("\n"),
+            r#"{"type":"assistant","isApiErrorMessage":true,"message":{"id":"e1","role":"assistant","model":"<synthetic>","content":[{"type":"text","text":"API Error: 529 Overloaded"}],"stop_reason":"stop_sequence"},"uuid":"a4"}"#
+        );
+        assert_eq!(final_status(&api_error), "failed");
+
+        let truncated = lines[4].replace("\"end_turn\"", "\"max_tokens\"");
+        assert_eq!(final_status(&format!("{}\n{}", lines[..4].join("\n"), truncated)), "failed");
+    }
+}
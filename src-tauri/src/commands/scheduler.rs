@@ -0,0 +1,935 @@
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Local, LocalResult, TimeZone, Timelike};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use super::agents::{execute_agent, AgentDb};
+use super::claude::{start_claude_session, SessionChannels, SessionMode, SessionOutcome};
+
+/// How often due schedules are checked
+const TICK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often a scheduled agent run is checked for completion
+const AGENT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// An occurrence at most this many seconds old counts as on time, not missed
+const ON_TIME_GRACE_SECS: i64 = 120;
+
+/// The most missed occurrences the 'run_all' policy catches up on
+const MAX_CATCH_UP_RUNS: usize = 5;
+
+/// Missed occurrences are counted up to this many, then the count is capped
+const MAX_COUNTED_OCCURRENCES: usize = 10_000;
+
+/// Event emitted whenever a scheduled run starts or finishes
+const SCHEDULE_EVENT: &str = "schedule-run";
+
+const DEFAULT_MODEL: &str = "sonnet";
+
+const CATCH_UP_POLICIES: [&str; 3] = ["skip", "run_once", "run_all"];
+
+/// A parsed five-field cron expression (minute hour day-of-month month
+/// day-of-week), evaluated in local time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CronExpression {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+fn parse_value(value: &str, names: &[&str], offset: u32) -> Result<u32, String> {
+    if let Ok(n) = value.parse::<u32>() {
+        return Ok(n);
+    }
+    names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(value))
+        .map(|i| i as u32 + offset)
+        .ok_or_else(|| format!("invalid value '{}'", value))
+}
+
+/// Parses one field into a bitmask of allowed values. Supports `*`, values,
+/// `a-b` ranges, `/n` steps, comma lists and month or weekday names.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str], name_offset: u32) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("invalid step '{}'", step))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, names, name_offset)?, parse_value(b, names, name_offset)?)
+        } else {
+            let value = parse_value(range, names, name_offset)?;
+            // 'n/step' means every step starting at n
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("'{}' is outside {}-{}", range, min, max));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+impl CronExpression {
+    pub(crate) fn parse(expression: &str) -> Result<Self, String> {
+        let expression = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "Expected 5 fields (minute hour day month weekday), found {}",
+                fields.len()
+            ));
+        };
+        let field = |name: &str, result: Result<u64, String>| {
+            result.map_err(|e| format!("Invalid {} field: {}", name, e))
+        };
+        let mut weekdays = field("weekday", parse_field(weekday, 0, 7, &WEEKDAY_NAMES, 0))?;
+        // Both 0 and 7 mean Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: field("minute", parse_field(minute, 0, 59, &[], 0))?,
+            hours: field("hour", parse_field(hour, 0, 23, &[], 0))?,
+            days: field("day", parse_field(day, 1, 31, &[], 0))?,
+            months: field("month", parse_field(month, 1, 12, &MONTH_NAMES, 1))?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    /// Like cron, a restricted day-of-month and day-of-week match either one
+    fn matches_date(&self, date: chrono::NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// The first occurrence strictly after `after`. Times skipped by a DST
+    /// change are skipped; repeated times run once.
+    pub(crate) fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = after.timezone();
+        let start = after.naive_local() + ChronoDuration::minutes(1);
+        let mut date = start.date();
+        let (mut first_hour, mut first_minute) = (start.hour(), start.minute());
+        // Five years covers every combination, including February 29th
+        for _ in 0..(366 * 5) {
+            if self.matches_date(date) {
+                for hour in first_hour..24 {
+                    if self.hours & (1 << hour) == 0 {
+                        continue;
+                    }
+                    let from = if hour == first_hour { first_minute } else { 0 };
+                    for minute in from..60 {
+                        if self.minutes & (1 << minute) == 0 {
+                            continue;
+                        }
+                        let naive = date.and_hms_opt(hour, minute, 0)?;
+                        let candidate = match tz.from_local_datetime(&naive) {
+                            LocalResult::Single(dt) | LocalResult::Ambiguous(dt, _) => dt,
+                            LocalResult::None => continue,
+                        };
+                        if candidate > *after {
+                            return Some(candidate);
+                        }
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+            first_hour = 0;
+            first_minute = 0;
+        }
+        None
+    }
+}
+
+/// A recurring agent run or session prompt
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Schedule {
+    pub id: i64,
+    pub name: String,
+    pub cron_expression: String,
+    /// 'agent' runs `agent_id` with `prompt` as its task, 'prompt' starts a
+    /// new session with `prompt`
+    pub target_type: String,
+    pub agent_id: Option<i64>,
+    pub project_path: String,
+    pub prompt: String,
+    pub model: Option<String>,
+    /// 'skip', 'run_once' or 'run_all': what happens to occurrences missed
+    /// while Claudia was not running
+    pub catch_up_policy: String,
+    pub enabled: bool,
+    pub last_run_at: Option<String>,
+    /// RFC 3339, None while disabled
+    pub next_run_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Fields of a schedule set by the user
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScheduleInput {
+    pub name: String,
+    pub cron_expression: String,
+    pub target_type: String,
+    pub agent_id: Option<i64>,
+    pub project_path: String,
+    pub prompt: String,
+    pub model: Option<String>,
+    pub catch_up_policy: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+/// One execution (or skipped execution) of a schedule
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScheduleRun {
+    pub id: i64,
+    pub schedule_id: i64,
+    pub scheduled_for: String,
+    /// 'schedule', 'catch_up' or 'manual'
+    pub trigger: String,
+    /// 'running', 'completed', 'failed', 'skipped' or 'interrupted'
+    pub status: String,
+    pub agent_run_id: Option<i64>,
+    pub session_id: Option<String>,
+    pub error: Option<String>,
+    pub started_at: String,
+    pub completed_at: Option<String>,
+}
+
+const SCHEDULE_COLUMNS: &str = "id, name, cron_expression, target_type, agent_id, project_path, prompt, model, catch_up_policy, enabled, last_run_at, next_run_at, created_at, updated_at";
+
+const RUN_COLUMNS: &str = "id, schedule_id, scheduled_for, trigger, status, agent_run_id, session_id, error, started_at, completed_at";
+
+fn map_schedule(row: &rusqlite::Row) -> rusqlite::Result<Schedule> {
+    Ok(Schedule {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        cron_expression: row.get(2)?,
+        target_type: row.get(3)?,
+        agent_id: row.get(4)?,
+        project_path: row.get(5)?,
+        prompt: row.get(6)?,
+        model: row.get(7)?,
+        catch_up_policy: row.get(8)?,
+        enabled: row.get(9)?,
+        last_run_at: row.get(10)?,
+        next_run_at: row.get(11)?,
+        created_at: row.get(12)?,
+        updated_at: row.get(13)?,
+    })
+}
+
+fn map_run(row: &rusqlite::Row) -> rusqlite::Result<ScheduleRun> {
+    Ok(ScheduleRun {
+        id: row.get(0)?,
+        schedule_id: row.get(1)?,
+        scheduled_for: row.get(2)?,
+        trigger: row.get(3)?,
+        status: row.get(4)?,
+        agent_run_id: row.get(5)?,
+        session_id: row.get(6)?,
+        error: row.get(7)?,
+        started_at: row.get(8)?,
+        completed_at: row.get(9)?,
+    })
+}
+
+fn get_schedule(conn: &Connection, id: i64) -> Result<Schedule, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM schedules WHERE id = ?1", SCHEDULE_COLUMNS),
+        params![id],
+        map_schedule,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Schedule not found: {}", id))
+}
+
+fn get_run(conn: &Connection, id: i64) -> Result<ScheduleRun, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM schedule_runs WHERE id = ?1", RUN_COLUMNS),
+        params![id],
+        map_run,
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn validate_input(conn: &Connection, input: &ScheduleInput) -> Result<CronExpression, String> {
+    if input.name.trim().is_empty() {
+        return Err("Schedule name cannot be empty".to_string());
+    }
+    if input.prompt.trim().is_empty() {
+        return Err("Prompt cannot be empty".to_string());
+    }
+    if !std::path::Path::new(&input.project_path).is_dir() {
+        return Err(format!("Project directory does not exist: {}", input.project_path));
+    }
+    match input.target_type.as_str() {
+        "prompt" => {}
+        "agent" => {
+            let agent_id = input.agent_id.ok_or("Agent schedules require an agent")?;
+            let exists: bool = conn
+                .query_row("SELECT EXISTS(SELECT 1 FROM agents WHERE id = ?1)", params![agent_id], |row| {
+                    row.get(0)
+                })
+                .map_err(|e| e.to_string())?;
+            if !exists {
+                return Err(format!("Agent not found: {}", agent_id));
+            }
+        }
+        other => return Err(format!("Invalid target type '{}': expected agent or prompt", other)),
+    }
+    if let Some(policy) = &input.catch_up_policy {
+        if !CATCH_UP_POLICIES.contains(&policy.as_str()) {
+            return Err(format!(
+                "Invalid catch-up policy '{}': expected {}",
+                policy,
+                CATCH_UP_POLICIES.join(", ")
+            ));
+        }
+    }
+    CronExpression::parse(&input.cron_expression)
+}
+
+fn next_run_from_now(cron: &CronExpression) -> Option<String> {
+    cron.next_after(&Local::now()).map(|t| t.to_rfc3339())
+}
+
+/// The runs a schedule owes at `now` given its stored next occurrence
+#[derive(Debug, PartialEq)]
+pub(crate) struct RunPlan<Tz: TimeZone> {
+    /// Occurrences to run, oldest first, with their trigger
+    pub runs: Vec<(DateTime<Tz>, &'static str)>,
+    /// Missed occurrences the policy skips
+    pub skipped: usize,
+    pub next: Option<DateTime<Tz>>,
+}
+
+/// Decides which due occurrences to run. The newest occurrence runs on time
+/// if it is within the grace period; older ones were missed and are handled
+/// according to the catch-up policy.
+pub(crate) fn plan_runs<Tz: TimeZone>(
+    cron: &CronExpression,
+    next_run_at: DateTime<Tz>,
+    now: &DateTime<Tz>,
+    policy: &str,
+) -> RunPlan<Tz> {
+    let mut due = Vec::new();
+    let mut cursor = Some(next_run_at);
+    while let Some(at) = cursor.filter(|at| at <= now) {
+        if due.len() >= MAX_COUNTED_OCCURRENCES {
+            break;
+        }
+        cursor = cron.next_after(&at);
+        due.push(at);
+    }
+    let next = cron.next_after(now);
+
+    let on_time = match due.last() {
+        Some(last) if (now.clone() - last.clone()).num_seconds() <= ON_TIME_GRACE_SECS => due.pop(),
+        _ => None,
+    };
+    let missed = due;
+
+    let mut runs: Vec<(DateTime<Tz>, &'static str)> = Vec::new();
+    let mut skipped = missed.len();
+    match policy {
+        "run_all" => {
+            let from = missed.len().saturating_sub(MAX_CATCH_UP_RUNS);
+            skipped = from;
+            runs.extend(missed[from..].iter().map(|at| (at.clone(), "catch_up")));
+        }
+        "run_once" if on_time.is_none() => {
+            if let Some(latest) = missed.last() {
+                runs.push((latest.clone(), "catch_up"));
+                skipped -= 1;
+            }
+        }
+        _ => {}
+    }
+    if let Some(at) = on_time {
+        runs.push((at, "schedule"));
+    }
+    RunPlan { runs, skipped, next }
+}
+
+fn emit_run(app: &AppHandle, run: &ScheduleRun) {
+    let _ = app.emit(SCHEDULE_EVENT, run);
+}
+
+/// Records the start of a run unless the schedule already has one running
+fn begin_run(
+    conn: &Connection,
+    schedule_id: i64,
+    scheduled_for: &str,
+    trigger: &str,
+) -> Result<Option<ScheduleRun>, String> {
+    let running: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM schedule_runs WHERE schedule_id = ?1 AND status = 'running')",
+            params![schedule_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if running {
+        return Ok(None);
+    }
+    conn.execute(
+        "INSERT INTO schedule_runs (schedule_id, scheduled_for, trigger, status) VALUES (?1, ?2, ?3, 'running')",
+        params![schedule_id, scheduled_for, trigger],
+    )
+    .map_err(|e| e.to_string())?;
+    let run_id = conn.last_insert_rowid();
+    conn.execute(
+        "UPDATE schedules SET last_run_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        params![schedule_id],
+    )
+    .map_err(|e| e.to_string())?;
+    get_run(conn, run_id).map(Some)
+}
+
+fn record_skipped(conn: &Connection, schedule_id: i64, scheduled_for: &str, trigger: &str, reason: &str) -> Result<ScheduleRun, String> {
+    conn.execute(
+        "INSERT INTO schedule_runs (schedule_id, scheduled_for, trigger, status, error, completed_at)
+         VALUES (?1, ?2, ?3, 'skipped', ?4, CURRENT_TIMESTAMP)",
+        params![schedule_id, scheduled_for, trigger, reason],
+    )
+    .map_err(|e| e.to_string())?;
+    get_run(conn, conn.last_insert_rowid())
+}
+
+fn finish_run(
+    app: &AppHandle,
+    run_id: i64,
+    session_id: Option<&str>,
+    error: Option<&str>,
+) -> Result<ScheduleRun, String> {
+    let db = app.state::<AgentDb>();
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let status = if error.is_some() { "failed" } else { "completed" };
+    conn.execute(
+        "UPDATE schedule_runs SET status = ?1, session_id = COALESCE(?2, session_id), error = ?3,
+         completed_at = CURRENT_TIMESTAMP WHERE id = ?4",
+        params![status, session_id, error, run_id],
+    )
+    .map_err(|e| e.to_string())?;
+    get_run(&conn, run_id)
+}
+
+/// Waits for an agent run to leave the pending and running states
+async fn wait_for_agent_run(app: &AppHandle, agent_run_id: i64) -> Result<Option<String>, (Option<String>, String)> {
+    loop {
+        tokio::time::sleep(AGENT_POLL_INTERVAL).await;
+        let row: Option<(String, String)> = {
+            let db = app.state::<AgentDb>();
+            let conn = db.0.lock().map_err(|e| (None, e.to_string()))?;
+            conn.query_row(
+                "SELECT status, session_id FROM agent_runs WHERE id = ?1",
+                params![agent_run_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| (None, e.to_string()))?
+        };
+        let Some((status, session_id)) = row else {
+            return Err((None, format!("Agent run {} was deleted", agent_run_id)));
+        };
+        let session_id = Some(session_id).filter(|s| !s.is_empty());
+        match status.as_str() {
+            "pending" | "running" => continue,
+            "completed" => return Ok(session_id),
+            other => return Err((session_id, format!("Agent run {}", other))),
+        }
+    }
+}
+
+/// Runs a schedule's agent or prompt to completion and records the outcome
+async fn execute_run(app: &AppHandle, schedule: &Schedule, run: ScheduleRun) {
+    emit_run(app, &run);
+    log::info!("Running schedule '{}' ({}) for {}", schedule.name, run.trigger, run.scheduled_for);
+
+    let model = schedule.model.clone();
+    let result = match (schedule.target_type.as_str(), schedule.agent_id) {
+        ("agent", Some(agent_id)) => {
+            match execute_agent(
+                app.clone(),
+                agent_id,
+                schedule.project_path.clone(),
+                schedule.prompt.clone(),
+                model,
+                None,
+                None,
+                app.state::<AgentDb>(),
+                app.state::<crate::process::ProcessRegistryState>(),
+            )
+            .await
+            {
+                Ok(agent_run_id) => {
+                    if let Ok(conn) = app.state::<AgentDb>().0.lock() {
+                        let _ = conn.execute(
+                            "UPDATE schedule_runs SET agent_run_id = ?1 WHERE id = ?2",
+                            params![agent_run_id, run.id],
+                        );
+                        if let Ok(run) = get_run(&conn, run.id) {
+                            emit_run(app, &run);
+                        }
+                    }
+                    wait_for_agent_run(app, agent_run_id).await
+                }
+                Err(e) => Err((None, e)),
+            }
+        }
+        ("agent", None) => Err((None, "Schedule has no agent".to_string())),
+        _ => {
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            match start_claude_session(
+                app.clone(),
+                schedule.project_path.clone(),
+                schedule.prompt.clone(),
+                model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+                SessionMode::New,
+                None,
+                SessionChannels {
+                    completion: Some(tx),
+                    ..Default::default()
+                },
+            )
+            .await
+            {
+                Ok(()) => match rx.await {
+                    Ok(outcome) if outcome.success => Ok(outcome.session_id),
+                    Ok(SessionOutcome { session_id, budget_exceeded: Some(violation), .. }) => {
+                        Err((session_id, violation.message()))
+                    }
+                    Ok(outcome) => Err((outcome.session_id, "Claude exited with an error".to_string())),
+                    Err(_) => Err((None, "Session ended without reporting completion".to_string())),
+                },
+                Err(e) => Err((None, e)),
+            }
+        }
+    };
+
+    let finished = match &result {
+        Ok(session_id) => finish_run(app, run.id, session_id.as_deref(), None),
+        Err((session_id, error)) => {
+            log::warn!("Scheduled run {} of '{}' failed: {}", run.id, schedule.name, error);
+            finish_run(app, run.id, session_id.as_deref(), Some(error))
+        }
+    };
+    match finished {
+        Ok(finished) => emit_run(app, &finished),
+        Err(e) => log::error!("Failed to record scheduled run {}: {}", run.id, e),
+    }
+}
+
+/// Occurrences to run as RFC 3339 times with their trigger, oldest first
+type PlannedRuns = Vec<(String, &'static str)>;
+
+/// Runs the planned occurrences of one schedule one after another
+async fn execute_plan(app: AppHandle, schedule: Schedule, runs: PlannedRuns) {
+    for (scheduled_for, trigger) in runs {
+        let started = {
+            let db = app.state::<AgentDb>();
+            let conn = match db.0.lock() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    log::error!("Failed to lock database: {}", e);
+                    return;
+                }
+            };
+            match begin_run(&conn, schedule.id, &scheduled_for, trigger) {
+                Ok(Some(run)) => Ok(run),
+                Ok(None) => record_skipped(&conn, schedule.id, &scheduled_for, trigger, "Previous run still running"),
+                Err(e) => Err(e),
+            }
+        };
+        match started {
+            Ok(run) if run.status == "running" => execute_run(&app, &schedule, run).await,
+            Ok(skipped) => emit_run(&app, &skipped),
+            Err(e) => log::error!("Failed to start scheduled run of '{}': {}", schedule.name, e),
+        }
+    }
+}
+
+/// Advances every due schedule and returns the runs to start
+fn collect_due(conn: &Connection, now: &DateTime<Local>) -> Result<Vec<(Schedule, PlannedRuns)>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM schedules WHERE enabled = 1 AND next_run_at IS NOT NULL",
+            SCHEDULE_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let schedules = stmt
+        .query_map([], map_schedule)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut due = Vec::new();
+    for schedule in schedules {
+        let next_run_at = schedule
+            .next_run_at
+            .as_deref()
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&Local));
+        let (Some(next_run_at), Ok(cron)) = (next_run_at, CronExpression::parse(&schedule.cron_expression)) else {
+            log::warn!("Schedule {} has an invalid time or expression, disabling it", schedule.id);
+            let _ = conn.execute("UPDATE schedules SET enabled = 0, next_run_at = NULL WHERE id = ?1", params![schedule.id]);
+            continue;
+        };
+        if next_run_at > *now {
+            continue;
+        }
+
+        let plan = plan_runs(&cron, next_run_at, now, &schedule.catch_up_policy);
+        conn.execute(
+            "UPDATE schedules SET next_run_at = ?1 WHERE id = ?2",
+            params![plan.next.map(|t| t.to_rfc3339()), schedule.id],
+        )
+        .map_err(|e| e.to_string())?;
+        if plan.skipped > 0 {
+            let reason = format!("Skipped {} missed run(s) while Claudia was not running", plan.skipped);
+            record_skipped(conn, schedule.id, &now.to_rfc3339(), "catch_up", &reason)?;
+        }
+        if !plan.runs.is_empty() {
+            let runs = plan.runs.into_iter().map(|(at, trigger)| (at.to_rfc3339(), trigger)).collect();
+            due.push((schedule, runs));
+        }
+    }
+    Ok(due)
+}
+
+/// Marks scheduled runs left running by a previous app instance as
+/// interrupted. They never reported completion and would otherwise block
+/// their schedules forever. Only the app runs schedules, so this must not
+/// happen when the CLI opens the database.
+pub fn interrupt_stale_runs(app: &AppHandle) {
+    let result = match app.state::<AgentDb>().0.lock() {
+        Ok(conn) => conn
+            .execute(
+                "UPDATE schedule_runs SET status = 'interrupted', completed_at = CURRENT_TIMESTAMP WHERE status = 'running'",
+                [],
+            )
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match result {
+        Ok(0) => {}
+        Ok(count) => log::info!("Marked {} stale scheduled runs as interrupted", count),
+        Err(e) => log::error!("Failed to reset stale scheduled runs: {}", e),
+    }
+}
+
+/// Checks for due schedules for as long as the app runs. Missed occurrences
+/// are caught up on the first check after startup.
+pub async fn run_scheduler(app: AppHandle) {
+    let mut interval = tokio::time::interval(TICK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let due = match app.state::<AgentDb>().0.lock() {
+            Ok(conn) => collect_due(&conn, &Local::now()),
+            Err(e) => Err(e.to_string()),
+        };
+        match due {
+            Ok(due) => {
+                for (schedule, runs) in due {
+                    tauri::async_runtime::spawn(execute_plan(app.clone(), schedule, runs));
+                }
+            }
+            Err(e) => log::error!("Failed to check schedules: {}", e),
+        }
+    }
+}
+
+/// Lists all schedules
+#[tauri::command]
+pub async fn list_schedules(db: State<'_, AgentDb>) -> Result<Vec<Schedule>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM schedules ORDER BY name", SCHEDULE_COLUMNS))
+        .map_err(|e| e.to_string())?;
+    let schedules = stmt
+        .query_map([], map_schedule)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(schedules)
+}
+
+/// Creates a schedule
+#[tauri::command]
+pub async fn create_schedule(db: State<'_, AgentDb>, schedule: ScheduleInput) -> Result<Schedule, String> {
+    log::info!("Creating schedule '{}' ({})", schedule.name, schedule.cron_expression);
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let cron = validate_input(&conn, &schedule)?;
+    let enabled = schedule.enabled.unwrap_or(true);
+    let next_run_at = if enabled { next_run_from_now(&cron) } else { None };
+
+    conn.execute(
+        "INSERT INTO schedules (name, cron_expression, target_type, agent_id, project_path, prompt, model, catch_up_policy, enabled, next_run_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            schedule.name,
+            schedule.cron_expression.trim(),
+            schedule.target_type,
+            schedule.agent_id,
+            schedule.project_path,
+            schedule.prompt,
+            schedule.model,
+            schedule.catch_up_policy.as_deref().unwrap_or("run_once"),
+            enabled,
+            next_run_at,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    get_schedule(&conn, conn.last_insert_rowid())
+}
+
+/// Replaces a schedule's settings. Its next run is recomputed from now.
+#[tauri::command]
+pub async fn update_schedule(
+    db: State<'_, AgentDb>,
+    id: i64,
+    schedule: ScheduleInput,
+) -> Result<Schedule, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let existing = get_schedule(&conn, id)?;
+    let cron = validate_input(&conn, &schedule)?;
+    let enabled = schedule.enabled.unwrap_or(existing.enabled);
+    let next_run_at = if enabled { next_run_from_now(&cron) } else { None };
+
+    conn.execute(
+        "UPDATE schedules SET name = ?1, cron_expression = ?2, target_type = ?3, agent_id = ?4, project_path = ?5,
+         prompt = ?6, model = ?7, catch_up_policy = ?8, enabled = ?9, next_run_at = ?10, updated_at = CURRENT_TIMESTAMP
+         WHERE id = ?11",
+        params![
+            schedule.name,
+            schedule.cron_expression.trim(),
+            schedule.target_type,
+            schedule.agent_id,
+            schedule.project_path,
+            schedule.prompt,
+            schedule.model,
+            schedule.catch_up_policy.as_deref().unwrap_or(&existing.catch_up_policy),
+            enabled,
+            next_run_at,
+            id,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    get_schedule(&conn, id)
+}
+
+/// Deletes a schedule and its run history
+#[tauri::command]
+pub async fn delete_schedule(db: State<'_, AgentDb>, id: i64) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM schedule_runs WHERE schedule_id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    let deleted = conn
+        .execute("DELETE FROM schedules WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    if deleted == 0 {
+        return Err(format!("Schedule not found: {}", id));
+    }
+    Ok(())
+}
+
+/// Enables or disables a schedule. Occurrences while disabled are not caught
+/// up; an enabled schedule next runs at its first occurrence from now.
+#[tauri::command]
+pub async fn set_schedule_enabled(
+    db: State<'_, AgentDb>,
+    id: i64,
+    enabled: bool,
+) -> Result<Schedule, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let schedule = get_schedule(&conn, id)?;
+    let next_run_at = if enabled {
+        next_run_from_now(&CronExpression::parse(&schedule.cron_expression)?)
+    } else {
+        None
+    };
+    conn.execute(
+        "UPDATE schedules SET enabled = ?1, next_run_at = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?3",
+        params![enabled, next_run_at, id],
+    )
+    .map_err(|e| e.to_string())?;
+    get_schedule(&conn, id)
+}
+
+/// Starts a schedule's run immediately without changing its next occurrence
+#[tauri::command]
+pub async fn run_schedule_now(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    id: i64,
+) -> Result<ScheduleRun, String> {
+    let (schedule, run) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let schedule = get_schedule(&conn, id)?;
+        let run = begin_run(&conn, id, &Local::now().to_rfc3339(), "manual")?
+            .ok_or_else(|| format!("Schedule '{}' is already running", schedule.name))?;
+        (schedule, run)
+    };
+
+    let spawned = run.clone();
+    tauri::async_runtime::spawn(async move {
+        execute_run(&app, &schedule, spawned).await;
+    });
+    Ok(run)
+}
+
+/// Lists recent runs, of one schedule or of all, newest first
+#[tauri::command]
+pub async fn list_schedule_runs(
+    db: State<'_, AgentDb>,
+    schedule_id: Option<i64>,
+    limit: Option<i64>,
+) -> Result<Vec<ScheduleRun>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM schedule_runs WHERE ?1 IS NULL OR schedule_id = ?1 ORDER BY id DESC LIMIT ?2",
+            RUN_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let runs = stmt
+        .query_map(params![schedule_id, limit.unwrap_or(100)], map_run)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(runs)
+}
+
+/// Validates a cron expression and returns its next occurrences
+#[tauri::command]
+pub async fn preview_schedule(cron_expression: String, count: Option<usize>) -> Result<Vec<String>, String> {
+    let cron = CronExpression::parse(&cron_expression)?;
+    let mut times = Vec::new();
+    let mut cursor = Local::now();
+    for _ in 0..count.unwrap_or(5).min(50) {
+        let Some(next) = cron.next_after(&cursor) else {
+            break;
+        };
+        times.push(next.to_rfc3339());
+        cursor = next;
+    }
+    Ok(times)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parses_and_finds_next_occurrences() {
+        let weekdays = CronExpression::parse("30 9 * * mon-fri").unwrap();
+        // Saturday morning rolls over to Monday
+        assert_eq!(
+            weekdays.next_after(&at("2026-10-17T08:00:00Z")),
+            Some(at("2026-10-19T09:30:00Z"))
+        );
+        let every_15 = CronExpression::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            every_15.next_after(&at("2026-10-17T08:00:00Z")),
+            Some(at("2026-10-17T08:15:00Z"))
+        );
+        // Day-of-month and weekday match either one when both are restricted
+        let either = CronExpression::parse("0 0 13 * 5").unwrap();
+        assert_eq!(
+            either.next_after(&at("2026-10-10T00:00:00Z")),
+            Some(at("2026-10-13T00:00:00Z"))
+        );
+        let leap = CronExpression::parse("0 0 29 2 *").unwrap();
+        assert_eq!(
+            leap.next_after(&at("2026-03-01T00:00:00Z")),
+            Some(at("2028-02-29T00:00:00Z"))
+        );
+        assert_eq!(
+            CronExpression::parse("@daily").unwrap(),
+            CronExpression::parse("0 0 * * *").unwrap()
+        );
+        assert_eq!(CronExpression::parse("0 0 * * 7"), CronExpression::parse("0 0 * * sun"));
+
+        assert!(CronExpression::parse("60 * * * *").is_err());
+        assert!(CronExpression::parse("* * * *").is_err());
+        assert!(CronExpression::parse("*/0 * * * *").is_err());
+        assert!(CronExpression::parse("0 0 * foo *").is_err());
+    }
+
+    #[test]
+    fn test_plans_catch_up_by_policy() {
+        let hourly = CronExpression::parse("0 * * * *").unwrap();
+        let next = at("2026-10-17T01:00:00Z");
+
+        // Three occurrences missed, the fourth is on time
+        let now = at("2026-10-17T04:01:00Z");
+        let plan = plan_runs(&hourly, next, &now, "skip");
+        assert_eq!(plan.runs, [(at("2026-10-17T04:00:00Z"), "schedule")]);
+        assert_eq!(plan.skipped, 3);
+        assert_eq!(plan.next, Some(at("2026-10-17T05:00:00Z")));
+
+        let plan = plan_runs(&hourly, next, &now, "run_all");
+        assert_eq!(plan.runs.len(), 4);
+        assert_eq!(plan.runs[0], (at("2026-10-17T01:00:00Z"), "catch_up"));
+        assert_eq!(plan.skipped, 0);
+
+        // Nothing on time: run the latest missed occurrence once
+        let now = at("2026-10-17T04:30:00Z");
+        let plan = plan_runs(&hourly, next, &now, "run_once");
+        assert_eq!(plan.runs, [(at("2026-10-17T04:00:00Z"), "catch_up")]);
+        assert_eq!(plan.skipped, 3);
+
+        let now = at("2026-10-17T12:30:00Z");
+        let plan = plan_runs(&hourly, next, &now, "run_all");
+        assert_eq!(plan.runs.len(), MAX_CATCH_UP_RUNS);
+        assert_eq!(plan.skipped, 12 - MAX_CATCH_UP_RUNS);
+    }
+}
@@ -0,0 +1,504 @@
+use rusqlite::{params, Connection};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::agents::AgentDb;
+use super::claude::extract_first_user_message;
+use super::project_index::projects_dir;
+use super::session_metadata::{parse_tags, SessionFilter};
+
+/// Session metadata cached per JSONL file
+#[derive(Debug, Clone)]
+pub(crate) struct CachedSession {
+    pub session_id: String,
+    pub project_id: String,
+    pub size: u64,
+    pub modified: u64,
+    pub created_at: u64,
+    pub first_message: Option<String>,
+    pub message_timestamp: Option<String>,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub pinned: bool,
+    pub archived: bool,
+    pub note: Option<String>,
+    pub forked_from_session_id: Option<String>,
+}
+
+/// A session file whose cached metadata is missing or out of date
+struct StaleFile {
+    path: PathBuf,
+    session_id: String,
+    size: u64,
+    modified: u64,
+    created_at: u64,
+}
+
+fn secs(time: std::io::Result<SystemTime>) -> u64 {
+    time.unwrap_or(SystemTime::UNIX_EPOCH)
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Reads the first user message of each stale file, spread over a few threads
+fn parse_in_parallel(stale: &[StaleFile]) -> Vec<(Option<String>, Option<String>)> {
+    if stale.is_empty() {
+        return Vec::new();
+    }
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(stale.len());
+    let chunk_size = stale.len().div_ceil(workers);
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = stale
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let handle = scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|file| extract_first_user_message(&file.path))
+                        .collect::<Vec<_>>()
+                });
+                (chunk.len(), handle)
+            })
+            .collect();
+        // Keep results aligned with `stale` even if a worker panicked
+        handles
+            .into_iter()
+            .flat_map(|(len, handle)| handle.join().unwrap_or_else(|_| vec![(None, None); len]))
+            .collect()
+    })
+}
+
+fn cached_files(conn: &Connection, project_id: &str) -> Result<HashMap<String, (u64, u64)>, String> {
+    let mut stmt = conn
+        .prepare("SELECT session_id, size, modified FROM session_cache WHERE project_id = ?1")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![project_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                (row.get::<_, i64>(1)? as u64, row.get::<_, i64>(2)? as u64),
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+}
+
+/// Brings the cache for one project in line with its directory. Only files
+/// whose size or mtime changed are opened; rows for deleted files are dropped.
+pub(crate) fn refresh_session_cache(db: &AgentDb, project_id: &str) -> Result<(), String> {
+    refresh_session_cache_in(db, &projects_dir()?, project_id)
+}
+
+/// The directory is listed and files are parsed without holding the lock,
+/// which is only taken to read the cached keys and to write the changes.
+fn refresh_session_cache_in(
+    db: &AgentDb,
+    projects_dir: &Path,
+    project_id: &str,
+) -> Result<(), String> {
+    let project_dir = projects_dir.join(project_id);
+    if !project_dir.exists() {
+        return Err(format!("Project directory not found: {}", project_id));
+    }
+
+    let cached = cached_files(&*db.0.lock().map_err(|e| e.to_string())?, project_id)?;
+
+    let entries = fs::read_dir(&project_dir)
+        .map_err(|e| format!("Failed to read project directory: {}", e))?;
+    let mut on_disk = HashSet::new();
+    let mut stale = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let Some(session_id) = path.file_stem().and_then(|s| s.to_str()).map(str::to_string)
+        else {
+            continue;
+        };
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let size = metadata.len();
+        let modified = secs(metadata.modified());
+        on_disk.insert(session_id.clone());
+
+        if cached.get(&session_id) == Some(&(size, modified)) {
+            continue;
+        }
+        stale.push(StaleFile {
+            path,
+            session_id,
+            size,
+            modified,
+            created_at: secs(metadata.created().or_else(|_| metadata.modified())),
+        });
+    }
+    let parsed = parse_in_parallel(&stale);
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    // Another listing may have refreshed the same files while we were reading
+    let current = cached_files(&conn, project_id)?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    for (file, (first_message, message_timestamp)) in stale.iter().zip(parsed) {
+        if current.get(&file.session_id) != cached.get(&file.session_id) {
+            continue;
+        }
+        tx.execute(
+            "INSERT INTO session_cache
+             (project_id, session_id, size, modified, created_at, first_message, message_timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(project_id, session_id) DO UPDATE SET
+                size = excluded.size,
+                modified = excluded.modified,
+                created_at = excluded.created_at,
+                first_message = excluded.first_message,
+                message_timestamp = excluded.message_timestamp",
+            params![
+                project_id,
+                file.session_id,
+                file.size as i64,
+                file.modified as i64,
+                file.created_at as i64,
+                first_message,
+                message_timestamp
+            ],
+        )
+        .map_err(|e| format!("Failed to update session cache: {}", e))?;
+    }
+    for (session_id, entry) in &cached {
+        if on_disk.contains(session_id) || current.get(session_id) != Some(entry) {
+            continue;
+        }
+        tx.execute(
+            "DELETE FROM session_cache WHERE project_id = ?1 AND session_id = ?2",
+            params![project_id, session_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    if !stale.is_empty() {
+        log::debug!(
+            "Refreshed {} cached sessions for project {}",
+            stale.len(),
+            project_id
+        );
+    }
+    Ok(())
+}
+
+/// SQL ordering for a session sort key; unknown keys sort by creation time
+fn order_clause(filter: &SessionFilter) -> String {
+    let column = match filter.sort_by.as_deref() {
+        Some("modified") => "c.modified",
+        Some("message_timestamp") => "COALESCE(c.message_timestamp, '')",
+        Some("size") => "c.size",
+        Some("title") => "COALESCE(m.title, c.first_message, '') COLLATE NOCASE",
+        _ => "c.created_at",
+    };
+    let direction = if filter.sort_order.as_deref() == Some("asc") {
+        "ASC"
+    } else {
+        "DESC"
+    };
+    let pinned = if filter.pinned_first.unwrap_or(true) {
+        "COALESCE(m.pinned, 0) DESC, "
+    } else {
+        ""
+    };
+    format!("{}{} {}, c.session_id {}", pinned, column, direction, direction)
+}
+
+/// Lists cached sessions of a project joined with their metadata, filtered
+/// and ordered as requested. Returns the requested page along with the total
+/// number of sessions matching the filter.
+pub(crate) fn list_cached_sessions(
+    conn: &Connection,
+    project_id: &str,
+    filter: &SessionFilter,
+    offset: usize,
+    limit: Option<usize>,
+) -> Result<(Vec<CachedSession>, usize), String> {
+    let mut conditions = vec!["c.project_id = ?".to_string()];
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(project_id.to_string())];
+
+    if filter.archived_only {
+        conditions.push("COALESCE(m.archived, 0) = 1".to_string());
+    } else if !filter.include_archived {
+        conditions.push("COALESCE(m.archived, 0) = 0".to_string());
+    }
+    if filter.pinned_only {
+        conditions.push("COALESCE(m.pinned, 0) = 1".to_string());
+    }
+    for tag in &filter.tags {
+        conditions.push(
+            "EXISTS (SELECT 1 FROM json_each(m.tags) WHERE value = ? COLLATE NOCASE)".to_string(),
+        );
+        params.push(Box::new(tag.clone()));
+    }
+    if let Some(query) = filter.query.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        conditions.push("(m.title LIKE ? OR c.first_message LIKE ? OR m.note LIKE ?)".to_string());
+        let pattern = format!("%{}%", query);
+        for _ in 0..3 {
+            params.push(Box::new(pattern.clone()));
+        }
+    }
+
+    let from_clause = format!(
+        "FROM session_cache c
+         LEFT JOIN session_metadata m ON m.session_id = c.session_id
+         WHERE {}",
+        conditions.join(" AND ")
+    );
+
+    let total: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) {}", from_clause),
+            rusqlite::params_from_iter(params.iter().map(|p| p.as_ref())),
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let sql = format!(
+        "SELECT c.session_id, c.project_id, c.size, c.modified, c.created_at,
+                c.first_message, c.message_timestamp,
+                m.title, m.tags, COALESCE(m.pinned, 0), COALESCE(m.archived, 0), m.note,
+                m.forked_from_session_id
+         {} ORDER BY {} LIMIT ? OFFSET ?",
+        from_clause,
+        order_clause(filter)
+    );
+    params.push(Box::new(limit.map(|l| l as i64).unwrap_or(-1)));
+    params.push(Box::new(offset as i64));
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let sessions = stmt
+        .query_map(
+            rusqlite::params_from_iter(params.iter().map(|p| p.as_ref())),
+            |row| {
+                Ok(CachedSession {
+                    session_id: row.get(0)?,
+                    project_id: row.get(1)?,
+                    size: row.get::<_, i64>(2)? as u64,
+                    modified: row.get::<_, i64>(3)? as u64,
+                    created_at: row.get::<_, i64>(4)? as u64,
+                    first_message: row.get(5)?,
+                    message_timestamp: row.get(6)?,
+                    title: row.get(7)?,
+                    tags: parse_tags(row.get(8)?),
+                    pinned: row.get(9)?,
+                    archived: row.get(10)?,
+                    note: row.get(11)?,
+                    forked_from_session_id: row.get(12)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok((sessions, total as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::create_schema;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    const PROJECT: &str = "-home-me-repo";
+
+    fn cache_db() -> AgentDb {
+        let conn = Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        AgentDb(Mutex::new(conn))
+    }
+
+    fn write_session(projects_dir: &Path, session_id: &str, prompt: &str) -> PathBuf {
+        let dir = projects_dir.join(PROJECT);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(format!("{}.jsonl", session_id));
+        let line = serde_json::json!({
+            "type": "user",
+            "timestamp": "2025-01-02T03:04:05Z",
+            "message": { "role": "user", "content": prompt },
+        });
+        fs::write(&file, format!("{}\n", line)).unwrap();
+        file
+    }
+
+    fn set_modified(path: &Path, secs: u64) {
+        let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    fn list(
+        db: &AgentDb,
+        filter: &SessionFilter,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> (Vec<String>, usize) {
+        let (sessions, total) =
+            list_cached_sessions(&db.0.lock().unwrap(), PROJECT, filter, offset, limit).unwrap();
+        (sessions.into_iter().map(|s| s.session_id).collect(), total)
+    }
+
+    fn first_message(db: &AgentDb, session_id: &str) -> Option<String> {
+        db.0.lock()
+            .unwrap()
+            .query_row(
+                "SELECT first_message FROM session_cache WHERE session_id = ?1",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    fn set_metadata(db: &AgentDb, session_id: &str, title: &str, pinned: bool, archived: bool) {
+        db.0.lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO session_metadata (session_id, project_id, title, pinned, archived)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![session_id, PROJECT, title, pinned, archived],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_refresh_detects_size_and_mtime_changes() {
+        let projects = TempDir::new().unwrap();
+        let db = cache_db();
+        let session = write_session(projects.path(), "s1", "first");
+        set_modified(&session, 1_000);
+        refresh_session_cache_in(&db, projects.path(), PROJECT).unwrap();
+        assert_eq!(first_message(&db, "s1").as_deref(), Some("first"));
+
+        // Same size and mtime: the file is not read again
+        write_session(projects.path(), "s1", "other");
+        set_modified(&session, 1_000);
+        refresh_session_cache_in(&db, projects.path(), PROJECT).unwrap();
+        assert_eq!(first_message(&db, "s1").as_deref(), Some("first"));
+
+        // Same size, new mtime
+        set_modified(&session, 2_000);
+        refresh_session_cache_in(&db, projects.path(), PROJECT).unwrap();
+        assert_eq!(first_message(&db, "s1").as_deref(), Some("other"));
+
+        // New size, same mtime
+        write_session(projects.path(), "s1", "a longer prompt");
+        set_modified(&session, 2_000);
+        refresh_session_cache_in(&db, projects.path(), PROJECT).unwrap();
+        assert_eq!(first_message(&db, "s1").as_deref(), Some("a longer prompt"));
+    }
+
+    #[test]
+    fn test_refresh_drops_deleted_files() {
+        let projects = TempDir::new().unwrap();
+        let db = cache_db();
+        write_session(projects.path(), "s1", "one");
+        let removed = write_session(projects.path(), "s2", "two");
+        fs::write(
+            projects.path().join(PROJECT).join("notes.txt"),
+            "not a session",
+        )
+        .unwrap();
+        refresh_session_cache_in(&db, projects.path(), PROJECT).unwrap();
+        assert_eq!(list(&db, &SessionFilter::default(), 0, None).1, 2);
+
+        fs::remove_file(removed).unwrap();
+        refresh_session_cache_in(&db, projects.path(), PROJECT).unwrap();
+        assert_eq!(list(&db, &SessionFilter::default(), 0, None).0, ["s1"]);
+
+        assert!(refresh_session_cache_in(&db, projects.path(), "-missing").is_err());
+    }
+
+    #[test]
+    fn test_list_filters_sorts_and_pages() {
+        let projects = TempDir::new().unwrap();
+        let db = cache_db();
+        for (i, (session_id, prompt)) in [
+            ("s1", "fix parser"),
+            ("s2", "add tests"),
+            ("s3", "fix lexer"),
+            ("s4", "docs"),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let file = write_session(projects.path(), session_id, prompt);
+            set_modified(&file, 1_000 + i as u64);
+        }
+        refresh_session_cache_in(&db, projects.path(), PROJECT).unwrap();
+        set_metadata(&db, "s1", "Parser", true, false);
+        set_metadata(&db, "s4", "Docs", false, true);
+
+        // Archived sessions are hidden by default; pinned ones come first
+        let by_modified = SessionFilter {
+            sort_by: Some("modified".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            list(&db, &by_modified, 0, None),
+            (vec!["s1".into(), "s3".into(), "s2".into()], 3)
+        );
+        let unpinned = SessionFilter {
+            pinned_first: Some(false),
+            ..by_modified.clone()
+        };
+        assert_eq!(list(&db, &unpinned, 0, None).0, ["s3", "s2", "s1"]);
+        let ascending = SessionFilter {
+            sort_order: Some("asc".to_string()),
+            ..unpinned.clone()
+        };
+        assert_eq!(list(&db, &ascending, 0, None).0, ["s1", "s2", "s3"]);
+
+        // Pages report the total of the whole filtered list
+        assert_eq!(list(&db, &unpinned, 1, Some(1)), (vec!["s2".into()], 3));
+        assert_eq!(list(&db, &unpinned, 3, Some(2)), (vec![], 3));
+
+        let archived = SessionFilter {
+            archived_only: true,
+            ..Default::default()
+        };
+        assert_eq!(list(&db, &archived, 0, None).0, ["s4"]);
+        let everything = SessionFilter {
+            include_archived: true,
+            ..unpinned.clone()
+        };
+        assert_eq!(list(&db, &everything, 0, None).1, 4);
+        let pinned = SessionFilter {
+            pinned_only: true,
+            ..Default::default()
+        };
+        assert_eq!(list(&db, &pinned, 0, None).0, ["s1"]);
+
+        // The query matches titles and first messages
+        let query = SessionFilter {
+            query: Some("FIX".to_string()),
+            ..unpinned.clone()
+        };
+        assert_eq!(list(&db, &query, 0, None).0, ["s3", "s1"]);
+        let by_title = SessionFilter {
+            sort_by: Some("title".to_string()),
+            sort_order: Some("asc".to_string()),
+            pinned_first: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(list(&db, &by_title, 0, None).0, ["s2", "s3", "s1"]);
+    }
+}
@@ -0,0 +1,698 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
+use super::project_index::{check_id, projects_dir};
+use super::usage::calculate_cost;
+use crate::stream_json::{ContentBlock, MessageContent, StreamMessage, Usage};
+
+/// Tool results longer than this are cut off in Markdown/HTML exports
+const MAX_TOOL_RESULT_CHARS: usize = 20_000;
+
+/// A session transcript normalized for export
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportedSession {
+    pub session_id: String,
+    pub project_id: String,
+    pub project_path: Option<String>,
+    pub started_at: Option<String>,
+    pub ended_at: Option<String>,
+    pub total_input_tokens: u64,
+    pub total_output_tokens: u64,
+    pub total_cache_creation_tokens: u64,
+    pub total_cache_read_tokens: u64,
+    pub total_cost: f64,
+    pub turns: Vec<ExportedTurn>,
+}
+
+/// One user or assistant turn. Tool results are attached to the assistant
+/// turn that made the call rather than shown as separate user turns.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportedTurn {
+    /// 'user' or 'assistant'
+    pub role: String,
+    pub uuid: Option<String>,
+    pub timestamp: Option<String>,
+    pub model: Option<String>,
+    pub blocks: Vec<ExportedBlock>,
+    pub usage: Option<TurnUsage>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExportedBlock {
+    Text {
+        text: String,
+    },
+    Thinking {
+        text: String,
+    },
+    ToolUse {
+        id: Option<String>,
+        name: String,
+        input: serde_json::Value,
+        /// Unified-style diff for Edit, MultiEdit and Write calls
+        diff: Option<String>,
+    },
+    ToolResult {
+        tool_use_id: Option<String>,
+        content: String,
+        is_error: bool,
+    },
+}
+
+/// Token usage and cost of a single assistant turn
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TurnUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cost: f64,
+}
+
+fn session_file_path(project_id: &str, session_id: &str) -> Result<PathBuf, String> {
+    check_id("project ID", project_id)?;
+    check_id("session ID", session_id)?;
+    Ok(projects_dir()?
+        .join(project_id)
+        .join(format!("{}.jsonl", session_id)))
+}
+
+/// Flattens tool result content, which is either a string or a list of text blocks
+fn tool_result_text(content: Option<&serde_json::Value>) -> String {
+    match content {
+        Some(serde_json::Value::String(text)) => text.clone(),
+        Some(serde_json::Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Some(other) => other.to_string(),
+        None => String::new(),
+    }
+}
+
+fn push_diff_hunk(diff: &mut String, old: &str, new: &str) {
+    diff.push_str("@@\n");
+    for line in old.lines() {
+        let _ = writeln!(diff, "-{}", line);
+    }
+    for line in new.lines() {
+        let _ = writeln!(diff, "+{}", line);
+    }
+}
+
+/// Builds a diff from the inputs of file-editing tools
+fn tool_diff(name: &str, input: &serde_json::Value) -> Option<String> {
+    let file_path = input.get("file_path").and_then(|p| p.as_str())?;
+    let mut diff = format!("--- a/{0}\n+++ b/{0}\n", file_path.trim_start_matches('/'));
+
+    match name {
+        "Edit" => {
+            let old = input.get("old_string").and_then(|s| s.as_str()).unwrap_or("");
+            let new = input.get("new_string").and_then(|s| s.as_str()).unwrap_or("");
+            push_diff_hunk(&mut diff, old, new);
+        }
+        "MultiEdit" => {
+            let edits = input.get("edits").and_then(|e| e.as_array())?;
+            for edit in edits {
+                let old = edit.get("old_string").and_then(|s| s.as_str()).unwrap_or("");
+                let new = edit.get("new_string").and_then(|s| s.as_str()).unwrap_or("");
+                push_diff_hunk(&mut diff, old, new);
+            }
+        }
+        "Write" => {
+            let content = input.get("content").and_then(|s| s.as_str()).unwrap_or("");
+            push_diff_hunk(&mut diff, "", content);
+        }
+        _ => return None,
+    }
+
+    Some(diff)
+}
+
+fn parse_blocks(content: &MessageContent) -> Vec<ExportedBlock> {
+    let items = match content {
+        MessageContent::Text(text) => return vec![ExportedBlock::Text { text: text.clone() }],
+        MessageContent::Blocks(items) => items,
+    };
+    items
+        .iter()
+        .filter_map(|item| match item {
+            ContentBlock::Text(block) => Some(ExportedBlock::Text {
+                text: block.text.clone(),
+            }),
+            ContentBlock::ToolUse(block) => Some(ExportedBlock::ToolUse {
+                id: Some(block.id.clone()).filter(|id| !id.is_empty()),
+                diff: tool_diff(&block.name, &block.input),
+                name: block.name.clone(),
+                input: block.input.clone(),
+            }),
+            ContentBlock::ToolResult(block) => Some(ExportedBlock::ToolResult {
+                tool_use_id: Some(block.tool_use_id.clone()).filter(|id| !id.is_empty()),
+                content: tool_result_text(block.content.as_ref()),
+                is_error: block.is_error.unwrap_or(false),
+            }),
+            ContentBlock::Unknown(value) if value["type"] == "thinking" => {
+                Some(ExportedBlock::Thinking {
+                    text: value["thinking"].as_str().unwrap_or_default().to_string(),
+                })
+            }
+            ContentBlock::Unknown(_) => None,
+        })
+        .collect()
+}
+
+fn turn_usage(model: Option<&str>, usage: &Usage, cost_usd: Option<f64>) -> TurnUsage {
+    let cost = cost_usd.unwrap_or_else(|| {
+        model
+            .map(|model| calculate_cost(model, usage))
+            .unwrap_or(0.0)
+    });
+    TurnUsage {
+        input_tokens: usage.input_tokens.unwrap_or(0),
+        output_tokens: usage.output_tokens.unwrap_or(0),
+        cache_creation_tokens: usage.cache_creation_input_tokens.unwrap_or(0),
+        cache_read_tokens: usage.cache_read_input_tokens.unwrap_or(0),
+        cost,
+    }
+}
+
+/// Reads a session JSONL and folds it into turns.
+pub(crate) fn load_exported_session(
+    project_id: &str,
+    session_id: &str,
+) -> Result<ExportedSession, String> {
+    let path = session_file_path(project_id, session_id)?;
+    if !path.exists() {
+        return Err(format!("Session file not found: {}", session_id));
+    }
+    let file = fs::File::open(&path).map_err(|e| format!("Failed to open session file: {}", e))?;
+    Ok(read_exported_session(BufReader::new(file), project_id, session_id))
+}
+
+/// Folds transcript lines into turns. Claude Code writes one line per content
+/// block of an assistant message, all sharing the message id and usage, so
+/// those lines are merged into one turn.
+fn read_exported_session(reader: impl BufRead, project_id: &str, session_id: &str) -> ExportedSession {
+    let mut session = ExportedSession {
+        session_id: session_id.to_string(),
+        project_id: project_id.to_string(),
+        project_path: None,
+        started_at: None,
+        ended_at: None,
+        total_input_tokens: 0,
+        total_output_tokens: 0,
+        total_cache_creation_tokens: 0,
+        total_cache_read_tokens: 0,
+        total_cost: 0.0,
+        turns: Vec::new(),
+    };
+    let mut last_message_id: Option<String> = None;
+
+    for line in reader.lines().map_while(Result::ok) {
+        let Some(msg) = StreamMessage::parse(&line) else {
+            continue;
+        };
+        let role = match &msg {
+            StreamMessage::User(_) => "user".to_string(),
+            StreamMessage::Assistant(_) => "assistant".to_string(),
+            _ => continue,
+        };
+        let Some(entry) = msg.conversation() else {
+            continue;
+        };
+        if entry.extra.get("isMeta").and_then(|m| m.as_bool()) == Some(true) {
+            continue;
+        }
+        let Some(message) = &entry.message else {
+            continue;
+        };
+
+        if session.project_path.is_none() {
+            session.project_path = entry.cwd.clone();
+        }
+        let timestamp = entry.timestamp.clone();
+        if session.started_at.is_none() {
+            session.started_at = timestamp.clone();
+        }
+        if timestamp.is_some() {
+            session.ended_at = timestamp.clone();
+        }
+
+        let blocks = message
+            .content
+            .as_ref()
+            .map(parse_blocks)
+            .unwrap_or_default();
+        let message_id = message.id.clone();
+
+        // Tool results arrive as user lines; keep them with the call that produced them
+        let only_results = !blocks.is_empty()
+            && blocks
+                .iter()
+                .all(|b| matches!(b, ExportedBlock::ToolResult { .. }));
+        if role == "user" && only_results {
+            if let Some(turn) = session.turns.last_mut().filter(|t| t.role == "assistant") {
+                turn.blocks.extend(blocks);
+                continue;
+            }
+        }
+
+        if role == "assistant" && message_id.is_some() && message_id == last_message_id {
+            if let Some(turn) = session.turns.last_mut() {
+                turn.blocks.extend(blocks);
+                continue;
+            }
+        }
+
+        let usage = match &message.usage {
+            Some(usage) if role == "assistant" => Some(turn_usage(
+                message.model.as_deref(),
+                usage,
+                entry.cost_usd,
+            )),
+            _ => None,
+        };
+        if let Some(usage) = &usage {
+            session.total_input_tokens += usage.input_tokens;
+            session.total_output_tokens += usage.output_tokens;
+            session.total_cache_creation_tokens += usage.cache_creation_tokens;
+            session.total_cache_read_tokens += usage.cache_read_tokens;
+            session.total_cost += usage.cost;
+        }
+
+        last_message_id = if role == "assistant" { message_id } else { None };
+        session.turns.push(ExportedTurn {
+            role,
+            uuid: entry.uuid.clone(),
+            timestamp,
+            model: message.model.clone(),
+            blocks,
+            usage,
+        });
+    }
+
+    session
+}
+
+fn truncate_result(content: &str) -> String {
+    if content.chars().count() <= MAX_TOOL_RESULT_CHARS {
+        return content.to_string();
+    }
+    let kept: String = content.chars().take(MAX_TOOL_RESULT_CHARS).collect();
+    format!("{}\n… (truncated)", kept)
+}
+
+/// Short label shown next to a tool name, e.g. the file or command it acted on
+fn tool_label(input: &serde_json::Value) -> Option<String> {
+    ["file_path", "notebook_path", "path", "command", "pattern", "url"]
+        .iter()
+        .find_map(|key| input.get(*key).and_then(|v| v.as_str()))
+        .map(|value| value.lines().next().unwrap_or_default().to_string())
+}
+
+fn usage_summary(usage: &TurnUsage) -> String {
+    format!(
+        "{} in / {} out / {} cache write / {} cache read · ${:.4}",
+        usage.input_tokens,
+        usage.output_tokens,
+        usage.cache_creation_tokens,
+        usage.cache_read_tokens,
+        usage.cost
+    )
+}
+
+/// Picks a backtick fence longer than any run of backticks in the content
+fn code_fence(content: &str) -> String {
+    let mut longest = 0;
+    let mut current = 0;
+    for c in content.chars() {
+        if c == '`' {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn push_fenced(out: &mut String, lang: &str, content: &str) {
+    let fence = code_fence(content);
+    let _ = writeln!(out, "{}{}\n{}\n{}\n", fence, lang, content.trim_end(), fence);
+}
+
+fn render_markdown(session: &ExportedSession) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Session {}\n", session.session_id);
+    if let Some(path) = &session.project_path {
+        let _ = writeln!(out, "- **Project:** `{}`", path);
+    }
+    if let (Some(start), Some(end)) = (&session.started_at, &session.ended_at) {
+        let _ = writeln!(out, "- **Time:** {} → {}", start, end);
+    }
+    let _ = writeln!(
+        out,
+        "- **Tokens:** {} in / {} out / {} cache write / {} cache read",
+        session.total_input_tokens,
+        session.total_output_tokens,
+        session.total_cache_creation_tokens,
+        session.total_cache_read_tokens
+    );
+    let _ = writeln!(out, "- **Cost:** ${:.4}\n", session.total_cost);
+
+    for turn in &session.turns {
+        let title = if turn.role == "user" { "User" } else { "Assistant" };
+        let _ = write!(out, "---\n\n## {}", title);
+        if let Some(timestamp) = &turn.timestamp {
+            let _ = write!(out, " · {}", timestamp);
+        }
+        out.push_str("\n\n");
+        if let Some(usage) = &turn.usage {
+            let model = turn.model.as_deref().unwrap_or("unknown model");
+            let _ = writeln!(out, "_{} · {}_\n", model, usage_summary(usage));
+        }
+
+        for block in &turn.blocks {
+            match block {
+                ExportedBlock::Text { text } => {
+                    let _ = writeln!(out, "{}\n", text.trim_end());
+                }
+                ExportedBlock::Thinking { text } => {
+                    out.push_str("<details>\n<summary>Thinking</summary>\n\n");
+                    let _ = writeln!(out, "{}\n\n</details>\n", text.trim_end());
+                }
+                ExportedBlock::ToolUse {
+                    name, input, diff, ..
+                } => {
+                    let summary = match tool_label(input) {
+                        Some(label) => format!(
+                            "Tool: {} · <code>{}</code>",
+                            html_escape(name),
+                            html_escape(&label)
+                        ),
+                        None => format!("Tool: {}", html_escape(name)),
+                    };
+                    let _ = writeln!(out, "<details>\n<summary>{}</summary>\n", summary);
+                    match diff {
+                        Some(diff) => push_fenced(&mut out, "diff", diff),
+                        None => push_fenced(
+                            &mut out,
+                            "json",
+                            &serde_json::to_string_pretty(input).unwrap_or_default(),
+                        ),
+                    }
+                    out.push_str("</details>\n\n");
+                }
+                ExportedBlock::ToolResult {
+                    content, is_error, ..
+                } => {
+                    let summary = if *is_error { "Tool error" } else { "Tool result" };
+                    let _ = writeln!(out, "<details>\n<summary>{}</summary>\n", summary);
+                    push_fenced(&mut out, "", &truncate_result(content));
+                    out.push_str("</details>\n\n");
+                }
+            }
+        }
+    }
+
+    out
+}
+
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn render_html_diff(out: &mut String, diff: &str) {
+    out.push_str("<pre class=\"diff\">");
+    for line in diff.lines() {
+        let class = match line.chars().next() {
+            Some('+') if !line.starts_with("+++") => "add",
+            Some('-') if !line.starts_with("---") => "del",
+            Some('@') => "hunk",
+            _ => "ctx",
+        };
+        let _ = writeln!(out, "<span class=\"{}\">{}</span>", class, html_escape(line));
+    }
+    out.push_str("</pre>\n");
+}
+
+const HTML_STYLE: &str = "body{font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',sans-serif;max-width:960px;margin:2rem auto;padding:0 1rem;color:#1f2328;line-height:1.5}\
+h1{font-size:1.5rem}.meta{color:#59636e;font-size:.9rem}\
+.turn{border:1px solid #d1d9e0;border-radius:8px;padding:.75rem 1rem;margin:1rem 0}\
+.turn.user{background:#f6f8fa}.turn h2{font-size:1rem;margin:0 0 .5rem}\
+.usage{color:#59636e;font-size:.8rem;margin-bottom:.5rem}\
+.text{white-space:pre-wrap}details{margin:.5rem 0}summary{cursor:pointer;font-weight:600}\
+pre{background:#f6f8fa;border-radius:6px;padding:.75rem;overflow-x:auto;font-size:.85rem}\
+.error summary{color:#cf222e}.diff span{display:block}.diff .add{background:#dafbe1}\
+.diff .del{background:#ffebe9}.diff .hunk{color:#0969da}";
+
+fn render_html(session: &ExportedSession) -> String {
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Session {}</title>\n<style>{}</style>\n</head>\n<body>\n",
+        html_escape(&session.session_id),
+        HTML_STYLE
+    );
+    let _ = writeln!(out, "<h1>Session {}</h1>", html_escape(&session.session_id));
+    out.push_str("<div class=\"meta\">\n");
+    if let Some(path) = &session.project_path {
+        let _ = writeln!(out, "<div>Project: <code>{}</code></div>", html_escape(path));
+    }
+    if let (Some(start), Some(end)) = (&session.started_at, &session.ended_at) {
+        let _ = writeln!(out, "<div>Time: {} → {}</div>", html_escape(start), html_escape(end));
+    }
+    let _ = writeln!(
+        out,
+        "<div>Tokens: {} in / {} out / {} cache write / {} cache read · Cost: ${:.4}</div>\n</div>",
+        session.total_input_tokens,
+        session.total_output_tokens,
+        session.total_cache_creation_tokens,
+        session.total_cache_read_tokens,
+        session.total_cost
+    );
+
+    for turn in &session.turns {
+        let title = if turn.role == "user" { "User" } else { "Assistant" };
+        let _ = write!(out, "<section class=\"turn {}\">\n<h2>{}", turn.role, title);
+        if let Some(timestamp) = &turn.timestamp {
+            let _ = write!(out, " · {}", html_escape(timestamp));
+        }
+        out.push_str("</h2>\n");
+        if let Some(usage) = &turn.usage {
+            let model = turn.model.as_deref().unwrap_or("unknown model");
+            let _ = writeln!(
+                out,
+                "<div class=\"usage\">{} · {}</div>",
+                html_escape(model),
+                html_escape(&usage_summary(usage))
+            );
+        }
+
+        for block in &turn.blocks {
+            match block {
+                ExportedBlock::Text { text } => {
+                    let _ = writeln!(out, "<div class=\"text\">{}</div>", html_escape(text.trim_end()));
+                }
+                ExportedBlock::Thinking { text } => {
+                    let _ = writeln!(
+                        out,
+                        "<details><summary>Thinking</summary><div class=\"text\">{}</div></details>",
+                        html_escape(text.trim_end())
+                    );
+                }
+                ExportedBlock::ToolUse {
+                    name, input, diff, ..
+                } => {
+                    let _ = write!(out, "<details><summary>Tool: {}", html_escape(name));
+                    if let Some(label) = tool_label(input) {
+                        let _ = write!(out, " · <code>{}</code>", html_escape(&label));
+                    }
+                    out.push_str("</summary>\n");
+                    match diff {
+                        Some(diff) => render_html_diff(&mut out, diff),
+                        None => {
+                            let _ = writeln!(
+                                out,
+                                "<pre>{}</pre>",
+                                html_escape(&serde_json::to_string_pretty(input).unwrap_or_default())
+                            );
+                        }
+                    }
+                    out.push_str("</details>\n");
+                }
+                ExportedBlock::ToolResult {
+                    content, is_error, ..
+                } => {
+                    let (class, summary) = if *is_error {
+                        (" class=\"error\"", "Tool error")
+                    } else {
+                        ("", "Tool result")
+                    };
+                    let _ = writeln!(
+                        out,
+                        "<details{}><summary>{}</summary><pre>{}</pre></details>",
+                        class,
+                        summary,
+                        html_escape(&truncate_result(content))
+                    );
+                }
+            }
+        }
+        out.push_str("</section>\n");
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Renders a session as a Markdown, self-contained HTML or normalized JSON transcript.
+/// When `output_path` is given the transcript is also written there.
+#[tauri::command]
+pub async fn export_session(
+    session_id: String,
+    project_id: String,
+    format: String,
+    output_path: Option<String>,
+) -> Result<String, String> {
+    log::info!(
+        "Exporting session {} in project {} as {}",
+        session_id,
+        project_id,
+        format
+    );
+
+    let session = load_exported_session(&project_id, &session_id)?;
+    let rendered = match format.as_str() {
+        "markdown" | "md" => render_markdown(&session),
+        "html" => render_html(&session),
+        "json" => serde_json::to_string_pretty(&session)
+            .map_err(|e| format!("Failed to serialize session: {}", e))?,
+        other => return Err(format!("Unsupported export format: {}", other)),
+    };
+
+    if let Some(output_path) = output_path {
+        fs::write(&output_path, &rendered)
+            .map_err(|e| format!("Failed to write export file: {}", e))?;
+        log::info!("Exported session {} to {}", session_id, output_path);
+    }
+
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A user prompt, one assistant message split over two lines, its tool
+    /// result, a meta line and a second assistant message
+    const TRANSCRIPT: &str = r#"{"type":"user","uuid":"u1","timestamp":"2025-01-01T10:00:00Z","cwd":"/p","message":{"role":"user","content":"Fix <main>"}}
+{"type":"assistant","uuid":"a1","timestamp":"2025-01-01T10:00:01Z","costUSD":0.5,"message":{"id":"msg_1","model":"claude-sonnet-4","content":[{"type":"text","text":"Looking"}],"usage":{"input_tokens":10,"output_tokens":5}}}
+{"type":"assistant","uuid":"a2","timestamp":"2025-01-01T10:00:02Z","costUSD":0.5,"message":{"id":"msg_1","model":"claude-sonnet-4","content":[{"type":"tool_use","id":"t1","name":"Edit","input":{"file_path":"/src/main.rs","old_string":"a","new_string":"b"}}],"usage":{"input_tokens":10,"output_tokens":5}}}
+{"type":"user","uuid":"u2","timestamp":"2025-01-01T10:00:03Z","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":[{"type":"text","text":"done"}]}]}}
+{"type":"user","isMeta":true,"message":{"role":"user","content":"caveat"}}
+{"type":"assistant","uuid":"a3","timestamp":"2025-01-01T10:00:04Z","costUSD":0.25,"message":{"id":"msg_2","model":"claude-sonnet-4","content":[{"type":"text","text":"Fixed"}],"usage":{"input_tokens":20,"output_tokens":7}}}
+"#;
+
+    fn session() -> ExportedSession {
+        read_exported_session(TRANSCRIPT.as_bytes(), "project", "session")
+    }
+
+    #[test]
+    fn test_merges_lines_and_attaches_tool_results() {
+        let session = session();
+        let roles: Vec<&str> = session.turns.iter().map(|t| t.role.as_str()).collect();
+        assert_eq!(roles, ["user", "assistant", "assistant"]);
+        assert_eq!(session.project_path.as_deref(), Some("/p"));
+        assert_eq!(session.started_at.as_deref(), Some("2025-01-01T10:00:00Z"));
+        assert_eq!(session.ended_at.as_deref(), Some("2025-01-01T10:00:04Z"));
+
+        let first = &session.turns[1];
+        assert_eq!(first.uuid.as_deref(), Some("a1"));
+        assert_eq!(first.blocks.len(), 3);
+        assert!(matches!(&first.blocks[0], ExportedBlock::Text { text } if text == "Looking"));
+        match &first.blocks[1] {
+            ExportedBlock::ToolUse { id, name, diff, .. } => {
+                assert_eq!((id.as_deref(), name.as_str()), (Some("t1"), "Edit"));
+                assert_eq!(diff.as_deref(), Some("--- a/src/main.rs\n+++ b/src/main.rs\n@@\n-a\n+b\n"));
+            }
+            other => panic!("expected a tool use, got {:?}", other),
+        }
+        match &first.blocks[2] {
+            ExportedBlock::ToolResult { tool_use_id, content, is_error } => {
+                assert_eq!((tool_use_id.as_deref(), content.as_str(), *is_error), (Some("t1"), "done", false));
+            }
+            other => panic!("expected a tool result, got {:?}", other),
+        }
+
+        // Usage repeated on every line of a message is counted once
+        assert_eq!(session.total_input_tokens, 30);
+        assert_eq!(session.total_output_tokens, 12);
+        assert!((session.total_cost - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_tool_result_without_call_stays_a_user_turn() {
+        let transcript = r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t0","content":"orphan","is_error":true}]}}"#;
+        let session = read_exported_session(transcript.as_bytes(), "project", "session");
+        assert_eq!(session.turns.len(), 1);
+        assert_eq!(session.turns[0].role, "user");
+        assert!(matches!(&session.turns[0].blocks[0], ExportedBlock::ToolResult { is_error: true, .. }));
+    }
+
+    #[test]
+    fn test_renderers_escape_html() {
+        let mut session = session();
+        session.turns[1].blocks.push(ExportedBlock::ToolUse {
+            id: None,
+            name: "mcp__<x>".to_string(),
+            input: serde_json::json!({"command": "echo \"<b>\" & exit"}),
+            diff: None,
+        });
+
+        let markdown = render_markdown(&session);
+        assert!(markdown.contains("<summary>Tool: Edit · <code>/src/main.rs</code></summary>"));
+        assert!(markdown.contains("<summary>Tool: mcp__&lt;x&gt; · <code>echo &quot;&lt;b&gt;&quot; &amp; exit</code></summary>"));
+        assert!(markdown.contains("```diff\n--- a/src/main.rs"));
+
+        let html = render_html(&session);
+        assert!(html.contains("<div class=\"text\">Fix &lt;main&gt;</div>"));
+        assert!(html.contains("<summary>Tool: mcp__&lt;x&gt; · <code>echo &quot;&lt;b&gt;&quot; &amp; exit</code></summary>"));
+        assert!(html.contains("<span class=\"del\">-a</span>"));
+        assert!(!html.contains("<main>"));
+        assert!(!html.contains("<b>"));
+
+        assert_eq!(html_escape("a<'b'>&\"c\""), "a&lt;&#39;b&#39;&gt;&amp;&quot;c&quot;");
+        assert_eq!(code_fence("``` nested ````"), "`````");
+    }
+
+    #[test]
+    fn test_rejects_ids_that_leave_the_projects_dir() {
+        for (project_id, session_id) in [
+            ("..", "session"),
+            ("project", "../../.ssh/id_rsa"),
+            ("a/b", "session"),
+            ("project", "a\\b"),
+            ("", "session"),
+        ] {
+            let err = session_file_path(project_id, session_id).unwrap_err();
+            assert!(err.starts_with("Invalid"), "{}", err);
+        }
+    }
+}
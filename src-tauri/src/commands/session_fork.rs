@@ -0,0 +1,273 @@
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use tauri::State;
+
+use super::agents::AgentDb;
+use super::project_index::{check_id, projects_dir};
+
+/// Upper bound on tool-result lines pulled in after the fork point
+const MAX_TRAILING_TOOL_RESULTS: usize = 16;
+
+/// Result of forking a session
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SessionForkResult {
+    /// Fresh session id of the fork, usable with `resume_claude_code`
+    pub session_id: String,
+    pub project_id: String,
+    pub forked_from_session_id: String,
+    pub forked_from_message_uuid: String,
+    /// Number of transcript lines copied into the fork
+    pub message_count: usize,
+}
+
+fn entry_uuid(entry: &serde_json::Value) -> Option<&str> {
+    entry.get("uuid").and_then(|u| u.as_str())
+}
+
+fn parent_uuid(entry: &serde_json::Value) -> Option<&str> {
+    entry.get("parentUuid").and_then(|u| u.as_str())
+}
+
+fn has_block(entry: &serde_json::Value, block_type: &str) -> bool {
+    entry
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_array())
+        .map(|blocks| {
+            blocks
+                .iter()
+                .any(|b| b.get("type").and_then(|t| t.as_str()) == Some(block_type))
+        })
+        .unwrap_or(false)
+}
+
+/// Values of `key` on the content blocks of the given type, e.g. the `id` of
+/// each `tool_use` block
+fn block_ids<'a>(entry: &'a serde_json::Value, block_type: &str, key: &str) -> Vec<&'a str> {
+    entry
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_array())
+        .map(|blocks| {
+            blocks
+                .iter()
+                .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some(block_type))
+                .filter_map(|b| b.get(key).and_then(|v| v.as_str()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Picks the transcript lines that lead to `message_uuid`, in file order.
+/// Only the target's ancestors are kept so abandoned branches and sidechains
+/// don't leak into the fork. Tool calls on the branch that have no result yet
+/// get their results pulled in too, otherwise Claude Code would refuse to
+/// resume the session. Parallel calls are written as a chain of tool_use
+/// lines followed by a chain of tool_result lines, so the chain is followed
+/// until every call is answered.
+fn select_branch(
+    entries: &[serde_json::Value],
+    message_uuid: &str,
+) -> Result<Vec<serde_json::Value>, String> {
+    let by_uuid: HashMap<&str, usize> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| entry_uuid(e).map(|u| (u, i)))
+        .collect();
+
+    let mut target = *by_uuid
+        .get(message_uuid)
+        .ok_or_else(|| format!("Message {} not found in session", message_uuid))?;
+
+    let mut keep = HashSet::new();
+    let mut current = Some(target);
+    while let Some(index) = current {
+        if !keep.insert(index) {
+            break;
+        }
+        current = parent_uuid(&entries[index]).and_then(|p| by_uuid.get(p).copied());
+    }
+
+    let mut pending: HashSet<&str> = keep
+        .iter()
+        .flat_map(|&i| block_ids(&entries[i], "tool_use", "id"))
+        .collect();
+    for &i in &keep {
+        for id in block_ids(&entries[i], "tool_result", "tool_use_id") {
+            pending.remove(id);
+        }
+    }
+
+    for _ in 0..MAX_TRAILING_TOOL_RESULTS {
+        if pending.is_empty() {
+            break;
+        }
+        let Some(target_uuid) = entry_uuid(&entries[target]) else {
+            break;
+        };
+        let child = entries.iter().position(|e| {
+            parent_uuid(e) == Some(target_uuid)
+                && (has_block(e, "tool_result") || has_block(e, "tool_use"))
+        });
+        match child {
+            Some(index) => {
+                keep.insert(index);
+                pending.extend(block_ids(&entries[index], "tool_use", "id"));
+                for id in block_ids(&entries[index], "tool_result", "tool_use_id") {
+                    pending.remove(id);
+                }
+                target = index;
+            }
+            None => break,
+        }
+    }
+
+    let mut indices: Vec<usize> = keep.into_iter().collect();
+    indices.sort_unstable();
+    Ok(indices.into_iter().map(|i| entries[i].clone()).collect())
+}
+
+/// Rewrites session ids and uuids so the fork is a standalone session whose
+/// parent chain only references its own lines
+fn rewrite_ids(branch: &mut [serde_json::Value], new_session_id: &str) {
+    let uuid_map: HashMap<String, String> = branch
+        .iter()
+        .filter_map(entry_uuid)
+        .map(|u| (u.to_string(), uuid::Uuid::new_v4().to_string()))
+        .collect();
+
+    for entry in branch.iter_mut() {
+        let Some(object) = entry.as_object_mut() else {
+            continue;
+        };
+        object.insert("sessionId".to_string(), new_session_id.into());
+        for key in ["uuid", "parentUuid", "logicalParentUuid"] {
+            let mapped = object
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(|old| uuid_map.get(old).cloned());
+            match mapped {
+                Some(Some(new_uuid)) => {
+                    object.insert(key.to_string(), new_uuid.into());
+                }
+                // Points at a line that is not part of the fork
+                Some(None) if key != "uuid" => {
+                    object.insert(key.to_string(), serde_json::Value::Null);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Forks a session at a message: the conversation up to and including
+/// `message_uuid` is copied into a new session file with a fresh session id.
+/// Project files are not touched.
+#[tauri::command]
+pub async fn fork_session(
+    db: State<'_, AgentDb>,
+    project_id: String,
+    session_id: String,
+    message_uuid: String,
+    title: Option<String>,
+) -> Result<SessionForkResult, String> {
+    log::info!(
+        "Forking session {} at message {}",
+        session_id,
+        message_uuid
+    );
+
+    check_id("project ID", &project_id)?;
+    check_id("session ID", &session_id)?;
+    let project_dir = projects_dir()?.join(&project_id);
+    let source_path = project_dir.join(format!("{}.jsonl", session_id));
+    let content = fs::read_to_string(&source_path)
+        .map_err(|e| format!("Failed to read session file: {}", e))?;
+
+    let entries: Vec<serde_json::Value> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .filter(|entry: &serde_json::Value| {
+            entry_uuid(entry).is_some()
+                && entry.get("isSidechain").and_then(|s| s.as_bool()) != Some(true)
+        })
+        .collect();
+
+    let mut branch = select_branch(&entries, &message_uuid)?;
+    let new_session_id = uuid::Uuid::new_v4().to_string();
+    rewrite_ids(&mut branch, &new_session_id);
+
+    let mut output = String::new();
+    for entry in &branch {
+        output.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?);
+        output.push('\n');
+    }
+    let fork_path = project_dir.join(format!("{}.jsonl", new_session_id));
+    fs::write(&fork_path, output).map_err(|e| format!("Failed to write forked session: {}", e))?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO session_metadata
+         (session_id, project_id, title, forked_from_session_id, forked_from_message_uuid)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            new_session_id,
+            project_id,
+            title.filter(|t| !t.trim().is_empty()),
+            session_id,
+            message_uuid
+        ],
+    )
+    .map_err(|e| format!("Failed to record fork: {}", e))?;
+
+    log::info!(
+        "Forked session {} into {} with {} messages",
+        session_id,
+        new_session_id,
+        branch.len()
+    );
+
+    Ok(SessionForkResult {
+        session_id: new_session_id,
+        project_id,
+        forked_from_session_id: session_id,
+        forked_from_message_uuid: message_uuid,
+        message_count: branch.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn line(uuid: &str, parent: Option<&str>, content: serde_json::Value) -> serde_json::Value {
+        json!({"uuid": uuid, "parentUuid": parent, "message": {"content": content}})
+    }
+
+    #[test]
+    fn test_fork_keeps_results_of_parallel_tool_calls() {
+        let entries = vec![
+            line("u1", None, json!([{"type": "text", "text": "list and read"}])),
+            line("a1", Some("u1"), json!([{"type": "tool_use", "id": "A", "name": "LS"}])),
+            line("a2", Some("a1"), json!([{"type": "tool_use", "id": "B", "name": "Read"}])),
+            line("r1", Some("a2"), json!([{"type": "tool_result", "tool_use_id": "A"}])),
+            line("r2", Some("r1"), json!([{"type": "tool_result", "tool_use_id": "B"}])),
+            line("a3", Some("r2"), json!([{"type": "text", "text": "done"}])),
+        ];
+        let uuids = |branch: Vec<serde_json::Value>| -> Vec<String> {
+            branch.iter().map(|e| entry_uuid(e).unwrap().to_string()).collect()
+        };
+
+        // Forking at either call or at the first result answers both calls
+        for at in ["a1", "a2", "r1"] {
+            let branch = select_branch(&entries, at).unwrap();
+            assert_eq!(uuids(branch), ["u1", "a1", "a2", "r1", "r2"], "fork at {}", at);
+        }
+        let branch = select_branch(&entries, "a3").unwrap();
+        assert_eq!(branch.len(), 6);
+        assert!(select_branch(&entries, "missing").is_err());
+    }
+}
@@ -0,0 +1,202 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use super::agents::AgentDb;
+
+/// User-editable metadata attached to a session
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionMetadata {
+    pub session_id: String,
+    pub project_id: String,
+    /// Custom title shown instead of the first message
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub pinned: bool,
+    pub archived: bool,
+    /// Free-form note
+    pub note: Option<String>,
+    /// Session this one was forked from, if any
+    pub forked_from_session_id: Option<String>,
+    /// Message in the origin session the fork was taken at
+    pub forked_from_message_uuid: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+/// Partial update of session metadata. Fields left out keep their current
+/// value; an empty title or note clears it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionMetadataUpdate {
+    pub title: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub pinned: Option<bool>,
+    pub archived: Option<bool>,
+    pub note: Option<String>,
+}
+
+/// Filter and sort options for session listings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionFilter {
+    /// Include archived sessions (hidden by default)
+    #[serde(default)]
+    pub include_archived: bool,
+    /// Only return archived sessions
+    #[serde(default)]
+    pub archived_only: bool,
+    /// Only return pinned sessions
+    #[serde(default)]
+    pub pinned_only: bool,
+    /// Sessions must carry every one of these tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Case-insensitive match against title, first message and note
+    pub query: Option<String>,
+    /// 'created_at' (default), 'modified', 'message_timestamp', 'size' or 'title'
+    pub sort_by: Option<String>,
+    /// 'desc' (default) or 'asc'
+    pub sort_order: Option<String>,
+    /// Keep pinned sessions above the rest regardless of sort (default true)
+    pub pinned_first: Option<bool>,
+}
+
+pub(crate) fn parse_tags(raw: Option<String>) -> Vec<String> {
+    raw.and_then(|tags| serde_json::from_str(&tags).ok())
+        .unwrap_or_default()
+}
+
+/// Trims tags, drops empty ones and duplicates, keeping the first spelling
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_string();
+        if !tag.is_empty() && !normalized.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+/// Empty strings clear a text field
+fn non_empty(value: String) -> Option<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_string())
+    }
+}
+
+pub(crate) fn get_metadata(
+    conn: &Connection,
+    session_id: &str,
+) -> Result<Option<SessionMetadata>, String> {
+    conn.query_row(
+        "SELECT session_id, project_id, title, tags, pinned, archived, note,
+                forked_from_session_id, forked_from_message_uuid, updated_at
+         FROM session_metadata WHERE session_id = ?1",
+        params![session_id],
+        |row| {
+            Ok(SessionMetadata {
+                session_id: row.get(0)?,
+                project_id: row.get(1)?,
+                title: row.get(2)?,
+                tags: parse_tags(row.get(3)?),
+                pinned: row.get(4)?,
+                archived: row.get(5)?,
+                note: row.get(6)?,
+                forked_from_session_id: row.get(7)?,
+                forked_from_message_uuid: row.get(8)?,
+                updated_at: row.get(9)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// Gets the metadata of a session, with defaults if none has been set
+#[tauri::command]
+pub async fn get_session_metadata(
+    db: State<'_, AgentDb>,
+    project_id: String,
+    session_id: String,
+) -> Result<SessionMetadata, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(get_metadata(&conn, &session_id)?.unwrap_or(SessionMetadata {
+        session_id,
+        project_id,
+        ..Default::default()
+    }))
+}
+
+/// Updates session metadata
+#[tauri::command]
+pub async fn update_session_metadata(
+    db: State<'_, AgentDb>,
+    project_id: String,
+    session_id: String,
+    update: SessionMetadataUpdate,
+) -> Result<SessionMetadata, String> {
+    log::info!("Updating metadata for session {}", session_id);
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let current = get_metadata(&conn, &session_id)?.unwrap_or_default();
+
+    let title = match update.title {
+        Some(title) => non_empty(title),
+        None => current.title,
+    };
+    let tags = normalize_tags(update.tags.unwrap_or(current.tags));
+    let note = match update.note {
+        Some(note) => non_empty(note),
+        None => current.note,
+    };
+    let tags_json = serde_json::to_string(&tags).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "INSERT INTO session_metadata (session_id, project_id, title, tags, pinned, archived, note)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(session_id) DO UPDATE SET
+            project_id = excluded.project_id,
+            title = excluded.title,
+            tags = excluded.tags,
+            pinned = excluded.pinned,
+            archived = excluded.archived,
+            note = excluded.note,
+            updated_at = CURRENT_TIMESTAMP",
+        params![
+            session_id,
+            project_id,
+            title,
+            tags_json,
+            update.pinned.unwrap_or(current.pinned),
+            update.archived.unwrap_or(current.archived),
+            note
+        ],
+    )
+    .map_err(|e| format!("Failed to update session metadata: {}", e))?;
+
+    get_metadata(&conn, &session_id)?.ok_or_else(|| "Session metadata not found".to_string())
+}
+
+/// Lists every tag in use, optionally limited to one project
+#[tauri::command]
+pub async fn list_session_tags(
+    db: State<'_, AgentDb>,
+    project_id: Option<String>,
+) -> Result<Vec<String>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT DISTINCT tag.value FROM session_metadata m, json_each(m.tags) tag
+             WHERE ?1 IS NULL OR m.project_id = ?1
+             ORDER BY tag.value COLLATE NOCASE",
+        )
+        .map_err(|e| e.to_string())?;
+    let tags = stmt
+        .query_map(params![project_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(tags)
+}
@@ -0,0 +1,714 @@
+use chrono::{Days, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager, State};
+
+use super::agents::AgentDb;
+use super::project_index::{
+    indexed_project_path, project_path_for_id, projects_dir, refresh_project_index_in,
+};
+
+/// Default number of hits returned by `search_sessions`
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+/// How often new transcript lines are indexed in the background
+const INDEX_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Input keys of tool calls that name files, in the order we look for them
+const TOOL_PATH_KEYS: [&str; 3] = ["file_path", "notebook_path", "path"];
+
+/// A single full-text search hit inside a session transcript
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SessionSearchResult {
+    pub session_id: String,
+    /// Directory name under ~/.claude/projects
+    pub project_id: String,
+    pub project_path: String,
+    /// 'user', 'assistant' or 'tool'
+    pub kind: String,
+    pub message_uuid: Option<String>,
+    pub timestamp: Option<String>,
+    /// Matched text with hits wrapped in `<mark>` tags
+    pub snippet: String,
+    /// FTS5 bm25 rank (lower is better)
+    pub rank: f64,
+}
+
+/// Summary of an index refresh
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SessionIndexStats {
+    pub files_scanned: usize,
+    pub files_updated: usize,
+    pub files_removed: usize,
+    pub entries_added: usize,
+    pub total_entries: i64,
+}
+
+/// A JSONL file found on disk along with what we need to decide whether to reindex it
+struct SessionFile {
+    path: PathBuf,
+    project_id: String,
+    session_id: String,
+    size: u64,
+    modified: i64,
+}
+
+/// Searchable text pulled out of one JSONL line
+struct IndexedEntry {
+    kind: &'static str,
+    content: String,
+}
+
+/// Lists every session JSONL under ~/.claude/projects
+fn collect_session_files(projects_dir: &Path) -> Vec<SessionFile> {
+    let mut files = Vec::new();
+    let Ok(project_entries) = fs::read_dir(projects_dir) else {
+        return files;
+    };
+
+    for project_entry in project_entries.flatten() {
+        let project_dir = project_entry.path();
+        if !project_dir.is_dir() {
+            continue;
+        }
+        let Some(project_id) = project_dir.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Ok(session_entries) = fs::read_dir(&project_dir) else {
+            continue;
+        };
+
+        for session_entry in session_entries.flatten() {
+            let path = session_entry.path();
+            if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+                continue;
+            }
+            let Some(session_id) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let Ok(metadata) = session_entry.metadata() else {
+                continue;
+            };
+            let modified = metadata
+                .modified()
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64;
+
+            files.push(SessionFile {
+                path: path.clone(),
+                project_id: project_id.to_string(),
+                session_id: session_id.to_string(),
+                size: metadata.len(),
+                modified,
+            });
+        }
+    }
+
+    files
+}
+
+/// Extracts the tool name and any file path the tool call refers to
+fn tool_use_text(block: &serde_json::Value) -> Option<String> {
+    let name = block.get("name").and_then(|n| n.as_str())?;
+    let mut text = name.to_string();
+    if let Some(input) = block.get("input") {
+        for key in TOOL_PATH_KEYS {
+            if let Some(path) = input.get(key).and_then(|p| p.as_str()) {
+                text.push(' ');
+                text.push_str(path);
+                break;
+            }
+        }
+    }
+    Some(text)
+}
+
+/// Pulls the searchable pieces out of one transcript line.
+/// Tool results and thinking blocks are skipped to keep the index small.
+fn extract_entries(json: &serde_json::Value) -> Vec<IndexedEntry> {
+    let mut entries = Vec::new();
+    let kind = match json.get("type").and_then(|t| t.as_str()) {
+        Some("user") => "user",
+        Some("assistant") => "assistant",
+        _ => return entries,
+    };
+    let Some(content) = json.get("message").and_then(|m| m.get("content")) else {
+        return entries;
+    };
+
+    match content {
+        serde_json::Value::String(text) if !text.trim().is_empty() => {
+            entries.push(IndexedEntry {
+                kind,
+                content: text.clone(),
+            });
+        }
+        serde_json::Value::Array(blocks) => {
+            for block in blocks {
+                match block.get("type").and_then(|t| t.as_str()) {
+                    Some("text") => {
+                        if let Some(text) = block.get("text").and_then(|t| t.as_str()) {
+                            if !text.trim().is_empty() {
+                                entries.push(IndexedEntry {
+                                    kind,
+                                    content: text.to_string(),
+                                });
+                            }
+                        }
+                    }
+                    Some("tool_use") => {
+                        if let Some(text) = tool_use_text(block) {
+                            entries.push(IndexedEntry {
+                                kind: "tool",
+                                content: text,
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+
+    entries
+}
+
+fn delete_file_entries(conn: &Connection, session_id: &str, project_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM session_search_fts WHERE session_id = ?1 AND project_id = ?2",
+        params![session_id, project_id],
+    )?;
+    Ok(())
+}
+
+/// What the index has recorded about a session file
+#[derive(Debug, Clone, PartialEq)]
+struct KnownFile {
+    session_id: String,
+    project_id: String,
+    size: i64,
+    modified: i64,
+    indexed_offset: i64,
+    project_path: Option<String>,
+}
+
+/// Searchable text of one line, with the line's message uuid and timestamp
+struct PendingEntry {
+    entry: IndexedEntry,
+    message_uuid: Option<String>,
+    timestamp: Option<String>,
+}
+
+/// The not yet indexed part of a session file, read without the database lock
+struct FileUpdate {
+    file: SessionFile,
+    /// The file shrank, so it was rewritten and its old entries are dropped
+    reset: bool,
+    /// Offset after the last complete line read
+    offset: u64,
+    entries: Vec<PendingEntry>,
+}
+
+fn known_files(conn: &Connection) -> Result<HashMap<String, KnownFile>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT file_path, session_id, project_id, size, modified, indexed_offset, project_path
+             FROM session_search_files",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                KnownFile {
+                    session_id: row.get(1)?,
+                    project_id: row.get(2)?,
+                    size: row.get(3)?,
+                    modified: row.get(4)?,
+                    indexed_offset: row.get(5)?,
+                    project_path: row.get(6)?,
+                },
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<HashMap<_, _>, _>>().map_err(|e| e.to_string())
+}
+
+/// Reads the part of a session file we have not indexed yet, or `None` if it
+/// is unchanged. Only complete lines are consumed so a line being written is
+/// picked up next time.
+fn read_file_update(file: SessionFile, known: Option<&KnownFile>) -> Result<Option<FileUpdate>, String> {
+    let (reset, mut offset) = match known {
+        Some(known) if known.size == file.size as i64 && known.modified == file.modified => {
+            return Ok(None);
+        }
+        Some(known) if (file.size as i64) < known.indexed_offset => (true, 0u64),
+        Some(known) => (false, known.indexed_offset as u64),
+        None => (false, 0u64),
+    };
+
+    let path_str = file.path.to_string_lossy().to_string();
+    let mut handle = fs::File::open(&file.path)
+        .map_err(|e| format!("Failed to open {}: {}", path_str, e))?;
+    handle
+        .seek(SeekFrom::Start(offset))
+        .map_err(|e| format!("Failed to seek {}: {}", path_str, e))?;
+    let mut reader = BufReader::new(handle);
+
+    let mut entries = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read {}: {}", path_str, e))?;
+        if read == 0 || !line.ends_with('\n') {
+            break;
+        }
+        offset += read as u64;
+
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(line.trim_end()) else {
+            continue;
+        };
+        let timestamp = json.get("timestamp").and_then(|t| t.as_str());
+        let message_uuid = json.get("uuid").and_then(|u| u.as_str());
+        entries.extend(extract_entries(&json).into_iter().map(|entry| PendingEntry {
+            entry,
+            message_uuid: message_uuid.map(String::from),
+            timestamp: timestamp.map(String::from),
+        }));
+    }
+
+    Ok(Some(FileUpdate {
+        file,
+        reset,
+        offset,
+        entries,
+    }))
+}
+
+/// Writes one file's new entries and how far it has been indexed
+fn apply_file_update(conn: &Connection, update: &FileUpdate, known: Option<&KnownFile>) -> Result<(), String> {
+    let file = &update.file;
+    let path_str = file.path.to_string_lossy().to_string();
+    let project_path = known
+        .filter(|_| !update.reset)
+        .and_then(|known| known.project_path.clone())
+        .unwrap_or_else(|| project_path_for_id(conn, &file.project_id));
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    if update.reset {
+        delete_file_entries(&tx, &file.session_id, &file.project_id).map_err(|e| e.to_string())?;
+    }
+    for pending in &update.entries {
+        tx.execute(
+            "INSERT INTO session_search_fts
+             (content, kind, session_id, project_id, project_path, message_uuid, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                pending.entry.content,
+                pending.entry.kind,
+                file.session_id,
+                file.project_id,
+                project_path,
+                pending.message_uuid,
+                pending.timestamp
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.execute(
+        "INSERT INTO session_search_files
+         (file_path, project_id, session_id, project_path, size, modified, indexed_offset)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(file_path) DO UPDATE SET
+            project_path = excluded.project_path,
+            size = excluded.size,
+            modified = excluded.modified,
+            indexed_offset = excluded.indexed_offset",
+        params![
+            path_str,
+            file.project_id,
+            file.session_id,
+            project_path,
+            file.size as i64,
+            file.modified,
+            update.offset as i64
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Brings the index in line with the files under `projects_dir`: new or
+/// grown files are indexed from where we left off and files that disappeared
+/// are dropped. Files are scanned and read without holding the lock, which
+/// is only taken to look up and write the index.
+fn refresh_index(db: &AgentDb, projects_dir: &Path) -> Result<SessionIndexStats, String> {
+    let known = known_files(&*db.0.lock().map_err(|e| e.to_string())?)?;
+
+    let files = collect_session_files(projects_dir);
+    let mut stats = SessionIndexStats {
+        files_scanned: files.len(),
+        ..Default::default()
+    };
+    let on_disk: HashSet<String> = files
+        .iter()
+        .map(|f| f.path.to_string_lossy().to_string())
+        .collect();
+    let mut updates = Vec::new();
+    for file in files {
+        let path = file.path.clone();
+        let previous = known.get(&*path.to_string_lossy());
+        match read_file_update(file, previous) {
+            Ok(Some(update)) => updates.push(update),
+            Ok(None) => {}
+            Err(e) => log::warn!("Failed to index session {}: {}", path.display(), e),
+        }
+    }
+
+    // A project directory the project index has not seen yet would be
+    // recorded with a guessed path, so rescan it once before writing
+    let unindexed = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        updates
+            .iter()
+            .any(|update| indexed_project_path(&conn, &update.file.project_id).is_none())
+    };
+    if unindexed {
+        if let Err(e) = refresh_project_index_in(db, projects_dir) {
+            log::warn!("Failed to refresh project index: {}", e);
+        }
+    }
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    // Another refresh may have written the same files while we were reading
+    let current = known_files(&conn)?;
+
+    for (file_path, file) in &known {
+        if on_disk.contains(file_path) || current.get(file_path) != Some(file) {
+            continue;
+        }
+        delete_file_entries(&conn, &file.session_id, &file.project_id).map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM session_search_files WHERE file_path = ?1",
+            params![file_path],
+        )
+        .map_err(|e| e.to_string())?;
+        stats.files_removed += 1;
+    }
+
+    for update in &updates {
+        let path = update.file.path.to_string_lossy();
+        let previous = known.get(&*path);
+        if current.get(&*path) != previous {
+            continue;
+        }
+        match apply_file_update(&conn, update, previous) {
+            Ok(()) => {
+                stats.files_updated += 1;
+                stats.entries_added += update.entries.len();
+            }
+            Err(e) => log::warn!("Failed to index session {}: {}", path, e),
+        }
+    }
+
+    stats.total_entries = conn
+        .query_row("SELECT COUNT(*) FROM session_search_fts", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+
+    Ok(stats)
+}
+
+/// Indexes new and changed session transcripts under ~/.claude/projects
+pub(crate) fn refresh_session_index(db: &AgentDb) -> Result<SessionIndexStats, String> {
+    refresh_index(db, &projects_dir()?)
+}
+
+/// Keeps the index current in the background, so searches only query it
+pub async fn run_session_indexer(app: AppHandle) {
+    let mut interval = tokio::time::interval(INDEX_REFRESH_INTERVAL);
+    loop {
+        interval.tick().await;
+        let app = app.clone();
+        let result =
+            tokio::task::spawn_blocking(move || refresh_session_index(&app.state::<AgentDb>()))
+                .await;
+        match result {
+            Ok(Ok(stats)) if stats.files_updated > 0 || stats.files_removed > 0 => {
+                log::debug!("Session index refreshed: {:?}", stats)
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => log::warn!("Failed to refresh session index: {}", e),
+            Err(e) => log::warn!("Session indexer task failed: {}", e),
+        }
+    }
+}
+
+/// Turns free text into an FTS5 query: every word must match, the last one as a prefix.
+/// Quoting each word keeps FTS5 operators and punctuation in the input from being parsed.
+fn build_match_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        return None;
+    }
+    Some(format!("{}*", terms.join(" ")))
+}
+
+/// Start of the local calendar day `days_later` days after `date` (the
+/// `YYYY-MM-DD` prefix is used), as a UTC timestamp
+fn day_start_utc<Tz: TimeZone>(tz: &Tz, date: &str, days_later: u64) -> Result<String, String> {
+    let day = date.get(..10).unwrap_or(date);
+    let day = NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD", date))?
+        .checked_add_days(Days::new(days_later))
+        .ok_or_else(|| format!("Invalid date '{}'", date))?;
+    let midnight = day.and_time(NaiveTime::MIN);
+    // Midnight can fall into a DST gap; the day then starts at the first
+    // instant after it
+    let start = tz
+        .from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| tz.from_local_datetime(&(midnight + chrono::Duration::hours(1))).earliest())
+        .ok_or_else(|| format!("Invalid local date '{}'", date))?;
+    Ok(start.with_timezone(&Utc).format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+}
+
+/// Searches all session transcripts. The index is kept current in the
+/// background by `run_session_indexer`.
+/// Dates are local calendar days given as `YYYY-MM-DD`; both bounds are inclusive.
+#[tauri::command]
+pub async fn search_sessions(
+    db: State<'_, AgentDb>,
+    query: String,
+    project_id: Option<String>,
+    date_from: Option<String>,
+    date_to: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<SessionSearchResult>, String> {
+    log::info!("Searching sessions for: {}", query);
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    search_sessions_internal(&conn, &query, project_id, date_from, date_to, limit)
+}
+
+/// Internal function to search sessions, shared with the CLI
+pub(crate) fn search_sessions_internal(
+    conn: &Connection,
+    query: &str,
+    project_id: Option<String>,
+    date_from: Option<String>,
+    date_to: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<SessionSearchResult>, String> {
+    // Transcript timestamps are UTC, so turn the local days into UTC instants
+    let from = date_from
+        .map(|d| day_start_utc(&Local, &d, 0))
+        .transpose()?;
+    let until = date_to.map(|d| day_start_utc(&Local, &d, 1)).transpose()?;
+    search_index(conn, query, project_id, from, until, limit)
+}
+
+/// Runs a search limited to entries at or after `from` and before `until`,
+/// both UTC timestamps
+fn search_index(
+    conn: &Connection,
+    query: &str,
+    project_id: Option<String>,
+    from: Option<String>,
+    until: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<SessionSearchResult>, String> {
+    let Some(match_query) = build_match_query(query) else {
+        return Ok(Vec::new());
+    };
+    let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT) as i64;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT session_id, project_id, project_path, kind, message_uuid, timestamp,
+                    snippet(session_search_fts, 0, '<mark>', '</mark>', '…', 16),
+                    bm25(session_search_fts)
+             FROM session_search_fts
+             WHERE session_search_fts MATCH ?1
+               AND (?2 IS NULL OR project_id = ?2)
+               AND (?3 IS NULL OR julianday(timestamp) >= julianday(?3))
+               AND (?4 IS NULL OR julianday(timestamp) < julianday(?4))
+             ORDER BY bm25(session_search_fts)
+             LIMIT ?5",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let results = stmt
+        .query_map(
+            params![match_query, project_id, from, until, limit],
+            |row| {
+                Ok(SessionSearchResult {
+                    session_id: row.get(0)?,
+                    project_id: row.get(1)?,
+                    project_path: row.get(2)?,
+                    kind: row.get(3)?,
+                    message_uuid: row.get(4)?,
+                    timestamp: row.get(5)?,
+                    snippet: row.get(6)?,
+                    rank: row.get(7)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Search failed: {}", e))?;
+
+    Ok(results)
+}
+
+/// Indexes any new or changed session files without searching
+#[tauri::command]
+pub async fn refresh_session_search_index(app: AppHandle) -> Result<SessionIndexStats, String> {
+    tokio::task::spawn_blocking(move || refresh_session_index(&app.state::<AgentDb>()))
+        .await
+        .map_err(|e| format!("Session indexer task failed: {}", e))?
+}
+
+/// Drops the whole index and rebuilds it from scratch
+#[tauri::command]
+pub async fn rebuild_session_search_index(app: AppHandle) -> Result<SessionIndexStats, String> {
+    log::info!("Rebuilding session search index");
+
+    tokio::task::spawn_blocking(move || {
+        let db = app.state::<AgentDb>();
+        {
+            let conn = db.0.lock().map_err(|e| e.to_string())?;
+            conn.execute("DELETE FROM session_search_fts", [])
+                .map_err(|e| e.to_string())?;
+            conn.execute("DELETE FROM session_search_files", [])
+                .map_err(|e| e.to_string())?;
+        }
+        refresh_session_index(&db)
+    })
+    .await
+    .map_err(|e| format!("Session indexer task failed: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agents::create_schema;
+    use chrono::FixedOffset;
+    use std::sync::Mutex;
+
+    fn test_db() -> AgentDb {
+        let conn = Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO project_index (project_id, project_path) VALUES ('-repo', '/repo')",
+            [],
+        )
+        .unwrap();
+        AgentDb(Mutex::new(conn))
+    }
+
+    fn search(db: &AgentDb, query: &str) -> Vec<SessionSearchResult> {
+        search_sessions_internal(&db.0.lock().unwrap(), query, None, None, None, None).unwrap()
+    }
+
+    fn user_line(uuid: &str, text: &str) -> String {
+        format!(
+            r#"{{"type":"user","uuid":"{}","timestamp":"2025-01-02T03:04:05Z","message":{{"content":"{}"}}}}"#,
+            uuid, text
+        )
+    }
+
+    #[test]
+    fn test_build_match_query() {
+        assert_eq!(build_match_query("   "), None);
+        assert_eq!(build_match_query("fix login").as_deref(), Some(r#""fix" "login"*"#));
+        // Operators and quotes are searched for literally
+        assert_eq!(
+            build_match_query(r#"a OR "b"#).as_deref(),
+            Some(r#""a" "OR" """b"*"#)
+        );
+    }
+
+    #[test]
+    fn test_refresh_indexes_incrementally() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("-repo");
+        fs::create_dir(&project).unwrap();
+        let session = project.join("s1.jsonl");
+        let db = test_db();
+
+        fs::write(&session, format!("{}\n", user_line("u1", "refactor the parser"))).unwrap();
+        let stats = refresh_index(&db, dir.path()).unwrap();
+        assert_eq!((stats.files_updated, stats.entries_added), (1, 1));
+        let hits = search(&db, "pars");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].project_path, "/repo");
+        assert_eq!(hits[0].message_uuid.as_deref(), Some("u1"));
+
+        // Unchanged files are skipped; a partially written line waits
+        assert_eq!(refresh_index(&db, dir.path()).unwrap().files_updated, 0);
+        let mut content = fs::read_to_string(&session).unwrap();
+        content.push_str(&format!("{}\n{{\"type\":\"user\"", user_line("u2", "tokenizer tests")));
+        fs::write(&session, &content).unwrap();
+        let stats = refresh_index(&db, dir.path()).unwrap();
+        assert_eq!((stats.entries_added, stats.total_entries), (1, 2));
+        assert_eq!(search(&db, "tokenizer").len(), 1);
+
+        // A rewritten (shorter) file is indexed from scratch
+        fs::write(&session, format!("{}\n", user_line("u3", "lexer"))).unwrap();
+        let stats = refresh_index(&db, dir.path()).unwrap();
+        assert_eq!(stats.total_entries, 1);
+        assert!(search(&db, "parser").is_empty());
+
+        fs::remove_file(&session).unwrap();
+        let stats = refresh_index(&db, dir.path()).unwrap();
+        assert_eq!((stats.files_removed, stats.total_entries), (1, 0));
+    }
+
+    #[test]
+    fn test_day_start_utc() {
+        let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+        let new_york = FixedOffset::west_opt(5 * 3600).unwrap();
+        assert_eq!(day_start_utc(&tokyo, "2025-01-02", 0).unwrap(), "2025-01-01T15:00:00.000Z");
+        assert_eq!(day_start_utc(&tokyo, "2025-01-02", 1).unwrap(), "2025-01-02T15:00:00.000Z");
+        assert_eq!(day_start_utc(&new_york, "2025-12-31", 1).unwrap(), "2026-01-01T05:00:00.000Z");
+        // Only the date part of a full timestamp counts
+        assert_eq!(day_start_utc(&Utc, "2025-01-02T20:00:00Z", 0).unwrap(), "2025-01-02T00:00:00.000Z");
+        assert!(day_start_utc(&Utc, "02/01/2025", 0).is_err());
+        assert!(day_start_utc(&Utc, "2025-13-01", 0).is_err());
+    }
+
+    #[test]
+    fn test_date_filter_uses_local_days() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("-repo");
+        fs::create_dir(&project).unwrap();
+        let db = test_db();
+        // 2025-01-02T03:04:05Z is still January 1st in New York
+        fs::write(project.join("s1.jsonl"), format!("{}\n", user_line("u1", "parser"))).unwrap();
+        refresh_index(&db, dir.path()).unwrap();
+
+        let new_york = FixedOffset::west_opt(5 * 3600).unwrap();
+        let on_day = |day: &str| {
+            let from = day_start_utc(&new_york, day, 0).ok();
+            let until = day_start_utc(&new_york, day, 1).ok();
+            search_index(&db.0.lock().unwrap(), "parser", None, from, until, None)
+                .unwrap()
+                .len()
+        };
+        assert_eq!(on_day("2025-01-01"), 1);
+        assert_eq!(on_day("2025-01-02"), 0);
+    }
+}
@@ -0,0 +1,670 @@
+use regex::Regex;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use super::config_history::write_config_file;
+
+/// Settings scopes from lowest to highest precedence
+const SCOPES: [&str; 4] = ["user", "project", "local", "enterprise"];
+
+/// Hook events Claude Code dispatches. Newer releases may add events, so
+/// others are only reported as warnings.
+const HOOK_EVENTS: [&str; 9] = [
+    "PreToolUse",
+    "PostToolUse",
+    "Notification",
+    "UserPromptSubmit",
+    "Stop",
+    "SubagentStop",
+    "PreCompact",
+    "SessionStart",
+    "SessionEnd",
+];
+
+const PERMISSION_MODES: [&str; 4] = ["default", "acceptEdits", "plan", "bypassPermissions"];
+
+/// One settings file and what it contains
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SettingsScope {
+    /// 'user', 'project', 'local' or 'enterprise'
+    pub scope: String,
+    pub path: String,
+    pub exists: bool,
+    pub settings: Value,
+    /// Why the file could not be read; such scopes are left out of the merge
+    pub error: Option<String>,
+    pub issues: Vec<SettingsIssue>,
+}
+
+/// A value a scope sets for a key
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SettingOrigin {
+    pub scope: String,
+    pub value: Value,
+}
+
+/// One key of the merged settings and where its value comes from
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EffectiveSetting {
+    /// Dotted path, e.g. 'permissions.allow' or 'env.DEBUG'
+    pub key: String,
+    pub value: Value,
+    /// The highest-precedence scope that sets the key
+    pub source: String,
+    /// True for arrays, whose entries are combined across scopes rather than
+    /// overridden
+    pub merged: bool,
+    /// Every scope that sets the key, highest precedence first
+    pub origins: Vec<SettingOrigin>,
+}
+
+/// The settings Claude Code applies in a project
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EffectiveSettings {
+    pub project_path: Option<String>,
+    /// Lowest precedence first
+    pub scopes: Vec<SettingsScope>,
+    pub settings: Value,
+    pub keys: Vec<EffectiveSetting>,
+}
+
+/// How serious a settings issue is
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueSeverity {
+    /// The value has the wrong shape and Claude Code cannot use it
+    Error,
+    /// The value is well-formed but not one this app knows about, such as a
+    /// hook event added by a newer Claude Code
+    Warning,
+}
+
+/// A schema violation at a path inside a settings object
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SettingsIssue {
+    /// e.g. 'hooks.PreToolUse[0].hooks[1].command'
+    pub path: String,
+    pub message: String,
+    pub severity: IssueSeverity,
+}
+
+fn managed_settings_path() -> PathBuf {
+    if cfg!(target_os = "macos") {
+        PathBuf::from("/Library/Application Support/ClaudeCode/managed-settings.json")
+    } else if cfg!(windows) {
+        PathBuf::from(r"C:\ProgramData\ClaudeCode\managed-settings.json")
+    } else {
+        PathBuf::from("/etc/claude-code/managed-settings.json")
+    }
+}
+
+/// Resolves the settings file for a scope. Project and local scopes live
+/// under `<project>/.claude`.
+pub(crate) fn settings_path(scope: &str, project_path: Option<&str>) -> Result<PathBuf, String> {
+    match scope {
+        "user" => Ok(super::claude::get_claude_dir()
+            .map_err(|e| e.to_string())?
+            .join("settings.json")),
+        "project" => {
+            let path = project_path.ok_or("Project path required for project scope")?;
+            Ok(PathBuf::from(path).join(".claude").join("settings.json"))
+        }
+        "local" => {
+            let path = project_path.ok_or("Project path required for local scope")?;
+            Ok(PathBuf::from(path).join(".claude").join("settings.local.json"))
+        }
+        "enterprise" => Ok(managed_settings_path()),
+        _ => Err(format!("Invalid scope: {}", scope)),
+    }
+}
+
+/// Reads a settings file, returning None if it does not exist
+pub(crate) fn read_settings_file(path: &Path) -> Result<Option<Value>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read settings: {}", e))?;
+    let settings: Value =
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse settings: {}", e))?;
+    if !settings.is_object() {
+        return Err("Settings file must contain a JSON object".to_string());
+    }
+    Ok(Some(settings))
+}
+
+/// Writes settings with 2-space indentation and records the write in the
+/// file's version history under `source`
+pub(crate) fn write_settings_file(
+    conn: &Connection,
+    path: &Path,
+    settings: &Value,
+    source: &str,
+) -> Result<(), String> {
+    let json_string = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    write_config_file(conn, path, &json_string, source)
+}
+
+// Validation
+
+fn rule_tool_regex() -> &'static Regex {
+    static TOOL: OnceLock<Regex> = OnceLock::new();
+    TOOL.get_or_init(|| {
+        Regex::new(r"^(?:mcp__[A-Za-z0-9_-]+__\*|[A-Za-z][A-Za-z0-9_-]*)$").expect("valid regex")
+    })
+}
+
+/// Tools whose rule specifiers are gitignore-style path patterns
+pub(crate) const PATH_RULE_TOOLS: [&str; 5] = ["Read", "Edit", "Write", "MultiEdit", "NotebookEdit"];
+
+/// Splits a permission rule such as `Bash(npm run test:*)` into its tool
+/// name and optional specifier, rejecting malformed rules
+pub(crate) fn parse_permission_rule(rule: &str) -> Result<(&str, Option<&str>), String> {
+    if rule.trim() != rule || rule.is_empty() {
+        return Err("rule must be non-empty without surrounding whitespace".to_string());
+    }
+    let (tool, specifier) = match rule.find('(') {
+        Some(open) => {
+            let Some(inner) = rule[open + 1..].strip_suffix(')') else {
+                return Err("missing closing ')'".to_string());
+            };
+            if inner.trim().is_empty() {
+                return Err("empty specifier; omit the parentheses to match every use".to_string());
+            }
+            (&rule[..open], Some(inner))
+        }
+        None if rule.contains(')') => return Err("unexpected ')'".to_string()),
+        None => (rule, None),
+    };
+    if !rule_tool_regex().is_match(tool) {
+        return Err(format!("invalid tool name '{}'", tool));
+    }
+    let Some(spec) = specifier else {
+        return Ok((tool, None));
+    };
+    if tool.starts_with("mcp__") {
+        return Err("MCP rules do not take a specifier".to_string());
+    }
+    if tool == "Bash" && spec.trim_end_matches(":*").contains(":*") {
+        return Err("':*' is only allowed at the end of a Bash rule".to_string());
+    }
+    if tool == "WebFetch" && spec.strip_prefix("domain:").is_none_or(|d| d.trim().is_empty()) {
+        return Err("WebFetch rules take the form WebFetch(domain:example.com)".to_string());
+    }
+    if PATH_RULE_TOOLS.contains(&tool) {
+        let pattern = spec.trim_start_matches('/').trim_start_matches("~/");
+        if let Err(e) = ignore::gitignore::GitignoreBuilder::new("/").add_line(None, pattern) {
+            return Err(format!("invalid path pattern: {}", e));
+        }
+    }
+    Ok((tool, Some(spec)))
+}
+
+struct Validator {
+    issues: Vec<SettingsIssue>,
+}
+
+impl Validator {
+    fn issue(&mut self, path: &str, message: impl Into<String>) {
+        self.issues.push(SettingsIssue {
+            path: path.to_string(),
+            message: message.into(),
+            severity: IssueSeverity::Error,
+        });
+    }
+
+    fn warning(&mut self, path: &str, message: impl Into<String>) {
+        self.issues.push(SettingsIssue {
+            path: path.to_string(),
+            message: message.into(),
+            severity: IssueSeverity::Warning,
+        });
+    }
+
+    fn object<'a>(&mut self, path: &str, value: &'a Value) -> Option<&'a Map<String, Value>> {
+        let object = value.as_object();
+        if object.is_none() {
+            self.issue(path, "expected an object");
+        }
+        object
+    }
+
+    fn array<'a>(&mut self, path: &str, value: &'a Value) -> Option<&'a Vec<Value>> {
+        let array = value.as_array();
+        if array.is_none() {
+            self.issue(path, "expected an array");
+        }
+        array
+    }
+
+    fn string<'a>(&mut self, path: &str, value: &'a Value) -> Option<&'a str> {
+        let string = value.as_str();
+        if string.is_none() {
+            self.issue(path, "expected a string");
+        }
+        string
+    }
+
+    /// Checks an array of strings, running `check` on each string
+    fn string_array(&mut self, path: &str, value: &Value, check: impl Fn(&str) -> Result<(), String>) {
+        let Some(items) = self.array(path, value) else {
+            return;
+        };
+        for (i, item) in items.iter().enumerate() {
+            let item_path = format!("{}[{}]", path, i);
+            if let Some(s) = self.string(&item_path, item) {
+                if let Err(e) = check(s) {
+                    self.issue(&item_path, e);
+                }
+            }
+        }
+    }
+
+    fn permissions(&mut self, value: &Value) {
+        let Some(permissions) = self.object("permissions", value) else {
+            return;
+        };
+        for list in ["allow", "deny", "ask"] {
+            let Some(rules) = permissions.get(list) else {
+                continue;
+            };
+            self.string_array(&format!("permissions.{}", list), rules, |rule| {
+                parse_permission_rule(rule).map(|_| ())
+            });
+        }
+        if let Some(dirs) = permissions.get("additionalDirectories") {
+            self.string_array("permissions.additionalDirectories", dirs, |_| Ok(()));
+        }
+        if let Some(mode) = permissions.get("defaultMode") {
+            if let Some(mode) = self.string("permissions.defaultMode", mode) {
+                if !PERMISSION_MODES.contains(&mode) {
+                    self.warning(
+                        "permissions.defaultMode",
+                        format!("unknown mode; expected one of {}", PERMISSION_MODES.join(", ")),
+                    );
+                }
+            }
+        }
+        if let Some(disable) = permissions.get("disableBypassPermissionsMode") {
+            if disable.as_str() != Some("disable") {
+                self.issue("permissions.disableBypassPermissionsMode", "expected \"disable\"");
+            }
+        }
+    }
+
+    fn hooks(&mut self, value: &Value) {
+        let Some(events) = self.object("hooks", value) else {
+            return;
+        };
+        for (event, matchers) in events {
+            let event_path = format!("hooks.{}", event);
+            if !HOOK_EVENTS.contains(&event.as_str()) {
+                self.warning(&event_path, "unknown hook event");
+            }
+            let Some(matchers) = self.array(&event_path, matchers) else {
+                continue;
+            };
+            for (i, entry) in matchers.iter().enumerate() {
+                self.hook_matcher(&format!("{}[{}]", event_path, i), entry);
+            }
+        }
+    }
+
+    fn hook_matcher(&mut self, path: &str, value: &Value) {
+        let Some(entry) = self.object(path, value) else {
+            return;
+        };
+        if let Some(matcher) = entry.get("matcher") {
+            let matcher_path = format!("{}.matcher", path);
+            if let Some(matcher) = self.string(&matcher_path, matcher) {
+                if !matcher.is_empty() && matcher != "*" {
+                    if let Err(e) = Regex::new(&format!("^(?:{})$", matcher)) {
+                        self.issue(&matcher_path, format!("invalid pattern: {}", e));
+                    }
+                }
+            }
+        }
+        let hooks_path = format!("{}.hooks", path);
+        let Some(hooks) = entry.get("hooks") else {
+            self.issue(&hooks_path, "missing required array");
+            return;
+        };
+        let Some(hooks) = self.array(&hooks_path, hooks) else {
+            return;
+        };
+        for (i, hook) in hooks.iter().enumerate() {
+            let hook_path = format!("{}[{}]", hooks_path, i);
+            let Some(hook) = self.object(&hook_path, hook) else {
+                continue;
+            };
+            match hook.get("type") {
+                Some(Value::String(t)) if t == "command" => {}
+                Some(_) => self.issue(&format!("{}.type", hook_path), "expected \"command\""),
+                None => self.issue(&format!("{}.type", hook_path), "missing required \"command\""),
+            }
+            let command_path = format!("{}.command", hook_path);
+            match hook.get("command") {
+                Some(command) => {
+                    if let Some(command) = self.string(&command_path, command) {
+                        if command.trim().is_empty() {
+                            self.issue(&command_path, "must not be empty");
+                        }
+                    }
+                }
+                None => self.issue(&command_path, "missing required string"),
+            }
+            if let Some(timeout) = hook.get("timeout") {
+                if !timeout.as_f64().is_some_and(|t| t > 0.0) {
+                    self.issue(&format!("{}.timeout", hook_path), "expected a positive number of seconds");
+                }
+            }
+        }
+    }
+
+    fn env(&mut self, value: &Value) {
+        let Some(env) = self.object("env", value) else {
+            return;
+        };
+        for (name, value) in env {
+            self.string(&format!("env.{}", name), value);
+        }
+    }
+
+    fn model(&mut self, value: &Value) {
+        if let Some(model) = self.string("model", value) {
+            if model.trim().is_empty() {
+                self.issue("model", "must not be empty");
+            }
+        }
+    }
+}
+
+/// Checks the permissions, hooks, env and model keys of a settings object.
+/// Other keys are passed through unchecked.
+pub(crate) fn validate_settings(settings: &Value) -> Vec<SettingsIssue> {
+    let mut validator = Validator { issues: Vec::new() };
+    let Some(root) = settings.as_object() else {
+        validator.issue("", "expected an object");
+        return validator.issues;
+    };
+    if let Some(value) = root.get("permissions") {
+        validator.permissions(value);
+    }
+    if let Some(value) = root.get("hooks") {
+        validator.hooks(value);
+    }
+    if let Some(value) = root.get("env") {
+        validator.env(value);
+    }
+    if let Some(value) = root.get("model") {
+        validator.model(value);
+    }
+    validator.issues
+}
+
+/// Fails with every error found, one per line, if the settings cannot be
+/// written. Warnings never block a write, and neither do errors in top-level
+/// keys that are unchanged from `previous`, the settings currently on disk.
+pub(crate) fn ensure_valid_settings(settings: &Value, previous: Option<&Value>) -> Result<(), String> {
+    let changed = |path: &str| {
+        let key = path.split(['.', '[']).next().unwrap_or_default();
+        key.is_empty() || previous.and_then(|p| p.get(key)) != settings.get(key)
+    };
+    let details: Vec<String> = validate_settings(settings)
+        .iter()
+        .filter(|i| i.severity == IssueSeverity::Error && changed(&i.path))
+        .map(|i| format!("{}: {}", i.path, i.message))
+        .collect();
+    if details.is_empty() {
+        return Ok(());
+    }
+    Err(format!("Invalid settings:\n{}", details.join("\n")))
+}
+
+// Merging
+
+/// Merges `overlay` into `base`: objects merge key by key, arrays combine
+/// with the overlay's entries first, and anything else is replaced
+fn merge_into(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(key) {
+                    Some(existing) => merge_into(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (Value::Array(base), Value::Array(overlay)) => {
+            let mut combined = overlay.clone();
+            for item in base.drain(..) {
+                if !combined.contains(&item) {
+                    combined.push(item);
+                }
+            }
+            *base = combined;
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+/// Collects the leaf values of a settings object with their key paths.
+/// Arrays and empty objects are leaves.
+fn flatten(value: &Value, path: &mut Vec<String>, out: &mut Vec<(Vec<String>, Value)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                path.push(key.clone());
+                flatten(child, path, out);
+                path.pop();
+            }
+        }
+        _ => out.push((path.clone(), value.clone())),
+    }
+}
+
+fn lookup<'a>(value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |v, key| v.get(key))
+}
+
+/// Merges scopes given lowest precedence first and records which scopes set
+/// each key
+pub(crate) fn merge_scopes(scopes: &[(String, Value)]) -> (Value, Vec<EffectiveSetting>) {
+    let mut merged = Value::Object(Map::new());
+    let mut origins: BTreeMap<Vec<String>, Vec<SettingOrigin>> = BTreeMap::new();
+    for (scope, settings) in scopes {
+        merge_into(&mut merged, settings);
+        let mut leaves = Vec::new();
+        flatten(settings, &mut Vec::new(), &mut leaves);
+        for (path, value) in leaves {
+            if path.is_empty() {
+                continue;
+            }
+            origins.entry(path).or_default().insert(
+                0,
+                SettingOrigin {
+                    scope: scope.clone(),
+                    value,
+                },
+            );
+        }
+    }
+
+    let keys = origins
+        .into_iter()
+        .map(|(path, origins)| {
+            let value = lookup(&merged, &path)
+                .cloned()
+                .unwrap_or_else(|| origins[0].value.clone());
+            EffectiveSetting {
+                key: path.join("."),
+                merged: value.is_array() && origins.len() > 1,
+                source: origins[0].scope.clone(),
+                value,
+                origins,
+            }
+        })
+        .collect();
+    (merged, keys)
+}
+
+/// Reads and merges every settings scope. Without a project only user and
+/// enterprise settings are considered.
+pub(crate) fn load_effective_settings(project_path: Option<String>) -> Result<EffectiveSettings, String> {
+    let mut scopes = Vec::new();
+    for scope in SCOPES {
+        if project_path.is_none() && (scope == "project" || scope == "local") {
+            continue;
+        }
+        let path = settings_path(scope, project_path.as_deref())?;
+        let mut info = SettingsScope {
+            scope: scope.to_string(),
+            path: path.to_string_lossy().to_string(),
+            exists: path.exists(),
+            settings: Value::Object(Map::new()),
+            error: None,
+            issues: Vec::new(),
+        };
+        match read_settings_file(&path) {
+            Ok(Some(settings)) => {
+                info.issues = validate_settings(&settings);
+                info.settings = settings;
+            }
+            Ok(None) => {}
+            Err(e) => info.error = Some(e),
+        }
+        scopes.push(info);
+    }
+
+    let layers: Vec<(String, Value)> = scopes
+        .iter()
+        .filter(|s| s.error.is_none())
+        .map(|s| (s.scope.clone(), s.settings.clone()))
+        .collect();
+    let (settings, keys) = merge_scopes(&layers);
+
+    Ok(EffectiveSettings {
+        project_path,
+        scopes,
+        settings,
+        keys,
+    })
+}
+
+/// Merges the user, project, local and enterprise settings the way Claude
+/// Code applies them, and annotates every key with the scope it comes from
+#[tauri::command]
+pub async fn get_effective_settings(project_path: Option<String>) -> Result<EffectiveSettings, String> {
+    log::info!("Computing effective settings for project: {:?}", project_path);
+    load_effective_settings(project_path)
+}
+
+/// Checks settings against the schema Claude Code expects for permissions,
+/// hooks, env and model, returning every error and warning found
+#[tauri::command]
+pub async fn validate_claude_settings(settings: Value) -> Result<Vec<SettingsIssue>, String> {
+    Ok(validate_settings(&settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merges_with_precedence_and_sources() {
+        let scopes = vec![
+            (
+                "user".to_string(),
+                json!({"model": "sonnet", "env": {"A": "1"}, "permissions": {"allow": ["Read", "Bash(ls:*)"]}}),
+            ),
+            (
+                "local".to_string(),
+                json!({"model": "opus", "permissions": {"allow": ["Bash(ls:*)", "Edit"]}}),
+            ),
+        ];
+        let (merged, keys) = merge_scopes(&scopes);
+        assert_eq!(
+            merged,
+            json!({"model": "opus", "env": {"A": "1"}, "permissions": {"allow": ["Bash(ls:*)", "Edit", "Read"]}})
+        );
+
+        let model = keys.iter().find(|k| k.key == "model").unwrap();
+        assert_eq!(model.source, "local");
+        assert_eq!(model.origins.len(), 2);
+        assert!(!model.merged);
+        let allow = keys.iter().find(|k| k.key == "permissions.allow").unwrap();
+        assert!(allow.merged);
+        assert_eq!(keys.iter().find(|k| k.key == "env.A").unwrap().source, "user");
+    }
+
+    #[test]
+    fn test_reports_precise_paths() {
+        let settings = json!({
+            "model": 4,
+            "env": {"OK": "1", "BAD": 2},
+            "permissions": {"allow": ["Read", "Bash(", 3], "defaultMode": "yolo"},
+            "hooks": {
+                "PreToolUse": [{"matcher": "Edit(", "hooks": [{"type": "command", "command": "ok"}, {"type": "command"}]}],
+                "OnSave": []
+            }
+        });
+        let issues = validate_settings(&settings);
+        let paths: Vec<&str> = issues.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "permissions.allow[1]",
+                "permissions.allow[2]",
+                "permissions.defaultMode",
+                "hooks.OnSave",
+                "hooks.PreToolUse[0].matcher",
+                "hooks.PreToolUse[0].hooks[1].command",
+                "env.BAD",
+                "model",
+            ]
+        );
+        let warnings: Vec<&str> = issues
+            .iter()
+            .filter(|i| i.severity == IssueSeverity::Warning)
+            .map(|i| i.path.as_str())
+            .collect();
+        assert_eq!(warnings, ["permissions.defaultMode", "hooks.OnSave"]);
+        assert!(parse_permission_rule("Bash(npm run test:*)").is_ok());
+        assert!(parse_permission_rule("mcp__github__create_issue").is_ok());
+        assert!(parse_permission_rule("mcp__github__*").is_ok());
+        assert!(parse_permission_rule("Edit(src/**)").is_ok());
+        assert!(parse_permission_rule("Bash(a:* b)").is_err());
+        assert!(parse_permission_rule("WebFetch(example.com)").is_err());
+    }
+
+    #[test]
+    fn test_blocks_writes_only_on_errors_in_changed_keys() {
+        // Newer hook events and modes are saved with a warning
+        let newer = json!({
+            "permissions": {"defaultMode": "dontAsk"},
+            "hooks": {"SubagentStart": [{"hooks": [{"type": "command", "command": "ok"}]}]}
+        });
+        assert!(ensure_valid_settings(&newer, None).is_ok());
+
+        // Structural errors under an unknown event still block
+        let broken = json!({"hooks": {"SubagentStart": [{"hooks": [{"type": "command"}]}]}});
+        let err = ensure_valid_settings(&broken, None).unwrap_err();
+        assert!(err.contains("hooks.SubagentStart[0].hooks[0].command"));
+
+        // An error already on disk does not block edits to other keys
+        let on_disk = json!({"env": {"BAD": 2}, "model": "sonnet"});
+        let edited = json!({"env": {"BAD": 2}, "model": "opus"});
+        assert!(ensure_valid_settings(&edited, Some(&on_disk)).is_ok());
+        let edited = json!({"env": {"BAD": 3}, "model": "sonnet"});
+        assert!(ensure_valid_settings(&edited, Some(&on_disk)).is_err());
+    }
+}
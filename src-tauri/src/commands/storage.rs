@@ -456,6 +456,28 @@ pub async fn storage_reset_database(app: AppHandle) -> Result<(), String> {
             .map_err(|e| format!("Failed to drop environment_variables table: {}", e))?;
         conn.execute("DROP TABLE IF EXISTS environment_variable_groups", [])
             .map_err(|e| format!("Failed to drop environment_variable_groups table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS prompt_queue_items", [])
+            .map_err(|e| format!("Failed to drop prompt_queue_items table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS prompt_queues", [])
+            .map_err(|e| format!("Failed to drop prompt_queues table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS session_search_fts", [])
+            .map_err(|e| format!("Failed to drop session_search_fts table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS session_search_files", [])
+            .map_err(|e| format!("Failed to drop session_search_files table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS project_index", [])
+            .map_err(|e| format!("Failed to drop project_index table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS session_cache", [])
+            .map_err(|e| format!("Failed to drop session_cache table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS session_metadata", [])
+            .map_err(|e| format!("Failed to drop session_metadata table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS worktrees", [])
+            .map_err(|e| format!("Failed to drop worktrees table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS config_versions", [])
+            .map_err(|e| format!("Failed to drop config_versions table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS schedule_runs", [])
+            .map_err(|e| format!("Failed to drop schedule_runs table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS schedules", [])
+            .map_err(|e| format!("Failed to drop schedules table: {}", e))?;
         
         // Re-enable foreign key constraints
         conn.execute("PRAGMA foreign_keys = ON", [])
@@ -0,0 +1,208 @@
+use std::time::Duration;
+use tauri::menu::{Menu, MenuBuilder, MenuEvent, MenuItem, SubmenuBuilder};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::agents::kill_agent_session;
+use super::claude::cancel_claude_execution;
+use super::quick_prompt::{quick_prompt_enabled, show_main_window, show_quick_prompt};
+use crate::process::{ProcessInfo, ProcessRegistryState, ProcessType};
+
+const TRAY_ID: &str = "main";
+
+/// How often the tray checks the registry for started or finished runs
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest task excerpt shown in a menu label
+const MAX_LABEL_TASK_CHARS: usize = 40;
+
+const OPEN_PREFIX: &str = "open:";
+const CANCEL_PREFIX: &str = "cancel:";
+
+fn project_name(project_path: &str) -> String {
+    std::path::Path::new(project_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| project_path.to_string())
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    let line = text.lines().next().unwrap_or("").trim();
+    if line.chars().count() > max_chars {
+        format!("{}…", line.chars().take(max_chars).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+fn process_label(process: &ProcessInfo) -> String {
+    match &process.process_type {
+        ProcessType::AgentRun { agent_name, .. } => {
+            format!("{} in {}", agent_name, project_name(&process.project_path))
+        }
+        ProcessType::ClaudeSession { .. } => format!(
+            "{}: {}",
+            project_name(&process.project_path),
+            truncate(&process.task, MAX_LABEL_TASK_CHARS)
+        ),
+    }
+}
+
+fn running_processes(app: &AppHandle) -> Vec<ProcessInfo> {
+    let registry = app.state::<ProcessRegistryState>();
+    let mut processes = registry.0.get_running_processes().unwrap_or_default();
+    processes.sort_by_key(|process| process.started_at);
+    processes
+}
+
+fn build_menu(app: &AppHandle, processes: &[ProcessInfo]) -> tauri::Result<Menu<tauri::Wry>> {
+    let header = match processes.len() {
+        0 => "Nothing running".to_string(),
+        1 => "1 running".to_string(),
+        n => format!("{} running", n),
+    };
+    let mut builder = MenuBuilder::new(app).item(&MenuItem::with_id(
+        app,
+        "running",
+        header,
+        false,
+        None::<&str>,
+    )?);
+    for process in processes {
+        let submenu = SubmenuBuilder::new(app, process_label(process))
+            .text(format!("{}{}", OPEN_PREFIX, process.run_id), "Open")
+            .text(format!("{}{}", CANCEL_PREFIX, process.run_id), "Cancel")
+            .build()?;
+        builder = builder.item(&submenu);
+    }
+    builder = builder.separator();
+    if quick_prompt_enabled(app) {
+        builder = builder.text("quick_prompt", "Quick Prompt…");
+    }
+    builder
+        .text("show", "Show Claudia")
+        .separator()
+        .text("quit", "Quit Claudia")
+        .build()
+}
+
+/// Creates the tray icon with an empty run list
+pub fn create_tray(app: &AppHandle) -> tauri::Result<()> {
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .menu(&build_menu(app, &[])?)
+        .tooltip("Claudia")
+        .show_menu_on_left_click(true)
+        .on_menu_event(handle_menu_event);
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+    Ok(())
+}
+
+/// Rebuilds the menu and the count badge for the given runs
+fn update_tray(app: &AppHandle, processes: &[ProcessInfo]) -> tauri::Result<()> {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return Ok(());
+    };
+    let count = processes.len();
+    tray.set_menu(Some(build_menu(app, processes)?))?;
+    tray.set_tooltip(Some(match count {
+        0 => "Claudia".to_string(),
+        n => format!("Claudia: {} running", n),
+    }))?;
+    // The title shows next to the icon on macOS and Linux
+    tray.set_title((count > 0).then(|| count.to_string()))?;
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.set_badge_count((count > 0).then_some(count as i64));
+    }
+    Ok(())
+}
+
+/// Rebuilds the tray menu now, e.g. after a setting it depends on changed
+pub(crate) fn refresh_tray(app: &AppHandle) {
+    let processes = running_processes(app);
+    if let Err(e) = update_tray(app, &processes) {
+        log::warn!("Failed to update tray menu: {}", e);
+    }
+}
+
+/// Keeps the tray menu in sync with the process registry
+pub async fn run_tray_updater(app: AppHandle) {
+    let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+    let mut shown: Option<Vec<i64>> = None;
+    loop {
+        interval.tick().await;
+        let processes = running_processes(&app);
+        let run_ids: Vec<i64> = processes.iter().map(|process| process.run_id).collect();
+        if shown.as_ref() == Some(&run_ids) {
+            continue;
+        }
+        match update_tray(&app, &processes) {
+            Ok(()) => shown = Some(run_ids),
+            Err(e) => log::warn!("Failed to update tray menu: {}", e),
+        }
+    }
+}
+
+fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    let id = event.id().as_ref();
+    match id {
+        "quick_prompt" => {
+            if let Err(e) = show_quick_prompt(app) {
+                log::error!("Failed to open quick prompt: {}", e);
+            }
+        }
+        "show" => show_main_window(app),
+        "quit" => app.exit(0),
+        _ => {
+            if let Some(run_id) = id.strip_prefix(OPEN_PREFIX).and_then(|id| id.parse().ok()) {
+                open_run(app, run_id);
+            } else if let Some(run_id) = id.strip_prefix(CANCEL_PREFIX).and_then(|id| id.parse().ok()) {
+                cancel_run(app, run_id);
+            }
+        }
+    }
+}
+
+fn find_process(app: &AppHandle, run_id: i64) -> Option<ProcessInfo> {
+    let registry = app.state::<ProcessRegistryState>();
+    registry.0.get_process(run_id).ok().flatten()
+}
+
+/// Shows the main window and asks it to open the run's output
+fn open_run(app: &AppHandle, run_id: i64) {
+    let Some(process) = find_process(app, run_id) else {
+        log::warn!("Run {} is no longer running", run_id);
+        return;
+    };
+    show_main_window(app);
+    let _ = app.emit("tray-open-run", &process);
+}
+
+fn cancel_run(app: &AppHandle, run_id: i64) {
+    let Some(process) = find_process(app, run_id) else {
+        log::warn!("Run {} is no longer running", run_id);
+        return;
+    };
+    log::info!("Cancelling run {} from the tray", run_id);
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let result = match process.process_type {
+            ProcessType::AgentRun { .. } => {
+                kill_agent_session(app.clone(), app.state(), app.state(), run_id)
+                    .await
+                    .map(|_| ())
+            }
+            ProcessType::ClaudeSession { session_id } => {
+                cancel_claude_execution(app.clone(), Some(session_id))
+                    .await
+                    .map(|_| ())
+            }
+        };
+        if let Err(e) = result {
+            log::error!("Failed to cancel run {} from the tray: {}", run_id, e);
+        }
+        refresh_tray(&app);
+    });
+}
@@ -6,6 +6,8 @@ use std::fs;
 use std::path::PathBuf;
 use tauri::command;
 
+use crate::stream_json::{StreamMessage, Usage};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UsageEntry {
     timestamp: String,
@@ -102,34 +104,7 @@ const SONNET_4_OUTPUT_PRICE: f64 = 15.0;
 const SONNET_4_CACHE_WRITE_PRICE: f64 = 3.75;
 const SONNET_4_CACHE_READ_PRICE: f64 = 0.30;
 
-#[derive(Debug, Deserialize)]
-struct JsonlEntry {
-    timestamp: String,
-    message: Option<MessageData>,
-    #[serde(rename = "sessionId")]
-    session_id: Option<String>,
-    #[serde(rename = "requestId")]
-    request_id: Option<String>,
-    #[serde(rename = "costUSD")]
-    cost_usd: Option<f64>,
-}
-
-#[derive(Debug, Deserialize)]
-struct MessageData {
-    id: Option<String>,
-    model: Option<String>,
-    usage: Option<UsageData>,
-}
-
-#[derive(Debug, Deserialize)]
-struct UsageData {
-    input_tokens: Option<u64>,
-    output_tokens: Option<u64>,
-    cache_creation_input_tokens: Option<u64>,
-    cache_read_input_tokens: Option<u64>,
-}
-
-fn calculate_cost(model: &str, usage: &UsageData) -> f64 {
+pub(crate) fn calculate_cost(model: &str, usage: &Usage) -> f64 {
     let input_tokens = usage.input_tokens.unwrap_or(0) as f64;
     let output_tokens = usage.output_tokens.unwrap_or(0) as f64;
     let cache_creation_tokens = usage.cache_creation_input_tokens.unwrap_or(0) as f64;
@@ -214,68 +189,70 @@ fn parse_jsonl_file(
                 continue;
             }
 
-            if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(line) {
-                // Extract the actual project path from cwd if we haven't already
-                if actual_project_path.is_none() {
-                    if let Some(cwd) = json_value.get("cwd").and_then(|v| v.as_str()) {
-                        actual_project_path = Some(cwd.to_string());
-                    }
+            let Some(msg) = StreamMessage::parse(line) else {
+                continue;
+            };
+
+            // Extract the actual project path from cwd if we haven't already
+            if actual_project_path.is_none() {
+                actual_project_path = match &msg {
+                    StreamMessage::System(system) => system.cwd.clone(),
+                    _ => msg.conversation().and_then(|c| c.cwd.clone()),
+                };
+            }
+
+            let (Some(entry), Some(message)) = (msg.conversation(), msg.message()) else {
+                continue;
+            };
+            let Some(timestamp) = &entry.timestamp else {
+                continue;
+            };
+
+            // Deduplication based on message ID and request ID
+            if let (Some(msg_id), Some(req_id)) = (&message.id, &entry.request_id) {
+                let unique_hash = format!("{}:{}", msg_id, req_id);
+                if processed_hashes.contains(&unique_hash) {
+                    continue; // Skip duplicate entry
                 }
+                processed_hashes.insert(unique_hash);
+            }
 
-                // Try to parse as JsonlEntry for usage data
-                if let Ok(entry) = serde_json::from_value::<JsonlEntry>(json_value) {
-                    if let Some(message) = &entry.message {
-                        // Deduplication based on message ID and request ID
-                        if let (Some(msg_id), Some(req_id)) = (&message.id, &entry.request_id) {
-                            let unique_hash = format!("{}:{}", msg_id, req_id);
-                            if processed_hashes.contains(&unique_hash) {
-                                continue; // Skip duplicate entry
-                            }
-                            processed_hashes.insert(unique_hash);
-                        }
+            if let Some(usage) = &message.usage {
+                // Skip entries without meaningful token usage
+                if usage.total_tokens() == 0 {
+                    continue;
+                }
 
-                        if let Some(usage) = &message.usage {
-                            // Skip entries without meaningful token usage
-                            if usage.input_tokens.unwrap_or(0) == 0
-                                && usage.output_tokens.unwrap_or(0) == 0
-                                && usage.cache_creation_input_tokens.unwrap_or(0) == 0
-                                && usage.cache_read_input_tokens.unwrap_or(0) == 0
-                            {
-                                continue;
-                            }
-
-                            let cost = entry.cost_usd.unwrap_or_else(|| {
-                                if let Some(model_str) = &message.model {
-                                    calculate_cost(model_str, usage)
-                                } else {
-                                    0.0
-                                }
-                            });
-
-                            // Use actual project path if found, otherwise use encoded name
-                            let project_path = actual_project_path
-                                .clone()
-                                .unwrap_or_else(|| encoded_project_name.to_string());
-
-                            entries.push(UsageEntry {
-                                timestamp: entry.timestamp,
-                                model: message
-                                    .model
-                                    .clone()
-                                    .unwrap_or_else(|| "unknown".to_string()),
-                                input_tokens: usage.input_tokens.unwrap_or(0),
-                                output_tokens: usage.output_tokens.unwrap_or(0),
-                                cache_creation_tokens: usage
-                                    .cache_creation_input_tokens
-                                    .unwrap_or(0),
-                                cache_read_tokens: usage.cache_read_input_tokens.unwrap_or(0),
-                                cost,
-                                session_id: entry.session_id.unwrap_or_else(|| session_id.clone()),
-                                project_path,
-                            });
-                        }
+                let cost = entry.cost_usd.unwrap_or_else(|| {
+                    if let Some(model_str) = &message.model {
+                        calculate_cost(model_str, usage)
+                    } else {
+                        0.0
                     }
-                }
+                });
+
+                // Use actual project path if found, otherwise use encoded name
+                let project_path = actual_project_path
+                    .clone()
+                    .unwrap_or_else(|| encoded_project_name.to_string());
+
+                entries.push(UsageEntry {
+                    timestamp: timestamp.clone(),
+                    model: message
+                        .model
+                        .clone()
+                        .unwrap_or_else(|| "unknown".to_string()),
+                    input_tokens: usage.input_tokens.unwrap_or(0),
+                    output_tokens: usage.output_tokens.unwrap_or(0),
+                    cache_creation_tokens: usage.cache_creation_input_tokens.unwrap_or(0),
+                    cache_read_tokens: usage.cache_read_input_tokens.unwrap_or(0),
+                    cost,
+                    session_id: msg
+                        .session_id()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| session_id.clone()),
+                    project_path,
+                });
             }
         }
     }
@@ -0,0 +1,792 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::{AppHandle, Manager, State};
+
+use super::agents::AgentDb;
+use super::project_index::encode_project_path;
+
+/// A git worktree created so a session or agent run gets its own working tree
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Worktree {
+    pub id: i64,
+    /// Root of the repository the worktree belongs to
+    pub repo_path: String,
+    pub worktree_path: String,
+    /// Branch created for the worktree
+    pub branch: String,
+    /// Branch that was checked out when the worktree was created
+    pub base_branch: String,
+    pub base_commit: String,
+    pub session_id: Option<String>,
+    pub agent_run_id: Option<i64>,
+    /// 'active', 'merged', 'discarded' or 'removed' (directory vanished)
+    pub status: String,
+    /// Whether Claudia wrote an agent's hook settings into the worktree.
+    /// That file is left out of the diff and the merge.
+    pub generated_settings: bool,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Whether the worktree directory still exists
+    pub exists: bool,
+}
+
+/// One changed file in a worktree
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorktreeFileChange {
+    pub path: String,
+    /// Git status letter: 'A', 'M', 'D', 'R', ...
+    pub status: String,
+}
+
+/// Changes in a worktree relative to where it branched off its base branch
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorktreeDiff {
+    pub worktree_id: i64,
+    pub branch: String,
+    pub base_branch: String,
+    pub merge_base: String,
+    pub files: Vec<WorktreeFileChange>,
+    /// Output of `git diff --stat`
+    pub stat: String,
+    /// Full unified diff, including uncommitted and untracked files
+    pub diff: String,
+}
+
+/// Result of merging a worktree branch back into its base branch
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorktreeMergeResult {
+    pub worktree_id: i64,
+    pub merged: bool,
+    /// Merge commit on the base branch
+    pub commit: Option<String>,
+    /// Files that conflicted; the merge is aborted when non-empty
+    pub conflicts: Vec<String>,
+    pub message: String,
+}
+
+/// Runs git in `dir` and returns trimmed stdout
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(dir).args(args);
+
+    // On Windows, hide the console window to prevent CMD popup
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let output = cmd
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Runs `git diff --no-index` in `dir`, which exits with 1 when the inputs
+/// differ, and returns its stdout
+fn git_diff_no_index(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(dir).args(["diff", "--no-index"]).args(args);
+
+    // On Windows, hide the console window to prevent CMD popup
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let output = cmd
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    match output.status.code() {
+        Some(0) | Some(1) => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+        _ => Err(format!(
+            "git diff --no-index failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+fn repo_root(project_path: &str) -> Result<String, String> {
+    git(Path::new(project_path), &["rev-parse", "--show-toplevel"])
+        .map_err(|_| format!("{} is not inside a git repository", project_path))
+}
+
+/// Directory under the app data dir that holds all worktrees
+pub(crate) fn worktrees_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("worktrees"))
+        .map_err(|e| format!("Failed to get app data dir: {}", e))
+}
+
+/// Lowercase, dash-separated label usable in a branch name
+fn slug(label: &str) -> String {
+    let slug: String = label
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    let slug = slug.split('-').filter(|s| !s.is_empty()).collect::<Vec<_>>().join("-");
+    if slug.is_empty() {
+        "session".to_string()
+    } else {
+        slug.chars().take(40).collect()
+    }
+}
+
+fn map_worktree(row: &rusqlite::Row) -> rusqlite::Result<Worktree> {
+    let worktree_path: String = row.get(2)?;
+    Ok(Worktree {
+        id: row.get(0)?,
+        repo_path: row.get(1)?,
+        exists: Path::new(&worktree_path).exists(),
+        worktree_path,
+        branch: row.get(3)?,
+        base_branch: row.get(4)?,
+        base_commit: row.get(5)?,
+        session_id: row.get(6)?,
+        agent_run_id: row.get(7)?,
+        status: row.get(8)?,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+        generated_settings: row.get(11)?,
+    })
+}
+
+const WORKTREE_COLUMNS: &str = "id, repo_path, worktree_path, branch, base_branch, base_commit,
+    session_id, agent_run_id, status, created_at, updated_at, generated_settings";
+
+/// Agent hook settings written by `write_agent_hooks_settings`, as a pathspec
+const EXCLUDE_GENERATED_SETTINGS: &str = ":(exclude).claude/settings.json";
+
+/// `args` limited to the worktree's own changes, leaving out the hook
+/// settings Claudia generated for an agent run
+fn changes_args<'a>(worktree: &Worktree, args: &[&'a str]) -> Vec<&'a str> {
+    let mut args = args.to_vec();
+    args.extend(["--", "."]);
+    if worktree.generated_settings {
+        args.push(EXCLUDE_GENERATED_SETTINGS);
+    }
+    args
+}
+
+fn get_worktree(conn: &Connection, id: i64) -> Result<Worktree, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM worktrees WHERE id = ?1", WORKTREE_COLUMNS),
+        params![id],
+        map_worktree,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Worktree {} not found", id))
+}
+
+fn set_status(conn: &Connection, id: i64, status: &str) -> Result<(), String> {
+    conn.execute(
+        "UPDATE worktrees SET status = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
+        params![status, id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Creates a worktree on a new branch off the project's current HEAD and
+/// records it. `label` ends up in the branch name. Git runs before the
+/// database lock is taken, which is only held to insert the record.
+pub(crate) fn create_worktree(
+    db: &AgentDb,
+    worktrees_dir: &Path,
+    project_path: &str,
+    label: &str,
+) -> Result<Worktree, String> {
+    let root = repo_root(project_path)?;
+    let root_path = Path::new(&root);
+    let base_branch = git(root_path, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    let base_commit = git(root_path, &["rev-parse", "HEAD"])
+        .map_err(|_| "The repository has no commits yet".to_string())?;
+
+    let suffix = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+    let name = format!("{}-{}", slug(label), suffix);
+    let branch = format!("claudia/{}", name);
+    let worktree_path = worktrees_dir.join(encode_project_path(&root)).join(&name);
+    if let Some(parent) = worktree_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create worktrees directory: {}", e))?;
+    }
+    let worktree_path = worktree_path.to_string_lossy().to_string();
+
+    git(
+        root_path,
+        &["worktree", "add", "-b", &branch, &worktree_path, &base_commit],
+    )?;
+    log::info!("Created worktree {} on branch {}", worktree_path, branch);
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO worktrees (repo_path, worktree_path, branch, base_branch, base_commit)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![root, worktree_path, branch, base_branch, base_commit],
+    )
+    .map_err(|e| format!("Failed to record worktree: {}", e))?;
+    get_worktree(&conn, conn.last_insert_rowid())
+}
+
+/// Records which session ran in a worktree
+pub(crate) fn set_worktree_session(conn: &Connection, id: i64, session_id: &str) -> Result<(), String> {
+    conn.execute(
+        "UPDATE worktrees SET session_id = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
+        params![session_id, id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Records which agent run used a worktree and whether the agent's hook
+/// settings were written into it
+pub(crate) fn set_worktree_agent_run(
+    conn: &Connection,
+    id: i64,
+    run_id: i64,
+    generated_settings: bool,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE worktrees SET agent_run_id = ?1, generated_settings = ?2,
+         updated_at = CURRENT_TIMESTAMP WHERE id = ?3",
+        params![run_id, generated_settings, id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Removes a worktree whose session or agent run failed to start, so it is
+/// not left behind unused
+pub(crate) fn discard_unused_worktree(db: &AgentDb, worktree: &Worktree) {
+    log::info!("Removing unused worktree {}", worktree.worktree_path);
+    let result = remove_worktree(worktree).and_then(|()| {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        set_status(&conn, worktree.id, "discarded")
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to remove worktree {}: {}", worktree.worktree_path, e);
+    }
+}
+
+/// Removes the worktree directory and its branch
+fn remove_worktree(worktree: &Worktree) -> Result<(), String> {
+    let root = Path::new(&worktree.repo_path);
+    if Path::new(&worktree.worktree_path).exists() {
+        git(root, &["worktree", "remove", "--force", &worktree.worktree_path])?;
+    } else {
+        git(root, &["worktree", "prune"])?;
+    }
+    // The branch may already be gone; that's fine
+    if let Err(e) = git(root, &["branch", "-D", &worktree.branch]) {
+        log::debug!("Could not delete branch {}: {}", worktree.branch, e);
+    }
+    Ok(())
+}
+
+/// Commit the base branch points at, falling back to the recorded commit
+/// when the worktree was created from a detached HEAD
+fn merge_base(worktree: &Worktree) -> Result<String, String> {
+    let dir = Path::new(&worktree.worktree_path);
+    let base = if worktree.base_branch == "HEAD" {
+        worktree.base_commit.as_str()
+    } else {
+        worktree.base_branch.as_str()
+    };
+    git(dir, &["merge-base", base, "HEAD"]).or_else(|_| Ok(worktree.base_commit.clone()))
+}
+
+/// Lists tracked worktrees, optionally for one repository only
+#[tauri::command]
+pub async fn list_worktrees(
+    db: State<'_, AgentDb>,
+    project_path: Option<String>,
+    include_inactive: Option<bool>,
+) -> Result<Vec<Worktree>, String> {
+    let repo_path = project_path.map(|path| repo_root(&path).unwrap_or(path));
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM worktrees
+             WHERE (?1 IS NULL OR repo_path = ?1) AND (?2 OR status = 'active')
+             ORDER BY created_at DESC, id DESC",
+            WORKTREE_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let worktrees = stmt
+        .query_map(
+            params![repo_path, include_inactive.unwrap_or(false)],
+            map_worktree,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(worktrees)
+}
+
+/// Parses `git diff --name-status` output
+fn parse_name_status(output: &str) -> Vec<WorktreeFileChange> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split('\t');
+            let status = parts.next()?.chars().next()?.to_string();
+            let path = parts.next_back()?.to_string();
+            Some(WorktreeFileChange { path, status })
+        })
+        .collect()
+}
+
+/// Parses `git diff --numstat` output into (path, insertions, deletions);
+/// binary files have no line counts
+fn parse_numstat(output: &str) -> Vec<(String, Option<u64>, Option<u64>)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let added = parts.next()?.parse().ok();
+            let deleted = parts.next()?.parse().ok();
+            Some((parts.next()?.to_string(), added, deleted))
+        })
+        .collect()
+}
+
+/// Renders numstat rows like `git diff --stat`
+fn format_stat(rows: &[(String, Option<u64>, Option<u64>)]) -> String {
+    const MAX_BAR: u64 = 40;
+    if rows.is_empty() {
+        return String::new();
+    }
+    let width = rows.iter().map(|(path, _, _)| path.chars().count()).max().unwrap_or(0);
+    let largest = rows
+        .iter()
+        .map(|(_, added, deleted)| added.unwrap_or(0) + deleted.unwrap_or(0))
+        .max()
+        .unwrap_or(0);
+    let scale = |n: u64| if largest > MAX_BAR { (n * MAX_BAR).div_ceil(largest) } else { n };
+
+    let mut stat = String::new();
+    let (mut insertions, mut deletions) = (0, 0);
+    for (path, added, deleted) in rows {
+        match (added, deleted) {
+            (Some(added), Some(deleted)) => {
+                insertions += added;
+                deletions += deleted;
+                stat.push_str(&format!(
+                    " {:<width$} | {} {}{}\n",
+                    path,
+                    added + deleted,
+                    "+".repeat(scale(*added) as usize),
+                    "-".repeat(scale(*deleted) as usize),
+                ));
+            }
+            _ => stat.push_str(&format!(" {:<width$} | Bin\n", path)),
+        }
+    }
+    let files = if rows.len() == 1 { "file" } else { "files" };
+    stat.push_str(&format!(
+        " {} {} changed, {} insertions(+), {} deletions(-)",
+        rows.len(),
+        files,
+        insertions,
+        deletions
+    ));
+    stat
+}
+
+/// Changes in a worktree since its merge base. Untracked files are diffed
+/// with `--no-index`, so reading the diff leaves the worktree's index alone.
+fn worktree_diff(worktree: &Worktree) -> Result<WorktreeDiff, String> {
+    let dir = Path::new(&worktree.worktree_path);
+    let merge_base = merge_base(worktree)?;
+
+    let mut files = parse_name_status(&git(
+        dir,
+        &changes_args(worktree, &["diff", "--name-status", &merge_base]),
+    )?);
+    let mut numstat = parse_numstat(&git(
+        dir,
+        &changes_args(worktree, &["diff", "--numstat", &merge_base]),
+    )?);
+    let mut diff = git(dir, &changes_args(worktree, &["diff", &merge_base]))?;
+
+    let untracked = git(
+        dir,
+        &changes_args(worktree, &["ls-files", "--others", "--exclude-standard"]),
+    )?;
+    for path in untracked.lines().filter(|p| !p.is_empty()) {
+        let file_diff = git_diff_no_index(dir, &["--", "/dev/null", path])?;
+        if !diff.is_empty() && !file_diff.is_empty() {
+            diff.push('\n');
+        }
+        diff.push_str(file_diff.trim_end());
+        let file_numstat = git_diff_no_index(dir, &["--numstat", "--", "/dev/null", path])?;
+        let (added, deleted) = parse_numstat(&file_numstat)
+            .first()
+            .map_or((None, None), |(_, added, deleted)| (*added, *deleted));
+        numstat.push((path.to_string(), added, deleted));
+        files.push(WorktreeFileChange {
+            path: path.to_string(),
+            status: "A".to_string(),
+        });
+    }
+
+    Ok(WorktreeDiff {
+        worktree_id: worktree.id,
+        stat: format_stat(&numstat),
+        diff,
+        branch: worktree.branch.clone(),
+        base_branch: worktree.base_branch.clone(),
+        merge_base,
+        files,
+    })
+}
+
+/// Diffs a worktree against the point where it left its base branch,
+/// including uncommitted and untracked files
+#[tauri::command]
+pub async fn get_worktree_diff(db: State<'_, AgentDb>, id: i64) -> Result<WorktreeDiff, String> {
+    let worktree = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        get_worktree(&conn, id)?
+    };
+    if !worktree.exists {
+        return Err(format!("Worktree directory no longer exists: {}", worktree.worktree_path));
+    }
+    worktree_diff(&worktree)
+}
+
+/// Commits any pending changes in the worktree and merges its branch into
+/// the base branch, which must be checked out in the main working tree.
+/// On conflicts the merge is aborted and the conflicting files are reported.
+#[tauri::command]
+pub async fn merge_worktree(
+    db: State<'_, AgentDb>,
+    id: i64,
+    message: Option<String>,
+    remove: Option<bool>,
+) -> Result<WorktreeMergeResult, String> {
+    let worktree = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        get_worktree(&conn, id)?
+    };
+    if worktree.status != "active" {
+        return Err(format!("Worktree {} is already {}", id, worktree.status));
+    }
+    if worktree.base_branch == "HEAD" {
+        return Err("The worktree was created from a detached HEAD; merge its branch manually".to_string());
+    }
+    log::info!("Merging worktree {} into {}", worktree.branch, worktree.base_branch);
+
+    let dir = Path::new(&worktree.worktree_path);
+    let root = Path::new(&worktree.repo_path);
+    let message = message
+        .filter(|m| !m.trim().is_empty())
+        .unwrap_or_else(|| format!("Changes from {}", worktree.branch));
+
+    let status_args = changes_args(&worktree, &["status", "--porcelain"]);
+    if worktree.exists && !git(dir, &status_args)?.is_empty() {
+        git(dir, &changes_args(&worktree, &["add", "--all"]))?;
+        git(dir, &["commit", "-m", &message])?;
+    }
+
+    let range = format!("{}..{}", worktree.base_branch, worktree.branch);
+    let ahead: u64 = git(root, &["rev-list", "--count", &range])?
+        .parse()
+        .unwrap_or(0);
+    if ahead == 0 {
+        return Ok(WorktreeMergeResult {
+            worktree_id: id,
+            merged: false,
+            commit: None,
+            conflicts: Vec::new(),
+            message: "No changes to merge".to_string(),
+        });
+    }
+
+    let current = git(root, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    if current != worktree.base_branch {
+        return Err(format!(
+            "{} has {} checked out; check out {} to merge",
+            worktree.repo_path, current, worktree.base_branch
+        ));
+    }
+
+    let merge_message = format!("Merge {}: {}", worktree.branch, message);
+    if let Err(e) = git(root, &["merge", "--no-ff", "-m", &merge_message, &worktree.branch]) {
+        let conflicts: Vec<String> = git(root, &["diff", "--name-only", "--diff-filter=U"])
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        let _ = git(root, &["merge", "--abort"]);
+        if conflicts.is_empty() {
+            return Err(e);
+        }
+        return Ok(WorktreeMergeResult {
+            worktree_id: id,
+            merged: false,
+            commit: None,
+            message: format!("Merge aborted: {} conflicting files", conflicts.len()),
+            conflicts,
+        });
+    }
+    let commit = git(root, &["rev-parse", "HEAD"])?;
+
+    if remove.unwrap_or(true) {
+        remove_worktree(&worktree)?;
+    }
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    set_status(&conn, id, "merged")?;
+
+    Ok(WorktreeMergeResult {
+        worktree_id: id,
+        merged: true,
+        commit: Some(commit),
+        conflicts: Vec::new(),
+        message: format!("Merged {} commits into {}", ahead, worktree.base_branch),
+    })
+}
+
+/// Throws away a worktree and its branch. A worktree with uncommitted
+/// changes is only discarded with `force`.
+#[tauri::command]
+pub async fn discard_worktree(
+    db: State<'_, AgentDb>,
+    id: i64,
+    force: Option<bool>,
+) -> Result<Worktree, String> {
+    let worktree = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        get_worktree(&conn, id)?
+    };
+    if worktree.exists && !force.unwrap_or(false) {
+        let status = git(
+            Path::new(&worktree.worktree_path),
+            &changes_args(&worktree, &["status", "--porcelain"]),
+        )?;
+        if !status.is_empty() {
+            return Err(format!(
+                "Worktree {} has {} uncommitted changes; discard with force to delete them",
+                worktree.worktree_path,
+                status.lines().count()
+            ));
+        }
+    }
+    log::info!("Discarding worktree {}", worktree.worktree_path);
+    remove_worktree(&worktree)?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    set_status(&conn, id, "discarded")?;
+    get_worktree(&conn, id)
+}
+
+/// Cleans up stale worktrees: records whose directory has disappeared are
+/// pruned, and with `older_than_days` active worktrees older than that
+/// without any changes are discarded. Returns the affected worktrees.
+#[tauri::command]
+pub async fn cleanup_worktrees(
+    db: State<'_, AgentDb>,
+    older_than_days: Option<u32>,
+) -> Result<Vec<Worktree>, String> {
+    let active = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM worktrees WHERE status = 'active'",
+                WORKTREE_COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], map_worktree)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        rows
+    };
+    let cutoff = older_than_days
+        .map(|days| chrono::Utc::now().naive_utc() - chrono::Duration::days(days as i64));
+
+    let mut cleaned = Vec::new();
+    for worktree in active {
+        let status = if !worktree.exists {
+            let _ = git(Path::new(&worktree.repo_path), &["worktree", "prune"]);
+            "removed"
+        } else {
+            let created = chrono::NaiveDateTime::parse_from_str(&worktree.created_at, "%Y-%m-%d %H:%M:%S").ok();
+            let expired = matches!((cutoff, created), (Some(cutoff), Some(created)) if created < cutoff);
+            if !expired {
+                continue;
+            }
+            let dir = Path::new(&worktree.worktree_path);
+            let unchanged = git(dir, &changes_args(&worktree, &["status", "--porcelain"]))
+                .map(|s| s.is_empty())
+                .unwrap_or(false)
+                && git(dir, &["rev-parse", "HEAD"]).ok().as_deref() == Some(worktree.base_commit.as_str());
+            if !unchanged {
+                continue;
+            }
+            if let Err(e) = remove_worktree(&worktree) {
+                log::warn!("Failed to remove worktree {}: {}", worktree.worktree_path, e);
+                continue;
+            }
+            "discarded"
+        };
+
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        set_status(&conn, worktree.id, status)?;
+        cleaned.push(get_worktree(&conn, worktree.id)?);
+    }
+
+    log::info!("Cleaned up {} worktrees", cleaned.len());
+    Ok(cleaned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_slug_and_stat_formatting() {
+        assert_eq!(slug("Code Reviewer!"), "code-reviewer");
+        assert_eq!(slug("***"), "session");
+
+        let rows = parse_numstat("3\t1\tsrc/main.rs\n-\t-\tlogo.png\n");
+        assert_eq!(rows[0], ("src/main.rs".to_string(), Some(3), Some(1)));
+        assert_eq!(rows[1], ("logo.png".to_string(), None, None));
+        assert_eq!(
+            format_stat(&rows),
+            " src/main.rs | 4 +++-\n logo.png    | Bin\n 2 files changed, 3 insertions(+), 1 deletions(-)"
+        );
+        let changes = parse_name_status("M\tsrc/a.rs\nR100\told.rs\tnew.rs\n");
+        assert_eq!(changes[1].status, "R");
+        assert_eq!(changes[1].path, "new.rs");
+    }
+
+    #[test]
+    fn test_diff_includes_untracked_files_without_touching_the_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        run(repo, &["init", "-q", "-b", "main"]);
+        std::fs::write(repo.join("a.txt"), "one\n").unwrap();
+        run(repo, &["add", "a.txt"]);
+        run(repo, &["commit", "-q", "-m", "init"]);
+        let base_commit = git(repo, &["rev-parse", "HEAD"]).unwrap();
+
+        std::fs::write(repo.join("a.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(repo.join("new.txt"), "fresh\n").unwrap();
+        let worktree = Worktree {
+            id: 1,
+            repo_path: repo.to_string_lossy().to_string(),
+            worktree_path: repo.to_string_lossy().to_string(),
+            branch: "main".to_string(),
+            base_branch: "main".to_string(),
+            base_commit,
+            session_id: None,
+            agent_run_id: None,
+            status: "active".to_string(),
+            created_at: String::new(),
+            updated_at: String::new(),
+            generated_settings: false,
+            exists: true,
+        };
+
+        let diff = worktree_diff(&worktree).unwrap();
+        let files: Vec<(&str, &str)> = diff
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.status.as_str()))
+            .collect();
+        assert_eq!(files, [("a.txt", "M"), ("new.txt", "A")]);
+        assert!(diff.diff.contains("+two"));
+        assert!(diff.diff.contains("+fresh"));
+        assert!(diff.stat.ends_with("2 files changed, 2 insertions(+), 0 deletions(-)"));
+        // The new file is still untracked
+        assert_eq!(
+            git(repo, &["status", "--porcelain"]).unwrap(),
+            "M a.txt\n?? new.txt"
+        );
+    }
+
+    #[test]
+    fn test_generated_settings_are_not_a_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        run(repo, &["init", "-q", "-b", "main"]);
+        std::fs::write(repo.join("a.txt"), "one\n").unwrap();
+        run(repo, &["add", "a.txt"]);
+        run(repo, &["commit", "-q", "-m", "init"]);
+        let base_commit = git(repo, &["rev-parse", "HEAD"]).unwrap();
+
+        std::fs::create_dir(repo.join(".claude")).unwrap();
+        std::fs::write(repo.join(".claude/settings.json"), "{\"hooks\":{}}").unwrap();
+        std::fs::write(repo.join(".claude/agents.md"), "notes\n").unwrap();
+        let mut worktree = Worktree {
+            id: 1,
+            repo_path: repo.to_string_lossy().to_string(),
+            worktree_path: repo.to_string_lossy().to_string(),
+            branch: "main".to_string(),
+            base_branch: "main".to_string(),
+            base_commit,
+            session_id: None,
+            agent_run_id: Some(1),
+            status: "active".to_string(),
+            created_at: String::new(),
+            updated_at: String::new(),
+            generated_settings: true,
+            exists: true,
+        };
+
+        let diff = worktree_diff(&worktree).unwrap();
+        let files: Vec<&str> = diff.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(files, [".claude/agents.md"]);
+        assert!(!diff.diff.contains("hooks"));
+        assert_eq!(
+            git(repo, &changes_args(&worktree, &["status", "--porcelain"])).unwrap(),
+            "?? .claude/"
+        );
+
+        // Staging for the merge commit leaves the settings untracked
+        git(repo, &changes_args(&worktree, &["add", "--all"])).unwrap();
+        assert_eq!(
+            git(repo, &["status", "--porcelain"]).unwrap(),
+            "A  .claude/agents.md\n?? .claude/settings.json"
+        );
+
+        // Settings the agent run did not generate are a change like any other
+        worktree.generated_settings = false;
+        let files: Vec<String> = worktree_diff(&worktree)
+            .unwrap()
+            .files
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        assert_eq!(files, [".claude/agents.md", ".claude/settings.json"]);
+    }
+}
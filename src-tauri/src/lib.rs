@@ -3,9 +3,11 @@
 // Declare modules
 pub mod checkpoint;
 pub mod claude_binary;
+pub mod cli;
 pub mod commands;
 pub mod logger;
 pub mod process;
+pub mod stream_json;
 
 // Logger macros are automatically exported to crate root due to #[macro_export]
 // No need to re-export them manually
@@ -6,13 +6,14 @@ mod claude_binary;
 mod commands;
 mod logger;
 mod process;
+mod stream_json;
 
 use checkpoint::state::CheckpointState;
 use commands::agents::{
     cleanup_finished_processes, create_agent, delete_agent, delete_native_agents, execute_agent, export_agent,
     export_agent_to_file, fetch_github_agent_content, fetch_github_agents, get_agent,
     get_agent_run, get_agent_run_with_real_time_metrics, get_claude_binary_path, refresh_claude_binary_path,
-    get_live_session_output, get_session_output, get_session_status, import_agent,
+    get_live_output, get_live_session_output, get_session_output, get_session_status, import_agent,
     import_agent_from_file, import_agent_from_github, import_native_agents, init_database, kill_agent_session,
     list_agent_runs, list_agent_runs_with_metrics, list_agents, list_claude_installations,
     list_native_agents, list_running_sessions, load_agent_session_history, set_claude_binary_path, stream_session_output, update_agent, AgentDb,
@@ -25,10 +26,11 @@ use commands::claude::{
     clear_checkpoint_manager, continue_claude_code, create_checkpoint, delete_session, execute_claude_code,
     find_claude_md_files, fork_from_checkpoint, get_checkpoint_diff, get_checkpoint_settings,
     get_checkpoint_state_stats, get_claude_session_output, get_claude_settings, get_project_sessions,
+    get_project_sessions_page,
     get_recently_modified_files, get_session_timeline, get_system_prompt, list_checkpoints,
     list_directory_contents, list_projects, list_running_claude_sessions, load_session_history,
     open_new_session, read_claude_md_file, restore_checkpoint, resume_claude_code,
-    save_claude_md_file, save_claude_settings, save_system_prompt, search_files,
+    save_claude_md_file, save_claude_settings, save_system_prompt,
     track_checkpoint_message, track_session_messages, update_checkpoint_settings,
     get_hooks_config, update_hooks_config, validate_hook_command,
     ClaudeProcessState,
@@ -48,6 +50,61 @@ use commands::storage::{
     get_app_setting, save_app_setting,
 };
 use commands::proxy::{get_proxy_settings, save_proxy_settings, apply_proxy_settings};
+use commands::project_index::{get_project_index, rebuild_project_index, resolve_project_dir};
+use commands::prompt_queue::{
+    interrupt_stale_items, prompt_queue_add, prompt_queue_clear_finished, prompt_queue_list,
+    prompt_queue_pause, prompt_queue_remove, prompt_queue_reorder, prompt_queue_resume,
+    prompt_queue_retry, prompt_queue_set_session, PromptQueueState,
+};
+use commands::session_export::export_session;
+use commands::automation_api::{
+    get_automation_api_status, regenerate_automation_api_token, save_automation_api_settings,
+    start_automation_api_if_enabled, AutomationApiState,
+};
+use commands::run_recovery::{
+    get_recovery_report, reattach_agent_run, reconcile_agent_runs, reconcile_on_startup,
+    RecoveryState,
+};
+use commands::file_search::{
+    fuzzy_search_files, grep_files, refresh_file_index, search_files, FileSearchState,
+};
+use commands::file_watcher::{
+    list_directory_watches, unwatch_directory, watch_directory, FileWatcherState,
+};
+use commands::hook_simulator::simulate_hooks;
+use commands::memory::get_effective_memory;
+use commands::permissions::{
+    add_permission_rule, analyze_permission_rules, get_permission_rules, move_permission_rule,
+    remove_permission_rule, validate_permission_rule,
+};
+use commands::settings::{get_effective_settings, validate_claude_settings};
+use commands::scheduler::{
+    create_schedule, delete_schedule, interrupt_stale_runs, list_schedule_runs, list_schedules,
+    preview_schedule, run_schedule_now, run_scheduler, set_schedule_enabled, update_schedule,
+};
+use commands::config_history::{
+    diff_config_versions, get_config_version, list_config_history, list_config_versions,
+    restore_config_version,
+};
+use commands::notifications::{
+    get_notification_settings, save_notification_settings, send_test_notification,
+};
+use commands::quick_prompt::{
+    get_quick_prompt_settings, get_quick_prompt_target, handle_shortcut, open_quick_prompt,
+    register_saved_shortcut, save_quick_prompt_settings, submit_quick_prompt,
+};
+use commands::tray::{create_tray, run_tray_updater};
+use commands::session_fork::fork_session;
+use commands::worktrees::{
+    cleanup_worktrees, discard_worktree, get_worktree_diff, list_worktrees, merge_worktree,
+};
+use commands::session_metadata::{
+    get_session_metadata, list_session_tags, update_session_metadata,
+};
+use commands::session_search::{
+    rebuild_session_search_index, refresh_session_search_index, run_session_indexer,
+    search_sessions,
+};
 use process::ProcessRegistryState;
 use std::sync::Mutex;
 use tauri::Manager;
@@ -59,6 +116,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(handle_shortcut)
+                .build(),
+        )
         .plugin(tauri_plugin_window_state::Builder::default().build())
         .setup(|app| {
             // Initialize agents database
@@ -144,15 +207,49 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             // Initialize process registry
             app.manage(ProcessRegistryState::default());
 
+            // Reconcile agent runs left behind by a previous crash
+            app.manage(RecoveryState::default());
+            tauri::async_runtime::spawn(reconcile_on_startup(app.handle().clone()));
+            interrupt_stale_runs(app.handle());
+
             // Initialize Claude process state
             app.manage(ClaudeProcessState::default());
 
+            // Initialize prompt queue runner state
+            app.manage(PromptQueueState::default());
+            interrupt_stale_items(app.handle());
+
+            // Initialize file search index cache
+            app.manage(FileSearchState::default());
+
+            // Initialize directory watches for the file browser
+            app.manage(FileWatcherState::default());
+
+            // Start the local automation API if the user opted in
+            app.manage(AutomationApiState::default());
+            tauri::async_runtime::spawn(start_automation_api_if_enabled(app.handle().clone()));
+
+            // Index new session transcript lines for search in the background
+            tauri::async_runtime::spawn(run_session_indexer(app.handle().clone()));
+
+            // Start the scheduler for recurring agent runs and prompts
+            tauri::async_runtime::spawn(run_scheduler(app.handle().clone()));
+
+            // Register the quick prompt shortcut and the tray menu of running sessions
+            register_saved_shortcut(app.handle());
+            // Some desktops have no tray (e.g. Linux without appindicator)
+            if let Err(e) = create_tray(app.handle()) {
+                log::warn!("Failed to create tray icon: {}", e);
+            }
+            tauri::async_runtime::spawn(run_tray_updater(app.handle().clone()));
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             // Claude & Project Management
             list_projects,
             get_project_sessions,
+            get_project_sessions_page,
             get_claude_settings,
             open_new_session,
             get_system_prompt,
@@ -176,6 +273,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             get_hooks_config,
             update_hooks_config,
             validate_hook_command,
+            simulate_hooks,
 
             // Checkpoint Management
             create_checkpoint,
@@ -213,6 +311,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             cleanup_finished_processes,
             get_session_output,
             get_live_session_output,
+            get_live_output,
             stream_session_output,
             load_agent_session_history,
             get_claude_binary_path,
@@ -278,6 +377,105 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             // Proxy Settings
             get_proxy_settings,
             save_proxy_settings,
+
+            // Project Index
+            get_project_index,
+            rebuild_project_index,
+            resolve_project_dir,
+
+            // Prompt Queue
+            prompt_queue_list,
+            prompt_queue_add,
+            prompt_queue_remove,
+            prompt_queue_reorder,
+            prompt_queue_pause,
+            prompt_queue_resume,
+            prompt_queue_retry,
+            prompt_queue_set_session,
+            prompt_queue_clear_finished,
+
+            // Session Metadata
+            get_session_metadata,
+            update_session_metadata,
+            list_session_tags,
+            fork_session,
+
+            // Session Search
+            search_sessions,
+            refresh_session_search_index,
+            rebuild_session_search_index,
+
+            // Session Export
+            export_session,
+
+            // Automation API
+            get_automation_api_status,
+            save_automation_api_settings,
+            regenerate_automation_api_token,
+
+            // Worktrees
+            list_worktrees,
+            get_worktree_diff,
+            merge_worktree,
+            discard_worktree,
+            cleanup_worktrees,
+
+            // Run Recovery
+            get_recovery_report,
+            reconcile_agent_runs,
+            reattach_agent_run,
+
+            // Memory
+            get_effective_memory,
+
+            // File Search
+            fuzzy_search_files,
+            grep_files,
+            refresh_file_index,
+
+            // File Watching
+            watch_directory,
+            unwatch_directory,
+            list_directory_watches,
+
+            // Settings
+            get_effective_settings,
+            validate_claude_settings,
+
+            // Permissions
+            get_permission_rules,
+            add_permission_rule,
+            remove_permission_rule,
+            move_permission_rule,
+            validate_permission_rule,
+            analyze_permission_rules,
+
+            // Config History
+            list_config_history,
+            list_config_versions,
+            get_config_version,
+            diff_config_versions,
+            restore_config_version,
+
+            // Scheduler
+            list_schedules,
+            create_schedule,
+            update_schedule,
+            delete_schedule,
+            set_schedule_enabled,
+            run_schedule_now,
+            list_schedule_runs,
+            preview_schedule,
+            // Notifications
+            get_notification_settings,
+            save_notification_settings,
+            send_test_notification,
+            // Quick Prompt
+            get_quick_prompt_settings,
+            save_quick_prompt_settings,
+            open_quick_prompt,
+            get_quick_prompt_target,
+            submit_quick_prompt,
         ])
         .run(tauri::generate_context!())
         .map_err(|e| {
@@ -0,0 +1,298 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// Lines kept in memory per run; older ones are spilled to disk
+const MAX_BUFFERED_LINES: usize = 2_000;
+/// Bytes kept in memory per run, whichever limit is hit first
+const MAX_BUFFERED_BYTES: usize = 4 * 1024 * 1024;
+/// Every this many spilled lines, the byte position is indexed so a page can
+/// seek close to its first line instead of scanning the whole file
+const INDEX_STRIDE: u64 = 1_000;
+
+/// A slice of a run's output. Offsets count lines from the start of the run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LiveOutputPage {
+    pub lines: Vec<String>,
+    /// Offset of the first line in `lines`. Larger than the requested offset
+    /// only if earlier output could not be kept.
+    pub from_offset: u64,
+    /// Offset to request next to continue after this page
+    pub next_offset: u64,
+    /// Lines produced by the run so far
+    pub total_lines: u64,
+}
+
+impl LiveOutputPage {
+    /// The lines as newline-terminated text, the format the output had before
+    /// it was paged
+    pub fn text(&self) -> String {
+        let mut text = String::with_capacity(self.lines.iter().map(|l| l.len() + 1).sum());
+        for line in &self.lines {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Output of one run: the newest lines in a bounded in-memory ring buffer and
+/// everything older in a spill file that is removed with the buffer
+pub struct LiveOutput {
+    lines: VecDeque<String>,
+    buffered_bytes: usize,
+    /// Offset of the oldest line still in memory, i.e. the number spilled
+    first_offset: u64,
+    max_lines: usize,
+    max_bytes: usize,
+    spill_path: PathBuf,
+    spill: Option<BufWriter<File>>,
+    spilled_bytes: u64,
+    /// Byte position of every `INDEX_STRIDE`th spilled line
+    index: Vec<u64>,
+    /// Set once writing the spill file failed; spilled output is then
+    /// dropped instead of served with gaps
+    spill_failed: bool,
+}
+
+impl LiveOutput {
+    pub fn new(run_id: i64) -> Self {
+        let spill_path = std::env::temp_dir()
+            .join("claudia-live-output")
+            .join(format!("{}-{}.log", std::process::id(), run_id));
+        Self::with_limits(spill_path, MAX_BUFFERED_LINES, MAX_BUFFERED_BYTES)
+    }
+
+    fn with_limits(spill_path: PathBuf, max_lines: usize, max_bytes: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            buffered_bytes: 0,
+            first_offset: 0,
+            max_lines,
+            max_bytes,
+            spill_path,
+            spill: None,
+            spilled_bytes: 0,
+            index: Vec::new(),
+            spill_failed: false,
+        }
+    }
+
+    pub fn total_lines(&self) -> u64 {
+        self.first_offset + self.lines.len() as u64
+    }
+
+    /// Appends output, one line per `\n`-separated piece. A single trailing
+    /// newline ends the last line rather than starting an empty one, so raw
+    /// chunks and bare lines are stored alike.
+    pub fn push(&mut self, output: &str) {
+        let output = output.strip_suffix('\n').unwrap_or(output);
+        for line in output.split('\n') {
+            self.buffered_bytes += line.len();
+            self.lines.push_back(line.to_string());
+        }
+        // Always keep the newest line, however long
+        while self.lines.len() > 1
+            && (self.lines.len() > self.max_lines || self.buffered_bytes > self.max_bytes)
+        {
+            if let Some(line) = self.lines.pop_front() {
+                self.buffered_bytes -= line.len();
+                self.spill_line(&line);
+                self.first_offset += 1;
+            }
+        }
+    }
+
+    fn spill_line(&mut self, line: &str) {
+        if self.spill_failed {
+            return;
+        }
+        if let Err(e) = self.try_spill_line(line) {
+            log::warn!(
+                "Failed to spill live output to {}: {}",
+                self.spill_path.display(),
+                e
+            );
+            self.spill_failed = true;
+            self.spill = None;
+        }
+    }
+
+    fn try_spill_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.spill.is_none() {
+            if let Some(parent) = self.spill_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            self.spill = Some(BufWriter::new(File::create(&self.spill_path)?));
+        }
+        if self.first_offset.is_multiple_of(INDEX_STRIDE) {
+            self.index.push(self.spilled_bytes);
+        }
+        if let Some(spill) = self.spill.as_mut() {
+            spill.write_all(line.as_bytes())?;
+            spill.write_all(b"\n")?;
+        }
+        self.spilled_bytes += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    /// Oldest offset that can still be served
+    fn first_available(&self) -> u64 {
+        if self.spill_failed {
+            self.first_offset
+        } else {
+            0
+        }
+    }
+
+    /// Reads up to `limit` lines starting at `from_offset`; all remaining
+    /// lines when `limit` is `None`
+    pub fn read(&mut self, from_offset: u64, limit: Option<usize>) -> Result<LiveOutputPage, String> {
+        let total_lines = self.total_lines();
+        let start = from_offset.max(self.first_available()).min(total_lines);
+        let end = limit.map_or(total_lines, |limit| {
+            start.saturating_add(limit as u64).min(total_lines)
+        });
+
+        let mut lines = Vec::with_capacity((end - start) as usize);
+        if start < self.first_offset {
+            self.read_spilled(start, end.min(self.first_offset), &mut lines)?;
+        }
+        let buffered_start = (start.max(self.first_offset) - self.first_offset) as usize;
+        let buffered_end = (end.max(self.first_offset) - self.first_offset) as usize;
+        lines.extend(self.lines.range(buffered_start..buffered_end).cloned());
+
+        Ok(LiveOutputPage {
+            lines,
+            from_offset: start,
+            next_offset: end,
+            total_lines,
+        })
+    }
+
+    /// The lines still held in memory, i.e. the newest output, without
+    /// touching the spill file
+    pub fn tail(&self) -> LiveOutputPage {
+        let total_lines = self.total_lines();
+        LiveOutputPage {
+            lines: self.lines.iter().cloned().collect(),
+            from_offset: self.first_offset,
+            next_offset: total_lines,
+            total_lines,
+        }
+    }
+
+    fn read_spilled(&mut self, start: u64, end: u64, lines: &mut Vec<String>) -> Result<(), String> {
+        let to_string = |e: std::io::Error| format!("Failed to read spilled live output: {}", e);
+        if let Some(spill) = self.spill.as_mut() {
+            spill.flush().map_err(to_string)?;
+        }
+        let mut reader = BufReader::new(File::open(&self.spill_path).map_err(to_string)?);
+        let indexed = start / INDEX_STRIDE;
+        reader
+            .seek(SeekFrom::Start(self.index[indexed as usize]))
+            .map_err(to_string)?;
+
+        let mut offset = indexed * INDEX_STRIDE;
+        let mut line = String::new();
+        while offset < end {
+            line.clear();
+            if reader.read_line(&mut line).map_err(to_string)? == 0 {
+                break;
+            }
+            if offset >= start {
+                lines.push(line.strip_suffix('\n').unwrap_or(&line).to_string());
+            }
+            offset += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for LiveOutput {
+    fn drop(&mut self) {
+        self.spill = None;
+        if self.first_offset > 0 {
+            let _ = fs::remove_file(&self.spill_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pages_span_spill_file_and_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let spill_path = dir.path().join("run.log");
+        let mut output = LiveOutput::with_limits(spill_path.clone(), 10, 1024);
+        for i in 0..2_500 {
+            output.push(&format!("line {}", i));
+        }
+        assert_eq!(output.total_lines(), 2_500);
+        assert_eq!(output.lines.len(), 10);
+        assert!(spill_path.exists());
+
+        // Crosses an index stride, then from the spill file into memory
+        let page = output.read(995, Some(10)).unwrap();
+        assert_eq!(page.lines.first().unwrap(), "line 995");
+        assert_eq!(page.lines.last().unwrap(), "line 1004");
+        assert_eq!(page.next_offset, 1_005);
+        let page = output.read(2_485, Some(10)).unwrap();
+        assert_eq!(page.lines, (2_485..2_495).map(|i| format!("line {}", i)).collect::<Vec<_>>());
+
+        // Only new lines once caught up
+        let page = output.read(2_495, None).unwrap();
+        assert_eq!(page.lines.len(), 5);
+        assert!(output.read(2_500, None).unwrap().lines.is_empty());
+        output.push("last");
+        assert_eq!(output.read(2_500, None).unwrap().text(), "last\n");
+
+        drop(output);
+        assert!(!spill_path.exists());
+    }
+
+    #[test]
+    fn test_byte_limit_keeps_newest_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut output = LiveOutput::with_limits(dir.path().join("run.log"), 100, 8);
+        output.push("short");
+        output.push("a line longer than the byte limit");
+        assert_eq!(output.lines.len(), 1);
+        let page = output.read(0, None).unwrap();
+        assert_eq!(page.lines, vec!["short", "a line longer than the byte limit"]);
+    }
+
+    #[test]
+    fn test_trailing_newline_ends_the_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut output = LiveOutput::with_limits(dir.path().join("run.log"), 100, 1024);
+        output.push("{\"type\":\"system\"}\n");
+        output.push("first\nsecond\n");
+        output.push("bare");
+        output.push("blank follows\n\n");
+        assert_eq!(output.total_lines(), 6);
+        assert_eq!(
+            output.read(0, None).unwrap().lines,
+            vec!["{\"type\":\"system\"}", "first", "second", "bare", "blank follows", ""]
+        );
+    }
+
+    #[test]
+    fn test_tail_stays_in_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let spill_path = dir.path().join("run.log");
+        let mut output = LiveOutput::with_limits(spill_path.clone(), 3, 1024);
+        for i in 0..5 {
+            output.push(&format!("line {}", i));
+        }
+        // Served without the spill file
+        fs::remove_file(&spill_path).ok();
+        let tail = output.tail();
+        assert_eq!(tail.lines, vec!["line 2", "line 3", "line 4"]);
+        assert_eq!((tail.from_offset, tail.next_offset, tail.total_lines), (2, 5, 5));
+    }
+}
@@ -1,3 +1,7 @@
+pub mod live_output;
 pub mod registry;
+pub mod termination;
 
+pub use live_output::LiveOutputPage;
 pub use registry::*;
+pub use termination::{spawn_in_own_group, terminate_process_tree, TerminationReport};
@@ -4,6 +4,9 @@ use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use tokio::process::Child;
 
+use super::live_output::{LiveOutput, LiveOutputPage};
+use super::termination::{terminate_process_tree, TerminationReport};
+
 /// Type of process being tracked
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ProcessType {
@@ -33,7 +36,7 @@ pub struct ProcessInfo {
 pub struct ProcessHandle {
     pub info: ProcessInfo,
     pub child: Arc<Mutex<Option<Child>>>,
-    pub live_output: Arc<Mutex<String>>,
+    pub live_output: Arc<Mutex<LiveOutput>>,
 }
 
 /// Registry for tracking active agent processes
@@ -110,7 +113,7 @@ impl ProcessRegistry {
         let process_handle = ProcessHandle {
             info: process_info,
             child: Arc::new(Mutex::new(None)), // No tokio::process::Child handle for sidecar
-            live_output: Arc::new(Mutex::new(String::new())),
+            live_output: Arc::new(Mutex::new(LiveOutput::new(run_id))),
         };
 
         processes.insert(run_id, process_handle);
@@ -144,7 +147,7 @@ impl ProcessRegistry {
         let process_handle = ProcessHandle {
             info: process_info,
             child: Arc::new(Mutex::new(None)), // No child handle for Claude sessions
-            live_output: Arc::new(Mutex::new(String::new())),
+            live_output: Arc::new(Mutex::new(LiveOutput::new(run_id))),
         };
 
         processes.insert(run_id, process_handle);
@@ -163,7 +166,7 @@ impl ProcessRegistry {
         let process_handle = ProcessHandle {
             info: process_info,
             child: Arc::new(Mutex::new(Some(child))),
-            live_output: Arc::new(Mutex::new(String::new())),
+            live_output: Arc::new(Mutex::new(LiveOutput::new(run_id))),
         };
 
         processes.insert(run_id, process_handle);
@@ -237,9 +240,11 @@ impl ProcessRegistry {
         Ok(processes.get(&run_id).map(|handle| handle.info.clone()))
     }
 
-    /// Kill a running process with proper cleanup
-    pub async fn kill_process(&self, run_id: i64) -> Result<bool, String> {
-        use log::{error, info, warn};
+    /// Stop a running process and its descendants in escalating steps
+    /// (SIGINT, SIGTERM, then SIGKILL on its process group). Returns `None`
+    /// if the run is not registered.
+    pub async fn kill_process(&self, run_id: i64) -> Result<Option<TerminationReport>, String> {
+        use log::{info, warn};
 
         // First check if the process exists and get its PID
         let (pid, child_arc) = {
@@ -248,7 +253,7 @@ impl ProcessRegistry {
                 (handle.info.pid, handle.child.clone())
             } else {
                 warn!("Process {} not found in registry", run_id);
-                return Ok(false); // Process not found
+                return Ok(None); // Process not found
             }
         };
 
@@ -256,191 +261,37 @@ impl ProcessRegistry {
             "Attempting graceful shutdown of process {} (PID: {})",
             run_id, pid
         );
+        let report = self.kill_process_by_pid(run_id, pid).await;
 
-        // Send kill signal to the process
-        let kill_sent = {
-            let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
-            if let Some(child) = child_guard.as_mut() {
-                match child.start_kill() {
-                    Ok(_) => {
-                        info!("Successfully sent kill signal to process {}", run_id);
-                        true
-                    }
-                    Err(e) => {
-                        error!("Failed to send kill signal to process {}: {}", run_id, e);
-                        // Don't return error here, try fallback method
-                        false
-                    }
-                }
-            } else {
-                warn!("No child handle available for process {} (PID: {}), attempting system kill", run_id, pid);
-                false // Process handle not available, try fallback
-            }
-        };
-
-        // If direct kill didn't work, try system command as fallback
-        if !kill_sent {
-            info!("Attempting fallback kill for process {} (PID: {})", run_id, pid);
-            match self.kill_process_by_pid(run_id, pid) {
-                Ok(true) => return Ok(true),
-                Ok(false) => warn!("Fallback kill also failed for process {} (PID: {})", run_id, pid),
-                Err(e) => error!("Error during fallback kill: {}", e),
-            }
-            // Continue with the rest of the cleanup even if fallback failed
+        // Reap the child so it does not linger as a zombie
+        let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
+        if let Some(child) = child_guard.as_mut() {
+            let _ = child.try_wait();
         }
+        *child_guard = None;
 
-        // Wait for the process to exit (with timeout)
-        let wait_result = tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
-            loop {
-                // Check if process has exited
-                let status = {
-                    let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
-                    if let Some(child) = child_guard.as_mut() {
-                        match child.try_wait() {
-                            Ok(Some(status)) => {
-                                info!("Process {} exited with status: {:?}", run_id, status);
-                                *child_guard = None; // Clear the child handle
-                                Some(Ok::<(), String>(()))
-                            }
-                            Ok(None) => {
-                                // Still running
-                                None
-                            }
-                            Err(e) => {
-                                error!("Error checking process status: {}", e);
-                                Some(Err(e.to_string()))
-                            }
-                        }
-                    } else {
-                        // Process already gone
-                        Some(Ok(()))
-                    }
-                };
-
-                match status {
-                    Some(result) => return result,
-                    None => {
-                        // Still running, wait a bit
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                    }
-                }
-            }
-        })
-        .await;
-
-        match wait_result {
-            Ok(Ok(_)) => {
-                info!("Process {} exited gracefully", run_id);
-            }
-            Ok(Err(e)) => {
-                error!("Error waiting for process {}: {}", run_id, e);
-            }
-            Err(_) => {
-                warn!("Process {} didn't exit within 5 seconds after kill", run_id);
-                // Force clear the handle
-                if let Ok(mut child_guard) = child_arc.lock() {
-                    *child_guard = None;
-                }
-                // One more attempt with system kill
-                let _ = self.kill_process_by_pid(run_id, pid);
-            }
-        }
-
-        // Remove from registry after killing
-        self.unregister_process(run_id)?;
-
-        Ok(true)
+        Ok(Some(report))
     }
 
-    /// Kill a process by PID using system commands (fallback method)
-    pub fn kill_process_by_pid(&self, run_id: i64, pid: u32) -> Result<bool, String> {
-        use log::{error, info, warn};
-
-        info!("Attempting to kill process {} by PID {}", run_id, pid);
-
-        let kill_result = if cfg!(target_os = "windows") {
-            {
-                let mut cmd = std::process::Command::new("taskkill");
-                cmd.args(["/F", "/PID", &pid.to_string()]);
-                
-                // On Windows, hide the console window to prevent CMD popup
-                #[cfg(target_os = "windows")]
-                {
-                    use std::os::windows::process::CommandExt;
-                    const CREATE_NO_WINDOW: u32 = 0x08000000;
-                    cmd.creation_flags(CREATE_NO_WINDOW);
-                }
-                
-                cmd.output()
-            }
+    /// Stop a process tree by PID, for processes whose child handle is held
+    /// elsewhere or lost. The run stays registered if anything survived.
+    pub async fn kill_process_by_pid(&self, run_id: i64, pid: u32) -> TerminationReport {
+        use log::{error, info};
+
+        let report = terminate_process_tree(pid).await;
+        if report.survivors.is_empty() {
+            info!(
+                "Process {} (PID {}) stopped at stage {:?}",
+                run_id, pid, report.stage
+            );
+            let _ = self.unregister_process(run_id);
         } else {
-            // First try SIGTERM
-            let mut cmd = std::process::Command::new("kill");
-            cmd.args(["-TERM", &pid.to_string()]);
-            
-            // On Unix systems, this doesn't need CREATE_NO_WINDOW
-            let term_result = cmd.output();
-
-            match &term_result {
-                Ok(output) if output.status.success() => {
-                    info!("Sent SIGTERM to PID {}", pid);
-                    // Give it 2 seconds to exit gracefully
-                    std::thread::sleep(std::time::Duration::from_secs(2));
-
-                    // Check if still running
-                    let mut cmd = std::process::Command::new("kill");
-                    cmd.args(["-0", &pid.to_string()]);
-                    let check_result = cmd.output();
-
-                    if let Ok(output) = check_result {
-                        if output.status.success() {
-                            // Still running, send SIGKILL
-                            warn!(
-                                "Process {} still running after SIGTERM, sending SIGKILL",
-                                pid
-                            );
-                            {
-                                let mut cmd = std::process::Command::new("kill");
-                                cmd.args(["-KILL", &pid.to_string()]);
-                                cmd.output()
-                            }
-                        } else {
-                            term_result
-                        }
-                    } else {
-                        term_result
-                    }
-                }
-                _ => {
-                    // SIGTERM failed, try SIGKILL directly
-                    warn!("SIGTERM failed for PID {}, trying SIGKILL", pid);
-                    {
-                        let mut cmd = std::process::Command::new("kill");
-                        cmd.args(["-KILL", &pid.to_string()]);
-                        cmd.output()
-                    }
-                }
-            }
-        };
-
-        match kill_result {
-            Ok(output) => {
-                if output.status.success() {
-                    info!("Successfully killed process with PID {}", pid);
-                    // Remove from registry
-                    self.unregister_process(run_id)?;
-                    Ok(true)
-                } else {
-                    let error_msg = String::from_utf8_lossy(&output.stderr);
-                    warn!("Failed to kill PID {}: {}", pid, error_msg);
-                    Ok(false)
-                }
-            }
-            Err(e) => {
-                error!("Failed to execute kill command for PID {}: {}", pid, e);
-                Err(format!("Failed to execute kill command: {}", e))
-            }
+            error!(
+                "Process {} (PID {}) still has running processes {:?}",
+                run_id, pid, report.survivors
+            );
         }
+        report
     }
 
     /// Check if a process is still running by trying to get its status
@@ -478,26 +329,72 @@ impl ProcessRegistry {
         }
     }
 
+    /// Wait for a registered child to exit and return its exit status.
+    /// Returns `None` if the run is not registered or its child was taken,
+    /// e.g. because the run was cancelled.
+    pub async fn wait_for_exit(&self, run_id: i64) -> Option<std::process::ExitStatus> {
+        let child_arc = {
+            let processes = self.processes.lock().ok()?;
+            processes.get(&run_id)?.child.clone()
+        };
+        loop {
+            {
+                let mut child_guard = child_arc.lock().ok()?;
+                match child_guard.as_mut()?.try_wait() {
+                    Ok(Some(status)) => return Some(status),
+                    Ok(None) => {}
+                    Err(_) => return None,
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+    }
+
     /// Append to live output for a process
     pub fn append_live_output(&self, run_id: i64, output: &str) -> Result<(), String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
         if let Some(handle) = processes.get(&run_id) {
             let mut live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
-            live_output.push_str(output);
-            live_output.push('\n');
+            live_output.push(output);
         }
         Ok(())
     }
 
-    /// Get live output for a process
-    pub fn get_live_output(&self, run_id: i64) -> Result<String, String> {
+    /// Get up to `limit` lines of live output for a process, starting at line
+    /// `from_offset`. Pass the returned `next_offset` to fetch only new lines.
+    pub fn get_live_output(
+        &self,
+        run_id: i64,
+        from_offset: u64,
+        limit: Option<usize>,
+    ) -> Result<LiveOutputPage, String> {
+        // Older pages come from disk, so read without holding the registry lock
+        let live_output = {
+            let processes = self.processes.lock().map_err(|e| e.to_string())?;
+            match processes.get(&run_id) {
+                Some(handle) => handle.live_output.clone(),
+                None => {
+                    return Ok(LiveOutputPage {
+                        from_offset,
+                        next_offset: from_offset,
+                        ..Default::default()
+                    })
+                }
+            }
+        };
+        let mut live_output = live_output.lock().map_err(|e| e.to_string())?;
+        live_output.read(from_offset, limit)
+    }
+
+    /// Get the newest live output of a process as text: the lines still in
+    /// memory. Older output is only served in pages by `get_live_output`.
+    pub fn get_live_output_text(&self, run_id: i64) -> Result<String, String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
-        if let Some(handle) = processes.get(&run_id) {
-            let live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
-            Ok(live_output.clone())
-        } else {
-            Ok(String::new())
-        }
+        let Some(handle) = processes.get(&run_id) else {
+            return Ok(String::new());
+        };
+        let live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
+        Ok(live_output.tail().text())
     }
 
     /// Cleanup finished processes
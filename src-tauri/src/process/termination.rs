@@ -0,0 +1,297 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Time Claude gets after SIGINT to flush its session JSONL and exit
+const INTERRUPT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+/// Time given after SIGTERM before resorting to SIGKILL
+const TERMINATE_GRACE_PERIOD: Duration = Duration::from_secs(3);
+/// Time for the kernel to tear processes down after SIGKILL
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The step of an escalating cancellation that stopped a process tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminationStage {
+    /// Nothing was running any more
+    AlreadyExited,
+    /// Exited after SIGINT (on Windows, after a close request)
+    Interrupt,
+    /// Exited after SIGTERM
+    Terminate,
+    /// Exited after SIGKILL (on Windows, a forced tree kill)
+    Kill,
+    /// Processes were still alive after SIGKILL
+    Survived,
+}
+
+/// Outcome of stopping a process and its descendants
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminationReport {
+    pub pid: u32,
+    pub stage: TerminationStage,
+    /// Whether the process led its own process group, so the whole group
+    /// was signalled rather than each known descendant
+    pub process_group: bool,
+    /// PIDs of the process and its descendants that were still running
+    /// after the last step; empty when the whole tree is gone
+    pub survivors: Vec<u32>,
+}
+
+/// Starts the command in a new process group, so cancelling it reaches the
+/// Node, MCP and shell processes it starts as well
+pub fn spawn_in_own_group(cmd: &mut tokio::process::Command) {
+    #[cfg(unix)]
+    {
+        cmd.process_group(0);
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+        cmd.creation_flags(CREATE_NO_WINDOW | CREATE_NEW_PROCESS_GROUP);
+    }
+}
+
+/// One row of the process table
+#[derive(Debug, Clone, PartialEq)]
+struct ProcessEntry {
+    pid: u32,
+    ppid: u32,
+    pgid: u32,
+    zombie: bool,
+}
+
+/// Parses `ps -A -o pid=,ppid=,pgid=,stat=` output
+fn parse_process_table(output: &str) -> Vec<ProcessEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some(ProcessEntry {
+                pid: fields.next()?.parse().ok()?,
+                ppid: fields.next()?.parse().ok()?,
+                pgid: fields.next()?.parse().ok()?,
+                zombie: fields.next().is_some_and(|stat| stat.starts_with('Z')),
+            })
+        })
+        .collect()
+}
+
+/// Adds every descendant of the given processes to `tree`
+fn collect_descendants(table: &[ProcessEntry], tree: &mut Vec<u32>) {
+    let mut index = 0;
+    while index < tree.len() {
+        let parent = tree[index];
+        for entry in table {
+            if entry.ppid == parent && entry.pid != parent && !tree.contains(&entry.pid) {
+                tree.push(entry.pid);
+            }
+        }
+        index += 1;
+    }
+}
+
+/// Processes of the tree (or of the group) that are still running. Zombies
+/// count as gone: they have exited and only wait to be reaped.
+fn running(table: &[ProcessEntry], tree: &[u32], group: Option<u32>) -> Vec<u32> {
+    let mut running: Vec<u32> = table
+        .iter()
+        .filter(|entry| !entry.zombie)
+        .filter(|entry| tree.contains(&entry.pid) || Some(entry.pgid) == group)
+        .map(|entry| entry.pid)
+        .collect();
+    running.sort_unstable();
+    running
+}
+
+#[cfg(unix)]
+fn process_table() -> Option<Vec<ProcessEntry>> {
+    let output = std::process::Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid=,pgid=,stat="])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| parse_process_table(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Live processes among `tree` and the group. Without `ps`, falls back to
+/// probing each known PID, which cannot tell zombies apart.
+#[cfg(unix)]
+fn survivors(tree: &mut Vec<u32>, group: Option<u32>) -> Vec<u32> {
+    match process_table() {
+        Some(table) => {
+            // Pick up processes started since the last look
+            collect_descendants(&table, tree);
+            running(&table, tree, group)
+        }
+        None => tree
+            .iter()
+            .copied()
+            // SAFETY: signal 0 only checks whether the process exists
+            .filter(|pid| unsafe { libc::kill(*pid as libc::pid_t, 0) } == 0)
+            .collect(),
+    }
+}
+
+#[cfg(unix)]
+fn send_signal(pids: &[u32], group: Option<u32>, signal: libc::c_int) {
+    // SAFETY: kill(2) and killpg(2) only send signals and take no pointers
+    unsafe {
+        if let Some(group) = group {
+            libc::killpg(group as libc::pid_t, signal);
+        }
+        // Descendants that moved to another group are signalled one by one
+        for pid in pids {
+            let pid = *pid as libc::pid_t;
+            if group.is_none_or(|group| libc::getpgid(pid) != group as libc::pid_t) {
+                libc::kill(pid, signal);
+            }
+        }
+    }
+}
+
+/// Stops a process and all of its descendants in escalating steps: SIGINT,
+/// then SIGTERM, then SIGKILL, waiting after each for the whole tree to exit
+#[cfg(unix)]
+pub async fn terminate_process_tree(pid: u32) -> TerminationReport {
+    // SAFETY: getpgid(2) takes no pointers
+    let process_group = unsafe { libc::getpgid(pid as libc::pid_t) } == pid as libc::pid_t;
+    let group = process_group.then_some(pid);
+    let mut report = TerminationReport {
+        pid,
+        stage: TerminationStage::AlreadyExited,
+        process_group,
+        survivors: Vec::new(),
+    };
+    // PID 0 would address our own process group
+    if pid == 0 {
+        return report;
+    }
+
+    // Record the tree before signalling: orphaned grandchildren are
+    // reparented and could no longer be found through their parent
+    let mut tree = vec![pid];
+    let mut alive = survivors(&mut tree, group);
+    if alive.is_empty() {
+        return report;
+    }
+
+    let steps = [
+        (TerminationStage::Interrupt, libc::SIGINT, INTERRUPT_GRACE_PERIOD),
+        (TerminationStage::Terminate, libc::SIGTERM, TERMINATE_GRACE_PERIOD),
+        (TerminationStage::Kill, libc::SIGKILL, KILL_GRACE_PERIOD),
+    ];
+    for (stage, signal, grace_period) in steps {
+        log::info!(
+            "Sending {:?} to PID {} ({} running, group: {})",
+            stage,
+            pid,
+            alive.len(),
+            process_group
+        );
+        send_signal(&alive, group, signal);
+
+        let deadline = tokio::time::Instant::now() + grace_period;
+        while !alive.is_empty() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            alive = survivors(&mut tree, group);
+        }
+        if alive.is_empty() {
+            report.stage = stage;
+            return report;
+        }
+    }
+
+    log::error!("PIDs {:?} survived SIGKILL", alive);
+    report.stage = TerminationStage::Survived;
+    report.survivors = alive;
+    report
+}
+
+#[cfg(windows)]
+fn taskkill(pid: u32, force: bool) {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    let mut cmd = std::process::Command::new("taskkill");
+    if force {
+        cmd.arg("/F");
+    }
+    cmd.args(["/T", "/PID", &pid.to_string()]);
+    cmd.creation_flags(CREATE_NO_WINDOW);
+    let _ = cmd.output();
+}
+
+#[cfg(windows)]
+fn is_running(pid: u32) -> bool {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    let mut cmd = std::process::Command::new("tasklist");
+    cmd.args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"]);
+    cmd.creation_flags(CREATE_NO_WINDOW);
+    cmd.output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid)))
+        .unwrap_or(false)
+}
+
+/// Stops a process tree: first a close request to the tree, then a forced
+/// tree kill. Windows has no signals, and `taskkill /T` covers descendants.
+#[cfg(windows)]
+pub async fn terminate_process_tree(pid: u32) -> TerminationReport {
+    let mut report = TerminationReport {
+        pid,
+        stage: TerminationStage::AlreadyExited,
+        process_group: true,
+        survivors: Vec::new(),
+    };
+    if pid == 0 || !is_running(pid) {
+        return report;
+    }
+
+    let steps = [
+        (TerminationStage::Interrupt, false, INTERRUPT_GRACE_PERIOD),
+        (TerminationStage::Kill, true, KILL_GRACE_PERIOD),
+    ];
+    for (stage, force, grace_period) in steps {
+        taskkill(pid, force);
+        let deadline = tokio::time::Instant::now() + grace_period;
+        while is_running(pid) && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        if !is_running(pid) {
+            report.stage = stage;
+            return report;
+        }
+    }
+
+    report.stage = TerminationStage::Survived;
+    report.survivors = vec![pid];
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_tree_and_group() {
+        let table = parse_process_table(
+            "    1     0     1 Ss\n  100     1   100 Ss\n  101   100   100 S\n  102   101   102 S+\n  103   101   100 Z\n  200     1   100 S\n  300     1   300 S\nnot a row\n",
+        );
+        assert_eq!(table.len(), 7);
+        assert!(table[4].zombie);
+
+        let mut tree = vec![100];
+        collect_descendants(&table, &mut tree);
+        assert_eq!(tree, vec![100, 101, 102, 103]);
+
+        // 102 left the group but is still a descendant; 200 was reparented
+        // but is still in the group; the zombie has already exited
+        assert_eq!(running(&table, &tree, Some(100)), vec![100, 101, 102, 200]);
+        assert_eq!(running(&table, &tree, None), vec![100, 101, 102]);
+    }
+}
@@ -0,0 +1,448 @@
+//! Typed model of Claude Code's stream-json output and session JSONL files.
+//!
+//! Both formats are one JSON object per line with a `type` field. Parsing is
+//! tolerant: unknown entry types and content blocks are kept as raw JSON, and
+//! fields the model does not know about are preserved in `extra`, so a line
+//! serializes back to what was read.
+
+use serde::de::{DeserializeOwned, Deserializer};
+use serde::ser::{Error as _, Serializer};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// One line of stream-json output or of a session transcript
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamMessage {
+    System(SystemMessage),
+    User(ConversationMessage),
+    Assistant(ConversationMessage),
+    Result(ResultMessage),
+    /// Any other entry type (`summary`, ...) or a line that did not fit the model
+    Unknown(Value),
+}
+
+/// `system` entries; `subtype: "init"` carries the session ID
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SystemMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subtype: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// `user` and `assistant` entries
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ConversationMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<MessageBody>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(rename = "requestId", default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(rename = "costUSD", default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+    /// Stream-json uses `session_id`, transcripts `sessionId`; both stay here
+    /// so they serialize back unchanged. Use [`StreamMessage::session_id`].
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The API message inside a `user` or `assistant` entry
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct MessageBody {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<MessageContent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Message content is either plain text or a list of blocks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Blocks(Vec<ContentBlock>),
+}
+
+/// One block of array content
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContentBlock {
+    Text(TextBlock),
+    ToolUse(ToolUseBlock),
+    ToolResult(ToolResultBlock),
+    /// `thinking`, `image` and anything newer
+    Unknown(Value),
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct TextBlock {
+    pub text: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ToolUseBlock {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub input: Value,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ToolResultBlock {
+    #[serde(default)]
+    pub tool_use_id: String,
+    /// A string or a list of content blocks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_error: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The final `result` entry of a stream-json run
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ResultMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subtype: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_error: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_turns: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_cost_usd: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Token usage reported by the API
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_input_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read_input_tokens: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Usage {
+    /// Input, output and cache tokens combined
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens.unwrap_or(0)
+            + self.output_tokens.unwrap_or(0)
+            + self.cache_creation_input_tokens.unwrap_or(0)
+            + self.cache_read_input_tokens.unwrap_or(0)
+    }
+}
+
+/// Deserializes the fields of a tagged object other than `type` as `T`, or
+/// `None` if they do not fit
+fn typed<T: DeserializeOwned>(value: &Value) -> Option<T> {
+    let mut fields = value.as_object()?.clone();
+    fields.remove("type");
+    serde_json::from_value(Value::Object(fields)).ok()
+}
+
+/// Serializes `inner` with its `type` tag
+fn with_tag<T: Serialize, E: serde::ser::Error>(tag: &str, inner: &T) -> Result<Value, E> {
+    let mut object = Map::new();
+    object.insert("type".to_string(), Value::String(tag.to_string()));
+    match serde_json::to_value(inner).map_err(E::custom)? {
+        Value::Object(fields) => object.extend(fields),
+        other => return Err(E::custom(format!("expected an object, got {}", other))),
+    }
+    Ok(Value::Object(object))
+}
+
+impl StreamMessage {
+    /// Parses one line; `None` if it is not a JSON object
+    pub fn parse(line: &str) -> Option<Self> {
+        let value: Value = serde_json::from_str(line.trim()).ok()?;
+        value.is_object().then(|| Self::from_value(value))
+    }
+
+    pub fn from_value(value: Value) -> Self {
+        let parsed = match value.get("type").and_then(Value::as_str) {
+            Some("system") => typed(&value).map(Self::System),
+            Some("user") => typed(&value).map(Self::User),
+            Some("assistant") => typed(&value).map(Self::Assistant),
+            Some("result") => typed(&value).map(Self::Result),
+            _ => None,
+        };
+        parsed.unwrap_or(Self::Unknown(value))
+    }
+
+    /// The `type` of the entry, also for unknown ones
+    pub fn kind(&self) -> Option<&str> {
+        match self {
+            Self::System(_) => Some("system"),
+            Self::User(_) => Some("user"),
+            Self::Assistant(_) => Some("assistant"),
+            Self::Result(_) => Some("result"),
+            Self::Unknown(value) => value.get("type").and_then(Value::as_str),
+        }
+    }
+
+    /// Session ID from an init, conversation or result entry
+    pub fn session_id(&self) -> Option<&str> {
+        match self {
+            Self::System(system) => system.session_id.as_deref(),
+            Self::Result(result) => result.session_id.as_deref(),
+            Self::User(conversation) | Self::Assistant(conversation) => conversation
+                .extra
+                .get("session_id")
+                .or_else(|| conversation.extra.get("sessionId"))
+                .and_then(Value::as_str),
+            Self::Unknown(value) => value
+                .get("session_id")
+                .or_else(|| value.get("sessionId"))
+                .and_then(Value::as_str),
+        }
+    }
+
+    /// Session ID announced by the `system`/`init` entry
+    pub fn init_session_id(&self) -> Option<&str> {
+        match self {
+            Self::System(system) if system.subtype.as_deref() == Some("init") => {
+                system.session_id.as_deref()
+            }
+            _ => None,
+        }
+    }
+
+    pub fn conversation(&self) -> Option<&ConversationMessage> {
+        match self {
+            Self::User(conversation) | Self::Assistant(conversation) => Some(conversation),
+            _ => None,
+        }
+    }
+
+    /// The API message of a user or assistant entry
+    pub fn message(&self) -> Option<&MessageBody> {
+        self.conversation()?.message.as_ref()
+    }
+
+    pub fn timestamp(&self) -> Option<&str> {
+        match self {
+            Self::User(conversation) | Self::Assistant(conversation) => {
+                conversation.timestamp.as_deref()
+            }
+            Self::Unknown(value) => value.get("timestamp").and_then(Value::as_str),
+            _ => None,
+        }
+    }
+
+    /// Model of an assistant message or of the session (init entry)
+    pub fn model(&self) -> Option<&str> {
+        match self {
+            Self::System(system) => system.model.as_deref(),
+            _ => self.message()?.model.as_deref(),
+        }
+    }
+
+    /// Usage of an assistant message, or the run totals of a result entry
+    pub fn usage(&self) -> Option<&Usage> {
+        match self {
+            Self::Result(result) => result.usage.as_ref(),
+            _ => self.message()?.usage.as_ref(),
+        }
+    }
+
+    /// Cost reported by Claude itself, when present
+    pub fn reported_cost_usd(&self) -> Option<f64> {
+        match self {
+            Self::Result(result) => result.total_cost_usd,
+            _ => self.conversation()?.cost_usd,
+        }
+    }
+
+    /// Why the model stopped ('end_turn', 'tool_use', 'max_tokens', ...).
+    /// Kept in `extra` so a `null` serializes back unchanged.
+    pub fn stop_reason(&self) -> Option<&str> {
+        self.message()?.extra.get("stop_reason")?.as_str()
+    }
+
+    /// Content blocks of the message; empty for plain-text content
+    pub fn blocks(&self) -> &[ContentBlock] {
+        match self.message().and_then(|m| m.content.as_ref()) {
+            Some(MessageContent::Blocks(blocks)) => blocks,
+            _ => &[],
+        }
+    }
+
+    /// All tool calls made in this message
+    pub fn tool_uses(&self) -> impl Iterator<Item = &ToolUseBlock> {
+        self.blocks().iter().filter_map(|block| match block {
+            ContentBlock::ToolUse(tool_use) => Some(tool_use),
+            _ => None,
+        })
+    }
+
+    /// Text of the message, whether given as a string or as text blocks
+    pub fn text(&self) -> Option<String> {
+        self.message()?.content.as_ref()?.text()
+    }
+}
+
+impl MessageContent {
+    /// The text, with multiple text blocks joined by newlines. `None` when
+    /// there is no text at all, e.g. content made only of tool results.
+    pub fn text(&self) -> Option<String> {
+        match self {
+            Self::Text(text) => Some(text.clone()),
+            Self::Blocks(blocks) => {
+                let texts: Vec<&str> = blocks
+                    .iter()
+                    .filter_map(|block| match block {
+                        ContentBlock::Text(text) => Some(text.text.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                (!texts.is_empty()).then(|| texts.join("\n"))
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for StreamMessage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Value::deserialize(deserializer).map(Self::from_value)
+    }
+}
+
+impl Serialize for StreamMessage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = match self {
+            Self::System(system) => with_tag("system", system)?,
+            Self::User(user) => with_tag("user", user)?,
+            Self::Assistant(assistant) => with_tag("assistant", assistant)?,
+            Self::Result(result) => with_tag("result", result)?,
+            Self::Unknown(value) => return value.serialize(serializer),
+        };
+        value.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentBlock {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        let parsed = match value.get("type").and_then(Value::as_str) {
+            Some("text") => typed(&value).map(Self::Text),
+            Some("tool_use") => typed(&value).map(Self::ToolUse),
+            Some("tool_result") => typed(&value).map(Self::ToolResult),
+            _ => None,
+        };
+        Ok(parsed.unwrap_or(Self::Unknown(value)))
+    }
+}
+
+impl Serialize for ContentBlock {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = match self {
+            Self::Text(text) => with_tag("text", text)?,
+            Self::ToolUse(tool_use) => with_tag("tool_use", tool_use)?,
+            Self::ToolResult(tool_result) => with_tag("tool_result", tool_result)?,
+            Self::Unknown(value) => return value.serialize(serializer),
+        };
+        if !value.is_object() {
+            return Err(S::Error::custom("content block must be an object"));
+        }
+        value.serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_init_and_round_trips_unknown_fields() {
+        let line = r#"{"type":"system","subtype":"init","session_id":"abc","tools":["Bash"],"cwd":"/p"}"#;
+        let msg = StreamMessage::parse(line).unwrap();
+        assert_eq!(msg.init_session_id(), Some("abc"));
+        let back: Value = serde_json::to_value(&msg).unwrap();
+        assert_eq!(back, serde_json::from_str::<Value>(line).unwrap());
+    }
+
+    #[test]
+    fn test_reads_text_from_string_and_array_content() {
+        let plain = StreamMessage::parse(
+            r#"{"type":"user","sessionId":"s","message":{"role":"user","content":"hello"}}"#,
+        )
+        .unwrap();
+        assert_eq!(plain.text().as_deref(), Some("hello"));
+        assert_eq!(plain.session_id(), Some("s"));
+
+        let blocks = StreamMessage::parse(
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"a"},{"type":"image","source":{}},{"type":"text","text":"b"}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(blocks.text().as_deref(), Some("a\nb"));
+
+        let tool_result = StreamMessage::parse(
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t","content":"ok"}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(tool_result.text(), None);
+    }
+
+    #[test]
+    fn test_extracts_tool_uses_and_usage() {
+        let line = r#"{"type":"assistant","session_id":"s","message":{"id":"m","model":"claude-sonnet-4","content":[{"type":"thinking","thinking":"..."},{"type":"tool_use","id":"t","name":"Edit","input":{"file_path":"a.rs"}}],"usage":{"input_tokens":3,"output_tokens":4,"service_tier":"standard"}}}"#;
+        let msg = StreamMessage::parse(line).unwrap();
+        let tools: Vec<_> = msg.tool_uses().map(|t| t.name.as_str()).collect();
+        assert_eq!(tools, ["Edit"]);
+        assert_eq!(msg.usage().map(Usage::total_tokens), Some(7));
+        assert_eq!(msg.model(), Some("claude-sonnet-4"));
+        let back: Value = serde_json::to_value(&msg).unwrap();
+        assert_eq!(back, serde_json::from_str::<Value>(line).unwrap());
+    }
+
+    #[test]
+    fn test_keeps_unknown_entries_verbatim() {
+        let line = r#"{"type":"summary","summary":"s","leafUuid":"u"}"#;
+        let msg = StreamMessage::parse(line).unwrap();
+        assert!(matches!(msg, StreamMessage::Unknown(_)));
+        assert_eq!(msg.kind(), Some("summary"));
+        let back: Value = serde_json::to_value(&msg).unwrap();
+        assert_eq!(back, serde_json::from_str::<Value>(line).unwrap());
+        assert!(StreamMessage::parse("not json").is_none());
+        assert!(StreamMessage::parse("[1]").is_none());
+    }
+}
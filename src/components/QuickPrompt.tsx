@@ -0,0 +1,117 @@
+import React, { useState, useEffect, useRef } from "react";
+import { getCurrentWebviewWindow } from "@tauri-apps/api/webviewWindow";
+import { Loader2, Send, FolderOpen } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Textarea } from "@/components/ui/textarea";
+import { api } from "@/lib/api";
+
+/**
+ * Content of the quick prompt popup window opened by the global shortcut
+ *
+ * Starts a new session in the project a session was last started in. The
+ * backend hides this window and brings the main window to the front once the
+ * session is running.
+ */
+export const QuickPrompt: React.FC = () => {
+  const [prompt, setPrompt] = useState("");
+  const [projectPath, setProjectPath] = useState<string | null>(null);
+  const [submitting, setSubmitting] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+  const textareaRef = useRef<HTMLTextAreaElement>(null);
+
+  useEffect(() => {
+    loadTarget();
+
+    // The window is hidden rather than closed, so refresh whenever it is shown again
+    let unlisten: (() => void) | undefined;
+    getCurrentWebviewWindow()
+      .onFocusChanged(({ payload: focused }) => {
+        if (focused) {
+          loadTarget();
+          textareaRef.current?.focus();
+        }
+      })
+      .then((fn) => {
+        unlisten = fn;
+      });
+
+    return () => {
+      unlisten?.();
+    };
+  }, []);
+
+  /**
+   * Load the project the prompt will run in
+   */
+  const loadTarget = async () => {
+    try {
+      setProjectPath(await api.getQuickPromptTarget());
+      setError(null);
+    } catch (err) {
+      setError(String(err));
+    }
+  };
+
+  const hide = () => {
+    getCurrentWebviewWindow().hide();
+  };
+
+  const handleSubmit = async () => {
+    if (!prompt.trim() || !projectPath || submitting) return;
+    setSubmitting(true);
+    setError(null);
+    try {
+      await api.submitQuickPrompt(prompt, projectPath);
+      setPrompt("");
+    } catch (err) {
+      setError(String(err));
+    } finally {
+      setSubmitting(false);
+    }
+  };
+
+  const handleKeyDown = (e: React.KeyboardEvent<HTMLTextAreaElement>) => {
+    if (e.key === "Escape") {
+      e.preventDefault();
+      hide();
+    } else if (e.key === "Enter" && !e.shiftKey) {
+      e.preventDefault();
+      handleSubmit();
+    }
+  };
+
+  return (
+    <div className="flex h-screen flex-col gap-2 border border-border bg-background p-3">
+      <div className="flex items-center gap-2 text-xs text-muted-foreground">
+        <FolderOpen className="h-3.5 w-3.5 shrink-0" />
+        <span className="truncate font-mono">
+          {projectPath ?? "No recent project. Start a session in the main window first."}
+        </span>
+      </div>
+      <Textarea
+        ref={textareaRef}
+        autoFocus
+        value={prompt}
+        onChange={(e) => setPrompt(e.target.value)}
+        onKeyDown={handleKeyDown}
+        placeholder="Ask Claude..."
+        disabled={submitting || !projectPath}
+        className="flex-1 resize-none"
+      />
+      <div className="flex items-center justify-between gap-2">
+        <span className="truncate text-xs text-destructive">{error}</span>
+        <Button
+          size="sm"
+          onClick={handleSubmit}
+          disabled={submitting || !projectPath || !prompt.trim()}
+        >
+          {submitting ? (
+            <Loader2 className="h-4 w-4 animate-spin" />
+          ) : (
+            <Send className="h-4 w-4" />
+          )}
+        </Button>
+      </div>
+    </div>
+  );
+};
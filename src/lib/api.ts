@@ -30,6 +30,15 @@ export interface ProcessInfo {
 /**
  * Represents a project in the ~/.claude/projects directory
  */
+/**
+ * Represents a session started from the quick prompt window
+ */
+export interface QuickPromptSubmitted {
+  project_path: string;
+  prompt: string;
+  model: string;
+}
+
 export interface Project {
   /** The project ID (derived from the directory name) */
   id: string;
@@ -2181,4 +2190,32 @@ export const api = {
       throw error;
     }
   },
+
+  /**
+   * Gets the project a quick prompt runs in: the last one a session was started in
+   * @returns Promise resolving to the project path, or null if there is none
+   */
+  async getQuickPromptTarget(): Promise<string | null> {
+    try {
+      return await invoke<string | null>("get_quick_prompt_target");
+    } catch (error) {
+      logger.error("Failed to get quick prompt target:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Starts a new session from the quick prompt window and hands over to the main window
+   * @param prompt - The prompt to send
+   * @param projectPath - Project to run in; defaults to the quick prompt target
+   * @returns Promise resolving to the started session's details
+   */
+  async submitQuickPrompt(prompt: string, projectPath?: string): Promise<QuickPromptSubmitted> {
+    try {
+      return await invoke<QuickPromptSubmitted>("submit_quick_prompt", { prompt, projectPath });
+    } catch (error) {
+      logger.error("Failed to submit quick prompt:", error);
+      throw error;
+    }
+  },
 };
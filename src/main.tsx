@@ -1,6 +1,7 @@
 import React from "react";
 import ReactDOM from "react-dom/client";
 import App from "./App";
+import { QuickPrompt } from "./components/QuickPrompt";
 import { ErrorBoundary } from "./components/ErrorBoundary";
 import { I18nProvider } from "./components/I18nProvider";
 import { AnalyticsErrorBoundary } from "./components/AnalyticsErrorBoundary";
@@ -23,6 +24,9 @@ analytics.initialize();
 // Start resource monitoring (check every 2 minutes)
 resourceMonitor.startMonitoring(120000);
 
+// The quick prompt popup loads the same bundle with ?window=quick-prompt
+const isQuickPrompt = new URLSearchParams(window.location.search).get("window") === "quick-prompt";
+
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
     <PostHogProvider
@@ -37,7 +41,7 @@ ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
       <ErrorBoundary>
         <AnalyticsErrorBoundary>
           <I18nProvider>
-            <App />
+            {isQuickPrompt ? <QuickPrompt /> : <App />}
           </I18nProvider>
         </AnalyticsErrorBoundary>
       </ErrorBoundary>